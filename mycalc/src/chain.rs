@@ -0,0 +1,233 @@
+//! Pipelines of operations for `mycalc chain "add 1 2 3 | mul 2 | sub 1"`: each stage's
+//! result goes first among the numbers of the next, so that one is `(1 + 2 + 3) * 2 - 1`.
+//! Results stay full-precision `f64` from stage to stage.
+
+use crate::functions;
+use crate::input::parse_number;
+use crate::locale::Separators;
+use crate::{add, div, exp, finite, ln, mul, nth_root, pow, rem, sub, CalcError, RemMode};
+
+/// An operation a stage can run, on the numbers it's given, strictly or not.
+type Operation = fn(&[f64], bool) -> Result<f64, CalcError>;
+
+/// The operations of a stage, by name: those of a list of numbers, `mod` of two and the
+/// rest of one.
+pub const OPERATIONS: &[(&str, Operation)] = &[
+    ("add", add),
+    ("sub", sub),
+    ("mul", mul),
+    ("div", div),
+    ("pow", pow),
+    ("mod", |nums, _| rem(nums[0], nums[1], RemMode::Truncated)),
+    ("sqrt", |nums, _| nth_root(nums[0], 2)),
+    ("cbrt", |nums, _| nth_root(nums[0], 3)),
+    ("ln", |nums, _| ln(nums[0])),
+    ("exp", |nums, _| exp(nums[0])),
+];
+
+/// How many numbers the operation `name` takes, when that's fixed.
+fn arity(name: &str) -> Option<usize> {
+    match name {
+        "mod" => Some(2),
+        "sqrt" | "cbrt" | "ln" | "exp" => Some(1),
+        _ => None,
+    }
+}
+
+/// The names of the operations, for errors: "add, sub, ... or exp".
+pub fn operation_names() -> String {
+    let names: Vec<&str> = OPERATIONS.iter().map(|&(name, _)| name).collect();
+    let (last, rest) = names.split_last().expect("there are operations");
+    format!("{} or {}", rest.join(", "), last)
+}
+
+/// Evaluates the stages of `chain`, separated by `|`. Each is an operation and its
+/// numbers (or constants); after the first, the previous result goes before them. An
+/// error is wrapped in [`CalcError::Stage`] with the stage it happened in, from 1, and a
+/// result that isn't a finite number is one.
+/// E.g.: "add 1 2 3 | mul 2 | sub 1" => 11
+pub fn eval_chain(chain: &str) -> Result<f64, CalcError> {
+    eval_chain_localized(chain, Separators::default(), true)
+}
+
+/// Evaluates `chain` like [`eval_chain`], reading its numbers with `separators`; without
+/// `strict`, results may be infinite or NaN.
+pub fn eval_chain_localized(
+    chain: &str,
+    separators: Separators,
+    strict: bool,
+) -> Result<f64, CalcError> {
+    let mut previous = None;
+    let mut start = 0;
+    for (i, stage) in chain.split('|').enumerate() {
+        let result = run_stage(stage, start, previous, separators, strict).map_err(|err| {
+            CalcError::Stage {
+                stage: i + 1,
+                error: Box::new(err),
+            }
+        })?;
+        previous = Some(result);
+        start += stage.len() + 1;
+    }
+    Ok(previous.expect("split yields at least one stage"))
+}
+
+/// The result of `stage`, which starts at byte `start` of the chain, with the result of
+/// the stage before it if there's one.
+fn run_stage(
+    stage: &str,
+    start: usize,
+    previous: Option<f64>,
+    separators: Separators,
+    strict: bool,
+) -> Result<f64, CalcError> {
+    let words = words(stage, start);
+    let Some(&(at, word)) = words.first() else {
+        return Err(CalcError::syntax(start, "empty stage"));
+    };
+    let &(name, operation) = OPERATIONS
+        .iter()
+        .find(|&&(known, _)| known == word)
+        .ok_or_else(|| CalcError::UnknownOperation {
+            at,
+            name: word.to_string(),
+        })?;
+    let mut nums: Vec<f64> = previous.into_iter().collect();
+    for &(at, word) in &words[1..] {
+        let n = functions::constant(word)
+            .map(Ok)
+            .unwrap_or_else(|| parse_number(word, separators))
+            .map_err(|reason| {
+                let message = match reason {
+                    Some(reason) => format!("invalid number `{}` ({})", word, reason),
+                    None => format!("invalid number `{}`", word),
+                };
+                CalcError::syntax(at, message)
+            })?;
+        nums.push(n);
+    }
+    match arity(name) {
+        Some(needed) if nums.len() < needed => Err(CalcError::TooFewNumbers {
+            function: name,
+            needed,
+            got: nums.len(),
+        }),
+        Some(needed) if nums.len() > needed => Err(CalcError::domain(format!(
+            "{} takes {} number{}, got {}",
+            name,
+            needed,
+            if needed == 1 { "" } else { "s" },
+            nums.len()
+        ))),
+        _ => {
+            let result = operation(&nums, strict)?;
+            if strict {
+                finite(name, None, None, result)
+            } else {
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// The whitespace-separated words of `text` with the byte offset of each, `text` starting
+/// at `start`.
+fn words(text: &str, start: usize) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(|c: char| !c.is_whitespace()) {
+        let word = &rest[i..];
+        let end = word.find(char::is_whitespace).unwrap_or(word.len());
+        words.push((start + text.len() - rest.len() + i, &word[..end]));
+        rest = &word[end..];
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_result_goes_first_in_the_next_stage() {
+        assert_eq!(eval_chain("add 1 2 3 | mul 2 | sub 1"), Ok(11.0));
+        assert_eq!(eval_chain("add 1 2 3|mul 2|sub 1"), Ok(11.0));
+        // 100 / 2 / 5, not 2 / 5 / 100
+        assert_eq!(eval_chain("add 100 | div 2 5"), Ok(10.0));
+        assert_eq!(eval_chain("add 2 | pow 3 2"), Ok(512.0));
+        assert_eq!(eval_chain("add 16 | sqrt | mod 3"), Ok(1.0));
+        assert_eq!(eval_chain("mul 2 pi | div pi"), Ok(2.0));
+        assert_eq!(eval_chain("add 1.5k 500"), Ok(2000.0));
+    }
+
+    #[test]
+    fn test_intermediate_results_keep_every_digit() {
+        let result = eval_chain("div 1 3 | mul 3").unwrap();
+        assert_eq!(result, 1.0);
+        let result = eval_chain("add 2 | sqrt | pow 2").unwrap();
+        assert!((result - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_unknown_operation_names_the_stage() {
+        let err = eval_chain("add 1 2 | times 2").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::Stage {
+                stage: 2,
+                error: Box::new(CalcError::UnknownOperation {
+                    at: 10,
+                    name: "times".to_string()
+                })
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "stage 2: unknown operation `times` at offset 10 \
+             (add, sub, mul, div, pow, mod, sqrt, cbrt, ln or exp)"
+        );
+        assert_eq!(err.offset(), Some(10));
+    }
+
+    #[test]
+    fn test_errors_in_a_stage() {
+        assert_eq!(
+            eval_chain("add 1 | div 0").unwrap_err().to_string(),
+            "stage 2: division by zero: operand 2 is zero"
+        );
+        assert_eq!(
+            eval_chain("add 1 | mul x").unwrap_err().to_string(),
+            "stage 2: invalid number `x` at offset 12"
+        );
+        assert_eq!(
+            eval_chain("add 1 ||").unwrap_err().to_string(),
+            "stage 2: empty stage at offset 7"
+        );
+        assert_eq!(
+            eval_chain("sub 1").unwrap_err().to_string(),
+            "stage 1: sub needs at least 2 numbers, got 1"
+        );
+        assert_eq!(
+            eval_chain("add 1 | sqrt 4").unwrap_err().to_string(),
+            "stage 2: sqrt takes 1 number, got 2"
+        );
+        assert_eq!(
+            eval_chain("mul 1e200 1e200 | div 1e300")
+                .unwrap_err()
+                .to_string(),
+            "stage 1: overflow in mul at operand 2"
+        );
+        assert_eq!(
+            eval_chain("add 1e300 | exp").unwrap_err().to_string(),
+            "stage 2: overflow in exp"
+        );
+        assert_eq!(
+            eval_chain_localized("mul 1e200 1e200 | div 1e300", Separators::default(), false),
+            Ok(f64::INFINITY)
+        );
+        assert_eq!(
+            eval_chain("mod 1").unwrap_err().to_string(),
+            "stage 1: mod needs at least 2 numbers, got 1"
+        );
+    }
+}
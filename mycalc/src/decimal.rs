@@ -0,0 +1,122 @@
+//! Base-10 arithmetic for `--decimal`, e.g. for money: numbers are `rust_decimal::Decimal`,
+//! so `0.1 + 0.2` is exactly `0.3`. Results keep at most 28 decimals, and one that doesn't
+//! fit in a `Decimal` is an overflow error.
+
+use crate::CalcError;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::str::FromStr;
+
+fn overflow(operation: &'static str) -> CalcError {
+    CalcError::overflow(operation)
+}
+
+/// Add all numbers.
+pub fn add_d(nums: &[Decimal]) -> Result<Decimal, CalcError> {
+    nums.iter().try_fold(Decimal::ZERO, |acc, x| {
+        acc.checked_add(*x).ok_or(overflow("add"))
+    })
+}
+
+/// Subtraction is left-associative.
+/// E.g.: [10, 3, 2] => (10 - 3 - 2) = 5
+pub fn sub_d(nums: &[Decimal]) -> Result<Decimal, CalcError> {
+    let (first, rest) = crate::operands("sub", nums)?;
+    rest.iter()
+        .try_fold(*first, |acc, x| acc.checked_sub(*x).ok_or(overflow("sub")))
+}
+
+/// Multiply all numbers.
+pub fn mul_d(nums: &[Decimal]) -> Result<Decimal, CalcError> {
+    nums.iter().try_fold(Decimal::ONE, |acc, x| {
+        acc.checked_mul(*x).ok_or(overflow("mul"))
+    })
+}
+
+/// Divide is left-associative. Error if any divisor is zero.
+/// E.g.: [20, 2, 5] => (20 / 2 / 5) = 2
+pub fn div_d(nums: &[Decimal]) -> Result<Decimal, CalcError> {
+    let (first, rest) = crate::operands("div", nums)?;
+    rest.iter().enumerate().try_fold(*first, |acc, (i, x)| {
+        if x.is_zero() {
+            return Err(CalcError::zero_divisor(i + 2));
+        }
+        acc.checked_div(*x).ok_or(overflow("div"))
+    })
+}
+
+/// `d` with exactly `precision` decimals, rounding half to even (banker's rounding).
+/// E.g.: (0.125, 2) => "0.12", (0.135, 2) => "0.14"
+pub fn format_d(d: Decimal, precision: usize) -> String {
+    let dp = u32::try_from(precision).unwrap_or(u32::MAX);
+    let rounded = d.round_dp_with_strategy(dp, RoundingStrategy::MidpointNearestEven);
+    format!("{:.*}", precision, rounded)
+}
+
+impl crate::input::Number for Decimal {
+    const NAME: &'static str = "decimal number";
+
+    fn parse(token: &str) -> Option<Self> {
+        Decimal::from_str(token)
+            .or_else(|_| Decimal::from_scientific(token))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Number;
+
+    fn d(raw: &str) -> Decimal {
+        Decimal::parse(raw).unwrap_or_else(|| panic!("{:?}", raw))
+    }
+
+    #[test]
+    fn test_point_one_plus_point_two_is_point_three() {
+        assert_eq!(add_d(&[d("0.1"), d("0.2")]).unwrap(), d("0.3"));
+        assert_eq!(
+            sub_d(&[d("0.3"), d("0.1"), d("0.2")]).unwrap(),
+            Decimal::ZERO
+        );
+        assert_eq!(mul_d(&[d("19.99"), d("3")]).unwrap(), d("59.97"));
+        assert_eq!(div_d(&[d("20"), d("2"), d("5")]).unwrap(), d("2"));
+        assert_eq!(d("1.5e3"), d("1500"));
+    }
+
+    #[test]
+    fn test_repeating_division_is_rounded_to_the_precision() {
+        let third = div_d(&[d("10"), d("3")]).unwrap();
+        assert_eq!(format_d(third, 2), "3.33");
+        assert_eq!(format_d(third, 5), "3.33333");
+        let two_thirds = div_d(&[d("2"), d("3")]).unwrap();
+        assert_eq!(format_d(two_thirds, 4), "0.6667");
+        assert_eq!(format_d(d("-2") / d("3"), 1), "-0.7");
+    }
+
+    #[test]
+    fn test_bankers_rounding() {
+        assert_eq!(format_d(d("0.125"), 2), "0.12");
+        assert_eq!(format_d(d("0.135"), 2), "0.14");
+        assert_eq!(format_d(d("2.5"), 0), "2");
+        assert_eq!(format_d(d("3.5"), 0), "4");
+        assert_eq!(format_d(d("7"), 3), "7.000");
+    }
+
+    #[test]
+    fn test_decimal_errors() {
+        assert_eq!(
+            div_d(&[d("1"), d("0.00")]).unwrap_err().to_string(),
+            "division by zero: operand 2 is zero"
+        );
+        assert_eq!(
+            sub_d(&[d("1")]).unwrap_err().to_string(),
+            "sub needs at least 2 numbers, got 1"
+        );
+        assert!(div_d(&[]).is_err());
+        assert_eq!(
+            mul_d(&[Decimal::MAX, d("2")]).unwrap_err().to_string(),
+            "overflow in mul"
+        );
+        assert!(Decimal::parse("1/3").is_none());
+    }
+}
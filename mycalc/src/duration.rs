@@ -0,0 +1,316 @@
+//! Durations for `mycalc time`, in seconds: read from `1h30m45s` or `HH:MM:SS(.fff)`, and
+//! written back in either form. Hours aren't limited to a day, so `27:00:00` is 27 hours.
+
+use crate::CalcError;
+
+/// Most decimals of a second [`format_hms`] and [`format_clock`] write: nanoseconds.
+pub const MAX_DECIMALS: usize = 9;
+
+/// The seconds of `text`: numbers followed by `h`, `m` or `s`, in that order and each at
+/// most once (`1h30m`, `90m`, `1.5h`, `45.25s`), or hours, minutes and seconds separated by
+/// colons, with or without leading zeros (`01:50:30`, `1:2:3.5`, `27:00:00.5`). A leading `-`
+/// makes it negative.
+/// E.g.: "1h30m" => 5400, "02:15:00" => 8100, "-90s" => -90
+pub fn parse_duration(text: &str) -> Result<f64, CalcError> {
+    let invalid = |reason| CalcError::InvalidDuration {
+        duration: text.to_string(),
+        reason,
+    };
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let seconds = if unsigned.contains(':') {
+        clock_seconds(unsigned).ok_or_else(|| {
+            invalid("expected hours:minutes:seconds, with minutes and seconds below 60")
+        })?
+    } else {
+        unit_seconds(unsigned).map_err(invalid)?
+    };
+    Ok(sign * seconds)
+}
+
+/// The seconds of `HH:MM:SS(.fff)`, with any number of hour digits; `None` if it isn't one.
+fn clock_seconds(text: &str) -> Option<f64> {
+    let mut parts = text.split(':');
+    let (hours, minutes, seconds) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() {
+        return None;
+    }
+    let whole = seconds
+        .split('.')
+        .next()
+        .expect("split yields at least one part");
+    if !is_digits(hours) || !is_digits(minutes) || !is_digits(whole) {
+        return None;
+    }
+    let (hours, minutes, seconds) = (decimal(hours)?, decimal(minutes)?, decimal(seconds)?);
+    if minutes >= 60.0 || seconds >= 60.0 {
+        return None;
+    }
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// The seconds of `1h30m45s` and the like, or why it isn't a duration.
+fn unit_seconds(text: &str) -> Result<f64, &'static str> {
+    const UNITS: [(char, f64); 3] = [('h', 3600.0), ('m', 60.0), ('s', 1.0)];
+    if text.is_empty() {
+        return Err("expected a number and h, m or s, or HH:MM:SS");
+    }
+    let mut seconds = 0.0;
+    let mut rest = text;
+    // Units before this one in UNITS have been given, or skipped
+    let mut next = 0;
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or("expected h, m or s after each number")?;
+        let (number, unit) = (&rest[..end], rest[end..].chars().next().expect("end < len"));
+        if number.is_empty() {
+            return Err("expected a number before each of h, m and s");
+        }
+        let number = decimal(number).ok_or("invalid number")?;
+        let i = UNITS
+            .iter()
+            .position(|&(known, _)| known == unit.to_ascii_lowercase())
+            .ok_or("expected h, m or s after each number")?;
+        if i < next {
+            return Err("h, m and s go in that order, each once");
+        }
+        seconds += number * UNITS[i].1;
+        next = i + 1;
+        rest = &rest[end + unit.len_utf8()..];
+    }
+    Ok(seconds)
+}
+
+fn is_digits(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+/// `text`, digits with a `.` among them or not (`12`, `1.5`, `.5`, `2.`), as a number;
+/// `None` if it isn't one.
+fn decimal(text: &str) -> Option<f64> {
+    let (int, frac) = text.split_once('.').unwrap_or((text, ""));
+    let digits = format!("{}{}", int, frac);
+    if !is_digits(&digits) {
+        return None;
+    }
+    format!("0{}.{}0", int, frac).parse().ok()
+}
+
+/// The sum of `durations`, in seconds, without the rounding errors of adding fractions of
+/// a second one at a time.
+pub fn total(durations: &[f64]) -> f64 {
+    crate::sum(durations)
+}
+
+/// `seconds` as hours, minutes and seconds, leaving out the ones that are zero, with up to
+/// `decimals` decimals of a second ([`MAX_DECIMALS`] at most) and no trailing zeros.
+/// E.g.: (5400, 2) => "1h30m", (-1470.5, 2) => "-24m30.5s", (0, 2) => "0s"
+pub fn format_hms(seconds: f64, decimals: usize) -> String {
+    let Some(parts) = Parts::new(seconds, decimals) else {
+        return seconds.to_string();
+    };
+    let mut text = parts.sign.to_string();
+    if parts.hours > 0 {
+        text += &format!("{}h", parts.hours);
+    }
+    if parts.minutes > 0 {
+        text += &format!("{}m", parts.minutes);
+    }
+    if parts.seconds > 0 || parts.frac.is_some() || text.len() == parts.sign.len() {
+        text += &format!("{}{}s", parts.seconds, parts.frac());
+    }
+    text
+}
+
+/// `seconds` as `HH:MM:SS`, with up to `decimals` decimals of a second ([`MAX_DECIMALS`] at
+/// most) and no trailing zeros; hours go beyond 24, and 99.
+/// E.g.: (8130, 2) => "02:15:30", (-1470.5, 2) => "-00:24:30.5", (97200, 0) => "27:00:00"
+pub fn format_clock(seconds: f64, decimals: usize) -> String {
+    match Parts::new(seconds, decimals) {
+        Some(parts) => format!(
+            "{}{:02}:{:02}:{:02}{}",
+            parts.sign,
+            parts.hours,
+            parts.minutes,
+            parts.seconds,
+            parts.frac()
+        ),
+        None => seconds.to_string(),
+    }
+}
+
+/// A number of seconds, rounded, split into what a duration is written with.
+struct Parts {
+    sign: &'static str,
+    hours: u128,
+    minutes: u128,
+    seconds: u128,
+    /// The decimals of the seconds without trailing zeros, if they aren't all zeros
+    frac: Option<String>,
+}
+
+impl Parts {
+    /// `seconds` rounded to `decimals` decimals and split; `None` if it isn't finite.
+    fn new(seconds: f64, decimals: usize) -> Option<Parts> {
+        if !seconds.is_finite() {
+            return None;
+        }
+        let decimals = decimals.min(MAX_DECIMALS);
+        let scale = 10u128.pow(decimals as u32);
+        // In units of 10^-decimals seconds, so carrying 59.999 into a minute is exact
+        let ticks = (seconds.abs() * scale as f64).round() as u128;
+        let (whole, frac) = (ticks / scale, ticks % scale);
+        let frac = format!("{:0width$}", frac, width = decimals);
+        let frac = frac.trim_end_matches('0');
+        Some(Parts {
+            sign: if seconds < 0.0 && ticks > 0 { "-" } else { "" },
+            hours: whole / 3600,
+            minutes: whole / 60 % 60,
+            seconds: whole % 60,
+            frac: (!frac.is_empty()).then(|| frac.to_string()),
+        })
+    }
+
+    /// `.` and the decimals, or nothing.
+    fn frac(&self) -> String {
+        self.frac
+            .as_ref()
+            .map(|frac| format!(".{}", frac))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        assert_eq!(parse_duration("1h30m45s"), Ok(5445.0));
+        assert_eq!(parse_duration("1h30m"), Ok(5400.0));
+        assert_eq!(parse_duration("2h"), Ok(7200.0));
+        assert_eq!(parse_duration("1H5S"), Ok(3605.0));
+        assert_eq!(parse_duration("-45m"), Ok(-2700.0));
+        assert_eq!(parse_duration("+45m"), Ok(2700.0));
+        assert_eq!(parse_duration("0s"), Ok(0.0));
+    }
+
+    #[test]
+    fn test_missing_and_overflowing_components() {
+        assert_eq!(parse_duration("90m"), Ok(5400.0));
+        assert_eq!(parse_duration("90s"), Ok(90.0));
+        assert_eq!(parse_duration("1h90s"), Ok(3690.0));
+        assert_eq!(parse_duration("36h"), Ok(129600.0));
+        assert_eq!(parse_duration("100m75s"), Ok(6075.0));
+    }
+
+    #[test]
+    fn test_fractional_seconds() {
+        assert_eq!(parse_duration("1.5h"), Ok(5400.0));
+        assert_eq!(parse_duration("0.25s"), Ok(0.25));
+        assert_eq!(parse_duration(".5m"), Ok(30.0));
+        assert_eq!(parse_duration("2m3.125s"), Ok(123.125));
+        assert_eq!(parse_duration("00:00:01.5"), Ok(1.5));
+        assert_eq!(parse_duration("01:02:03.004"), Ok(3723.004));
+        assert_eq!(total(&[0.1; 10]), 1.0);
+        assert_eq!(format_hms(1.25, 2), "1.25s");
+        assert_eq!(format_hms(1.256, 2), "1.26s");
+        assert_eq!(format_hms(1.5, 0), "2s");
+        assert_eq!(format_clock(3723.004, 3), "01:02:03.004");
+        assert_eq!(format_clock(3723.004, 2), "01:02:03");
+        // Rounding carries into the minutes and hours
+        assert_eq!(format_clock(3599.999, 2), "01:00:00");
+        assert_eq!(format_hms(59.996, 2), "1m");
+    }
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(parse_duration("02:15:00"), Ok(8100.0));
+        assert_eq!(parse_duration("1:50:30"), Ok(6630.0));
+        assert_eq!(parse_duration("1:2:3.5"), Ok(3723.5));
+        assert_eq!(parse_duration("0:0:0.25"), Ok(0.25));
+        assert_eq!(parse_duration("-00:24:30"), Ok(-1470.0));
+        assert_eq!(format_clock(8130.0, 2), "02:15:30");
+        assert_eq!(format_clock(-1470.0, 2), "-00:24:30");
+        assert_eq!(format_clock(0.0, 2), "00:00:00");
+        assert_eq!(format_clock(-0.001, 2), "00:00:00");
+    }
+
+    #[test]
+    fn test_over_24_hours() {
+        assert_eq!(parse_duration("27:00:00"), Ok(97200.0));
+        assert_eq!(parse_duration("100:00:01"), Ok(360001.0));
+        assert_eq!(format_clock(97200.0, 2), "27:00:00");
+        assert_eq!(format_clock(360001.0, 2), "100:00:01");
+        assert_eq!(format_hms(97200.0, 2), "27h");
+        assert_eq!(format_hms(90061.0, 2), "25h1m1s");
+    }
+
+    #[test]
+    fn test_format_hms() {
+        assert_eq!(format_hms(5400.0, 2), "1h30m");
+        assert_eq!(format_hms(8130.0, 2), "2h15m30s");
+        assert_eq!(format_hms(3630.0, 2), "1h30s");
+        assert_eq!(format_hms(-1470.5, 2), "-24m30.5s");
+        assert_eq!(format_hms(0.0, 2), "0s");
+        assert_eq!(format_hms(0.004, 2), "0s");
+        assert_eq!(format_hms(f64::INFINITY, 2), "inf");
+        // What it writes reads back
+        for seconds in [5400.0, 8130.0, 3630.0, -1470.5, 0.0, 90061.0] {
+            assert_eq!(parse_duration(&format_hms(seconds, 2)), Ok(seconds));
+            assert_eq!(parse_duration(&format_clock(seconds, 2)), Ok(seconds));
+        }
+    }
+
+    #[test]
+    fn test_invalid_durations() {
+        for (text, reason) in [
+            ("", "expected a number and h, m or s, or HH:MM:SS"),
+            ("90", "expected h, m or s after each number"),
+            ("1h30", "expected h, m or s after each number"),
+            ("1d", "expected h, m or s after each number"),
+            ("h", "expected a number before each of h, m and s"),
+            ("1.2.3s", "invalid number"),
+            (".s", "invalid number"),
+            ("30m1h", "h, m and s go in that order, each once"),
+            ("1m1m", "h, m and s go in that order, each once"),
+            (
+                "1:30",
+                "expected hours:minutes:seconds, with minutes and seconds below 60",
+            ),
+            (
+                "01:60:00",
+                "expected hours:minutes:seconds, with minutes and seconds below 60",
+            ),
+            (
+                "01:00:60",
+                "expected hours:minutes:seconds, with minutes and seconds below 60",
+            ),
+            (
+                "01:00:.5",
+                "expected hours:minutes:seconds, with minutes and seconds below 60",
+            ),
+            (
+                "1:00:00:00",
+                "expected hours:minutes:seconds, with minutes and seconds below 60",
+            ),
+        ] {
+            assert_eq!(
+                parse_duration(text),
+                Err(CalcError::InvalidDuration {
+                    duration: text.to_string(),
+                    reason
+                }),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            parse_duration("30m1h").unwrap_err().to_string(),
+            "invalid duration `30m1h`: h, m and s go in that order, each once"
+        );
+    }
+}
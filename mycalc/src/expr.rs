@@ -0,0 +1,846 @@
+//! Arithmetic expressions for `mycalc eval`: a tokenizer and a Pratt parser that evaluates
+//! as it parses.
+//!
+//! `^` (or `**`) binds tighter than unary minus, as in written maths: `-2^2` is `-(2^2)`.
+//! An expression can start with assignments, `x = y = 2 * pi`, and use the variables of an
+//! [`Env`] afterwards.
+//!
+//! With [`Locale::Comma`], literals take a decimal comma (`1,5`) and `;` separates the
+//! arguments of a function instead of `,`. Literals can have `_` between digits and, unless
+//! [`Separators::suffixes`] is off, an SI suffix: `2.5k`, `750m`.
+
+use crate::functions;
+use crate::locale::{Locale, Separators};
+use crate::suffix::SUFFIXES;
+use crate::{finite, power, CalcError};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Num(f64),
+    Ident(&'a str),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    /// Between the arguments of a function: `,`, or `;` in the comma locale
+    Comma(char),
+    Assign,
+    LParen,
+    RParen,
+}
+
+impl Token<'_> {
+    fn describe(self) -> String {
+        match self {
+            Token::Num(n) => format!("number {}", n),
+            Token::Ident(name) => format!("`{}`", name),
+            Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Caret => {
+                self.symbol().to_string()
+            }
+            Token::Comma(c) => format!("`{}`", c),
+            Token::Assign => "`=`".to_string(),
+            Token::LParen => "`(`".to_string(),
+            Token::RParen => "`)`".to_string(),
+        }
+    }
+
+    /// The symbol of a binary operator, for errors.
+    fn symbol(self) -> &'static str {
+        match self {
+            Token::Plus => "`+`",
+            Token::Minus => "`-`",
+            Token::Star => "`*`",
+            Token::Slash => "`/`",
+            Token::Caret => "`^`",
+            _ => unreachable!("only binary operators have a symbol"),
+        }
+    }
+}
+
+/// Tokens with the byte offset where each starts.
+fn tokenize(expr: &str, separators: Separators) -> Result<Vec<(Token<'_>, usize)>, CalcError> {
+    let locale = separators.locale;
+    let bytes = expr.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let token = match c {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                i += 1;
+                continue;
+            }
+            b'+' => Token::Plus,
+            b'-' => Token::Minus,
+            b'*' if bytes.get(i + 1) == Some(&b'*') => {
+                tokens.push((Token::Caret, i));
+                i += 2;
+                continue;
+            }
+            b'*' => Token::Star,
+            b'/' => Token::Slash,
+            b'^' => Token::Caret,
+            b',' if locale == Locale::Dot => Token::Comma(','),
+            b';' if locale == Locale::Comma => Token::Comma(';'),
+            b'=' => Token::Assign,
+            b'(' => Token::LParen,
+            b')' => Token::RParen,
+            b'0'..=b'9' | b'.' | b',' => {
+                let (n, end) = number(expr, i, separators)?;
+                tokens.push((Token::Num(n), i));
+                i = end;
+                continue;
+            }
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => {
+                let end = expr[i..]
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .map_or(expr.len(), |len| i + len);
+                tokens.push((Token::Ident(&expr[i..end]), i));
+                i = end;
+                continue;
+            }
+            _ => {
+                let ch = expr[i..].chars().next().unwrap_or('?');
+                return Err(CalcError::syntax(
+                    i,
+                    format!("unexpected character `{}`", ch),
+                ));
+            }
+        };
+        tokens.push((token, i));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// The number starting at `start`: digits with an optional fraction, exponent and suffix
+/// (`12`, `.5`, `1.5e3`, `2E-4`, `1_000`, `2.5k`, or `1,5e3` in the comma locale). Returns
+/// it with the offset just past it.
+fn number(expr: &str, start: usize, separators: Separators) -> Result<(f64, usize), CalcError> {
+    let locale = separators.locale;
+    let bytes = expr.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'_') {
+            i += 1;
+        }
+        i
+    };
+    // In the comma locale a `.` is taken too, for the shared parser to reject
+    let separator = |b: u8| b == b'.' || (b == b',' && locale == Locale::Comma);
+    let mut end = digits(start);
+    if end < bytes.len() && separator(bytes[end]) {
+        end = digits(end + 1);
+    }
+    if end == start + 1 && separator(bytes[start]) {
+        return Err(CalcError::syntax(
+            start,
+            format!("expected digits around `{}`", bytes[start] as char),
+        ));
+    }
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exp = end + 1;
+        if exp < bytes.len() && (bytes[exp] == b'+' || bytes[exp] == b'-') {
+            exp += 1;
+        }
+        let exp_end = digits(exp);
+        if exp_end == exp {
+            return Err(CalcError::syntax(end, "expected digits after the exponent"));
+        }
+        end = exp_end;
+    }
+    // A suffix is a letter on its own: `2m` is 0.002, but `2mx` isn't a number
+    if separators.suffixes {
+        let mut rest = expr[end..].chars();
+        if let Some(c) = rest.next() {
+            let is_suffix = SUFFIXES.iter().any(|&(suffix, _)| suffix == c);
+            if is_suffix && !rest.next().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+                end += c.len_utf8();
+            }
+        }
+    }
+    let literal = Separators {
+        grouping: false,
+        ..separators
+    }
+    .normalize(&expr[start..end])
+    .map_err(|reason| CalcError::syntax(start, reason))?;
+    let n = literal
+        .parse()
+        .map_err(|_| CalcError::syntax(start, "invalid number"))?;
+    Ok((n, end))
+}
+
+/// Binding power of unary minus: tighter than `* /`, looser than `^`.
+const PREFIX_BP: u8 = 5;
+
+/// Left and right binding powers of a binary operator; left-associative ones bind
+/// tighter on the right, the right-associative `^` tighter on the left.
+fn infix_bp(token: Token) -> Option<(u8, u8)> {
+    match token {
+        Token::Plus | Token::Minus => Some((1, 2)),
+        Token::Star | Token::Slash => Some((3, 4)),
+        Token::Caret => Some((8, 7)),
+        _ => None,
+    }
+}
+
+/// The variables of an `eval` or a REPL session. `ans` is the last result.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Env {
+    vars: HashMap<String, f64>,
+}
+
+impl Env {
+    pub fn new() -> Env {
+        Env::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.vars.get(name).copied()
+    }
+
+    /// The names of the variables set so far, including `ans`.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(String::as_str)
+    }
+
+    /// Sets the variable `name`; error if it's a constant, a function or `ans`.
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), CalcError> {
+        if let Some(kind) = reserved(name) {
+            return Err(CalcError::Reserved {
+                at: None,
+                name: name.to_string(),
+                kind,
+            });
+        }
+        self.vars.insert(name.to_string(), value);
+        Ok(())
+    }
+}
+
+/// What `name` is when it can't be assigned.
+fn reserved(name: &str) -> Option<&'static str> {
+    if functions::constant(name).is_some() {
+        Some("constant")
+    } else if functions::lookup(name).is_some() {
+        Some("function")
+    } else if name == "ans" {
+        Some("last result")
+    } else {
+        None
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [(Token<'a>, usize)],
+    env: &'a Env,
+    pos: usize,
+    /// Length of the expression, reported as the offset of a missing token
+    end: usize,
+    /// What separates the arguments of a function, for errors
+    separator: char,
+    /// Whether a result that isn't a finite number is an error
+    strict: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<(Token<'a>, usize)> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<(Token<'a>, usize)> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn expr(&mut self, min_bp: u8) -> Result<f64, CalcError> {
+        let mut lhs = match self.next() {
+            Some((Token::Num(n), _)) => n,
+            Some((Token::Minus, _)) => -self.expr(PREFIX_BP)?,
+            Some((Token::Ident(name), at)) => self.ident(name, at)?,
+            Some((Token::LParen, at)) => {
+                let value = self.expr(0)?;
+                match self.next() {
+                    Some((Token::RParen, _)) => value,
+                    Some((token, at)) => {
+                        return Err(CalcError::syntax(
+                            at,
+                            format!("expected `)`, found {}", token.describe()),
+                        ))
+                    }
+                    None => return Err(CalcError::syntax(at, "unclosed `(`")),
+                }
+            }
+            Some((token, at)) => {
+                return Err(CalcError::syntax(
+                    at,
+                    format!("expected a number, found {}", token.describe()),
+                ))
+            }
+            None => return Err(CalcError::syntax(self.end, "expected a number at the end")),
+        };
+        while let Some((op, at)) = self.peek() {
+            let Some((l_bp, r_bp)) = infix_bp(op) else {
+                break;
+            };
+            if l_bp < min_bp {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.expr(r_bp)?;
+            let result = match op {
+                Token::Plus => lhs + rhs,
+                Token::Minus => lhs - rhs,
+                Token::Star => lhs * rhs,
+                Token::Slash if rhs == 0.0 => {
+                    return Err(CalcError::DivisionByZero {
+                        at: Some(at),
+                        operand: None,
+                    })
+                }
+                Token::Slash => lhs / rhs,
+                Token::Caret => power(lhs, rhs).map_err(|err| match err {
+                    CalcError::Domain { message, .. } => CalcError::Domain {
+                        at: Some(at),
+                        message,
+                    },
+                    err => err,
+                })?,
+                _ => unreachable!("infix_bp only accepts binary operators"),
+            };
+            lhs = self.finite(op.symbol(), at, result)?;
+        }
+        Ok(lhs)
+    }
+
+    /// `x`, the result of `operation` at offset `at`; in strict mode, an error if it isn't
+    /// a finite number.
+    fn finite(&self, operation: &'static str, at: usize, x: f64) -> Result<f64, CalcError> {
+        if self.strict {
+            finite(operation, Some(at), None, x)
+        } else {
+            Ok(x)
+        }
+    }
+
+    /// The value of the name at offset `at`: a function call, a constant or a variable.
+    fn ident(&mut self, name: &str, at: usize) -> Result<f64, CalcError> {
+        if functions::lookup(name).is_some() || matches!(self.peek(), Some((Token::LParen, _))) {
+            return self.call(name, at);
+        }
+        if let Some(value) = functions::constant(name) {
+            // Strictly, any use of `inf` would fail later; say why where it is
+            if self.strict && value.is_infinite() {
+                return Err(CalcError::Domain {
+                    at: Some(at),
+                    message: format!("`{}` is not a finite number (strict mode)", name),
+                });
+            }
+            return Ok(value);
+        }
+        self.env
+            .get(name)
+            .ok_or_else(|| CalcError::UndefinedVariable {
+                at,
+                name: name.to_string(),
+            })
+    }
+
+    /// The call of the function `name` (at offset `at`), from its `(` to its `)`.
+    fn call(&mut self, name: &str, at: usize) -> Result<f64, CalcError> {
+        let Some(function) = functions::lookup(name) else {
+            return Err(CalcError::UnknownFunction {
+                at,
+                name: name.to_string(),
+                suggestion: functions::suggest(name),
+            });
+        };
+        match self.next() {
+            Some((Token::LParen, _)) => {}
+            _ => {
+                return Err(CalcError::syntax(
+                    at,
+                    format!("expected `(` after `{}`", name),
+                ))
+            }
+        }
+        let mut args = Vec::new();
+        if let Some((Token::RParen, _)) = self.peek() {
+            self.pos += 1;
+        } else {
+            loop {
+                args.push(self.expr(0)?);
+                match self.next() {
+                    Some((Token::Comma(_), _)) => continue,
+                    Some((Token::RParen, _)) => break,
+                    Some((token, at)) => {
+                        return Err(CalcError::syntax(
+                            at,
+                            format!(
+                                "expected `{}` or `)`, found {}",
+                                self.separator,
+                                token.describe()
+                            ),
+                        ))
+                    }
+                    None => return Err(CalcError::syntax(at, format!("unclosed `{}(`", name))),
+                }
+            }
+        }
+        if !function.accepts(args.len()) {
+            return Err(CalcError::syntax(
+                at,
+                format!("`{}` {}, got {}", name, function.arity(), args.len()),
+            ));
+        }
+        let result = (function.apply)(&args, self.strict).map_err(|message| CalcError::Domain {
+            at: Some(at),
+            message,
+        })?;
+        self.finite(function.name, at, result)
+    }
+}
+
+/// Evaluates an arithmetic expression with `+ - * / ^`, parentheses, unary minus, the
+/// [`functions`] and floating literals such as `1.5e3`. A result that isn't a finite
+/// number, of an operator, a function or the whole expression, is an error.
+/// E.g.: "2 + 3 * (4 - 1) / 2" => 6.5
+pub fn eval(expr: &str) -> Result<f64, CalcError> {
+    eval_in(expr, &mut Env::new())
+}
+
+/// Evaluates `expr` like [`eval`], with the variables of `env`. Leading assignments
+/// (`x = y = 3 * 4`) set those variables to the result, and every result is stored in
+/// `ans`; on an error `env` is unchanged.
+pub fn eval_in(expr: &str, env: &mut Env) -> Result<f64, CalcError> {
+    eval_localized(expr, env, Separators::default(), true)
+}
+
+/// Evaluates `expr` like [`eval_in`], reading its literals with the decimal separator and
+/// suffixes of `separators`; digit groups aren't taken in expressions. Without `strict`,
+/// results may be infinite or NaN.
+/// E.g.: ("max(1,5; 2)", comma locale) => 2
+pub fn eval_localized(
+    expr: &str,
+    env: &mut Env,
+    separators: Separators,
+    strict: bool,
+) -> Result<f64, CalcError> {
+    let locale = separators.locale;
+    let tokens = tokenize(expr, separators)?;
+    if tokens.is_empty() {
+        return Err(CalcError::syntax(0, "empty expression"));
+    }
+    let mut targets = Vec::new();
+    let mut rest = &tokens[..];
+    while let [(Token::Ident(name), at), (Token::Assign, _), after @ ..] = rest {
+        if let Some(kind) = reserved(name) {
+            return Err(CalcError::Reserved {
+                at: Some(*at),
+                name: name.to_string(),
+                kind,
+            });
+        }
+        targets.push(*name);
+        rest = after;
+    }
+    let separator = match locale {
+        Locale::Dot => ',',
+        Locale::Comma => ';',
+    };
+    let value = evaluate(rest, expr.len(), env, separator, strict)?;
+    for name in targets {
+        env.vars.insert(name.to_string(), value);
+    }
+    env.vars.insert("ans".to_string(), value);
+    Ok(value)
+}
+
+/// The value of the expression made of `tokens`, which end at offset `end`.
+fn evaluate(
+    tokens: &[(Token<'_>, usize)],
+    end: usize,
+    env: &Env,
+    separator: char,
+    strict: bool,
+) -> Result<f64, CalcError> {
+    let mut parser = Parser {
+        tokens,
+        env,
+        pos: 0,
+        end,
+        separator,
+        strict,
+    };
+    let value = parser.expr(0)?;
+    match parser.peek() {
+        None if strict => finite("the expression", None, None, value),
+        None => Ok(value),
+        Some((Token::RParen, at)) => Err(CalcError::syntax(at, "unmatched `)`")),
+        Some((token, at)) => Err(CalcError::syntax(
+            at,
+            format!("expected an operator, found {}", token.describe()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f64 = 1e-9;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < EPS
+    }
+
+    fn ok(expr: &str) -> f64 {
+        eval(expr).unwrap_or_else(|e| panic!("{:?}: {}", expr, e))
+    }
+
+    /// Byte offset of the error for `expr`.
+    fn err_at(expr: &str) -> usize {
+        eval(expr).unwrap_err().offset().unwrap()
+    }
+
+    #[test]
+    fn test_example_from_the_help() {
+        assert!(approx_eq(ok("2 + 3 * (4 - 1) / 2"), 6.5));
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert!(approx_eq(ok("2 + 3 * 4"), 14.0));
+        assert!(approx_eq(ok("2 * 3 + 4"), 10.0));
+        assert!(approx_eq(ok("10 - 6 / 2"), 7.0));
+        assert!(approx_eq(ok("1 + 2 * 3 - 4 / 2"), 5.0));
+    }
+
+    #[test]
+    fn test_left_associativity() {
+        assert!(approx_eq(ok("10 - 3 - 2"), 5.0));
+        assert!(approx_eq(ok("20 / 2 / 5"), 2.0));
+        assert!(approx_eq(ok("2 * 6 / 3 * 2"), 8.0));
+        assert!(approx_eq(ok("1 - 2 + 3"), 2.0));
+    }
+
+    #[test]
+    fn test_nested_parens() {
+        assert!(approx_eq(ok("(2 + 3) * 4"), 20.0));
+        assert!(approx_eq(ok("((1 + 2) * (3 + 4)) / 7"), 3.0));
+        assert!(approx_eq(ok("(((5)))"), 5.0));
+        assert!(approx_eq(ok("10 - (3 - 2)"), 9.0));
+        assert!(approx_eq(ok("2 * (3 + (4 - (1 + 1)))"), 10.0));
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        assert!(approx_eq(ok("-3"), -3.0));
+        assert!(approx_eq(ok("--3"), 3.0));
+        assert!(approx_eq(ok("2 * -3"), -6.0));
+        assert!(approx_eq(ok("-2 * 3"), -6.0));
+        assert!(approx_eq(ok("-(2 + 3)"), -5.0));
+        assert!(approx_eq(ok("4 - -1"), 5.0));
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert!(approx_eq(ok("1.5e3"), 1500.0));
+        assert!(approx_eq(ok("2E-2"), 0.02));
+        assert!(approx_eq(ok("1e+2 + .5"), 100.5));
+        assert!(approx_eq(ok("3.25"), 3.25));
+        assert!(approx_eq(ok("7."), 7.0));
+    }
+
+    #[test]
+    fn test_malformed_input_reports_the_offset() {
+        assert_eq!(err_at(""), 0);
+        assert_eq!(err_at("   "), 0);
+        assert_eq!(err_at("2 +"), 3);
+        assert_eq!(err_at("2 + * 3"), 4);
+        assert_eq!(err_at("(1 + 2"), 0);
+        assert_eq!(err_at("1 + 2)"), 5);
+        assert_eq!(err_at("2 3"), 2);
+        assert_eq!(err_at("2 (3)"), 2);
+        assert_eq!(err_at("()"), 1);
+        assert_eq!(err_at("1 + x"), 4);
+        assert_eq!(err_at("1e"), 1);
+        assert_eq!(err_at("1.5e+"), 3);
+        assert_eq!(err_at(". + 1"), 0);
+        assert_eq!(err_at("1.2.3"), 3);
+        assert_eq!(err_at("(1 2)"), 3);
+    }
+
+    #[test]
+    fn test_power_is_right_associative_and_above_unary_minus() {
+        assert!(approx_eq(ok("2 ^ 3 ^ 2"), 512.0));
+        assert!(approx_eq(ok("2 ** 3 ** 2"), 512.0));
+        assert!(approx_eq(ok("(2 ^ 3) ^ 2"), 64.0));
+        assert!(approx_eq(ok("-2 ^ 2"), -4.0));
+        assert!(approx_eq(ok("(-2) ^ 2"), 4.0));
+        assert!(approx_eq(ok("2 ^ -1"), 0.5));
+        assert!(approx_eq(ok("2 * 3 ^ 2"), 18.0));
+        assert!(approx_eq(ok("2 ^ -1 * 4"), 2.0));
+        assert!(approx_eq(ok("4 ^ 0.5"), 2.0));
+        assert_eq!(
+            eval("(-8) ^ 0.5"),
+            Err(CalcError::Domain {
+                at: Some(5),
+                message: "-8^0.5 is not a real number (negative base with a fractional exponent)"
+                    .to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_function_calls() {
+        assert!(approx_eq(ok("sqrt(16) + abs(-3)"), 7.0));
+        assert!(approx_eq(ok("min(4, 2, 8)"), 2.0));
+        assert!(approx_eq(ok("max(1, 2 * 5, 3)"), 10.0));
+        assert!(approx_eq(ok("log(1000)"), 3.0));
+        assert!(approx_eq(ok("log(8, 2)"), 3.0));
+        assert!(approx_eq(ok("ln(1)"), 0.0));
+        assert!(approx_eq(ok("sin(0) + cos(0) + tan(0)"), 1.0));
+        assert!(approx_eq(ok("-sqrt(4) ^ 2"), -4.0));
+        assert!(approx_eq(ok("sqrt(sqrt(81))"), 3.0));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        assert_eq!(
+            eval("1 + sqr(4)"),
+            Err(CalcError::UnknownFunction {
+                at: 4,
+                name: "sqr".to_string(),
+                suggestion: Some("sqrt"),
+            })
+        );
+        assert_eq!(
+            eval("sqrt(1, 2)").unwrap_err().to_string(),
+            "`sqrt` takes 1 argument, got 2 at offset 0"
+        );
+        assert_eq!(
+            eval("max()").unwrap_err().to_string(),
+            "`max` takes at least 1 argument, got 0 at offset 0"
+        );
+        assert_eq!(
+            eval("2 * sqrt(-4)").unwrap_err().to_string(),
+            "sqrt of a negative number (-4) at offset 4"
+        );
+        assert_eq!(err_at("ln(0)"), 0);
+        assert_eq!(err_at("sqrt 4"), 0);
+        assert_eq!(err_at("max(1, 2"), 0);
+        assert_eq!(err_at("max(1; 2)"), 5);
+        assert_eq!(err_at("max(1,)"), 6);
+    }
+
+    #[test]
+    fn test_variables_and_chained_assignments() {
+        let mut env = Env::new();
+        assert_eq!(eval_in("x = 3 * 4", &mut env), Ok(12.0));
+        assert_eq!(eval_in("y = x / 2", &mut env), Ok(6.0));
+        assert_eq!(eval_in("a = b = x + y", &mut env), Ok(18.0));
+        assert_eq!(env.get("a"), Some(18.0));
+        assert_eq!(env.get("b"), Some(18.0));
+        assert_eq!(eval_in("x = x + 1", &mut env), Ok(13.0));
+        assert!(approx_eq(
+            eval_in("2 * pi * x", &mut env).unwrap(),
+            26.0 * std::f64::consts::PI
+        ));
+        assert!(approx_eq(eval_in("ln(e)", &mut env).unwrap(), 1.0));
+        // Variables don't change eval without an environment
+        assert_eq!(err_at("x = 1 + y"), 8);
+    }
+
+    #[test]
+    fn test_constants() {
+        assert!(approx_eq(ok("cos(pi)"), -1.0));
+        assert!(approx_eq(ok("tau / 2 - pi"), 0.0));
+        assert!(approx_eq(ok("phi * phi - phi"), 1.0));
+        assert!(approx_eq(ok("ln(e ^ 2)"), 2.0));
+        let lenient =
+            |expr: &str| eval_localized(expr, &mut Env::new(), Separators::default(), false);
+        assert_eq!(lenient("-inf"), Ok(f64::NEG_INFINITY));
+        assert_eq!(lenient("1 / inf"), Ok(0.0));
+        let mut env = Env::new();
+        env.set("r", 2.0).unwrap();
+        assert!(approx_eq(
+            eval_in("2 * pi * r", &mut env).unwrap(),
+            4.0 * std::f64::consts::PI
+        ));
+        for name in ["pi", "e", "tau", "phi", "inf"] {
+            assert_eq!(
+                eval_in(&format!("{} = 3", name), &mut env)
+                    .unwrap_err()
+                    .to_string(),
+                format!("can't assign to the constant `{}` at offset 0", name)
+            );
+            assert!(env.set(name, 3.0).is_err());
+        }
+    }
+
+    #[test]
+    fn test_results_that_are_not_finite() {
+        assert_eq!(
+            eval("0 / 0"),
+            Err(CalcError::DivisionByZero {
+                at: Some(2),
+                operand: None
+            })
+        );
+        assert_eq!(
+            eval("1 + 1e308 * 10").unwrap_err(),
+            CalcError::Overflow {
+                operation: "`*`",
+                at: Some(10),
+                operand: None
+            }
+        );
+        // `inf` itself, where it is, rather than what it makes of the expression
+        assert_eq!(
+            eval("2 + (inf - inf)").unwrap_err().to_string(),
+            "`inf` is not a finite number (strict mode) at offset 5"
+        );
+        assert_eq!(err_at("1 / inf"), 4);
+        // Even when the rest of the expression makes it finite again
+        assert_eq!(err_at("1 / (1e300 ^ 2)"), 11);
+        assert_eq!(err_at("sqrt(1e308) * 2 ^ 600"), 12);
+        assert_eq!(
+            eval("-inf").unwrap_err().to_string(),
+            "`inf` is not a finite number (strict mode) at offset 1"
+        );
+        // Where tan is undefined, as with `mycalc tan`
+        assert_eq!(
+            eval("1 + tan(pi / 2)").unwrap_err().to_string(),
+            "tan is undefined at 1.5707963267948966 radians at offset 4"
+        );
+        assert!(approx_eq(ok("tan(pi / 4)"), 1.0));
+        let lenient =
+            |expr: &str| eval_localized(expr, &mut Env::new(), Separators::default(), false);
+        assert_eq!(lenient("1 / (1e300 ^ 2)"), Ok(0.0));
+        assert_eq!(lenient("1e308 * 10"), Ok(f64::INFINITY));
+        assert!(lenient("inf - inf").unwrap().is_nan());
+        assert!(lenient("tan(pi / 2)").unwrap() > 1e15);
+        assert!(lenient("0 / 0").is_err());
+    }
+
+    #[test]
+    fn test_ans_is_the_last_result() {
+        let mut env = Env::new();
+        assert_eq!(
+            eval_in("ans", &mut env).unwrap_err().to_string(),
+            "undefined variable `ans` at offset 0"
+        );
+        assert_eq!(eval_in("2 + 3", &mut env), Ok(5.0));
+        assert_eq!(eval_in("ans * 2", &mut env), Ok(10.0));
+        assert_eq!(eval_in("x = 7", &mut env), Ok(7.0));
+        assert_eq!(env.get("ans"), Some(7.0));
+        // An error leaves ans and the variables alone
+        assert!(eval_in("y = 1 / 0", &mut env).is_err());
+        assert_eq!(env.get("ans"), Some(7.0));
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn test_undefined_and_reserved_names() {
+        let mut env = Env::new();
+        assert_eq!(
+            eval_in("1 + foo * 2", &mut env),
+            Err(CalcError::UndefinedVariable {
+                at: 4,
+                name: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            eval_in("pi = 3", &mut env).unwrap_err().to_string(),
+            "can't assign to the constant `pi` at offset 0"
+        );
+        assert_eq!(
+            eval_in("x = sqrt = 4", &mut env).unwrap_err().to_string(),
+            "can't assign to the function `sqrt` at offset 4"
+        );
+        assert_eq!(
+            env.set("ans", 1.0).unwrap_err().to_string(),
+            "can't assign to the last result `ans`"
+        );
+        assert!(env.set("e", 1.0).is_err());
+        assert_eq!(env.get("x"), None);
+        assert_eq!(err_at("1 = 2"), 2);
+        assert_eq!(err_at("2 + 1 = 2"), 6);
+        assert_eq!(err_at("x ="), 3);
+    }
+
+    #[test]
+    fn test_division_by_zero_points_at_the_operator() {
+        let err = eval("1 + 4 / (2 - 2)").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::DivisionByZero {
+                at: Some(6),
+                operand: None
+            }
+        );
+        assert!(approx_eq(ok("0 / 5"), 0.0));
+    }
+
+    #[test]
+    fn test_underscores_and_suffixes_in_literals() {
+        assert!(approx_eq(ok("1_000 + 2.5k"), 3500.0));
+        assert!(approx_eq(ok("max(1k, 750m) * 2"), 2000.0));
+        assert!(approx_eq(ok("3M / 1.5G"), 0.002));
+        assert!(approx_eq(ok("2µ + 15n"), 2.015e-6));
+        let mut env = Env::new();
+        env.set("m", 4.0).unwrap();
+        assert!(approx_eq(eval_in("2m", &mut env).unwrap(), 0.002));
+        assert!(approx_eq(eval_in("2 * m", &mut env).unwrap(), 8.0));
+        let err = eval("1__0").unwrap_err();
+        assert_eq!(err.to_string(), "a `_` goes between two digits at offset 0");
+        assert_eq!(
+            eval("2e3k").unwrap_err().to_string(),
+            "a suffix can't follow an exponent at offset 0"
+        );
+        // A letter followed by more isn't a suffix
+        assert!(eval("2mx").is_err());
+        let no_suffixes = Separators {
+            suffixes: false,
+            ..Separators::default()
+        };
+        assert!(eval_localized("2k", &mut Env::new(), no_suffixes, true).is_err());
+        assert!(approx_eq(
+            eval_localized("1_000", &mut Env::new(), no_suffixes, true).unwrap(),
+            1000.0
+        ));
+    }
+
+    #[test]
+    fn test_decimal_comma_literals() {
+        let comma = |expr: &str| {
+            eval_localized(
+                expr,
+                &mut Env::new(),
+                Separators::new(Locale::Comma, false),
+                true,
+            )
+        };
+        assert!(approx_eq(comma("1,5 * 2").unwrap(), 3.0));
+        assert!(approx_eq(comma("max(1,5; 2,25; ,5)").unwrap(), 2.25));
+        assert!(approx_eq(comma("2,5e2").unwrap(), 250.0));
+        let err = comma("1.5 + 1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the decimal separator is `,` with --locale comma at offset 0"
+        );
+        let err = comma("max(1 , 2)").unwrap_err();
+        assert_eq!(err.to_string(), "expected digits around `,` at offset 6");
+        let err = comma("max(1").unwrap_err();
+        assert_eq!(err.to_string(), "unclosed `max(` at offset 0");
+        let err = comma("max(1 2)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected `;` or `)`, found number 2 at offset 6"
+        );
+    }
+}
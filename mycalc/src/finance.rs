@@ -0,0 +1,245 @@
+//! Money over time for `mycalc finance`: savings growing with compound interest, and the
+//! monthly payment of an amortized loan, each with a year-by-year schedule. Rates are
+//! yearly fractions (`0.05` for 5%); a rate of zero is no interest, and negative rates or
+//! periods are errors.
+
+use crate::CalcError;
+
+/// One year of a schedule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Year {
+    /// From 1; the last year may be a part of one
+    pub year: u32,
+    /// Interest earned on savings, or paid on a loan, during the year
+    pub interest: f64,
+    /// Principal of a loan paid off during the year; 0 for savings
+    pub principal: f64,
+    /// What the savings are worth, or what is left to pay of a loan, at the end of the year
+    pub balance: f64,
+}
+
+/// Error if `rate` is negative or `years` is.
+fn check(rate: f64, years: f64) -> Result<(), CalcError> {
+    if rate < 0.0 {
+        return Err(CalcError::domain(format!("a negative rate ({})", rate)));
+    }
+    if years < 0.0 {
+        return Err(CalcError::domain(format!(
+            "a negative number of years ({})",
+            years
+        )));
+    }
+    Ok(())
+}
+
+/// What `principal` grows to in `years` at the yearly `rate`, compounded `per_year` times a
+/// year: principal * (1 + rate / per_year)^(per_year * years).
+/// E.g.: (1000, 0.05, 10, 1) => 1628.89, (1000, 0.05, 10, 12) => 1647.01
+pub fn compound(principal: f64, rate: f64, years: f64, per_year: u32) -> Result<f64, CalcError> {
+    check(rate, years)?;
+    if per_year == 0 {
+        return Err(CalcError::domain("interest compounds at least once a year"));
+    }
+    if rate == 0.0 {
+        return Ok(principal);
+    }
+    let per_year = f64::from(per_year);
+    Ok(principal * (1.0 + rate / per_year).powf(per_year * years))
+}
+
+/// The balance of [`compound`] and the interest earned in each year, a last part of one
+/// included.
+pub fn compound_schedule(
+    principal: f64,
+    rate: f64,
+    years: f64,
+    per_year: u32,
+) -> Result<Vec<Year>, CalcError> {
+    compound(principal, rate, years, per_year)?;
+    let mut schedule = Vec::new();
+    let mut previous = principal;
+    for year in 1..=years.ceil() as u32 {
+        let balance = compound(principal, rate, years.min(f64::from(year)), per_year)?;
+        schedule.push(Year {
+            year,
+            interest: balance - previous,
+            principal: 0.0,
+            balance,
+        });
+        previous = balance;
+    }
+    Ok(schedule)
+}
+
+/// The number of monthly payments of a loan of `years`, which must be a positive whole
+/// number of months.
+fn months(years: f64) -> Result<f64, CalcError> {
+    let months = years * 12.0;
+    if months <= 0.0 {
+        return Err(CalcError::domain(format!(
+            "a loan needs a positive number of years, got {}",
+            years
+        )));
+    }
+    if months.fract() != 0.0 {
+        return Err(CalcError::domain(format!(
+            "a loan lasts a whole number of months, got {} years",
+            years
+        )));
+    }
+    Ok(months)
+}
+
+/// The monthly payment that pays off `principal` in `years` at the yearly `rate`, with
+/// interest on what's left each month: principal * r / (1 - (1 + r)^-n), for the monthly
+/// rate r and n months; with no interest, principal / n.
+/// E.g.: (250000, 0.045, 30) => 1266.71, (12000, 0, 1) => 1000
+pub fn payment(principal: f64, rate: f64, years: f64) -> Result<f64, CalcError> {
+    check(rate, years)?;
+    let months = months(years)?;
+    if rate == 0.0 {
+        return Ok(principal / months);
+    }
+    let r = rate / 12.0;
+    Ok(principal * r / (1.0 - (1.0 + r).powf(-months)))
+}
+
+/// The interest and principal paid with the [`payment`]s of each year, and the balance
+/// left after them. The last payment pays off what's left, so the balance ends at 0
+/// instead of a rounding error from it.
+pub fn payment_schedule(principal: f64, rate: f64, years: f64) -> Result<Vec<Year>, CalcError> {
+    let payment = payment(principal, rate, years)?;
+    let months = months(years)? as u32;
+    let mut schedule = Vec::new();
+    let mut balance = principal;
+    let (mut interest, mut paid) = (0.0, 0.0);
+    for month in 1..=months {
+        let owed = balance * rate / 12.0;
+        let principal = if month == months {
+            balance
+        } else {
+            payment - owed
+        };
+        interest += owed;
+        paid += principal;
+        balance -= principal;
+        if month % 12 == 0 || month == months {
+            schedule.push(Year {
+                year: month.div_ceil(12),
+                interest,
+                principal: paid,
+                balance,
+            });
+            (interest, paid) = (0.0, 0.0);
+        }
+    }
+    Ok(schedule)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Within a cent of `expected`.
+    fn cents(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 0.005
+    }
+
+    #[test]
+    fn test_compound() {
+        assert!(cents(compound(1000.0, 0.05, 10.0, 1).unwrap(), 1628.89));
+        assert!(cents(compound(1000.0, 0.05, 10.0, 12).unwrap(), 1647.01));
+        assert!(cents(compound(1000.0, 0.05, 10.0, 365).unwrap(), 1648.66));
+        assert!(cents(compound(5000.0, 0.08, 2.5, 4).unwrap(), 6094.97));
+        assert_eq!(compound(1000.0, 0.0, 10.0, 12), Ok(1000.0));
+        assert_eq!(compound(1000.0, 0.05, 0.0, 12), Ok(1000.0));
+    }
+
+    #[test]
+    fn test_compound_schedule() {
+        let schedule = compound_schedule(1000.0, 0.05, 2.5, 1).unwrap();
+        assert_eq!(schedule.len(), 3);
+        assert!(cents(schedule[0].interest, 50.0));
+        assert!(cents(schedule[0].balance, 1050.0));
+        assert!(cents(schedule[1].interest, 52.5));
+        assert!(cents(schedule[1].balance, 1102.5));
+        assert_eq!(schedule[2].year, 3);
+        assert!(cents(
+            schedule[2].balance,
+            compound(1000.0, 0.05, 2.5, 1).unwrap()
+        ));
+        assert!(schedule.iter().all(|year| year.principal == 0.0));
+    }
+
+    #[test]
+    fn test_payment() {
+        assert!(cents(payment(250000.0, 0.045, 30.0).unwrap(), 1266.71));
+        assert!(cents(payment(200000.0, 0.06, 30.0).unwrap(), 1199.10));
+        assert!(cents(payment(10000.0, 0.05, 5.0).unwrap(), 188.71));
+        assert!(cents(payment(1200.0, 0.12, 0.5).unwrap(), 207.06));
+    }
+
+    #[test]
+    fn test_payment_without_interest() {
+        // The formula divides by zero at a rate of 0
+        assert_eq!(payment(12000.0, 0.0, 1.0), Ok(1000.0));
+        assert_eq!(payment(250000.0, 0.0, 30.0).unwrap(), 250000.0 / 360.0);
+        let schedule = payment_schedule(12000.0, 0.0, 2.0).unwrap();
+        assert_eq!(
+            schedule[0],
+            Year {
+                year: 1,
+                interest: 0.0,
+                principal: 6000.0,
+                balance: 6000.0
+            }
+        );
+        assert_eq!(schedule[1].balance, 0.0);
+    }
+
+    #[test]
+    fn test_payment_schedule() {
+        let schedule = payment_schedule(250000.0, 0.045, 30.0).unwrap();
+        assert_eq!(schedule.len(), 30);
+        assert!(cents(schedule[0].interest, 11167.49));
+        assert!(cents(schedule[0].principal, 4033.07));
+        assert!(cents(schedule[0].balance, 245966.93));
+        assert_eq!(schedule[29].balance, 0.0);
+        let paid: f64 = schedule.iter().map(|year| year.principal).sum();
+        assert!(cents(paid, 250000.0));
+        let interest: f64 = schedule.iter().map(|year| year.interest).sum();
+        assert!(cents(interest, 1266.7132745647145 * 360.0 - 250000.0));
+        // A last part of a year
+        let schedule = payment_schedule(1200.0, 0.12, 1.5).unwrap();
+        assert_eq!(schedule.len(), 2);
+        assert_eq!(schedule[1].year, 2);
+        assert_eq!(schedule[1].balance, 0.0);
+    }
+
+    #[test]
+    fn test_negative_and_zero_periods_and_rates() {
+        assert_eq!(
+            compound(1000.0, -0.05, 10.0, 1).unwrap_err().to_string(),
+            "a negative rate (-0.05)"
+        );
+        assert_eq!(
+            compound(1000.0, 0.05, -1.0, 1).unwrap_err().to_string(),
+            "a negative number of years (-1)"
+        );
+        assert_eq!(
+            compound(1000.0, 0.05, 10.0, 0).unwrap_err().to_string(),
+            "interest compounds at least once a year"
+        );
+        assert_eq!(
+            payment(1000.0, 0.05, 0.0).unwrap_err().to_string(),
+            "a loan needs a positive number of years, got 0"
+        );
+        assert_eq!(
+            payment(1000.0, 0.05, 1.01).unwrap_err().to_string(),
+            "a loan lasts a whole number of months, got 1.01 years"
+        );
+        assert!(payment(1000.0, -0.01, 1.0).is_err());
+        assert!(payment_schedule(1000.0, 0.05, -2.0).is_err());
+        assert!(compound_schedule(1000.0, 0.05, 1.0, 0).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! How results are printed: fixed decimals, or scientific and engineering notation for
+//! very large and very small numbers, for `--notation`.
+
+/// How [`format_number`] writes a number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notation {
+    /// `66000.00`
+    Fixed,
+    /// `6.60e4`: one digit before the point
+    Scientific,
+    /// `66.0e3`: an exponent that is a multiple of 3, like k, M or µ
+    Engineering,
+}
+
+/// `x` in `notation`. For [`Notation::Fixed`] and [`Notation::Scientific`], `precision`
+/// is the number of digits after the point; [`Notation::Engineering`] keeps the same
+/// significant digits as scientific and moves the point to make the exponent a multiple
+/// of 3. Infinities and NaN are written as Rust writes them.
+/// E.g.: (66000, 2, Engineering) => "66.0e3", (999, 1, Scientific) => "1.0e3"
+pub fn format_number(x: f64, precision: usize, notation: Notation) -> String {
+    match notation {
+        Notation::Fixed => format!("{:.*}", precision, x),
+        _ if !x.is_finite() => x.to_string(),
+        Notation::Scientific => format!("{:.*e}", precision, x),
+        Notation::Engineering => engineering(x, precision),
+    }
+}
+
+/// `formatted` without the trailing zeros of its decimals, nor a point left with none, for
+/// `--trim`; a zero loses its sign. Scientific and engineering notation, and numbers
+/// without a point, keep all their digits.
+/// E.g.: "2.50" => "2.5", "3.00" => "3", "-0.00" => "0", "6.60e4" => "6.60e4"
+pub fn trim_zeros(formatted: &str) -> String {
+    let trimmed = if formatted.contains('.') && !formatted.contains('e') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        formatted
+    };
+    match trimmed.strip_prefix('-') {
+        Some(digits) if digits.chars().all(|c| c == '0') => digits.to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+fn engineering(x: f64, precision: usize) -> String {
+    // Round in scientific notation first, so 999.9 => 1.00e3 before choosing the exponent
+    let sci = format!("{:.*e}", precision, x);
+    let (mantissa, exp) = sci
+        .split_once('e')
+        .expect("scientific notation has an exponent");
+    let exp: i32 = exp.parse().expect("the exponent is an integer");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", mantissa),
+    };
+    let digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let shift = exp.rem_euclid(3) as usize;
+    let digits = format!("{:0<width$}", digits, width = shift + 1);
+    let (int, frac) = digits.split_at(shift + 1);
+    let exp = exp - shift as i32;
+    if frac.is_empty() {
+        format!("{}{}e{}", sign, int, exp)
+    } else {
+        format!("{}{}.{}e{}", sign, int, frac, exp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Notation::{Engineering, Fixed, Scientific};
+
+    #[test]
+    fn test_fixed_is_the_usual_decimals() {
+        assert_eq!(format_number(2.0 / 3.0, 2, Fixed), "0.67");
+        assert_eq!(format_number(-5.0, 0, Fixed), "-5");
+    }
+
+    #[test]
+    fn test_scientific() {
+        assert_eq!(format_number(6.022e23, 3, Scientific), "6.022e23");
+        assert_eq!(format_number(66000.0, 2, Scientific), "6.60e4");
+        assert_eq!(format_number(-0.00015, 1, Scientific), "-1.5e-4");
+        assert_eq!(format_number(0.0, 2, Scientific), "0.00e0");
+        assert_eq!(format_number(7.0, 0, Scientific), "7e0");
+        // Subnormal
+        assert_eq!(format_number(5e-324, 2, Scientific), "4.94e-324");
+        assert_eq!(format_number(f64::INFINITY, 2, Scientific), "inf");
+        assert_eq!(format_number(f64::NAN, 2, Engineering), "NaN");
+    }
+
+    #[test]
+    fn test_rounding_bumps_the_exponent() {
+        assert_eq!(format_number(999.0, 1, Scientific), "1.0e3");
+        assert_eq!(format_number(9.996, 2, Scientific), "1.00e1");
+        assert_eq!(format_number(-9.999e-5, 2, Scientific), "-1.00e-4");
+        // Crossing into the next group of three
+        assert_eq!(format_number(999.9, 2, Engineering), "1.00e3");
+        assert_eq!(format_number(999_940.0, 3, Engineering), "999.9e3");
+        assert_eq!(format_number(999_960.0, 3, Engineering), "1.000e6");
+    }
+
+    #[test]
+    fn test_engineering_exponents_are_multiples_of_three() {
+        assert_eq!(format_number(66000.0, 2, Engineering), "66.0e3");
+        assert_eq!(format_number(1234.6, 3, Engineering), "1.235e3");
+        assert_eq!(format_number(123_456.0, 5, Engineering), "123.456e3");
+        assert_eq!(format_number(0.00047, 1, Engineering), "470e-6");
+        assert_eq!(format_number(-0.047, 2, Engineering), "-47.0e-3");
+        assert_eq!(format_number(7e4, 0, Engineering), "70e3");
+        assert_eq!(format_number(5.0, 2, Engineering), "5.00e0");
+        assert_eq!(format_number(0.0, 2, Engineering), "0.00e0");
+    }
+
+    #[test]
+    fn test_trim_zeros() {
+        let trimmed = |x: f64, precision| trim_zeros(&format_number(x, precision, Fixed));
+        assert_eq!(trimmed(3.0, 2), "3");
+        assert_eq!(trimmed(2.5, 2), "2.5");
+        assert_eq!(trimmed(-2.25, 4), "-2.25");
+        assert_eq!(trimmed(100.0, 0), "100");
+        assert_eq!(trimmed(-0.001, 2), "0");
+        assert_eq!(trimmed(-0.4, 0), "0");
+        assert_eq!(trimmed(-0.0, 3), "0");
+        assert_eq!(trimmed(f64::NEG_INFINITY, 2), "-inf");
+        // Other notations are left as they are
+        assert_eq!(trim_zeros(&format_number(66000.0, 2, Scientific)), "6.60e4");
+        assert_eq!(trim_zeros(&format_number(5.0, 2, Engineering)), "5.00e0");
+    }
+}
@@ -0,0 +1,187 @@
+//! Built-in functions callable from `mycalc eval`, e.g. `sqrt(2)` or `max(1, 5, 3)`, and
+//! constants like `pi`. Adding a function is one entry in [`FUNCTIONS`]; trigonometry
+//! works in radians.
+
+use crate::trig::{self, AngleUnit};
+
+pub struct Function {
+    pub name: &'static str,
+    /// Fewest arguments accepted
+    pub min_args: usize,
+    /// Most arguments accepted, `None` when variadic
+    pub max_args: Option<usize>,
+    /// Computes the result, strictly or not, or explains why the arguments are out of its
+    /// domain
+    pub apply: fn(&[f64], bool) -> Result<f64, String>,
+}
+
+pub const FUNCTIONS: &[Function] = &[
+    Function {
+        name: "sqrt",
+        min_args: 1,
+        max_args: Some(1),
+        apply: |a, _| match a[0] {
+            x if x < 0.0 => Err(format!("sqrt of a negative number ({})", x)),
+            x => Ok(x.sqrt()),
+        },
+    },
+    Function {
+        name: "abs",
+        min_args: 1,
+        max_args: Some(1),
+        apply: |a, _| Ok(a[0].abs()),
+    },
+    Function {
+        name: "min",
+        min_args: 1,
+        max_args: None,
+        apply: |a, _| Ok(a.iter().copied().fold(f64::INFINITY, f64::min)),
+    },
+    Function {
+        name: "max",
+        min_args: 1,
+        max_args: None,
+        apply: |a, _| Ok(a.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+    },
+    // log(x) is base 10, log(x, b) base b
+    Function {
+        name: "log",
+        min_args: 1,
+        max_args: Some(2),
+        apply: |a, _| match a {
+            [x, ..] if *x <= 0.0 => Err(format!("log of a non-positive number ({})", x)),
+            [_, b] if *b <= 0.0 || *b == 1.0 => Err(format!("log with base {}", b)),
+            [x, b] => Ok(x.log(*b)),
+            [x] => Ok(x.log10()),
+            _ => unreachable!("log takes one or two arguments"),
+        },
+    },
+    Function {
+        name: "ln",
+        min_args: 1,
+        max_args: Some(1),
+        apply: |a, _| match a[0] {
+            x if x <= 0.0 => Err(format!("ln of a non-positive number ({})", x)),
+            x => Ok(x.ln()),
+        },
+    },
+    Function {
+        name: "sin",
+        min_args: 1,
+        max_args: Some(1),
+        apply: |a, _| Ok(a[0].sin()),
+    },
+    Function {
+        name: "cos",
+        min_args: 1,
+        max_args: Some(1),
+        apply: |a, _| Ok(a[0].cos()),
+    },
+    Function {
+        name: "tan",
+        min_args: 1,
+        max_args: Some(1),
+        // Where it's undefined, strictly an error rather than a huge number
+        apply: |a, strict| {
+            trig::tan(a[0], AngleUnit::Radians, strict).map_err(|err| err.to_string())
+        },
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+/// Read-only names that an expression uses like numbers, e.g. `2 * pi`. They're looked
+/// up before variables, which can't take their names.
+pub const CONSTANTS: &[(&str, f64)] = &[
+    ("pi", std::f64::consts::PI),
+    ("e", std::f64::consts::E),
+    ("tau", std::f64::consts::TAU),
+    // The golden ratio, (1 + sqrt(5)) / 2
+    ("phi", 1.618_033_988_749_895),
+    ("inf", f64::INFINITY),
+];
+
+pub fn constant(name: &str) -> Option<f64> {
+    CONSTANTS
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .map(|&(_, value)| value)
+}
+
+impl Function {
+    /// "takes 1 argument", "takes 1 to 2 arguments", "takes at least 1 argument".
+    pub fn arity(&self) -> String {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+        match self.max_args {
+            Some(max) if max == self.min_args => format!("takes {} {}", max, plural(max)),
+            Some(max) => format!("takes {} to {} {}", self.min_args, max, plural(max)),
+            None => format!("takes at least {} {}", self.min_args, plural(self.min_args)),
+        }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min_args && self.max_args.is_none_or(|max| count <= max)
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// The known function closest to the unknown `name`, if it is only a typo or two away.
+pub fn suggest(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    FUNCTIONS
+        .iter()
+        .map(|f| (edit_distance(&name, f.name), f.name))
+        .filter(|&(distance, known)| distance <= 2 && distance < known.len())
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, known)| known)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("sqrt", "sqrt"), 0);
+        assert_eq!(edit_distance("sqr", "sqrt"), 1);
+        assert_eq!(edit_distance("sqtr", "sqrt"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_suggest_closest_function() {
+        assert_eq!(suggest("sqr"), Some("sqrt"));
+        assert_eq!(suggest("SIN"), Some("sin"));
+        assert_eq!(suggest("maxx"), Some("max"));
+        assert_eq!(suggest("tann"), Some("tan"));
+        assert_eq!(suggest("banana"), None);
+    }
+
+    #[test]
+    fn test_arity() {
+        assert_eq!(lookup("sqrt").unwrap().arity(), "takes 1 argument");
+        assert_eq!(lookup("log").unwrap().arity(), "takes 1 to 2 arguments");
+        assert_eq!(lookup("min").unwrap().arity(), "takes at least 1 argument");
+        assert!(lookup("max").unwrap().accepts(5));
+        assert!(!lookup("abs").unwrap().accepts(0));
+        assert!(lookup("cosh").is_none());
+    }
+}
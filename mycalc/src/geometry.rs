@@ -0,0 +1,123 @@
+//! Lengths in n dimensions for `mycalc hypot` and `mycalc dist`. The components are
+//! scaled by the largest one before they're squared, so `hypot` of numbers near
+//! `f64::MAX` doesn't overflow and of ones near the smallest `f64` doesn't underflow.
+
+use crate::{sum, CalcError};
+
+/// The length of the vector `nums`, sqrt(x² + y² + ...), without squaring numbers too
+/// large or too small for an `f64`. Like [`f64::hypot`], it's infinite if any number is,
+/// even a NaN. Error with fewer than 2 numbers.
+/// E.g.: [3, 4] => 5, [1, 2, 2] => 3
+pub fn hypot(nums: &[f64]) -> Result<f64, CalcError> {
+    if nums.len() < 2 {
+        return Err(CalcError::too_few("hypot", nums.len()));
+    }
+    Ok(length(nums.iter().copied()))
+}
+
+/// The Euclidean distance between the points `a` and `b`, which must have the same number
+/// of coordinates, at least 1.
+/// E.g.: ([1, 2, 3], [4, 6, 3]) => 5
+pub fn distance(a: &[f64], b: &[f64]) -> Result<f64, CalcError> {
+    if a.len() != b.len() {
+        return Err(CalcError::domain(format!(
+            "the points have different dimensions: {} and {}",
+            a.len(),
+            b.len()
+        )));
+    }
+    if a.is_empty() {
+        return Err(CalcError::TooFewNumbers {
+            function: "dist",
+            needed: 1,
+            got: 0,
+        });
+    }
+    Ok(length(a.iter().zip(b).map(|(x, y)| x - y)))
+}
+
+/// sqrt of the sum of the squares of `components`, each divided by the largest first.
+pub(crate) fn length(components: impl Iterator<Item = f64> + Clone) -> f64 {
+    let largest = components.clone().map(f64::abs).fold(0.0, f64::max);
+    if largest.is_infinite() {
+        return f64::INFINITY;
+    }
+    if components.clone().any(f64::is_nan) {
+        return f64::NAN;
+    }
+    if largest == 0.0 {
+        return 0.0;
+    }
+    let squares: Vec<f64> = components.map(|x| (x / largest).powi(2)).collect();
+    largest * sum(&squares).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 4.0 * f64::EPSILON * b.abs()
+    }
+
+    #[test]
+    fn test_hypot() {
+        assert_eq!(hypot(&[3.0, 4.0]), Ok(5.0));
+        assert_eq!(hypot(&[-3.0, 4.0]), Ok(5.0));
+        assert_eq!(hypot(&[1.0, 2.0, 2.0]), Ok(3.0));
+        assert_eq!(hypot(&[2.0, 3.0, 6.0]), Ok(7.0));
+        assert_eq!(hypot(&[0.0, 0.0, 0.0]), Ok(0.0));
+        assert_eq!(hypot(&[0.0, -7.5]), Ok(7.5));
+        for (x, y) in [(1.5, 2.25), (1e-3, 7e4), (123.456, 0.789)] {
+            assert!(close(hypot(&[x, y]).unwrap(), x.hypot(y)));
+        }
+        assert_eq!(
+            hypot(&[3.0]),
+            Err(CalcError::TooFewNumbers {
+                function: "hypot",
+                needed: 2,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_hypot_of_extreme_numbers() {
+        // Squaring any of these overflows, or underflows to 0
+        let max = f64::MAX;
+        assert!((max * max).is_infinite());
+        assert_eq!(hypot(&[max, 0.0]), Ok(max));
+        assert!(close(
+            hypot(&[max / 2.0, max / 2.0]).unwrap(),
+            max / 2.0 * 2f64.sqrt()
+        ));
+        assert!(close(hypot(&[3e300, 4e300]).unwrap(), 5e300));
+        assert!(close(hypot(&[3e-300, 4e-300]).unwrap(), 5e-300));
+        assert!(close(hypot(&[1e-200, 2e-200, 2e-200]).unwrap(), 3e-200));
+        assert!(close(hypot(&[1e300, 1.0]).unwrap(), 1e300));
+        // The length itself is too large
+        assert_eq!(hypot(&[max, max]), Ok(f64::INFINITY));
+        assert_eq!(hypot(&[f64::INFINITY, f64::NAN]), Ok(f64::INFINITY));
+        assert!(hypot(&[1.0, f64::NAN]).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance(&[1.0, 2.0, 3.0], &[4.0, 6.0, 3.0]), Ok(5.0));
+        assert_eq!(distance(&[4.0, 6.0, 3.0], &[1.0, 2.0, 3.0]), Ok(5.0));
+        assert_eq!(distance(&[-1.0], &[4.0]), Ok(5.0));
+        assert_eq!(distance(&[1.0, 1.0], &[1.0, 1.0]), Ok(0.0));
+        let max = f64::MAX / 2.0;
+        assert!(close(
+            distance(&[max, 0.0], &[0.0, max]).unwrap(),
+            max * 2f64.sqrt()
+        ));
+        assert_eq!(
+            distance(&[1.0, 2.0, 3.0], &[4.0, 6.0])
+                .unwrap_err()
+                .to_string(),
+            "the points have different dimensions: 3 and 2"
+        );
+        assert!(distance(&[], &[]).is_err());
+    }
+}
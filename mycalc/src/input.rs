@@ -0,0 +1,208 @@
+//! Numbers read from text rather than the command line, e.g. `echo "1 2 3.5" | mycalc add -`
+//! or `mycalc add --file values.txt`.
+
+use crate::locale::Separators;
+use crate::CalcError;
+use std::fs;
+use std::path::Path;
+
+/// What a list of numbers can hold: `f64`, `i128` for `--int`, `u128` for `gcd` and `lcm`
+/// or a `BigRational` for `--exact`.
+pub trait Number: Sized {
+    /// Name for errors, e.g. "integer"
+    const NAME: &'static str;
+
+    fn parse(token: &str) -> Option<Self>;
+}
+
+impl Number for f64 {
+    const NAME: &'static str = "number";
+
+    fn parse(token: &str) -> Option<Self> {
+        token.parse().ok()
+    }
+}
+
+impl Number for i128 {
+    const NAME: &'static str = "integer";
+
+    fn parse(token: &str) -> Option<Self> {
+        token.parse().ok()
+    }
+}
+
+impl Number for u128 {
+    const NAME: &'static str = "non-negative integer";
+
+    fn parse(token: &str) -> Option<Self> {
+        token.parse().ok()
+    }
+}
+
+/// `token` read as a `T` after [`Separators::normalize`]. Error with the reason when its
+/// separators are wrong, or `None` when it just isn't a `T`.
+pub fn parse_number<T: Number>(
+    token: &str,
+    separators: Separators,
+) -> Result<T, Option<&'static str>> {
+    let token = separators.normalize(token).map_err(Some)?;
+    T::parse(&token).ok_or(None)
+}
+
+/// The numbers of `text`, separated by whitespace, newlines and commas, or semicolons
+/// when commas are part of numbers (see [`Separators::comma_separates`]); lines starting
+/// with `#` are comments. A token that isn't a number is reported with `source` (e.g. `stdin` or
+/// the file name) and its line and column (both from 1).
+pub fn parse_numbers<T: Number>(
+    text: &str,
+    source: &str,
+    separators: Separators,
+) -> Result<Vec<T>, CalcError> {
+    let list_separator = if separators.comma_separates() {
+        ','
+    } else {
+        ';'
+    };
+    let is_separator = |c: char| c.is_whitespace() || c == list_separator;
+    let mut nums = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let mut rest = line;
+        while let Some(start) = rest.find(|c: char| !is_separator(c)) {
+            let token = &rest[start..];
+            let end = token.find(is_separator).unwrap_or(token.len());
+            let token = &token[..end];
+            let column = line.len() - rest.len() + start;
+            let n = parse_number(token, separators).map_err(|reason| CalcError::InvalidNumber {
+                expected: T::NAME,
+                source: source.to_string(),
+                token: token.to_string(),
+                line: i + 1,
+                column: line[..column].chars().count() + 1,
+                reason,
+            })?;
+            nums.push(n);
+            rest = &rest[start + end..];
+        }
+    }
+    Ok(nums)
+}
+
+/// The numbers of the text file at `path`, as [`parse_numbers`] reads them.
+pub fn read_numbers<T: Number>(path: &Path, separators: Separators) -> Result<Vec<T>, CalcError> {
+    let name = path.display().to_string();
+    match fs::read_to_string(path) {
+        Ok(text) => parse_numbers(&text, &name, separators),
+        Err(e) => Err(CalcError::Io {
+            source: name,
+            message: e.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Locale;
+
+    fn parse(text: &str) -> Vec<f64> {
+        parse_numbers(text, "test", Separators::default()).unwrap()
+    }
+
+    #[test]
+    fn test_numbers_across_lines_and_spaces() {
+        assert_eq!(
+            parse("1 2\t3.5\n\n  -4 1e3  \n"),
+            [1.0, 2.0, 3.5, -4.0, 1000.0]
+        );
+        assert!(parse("").is_empty());
+        assert!(parse(" \n \n").is_empty());
+    }
+
+    #[test]
+    fn test_comments_commas_and_crlf() {
+        assert_eq!(
+            parse("# header\n1,2, 3\n  # indented comment\n4\n"),
+            [1.0, 2.0, 3.0, 4.0]
+        );
+        assert_eq!(parse("1,2,\n3,\n"), [1.0, 2.0, 3.0]);
+        assert_eq!(parse("1,,2"), [1.0, 2.0]);
+        assert_eq!(parse("1.5\r\n2.5\r\n\r\n3\r\n"), [1.5, 2.5, 3.0]);
+        // Only whole lines are comments
+        assert!(parse_numbers::<f64>("1 # one", "test", Separators::default()).is_err());
+    }
+
+    #[test]
+    fn test_bad_token_reports_its_position() {
+        assert_eq!(
+            parse_numbers::<f64>("1 2\n3 four 5\n", "stdin", Separators::default()),
+            Err(CalcError::InvalidNumber {
+                expected: "number",
+                source: "stdin".to_string(),
+                token: "four".to_string(),
+                line: 2,
+                column: 3,
+                reason: None,
+            })
+        );
+        let err = parse_numbers::<f64>("# c\r\n1,\r\n  ½", "values.txt", Separators::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "values.txt: invalid number `½` at line 3, column 3"
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        let nums: Vec<i128> = parse_numbers(
+            "170141183460469231731687303715884105727, -2",
+            "test",
+            Separators::default(),
+        )
+        .unwrap();
+        assert_eq!(nums, [i128::MAX, -2]);
+        let err = parse_numbers::<i128>("1\n2.5", "stdin", Separators::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stdin: invalid integer `2.5` at line 2, column 1"
+        );
+    }
+
+    #[test]
+    fn test_decimal_comma_lists() {
+        let comma = Separators::new(Locale::Comma, false);
+        let nums: Vec<f64> = parse_numbers("1,5 2,25\n3;-0,5;\n", "stdin", comma).unwrap();
+        assert_eq!(nums, [1.5, 2.25, 3.0, -0.5]);
+        let grouped = Separators::new(Locale::Comma, true);
+        let nums: Vec<f64> = parse_numbers("1.234,5; 2.000.000", "stdin", grouped).unwrap();
+        assert_eq!(nums, [1234.5, 2_000_000.0]);
+        // With grouping in the dot locale, `,` is part of the numbers too
+        let nums: Vec<f64> =
+            parse_numbers("1,000.5;2", "stdin", Separators::new(Locale::Dot, true)).unwrap();
+        assert_eq!(nums, [1000.5, 2.0]);
+        let err = parse_numbers::<f64>("1,5\n2.5", "values.txt", comma).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "values.txt: invalid number `2.5` at line 2, column 1 \
+             (the decimal separator is `,` with --locale comma)"
+        );
+        assert_eq!(
+            parse_number::<i128>("1.000", grouped),
+            Err(Some("ambiguous: a lone `.` could be a decimal point"))
+        );
+        assert_eq!(parse_number::<i128>("1.000.000", grouped), Ok(1_000_000));
+        assert_eq!(parse_number::<i128>("1,5", comma), Err(None));
+    }
+
+    #[test]
+    fn test_read_numbers_from_a_missing_file() {
+        let err = read_numbers::<f64>(Path::new("/no/such/values.txt"), Separators::default())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("can't read /no/such/values.txt: "));
+    }
+}
@@ -0,0 +1,135 @@
+//! Exact integer arithmetic for `--int`: the same operations as the `f64` ones, on `i128`
+//! with checked arithmetic, so an overflow is an error instead of wrapping or going infinite.
+
+use crate::CalcError;
+
+/// What `div_i` does when a division leaves a remainder.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntDiv {
+    /// The division must be exact: 7 / 2 is an error
+    Exact,
+    /// Round toward negative infinity: -7 / 2 = -4
+    Floor,
+    /// Round toward zero: -7 / 2 = -3
+    Trunc,
+}
+
+fn overflow(operation: &'static str) -> CalcError {
+    CalcError::overflow(operation)
+}
+
+/// Add all numbers.
+pub fn add_i(nums: &[i128]) -> Result<i128, CalcError> {
+    nums.iter()
+        .try_fold(0i128, |acc, &x| acc.checked_add(x).ok_or(overflow("add")))
+}
+
+/// Subtraction is left-associative.
+/// E.g.: [10, 3, 2] => (10 - 3 - 2) = 5
+pub fn sub_i(nums: &[i128]) -> Result<i128, CalcError> {
+    let (first, rest) = crate::operands("sub", nums)?;
+    rest.iter()
+        .try_fold(*first, |acc, &x| acc.checked_sub(x).ok_or(overflow("sub")))
+}
+
+/// Multiply all numbers.
+pub fn mul_i(nums: &[i128]) -> Result<i128, CalcError> {
+    nums.iter()
+        .try_fold(1i128, |acc, &x| acc.checked_mul(x).ok_or(overflow("mul")))
+}
+
+/// Divide is left-associative. Error if any divisor is zero, or, with [`IntDiv::Exact`],
+/// if a step leaves a remainder.
+/// E.g.: [20, 2, 5] => (20 / 2 / 5) = 2
+pub fn div_i(nums: &[i128], mode: IntDiv) -> Result<i128, CalcError> {
+    let (first, rest) = crate::operands("div", nums)?;
+    rest.iter().enumerate().try_fold(*first, |acc, (i, &x)| {
+        if x == 0 {
+            return Err(CalcError::zero_divisor(i + 2));
+        }
+        // Only i128::MIN / -1 overflows
+        let q = acc.checked_div(x).ok_or(overflow("div"))?;
+        let r = acc % x;
+        match mode {
+            _ if r == 0 => Ok(q),
+            IntDiv::Exact => Err(CalcError::InexactDivision {
+                dividend: acc,
+                divisor: x,
+            }),
+            IntDiv::Floor if (r < 0) != (x < 0) => Ok(q - 1),
+            IntDiv::Floor | IntDiv::Trunc => Ok(q),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_basic_operations() {
+        assert_eq!(add_i(&[1, 2, 3]), Ok(6));
+        assert_eq!(sub_i(&[10, 3, 2]), Ok(5));
+        assert_eq!(mul_i(&[2, 3, 4]), Ok(24));
+        assert_eq!(div_i(&[20, 2, 5], IntDiv::Exact), Ok(2));
+        // Beyond what f64 holds exactly
+        assert_eq!(
+            add_i(&[9_007_199_254_740_993, 1]),
+            Ok(9_007_199_254_740_994)
+        );
+    }
+
+    #[test]
+    fn test_overflow_at_the_i128_boundaries() {
+        assert_eq!(add_i(&[i128::MAX, 0]), Ok(i128::MAX));
+        assert_eq!(
+            add_i(&[i128::MAX, 1]).unwrap_err().to_string(),
+            "overflow in add"
+        );
+        assert!(add_i(&[i128::MIN, -1]).is_err());
+        assert_eq!(sub_i(&[i128::MIN, -1]), Ok(i128::MIN + 1));
+        assert!(sub_i(&[i128::MIN, 1]).is_err());
+        assert!(sub_i(&[0, i128::MIN]).is_err());
+        assert!(mul_i(&[i128::MAX, 2]).is_err());
+        assert!(mul_i(&[i128::MIN, -1]).is_err());
+        assert_eq!(mul_i(&[i128::MIN, 1]), Ok(i128::MIN));
+        assert!(div_i(&[i128::MIN, -1], IntDiv::Exact).is_err());
+        assert_eq!(div_i(&[i128::MIN, 1], IntDiv::Exact), Ok(i128::MIN));
+    }
+
+    #[test]
+    fn test_div_modes() {
+        assert_eq!(
+            div_i(&[7, 2], IntDiv::Exact).unwrap_err().to_string(),
+            "7 / 2 is not a whole number"
+        );
+        assert_eq!(div_i(&[7, 2], IntDiv::Floor), Ok(3));
+        assert_eq!(div_i(&[7, 2], IntDiv::Trunc), Ok(3));
+        assert_eq!(div_i(&[-7, 2], IntDiv::Floor), Ok(-4));
+        assert_eq!(div_i(&[-7, 2], IntDiv::Trunc), Ok(-3));
+        assert_eq!(div_i(&[7, -2], IntDiv::Floor), Ok(-4));
+        assert_eq!(div_i(&[-7, -2], IntDiv::Floor), Ok(3));
+        // Each step rounds: 100 / 7 = 14, 14 / 3 = 4
+        assert_eq!(div_i(&[100, 7, 3], IntDiv::Trunc), Ok(4));
+    }
+
+    #[test]
+    fn test_int_div_by_zero_errors() {
+        let err = div_i(&[10, 0], IntDiv::Floor).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero: operand 2 is zero");
+        let err = div_i(&[10, 5, 0], IntDiv::Exact).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero: operand 3 is zero");
+        assert_eq!(div_i(&[0, 5], IntDiv::Exact), Ok(0));
+    }
+
+    #[test]
+    fn test_too_few_operands() {
+        assert_eq!(
+            sub_i(&[5]).unwrap_err().to_string(),
+            "sub needs at least 2 numbers, got 1"
+        );
+        assert!(sub_i(&[]).is_err());
+        assert!(div_i(&[5], IntDiv::Floor).is_err());
+        assert!(div_i(&[], IntDiv::Exact).is_err());
+    }
+}
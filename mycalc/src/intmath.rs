@@ -0,0 +1,87 @@
+//! Number theory on non-negative integers for `mycalc fact`, `gcd` and `lcm`.
+
+use crate::CalcError;
+
+/// The largest `n` whose factorial fits in a `u128`.
+pub const MAX_FACTORIAL: u32 = 34;
+
+/// `n!`, or an overflow error past [`MAX_FACTORIAL`].
+/// E.g.: 5 => 120, 0 => 1
+pub fn factorial(n: u32) -> Result<u128, CalcError> {
+    (1..=n as u128).try_fold(1u128, |acc, k| {
+        acc.checked_mul(k).ok_or(CalcError::overflow("factorial"))
+    })
+}
+
+/// Greatest common divisor of two numbers, by Euclid's algorithm; gcd(0, n) is n.
+fn gcd2(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Greatest common divisor of all numbers; 0 for no numbers or only zeros.
+/// E.g.: [12, 18, 27] => 3
+pub fn gcd(nums: &[u128]) -> u128 {
+    nums.iter().fold(0, |acc, &x| gcd2(acc, x))
+}
+
+/// Least common multiple of all numbers; 0 if any is 0, an error if it doesn't fit a `u128`.
+/// E.g.: [4, 6, 10] => 60
+pub fn lcm(nums: &[u128]) -> Result<u128, CalcError> {
+    nums.iter().try_fold(1u128, |acc, &x| {
+        if acc == 0 || x == 0 {
+            return Ok(0);
+        }
+        (acc / gcd2(acc, x))
+            .checked_mul(x)
+            .ok_or(CalcError::overflow("lcm"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_factorial_up_to_the_overflow_boundary() {
+        assert_eq!(factorial(0), Ok(1));
+        assert_eq!(factorial(1), Ok(1));
+        assert_eq!(factorial(5), Ok(120));
+        assert_eq!(factorial(20), Ok(2_432_902_008_176_640_000));
+        assert_eq!(
+            factorial(MAX_FACTORIAL),
+            Ok(295_232_799_039_604_140_847_618_609_643_520_000_000)
+        );
+        assert_eq!(
+            factorial(MAX_FACTORIAL + 1).unwrap_err().to_string(),
+            "overflow in factorial"
+        );
+        assert!(factorial(1000).is_err());
+    }
+
+    #[test]
+    fn test_gcd_with_zero() {
+        assert_eq!(gcd(&[0, 7]), 7);
+        assert_eq!(gcd(&[7, 0]), 7);
+        assert_eq!(gcd(&[0, 0]), 0);
+        assert_eq!(gcd(&[12, 18]), 6);
+        assert_eq!(gcd(&[12, 18, 27]), 3);
+        assert_eq!(gcd(&[17, 5]), 1);
+        assert_eq!(gcd(&[u128::MAX, u128::MAX]), u128::MAX);
+    }
+
+    #[test]
+    fn test_lcm_with_zero_and_overflow() {
+        assert_eq!(lcm(&[4, 6]), Ok(12));
+        assert_eq!(lcm(&[4, 6, 10]), Ok(60));
+        assert_eq!(lcm(&[0, 5]), Ok(0));
+        assert_eq!(lcm(&[5, 0, 3]), Ok(0));
+        assert_eq!(lcm(&[u128::MAX, 1]), Ok(u128::MAX));
+        assert_eq!(
+            lcm(&[u128::MAX, 2]).unwrap_err().to_string(),
+            "overflow in lcm"
+        );
+    }
+}
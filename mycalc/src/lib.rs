@@ -1,34 +1,663 @@
-/// Add all numbers.
-pub fn add(nums: &[f64]) -> f64 {
-    nums.iter().copied().sum()
+use serde::Serialize;
+use std::fmt;
+
+pub mod chain;
+mod decimal;
+pub mod duration;
+mod expr;
+pub mod finance;
+mod format;
+pub mod functions;
+pub mod geometry;
+pub mod input;
+mod int;
+pub mod intmath;
+pub mod locale;
+pub mod radix;
+pub mod rational;
+pub mod readline;
+pub mod repl;
+mod round;
+mod rpn;
+pub mod stats;
+pub mod suffix;
+pub mod trig;
+pub mod units;
+pub mod vector;
+
+pub use decimal::{add_d, div_d, format_d, mul_d, sub_d};
+pub use expr::{eval, eval_in, eval_localized, Env};
+pub use format::{format_number, trim_zeros, Notation};
+pub use int::{add_i, div_i, mul_i, sub_i, IntDiv};
+pub use round::{format_rounded, round_to, RoundMode};
+pub use rpn::{eval_rpn, eval_rpn_localized};
+
+/// Error from a calculation; for an expression, with the byte offset of the offending token.
+/// Serializes as an object with the variant name under `kind` and its fields, for
+/// `--output json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind")]
+pub enum CalcError {
+    Syntax {
+        at: usize,
+        message: String,
+    },
+    /// A zero divisor: where it is in an expression, or which operand (from 1) it was in a
+    /// list of numbers
+    DivisionByZero {
+        at: Option<usize>,
+        operand: Option<usize>,
+    },
+    /// Arguments outside what a function or operator is defined for, e.g. `sqrt(-1)`
+    Domain {
+        at: Option<usize>,
+        message: String,
+    },
+    UnknownFunction {
+        at: usize,
+        name: String,
+        suggestion: Option<&'static str>,
+    },
+    /// A token of a list of numbers read from `source` (stdin or a file) that isn't one
+    InvalidNumber {
+        /// What was expected, e.g. "number" or "integer"
+        expected: &'static str,
+        source: String,
+        token: String,
+        line: usize,
+        column: usize,
+        /// Why, when the token looks like a number with the wrong separators
+        reason: Option<&'static str>,
+    },
+    /// A file that couldn't be read
+    Io {
+        source: String,
+        message: String,
+    },
+    /// A result beyond what `i128` (`--int`) or `Decimal` (`--decimal`) holds, or, with
+    /// `--strict`, a floating-point result that is infinite: where it is in an expression,
+    /// or which operand (from 1) made it so in a list of numbers
+    Overflow {
+        operation: &'static str,
+        at: Option<usize>,
+        operand: Option<usize>,
+    },
+    /// An integer division with a remainder where only exact ones are allowed
+    InexactDivision {
+        dividend: i128,
+        divisor: i128,
+    },
+    /// E.g. the mean of no numbers
+    TooFewNumbers {
+        function: &'static str,
+        needed: usize,
+        got: usize,
+    },
+    /// A name in an expression that is neither a constant nor a variable set before
+    UndefinedVariable {
+        at: usize,
+        name: String,
+    },
+    /// An assignment to a name that isn't a variable, e.g. `pi = 3`
+    Reserved {
+        at: Option<usize>,
+        name: String,
+        /// What the name is, e.g. "constant" or "function"
+        #[serde(rename = "reserved_for")]
+        kind: &'static str,
+    },
+    /// A character that isn't a digit of the base a number is written in
+    InvalidDigit {
+        digit: char,
+        radix: u32,
+    },
+    /// An RPN token that is neither a number, a constant, an operator nor a function;
+    /// `index` counts the tokens from 1
+    UnknownToken {
+        index: usize,
+        token: String,
+    },
+    /// An RPN operator or function with fewer numbers on the stack than it takes
+    StackUnderflow {
+        index: usize,
+        token: String,
+        needed: usize,
+        got: usize,
+    },
+    /// An RPN expression that doesn't leave exactly one number on the stack
+    StackLeftover {
+        left: usize,
+    },
+    /// A `chain` stage whose operation isn't one of [`chain::OPERATIONS`]
+    UnknownOperation {
+        at: usize,
+        name: String,
+    },
+    /// An error in a stage (from 1) of a `chain`
+    Stage {
+        stage: usize,
+        error: Box<CalcError>,
+    },
+    /// A name that isn't one of [`units::UNITS`]
+    UnknownUnit {
+        name: String,
+    },
+    /// A conversion between units of different dimensions, e.g. from km to kg
+    IncompatibleUnits {
+        from: &'static str,
+        from_dimension: &'static str,
+        to: &'static str,
+        to_dimension: &'static str,
+    },
+    /// Text that is neither a duration like `1h30m` nor one like `01:30:00`
+    InvalidDuration {
+        duration: String,
+        reason: &'static str,
+    },
 }
 
-/// Subtraction is left-associative.
+impl CalcError {
+    fn syntax(at: usize, message: impl Into<String>) -> CalcError {
+        CalcError::Syntax {
+            at,
+            message: message.into(),
+        }
+    }
+
+    /// `function` got `got` numbers where it needs at least 2.
+    fn too_few(function: &'static str, got: usize) -> CalcError {
+        CalcError::TooFewNumbers {
+            function,
+            needed: 2,
+            got,
+        }
+    }
+
+    /// Division by the `operand`th number (from 1) of a list, which is zero.
+    fn zero_divisor(operand: usize) -> CalcError {
+        CalcError::DivisionByZero {
+            at: None,
+            operand: Some(operand),
+        }
+    }
+
+    fn overflow(operation: &'static str) -> CalcError {
+        CalcError::Overflow {
+            operation,
+            at: None,
+            operand: None,
+        }
+    }
+
+    fn domain(message: impl Into<String>) -> CalcError {
+        CalcError::Domain {
+            at: None,
+            message: message.into(),
+        }
+    }
+
+    /// Byte offset in the expression where the error was found, if it came from one.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            CalcError::Syntax { at, .. }
+            | CalcError::UnknownFunction { at, .. }
+            | CalcError::UndefinedVariable { at, .. }
+            | CalcError::UnknownOperation { at, .. } => Some(*at),
+            CalcError::Stage { error, .. } => error.offset(),
+            CalcError::DivisionByZero { at, .. }
+            | CalcError::Domain { at, .. }
+            | CalcError::Overflow { at, .. }
+            | CalcError::Reserved { at, .. } => *at,
+            CalcError::InvalidNumber { .. }
+            | CalcError::UnknownToken { .. }
+            | CalcError::StackUnderflow { .. }
+            | CalcError::StackLeftover { .. }
+            | CalcError::Io { .. }
+            | CalcError::InexactDivision { .. }
+            | CalcError::TooFewNumbers { .. }
+            | CalcError::InvalidDigit { .. }
+            | CalcError::UnknownUnit { .. }
+            | CalcError::IncompatibleUnits { .. }
+            | CalcError::InvalidDuration { .. } => None,
+        }
+    }
+
+    /// A line with a `^` under the offending token of `expr`, to print below it.
+    pub fn caret(&self, expr: &str) -> Option<String> {
+        let at = self.offset()?.min(expr.len());
+        let column = expr[..at].chars().count();
+        Some(format!("{}^", " ".repeat(column)))
+    }
+}
+
+impl fmt::Display for CalcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalcError::Syntax { at, message } => write!(f, "{} at offset {}", message, at),
+            CalcError::DivisionByZero { at, operand } => {
+                write!(f, "division by zero")?;
+                if let Some(operand) = operand {
+                    write!(f, ": operand {} is zero", operand)?;
+                }
+                match at {
+                    Some(at) => write!(f, " at offset {}", at),
+                    None => Ok(()),
+                }
+            }
+            CalcError::Domain { at, message } => match at {
+                Some(at) => write!(f, "{} at offset {}", message, at),
+                None => write!(f, "{}", message),
+            },
+            CalcError::UnknownFunction {
+                at,
+                name,
+                suggestion,
+            } => {
+                write!(f, "unknown function `{}` at offset {}", name, at)?;
+                match suggestion {
+                    Some(known) => write!(f, " (did you mean `{}`?)", known),
+                    None => Ok(()),
+                }
+            }
+            CalcError::InvalidNumber {
+                expected,
+                source,
+                token,
+                line,
+                column,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "{}: invalid {} `{}` at line {}, column {}",
+                    source, expected, token, line, column
+                )?;
+                match reason {
+                    Some(reason) => write!(f, " ({})", reason),
+                    None => Ok(()),
+                }
+            }
+            CalcError::Io { source, message } => write!(f, "can't read {}: {}", source, message),
+            CalcError::Overflow {
+                operation,
+                at,
+                operand,
+            } => {
+                write!(f, "overflow in {}", operation)?;
+                if let Some(operand) = operand {
+                    write!(f, " at operand {}", operand)?;
+                }
+                match at {
+                    Some(at) => write!(f, " at offset {}", at),
+                    None => Ok(()),
+                }
+            }
+            CalcError::InexactDivision { dividend, divisor } => {
+                write!(f, "{} / {} is not a whole number", dividend, divisor)
+            }
+            CalcError::TooFewNumbers {
+                function,
+                needed,
+                got,
+            } => write!(
+                f,
+                "{} needs at least {} number{}, got {}",
+                function,
+                needed,
+                if *needed == 1 { "" } else { "s" },
+                got
+            ),
+            CalcError::UndefinedVariable { at, name } => {
+                write!(f, "undefined variable `{}` at offset {}", name, at)
+            }
+            CalcError::Reserved { at, name, kind } => {
+                write!(f, "can't assign to the {} `{}`", kind, name)?;
+                match at {
+                    Some(at) => write!(f, " at offset {}", at),
+                    None => Ok(()),
+                }
+            }
+            CalcError::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit `{}` for base {}", digit, radix)
+            }
+            CalcError::UnknownToken { index, token } => {
+                write!(f, "unknown token `{}` at token {}", token, index)
+            }
+            CalcError::StackUnderflow {
+                index,
+                token,
+                needed,
+                got,
+            } => write!(
+                f,
+                "`{}` at token {} needs {} number{} on the stack, got {}",
+                token,
+                index,
+                needed,
+                if *needed == 1 { "" } else { "s" },
+                got
+            ),
+            CalcError::StackLeftover { left } => {
+                write!(f, "expected one number left on the stack, got {}", left)
+            }
+            CalcError::UnknownOperation { at, name } => write!(
+                f,
+                "unknown operation `{}` at offset {} ({})",
+                name,
+                at,
+                chain::operation_names()
+            ),
+            CalcError::Stage { stage, error } => write!(f, "stage {}: {}", stage, error),
+            CalcError::UnknownUnit { name } => write!(f, "unknown unit `{}`", name),
+            CalcError::IncompatibleUnits {
+                from,
+                from_dimension,
+                to,
+                to_dimension,
+            } => write!(
+                f,
+                "can't convert {} ({}) to {} ({})",
+                from, from_dimension, to, to_dimension
+            ),
+            CalcError::InvalidDuration { duration, reason } => {
+                write!(f, "invalid duration `{}`: {}", duration, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CalcError {}
+
+/// The first number and the rest, for the left-associative operations. Error unless
+/// there are at least 2 numbers.
+fn operands<'a, T>(function: &'static str, nums: &'a [T]) -> Result<(&'a T, &'a [T]), CalcError> {
+    match nums {
+        [first, rest @ ..] if !rest.is_empty() => Ok((first, rest)),
+        _ => Err(CalcError::too_few(function, nums.len())),
+    }
+}
+
+/// `x`, a result of `operation`, if it's a finite number. Otherwise, for `--strict`, an
+/// overflow error when it's infinite and a domain error when it's NaN, saying where it is
+/// in an expression or which operand made it so.
+pub fn finite(
+    operation: &'static str,
+    at: Option<usize>,
+    operand: Option<usize>,
+    x: f64,
+) -> Result<f64, CalcError> {
+    if x.is_infinite() {
+        return Err(CalcError::Overflow {
+            operation,
+            at,
+            operand,
+        });
+    }
+    if x.is_nan() {
+        let mut message = format!("not a number (NaN) in {}", operation);
+        if let Some(operand) = operand {
+            message += &format!(" at operand {}", operand);
+        }
+        return Err(CalcError::Domain { at, message });
+    }
+    Ok(x)
+}
+
+/// `step` applied to `nums` left to right, from `init`. With `strict`, an error at the
+/// first operand after which the running result isn't a finite number.
+fn fold(
+    operation: &'static str,
+    nums: &[f64],
+    init: f64,
+    strict: bool,
+    mut step: impl FnMut(f64, usize, f64) -> Result<f64, CalcError>,
+) -> Result<f64, CalcError> {
+    let mut acc = init;
+    for (i, &x) in nums.iter().enumerate() {
+        acc = step(acc, i + 1, x)?;
+        if strict {
+            finite(operation, None, Some(i + 1), acc)?;
+        }
+    }
+    Ok(acc)
+}
+
+/// Add all numbers; none add up to 0. The sum is compensated, so small numbers aren't
+/// lost next to big ones that cancel out later.
+/// E.g.: [1e16, 1, -1e16] => 1 (0 with [`add_naive`])
+pub fn add(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    let total = sum(nums);
+    if strict && !total.is_finite() {
+        // Once infinite or NaN, a running sum stays so: find where it became one
+        add_naive(nums, strict)?;
+        return finite("add", None, Some(nums.len()), total);
+    }
+    Ok(total)
+}
+
+/// Add all numbers left to right, rounding after each addition, for comparison with
+/// [`add`]: `mycalc --naive-sum add`.
+pub fn add_naive(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    fold("add", nums, 0.0, strict, |acc, _, x| Ok(acc + x))
+}
+
+/// Neumaier's compensated sum: what each addition rounds off is kept apart and added
+/// back at the end, so the result is as if summed with twice the precision.
+pub(crate) fn sum(nums: &[f64]) -> f64 {
+    let mut sum = 0.0;
+    let mut lost = 0.0;
+    for &x in nums {
+        let t = sum + x;
+        lost += if sum.abs() >= x.abs() {
+            (sum - t) + x
+        } else {
+            (x - t) + sum
+        };
+        sum = t;
+    }
+    // Once the sum is infinite or NaN, what was lost is NaN and means nothing
+    if sum.is_finite() {
+        sum + lost
+    } else {
+        sum
+    }
+}
+
+/// Subtraction is left-associative. Error with fewer than 2 numbers.
 /// E.g.: [10, 3, 2] => (10 - 3 - 2) = 5
-pub fn sub(nums: &[f64]) -> f64 {
-    let (first, rest) = nums.split_first().expect("at least 2 numbers");
-    rest.iter().fold(*first, |acc, &x| acc - x)
+pub fn sub(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    operands("sub", nums)?;
+    fold("sub", nums, 0.0, strict, |acc, i, x| match i {
+        1 => Ok(x),
+        _ => Ok(acc - x),
+    })
 }
 
-/// Multiply all numbers.
-pub fn mul(nums: &[f64]) -> f64 {
-    nums.iter().product()
+/// Multiply all numbers; none multiply to 1.
+pub fn mul(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    fold("mul", nums, 1.0, strict, |acc, _, x| Ok(acc * x))
 }
 
-/// Divide is left-associative. Error if any divisor is zero.
+/// Divide is left-associative. Error with fewer than 2 numbers or if any divisor is zero,
+/// saying which operand it was.
 /// E.g.: [20, 2, 5] => (20 / 2 / 5) = 2
-pub fn div(nums: &[f64]) -> Result<f64, &'static str> {
-    let (first, rest) = nums.split_first().expect("at least 2 numbers");
-    let mut acc = *first;
-    for &x in rest {
-        if x == 0.0 {
-            return Err("Error: division by zero");
+pub fn div(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    operands("div", nums)?;
+    fold("div", nums, 0.0, strict, |acc, i, x| match i {
+        1 => Ok(x),
+        _ if x == 0.0 => Err(CalcError::zero_divisor(i)),
+        _ => Ok(acc / x),
+    })
+}
+
+/// How `rem` and `divmod` treat negative operands.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RemMode {
+    /// The quotient is rounded toward zero and the remainder has the sign of the dividend,
+    /// like Rust's `%`: -7 mod 3 = -1
+    Truncated,
+    /// The remainder is never negative: -7 mod 3 = 2
+    Euclidean,
+}
+
+/// Remainder of `a / b`. Error if `b` is zero.
+pub fn rem(a: f64, b: f64, mode: RemMode) -> Result<f64, CalcError> {
+    divmod(a, b, mode).map(|(_, r)| r)
+}
+
+/// Quotient and remainder of `a / b`, so that `a = q * b + r`. Error if `b` is zero.
+/// E.g.: (-7, 3) => (-2, -1) truncated, (-3, 2) Euclidean
+pub fn divmod(a: f64, b: f64, mode: RemMode) -> Result<(f64, f64), CalcError> {
+    if b == 0.0 {
+        return Err(CalcError::zero_divisor(2));
+    }
+    Ok(match mode {
+        RemMode::Truncated => ((a / b).trunc(), a % b),
+        RemMode::Euclidean => (a.div_euclid(b), a.rem_euclid(b)),
+    })
+}
+
+/// `base` raised to `exp`, or an error where there is no real result.
+/// 0^0 is 1, the usual convention (and what `f64::powf` gives).
+fn power(base: f64, exp: f64) -> Result<f64, CalcError> {
+    if base < 0.0 && exp.fract() != 0.0 {
+        return Err(CalcError::domain(format!(
+            "{}^{} is not a real number (negative base with a fractional exponent)",
+            base, exp
+        )));
+    }
+    Ok(base.powf(exp))
+}
+
+/// The `n`th root of `x`. Odd roots of negative numbers are negative (cbrt(-8) = -2);
+/// even roots of negative numbers and the 0th root are errors.
+pub fn nth_root(x: f64, n: u32) -> Result<f64, CalcError> {
+    match n {
+        0 => Err(CalcError::domain("the 0th root is undefined")),
+        _ if x < 0.0 && n.is_multiple_of(2) => Err(CalcError::domain(format!(
+            "even root ({}) of a negative number ({}) is not a real number",
+            n, x
+        ))),
+        2 => Ok(x.sqrt()),
+        3 => Ok(x.cbrt()),
+        _ => Ok(x.signum() * x.abs().powf(1.0 / n as f64)),
+    }
+}
+
+/// Power tower, right-associative like in maths. Error with fewer than 2 numbers; with
+/// `strict`, an overflow names the base whose power was too large.
+/// E.g.: [2, 3, 2] => 2^(3^2) = 512
+pub fn pow(nums: &[f64], strict: bool) -> Result<f64, CalcError> {
+    if nums.len() < 2 {
+        return Err(CalcError::too_few("pow", nums.len()));
+    }
+    let mut acc = 0.0;
+    for (i, &x) in nums.iter().enumerate().rev() {
+        acc = if i == nums.len() - 1 {
+            x
+        } else {
+            power(x, acc)?
+        };
+        if strict {
+            finite("pow", None, Some(i + 1), acc)?;
         }
-        acc /= x;
     }
     Ok(acc)
 }
 
+/// The logarithm of `x` in base `base`. Error unless `x` is positive and `base` is positive
+/// and not 1.
+/// E.g.: (8, 2) => 3
+pub fn log_base(x: f64, base: f64) -> Result<f64, CalcError> {
+    if base <= 0.0 || base == 1.0 || base.is_nan() {
+        return Err(CalcError::domain(format!(
+            "log base must be positive and not 1, got {}",
+            base
+        )));
+    }
+    Ok(ln(x)? / base.ln())
+}
+
+/// The natural logarithm of `x`. Error unless `x` is positive.
+pub fn ln(x: f64) -> Result<f64, CalcError> {
+    if x <= 0.0 || x.is_nan() {
+        return Err(CalcError::domain(format!(
+            "log is only defined for positive numbers, got {}",
+            x
+        )));
+    }
+    Ok(x.ln())
+}
+
+/// e raised to `x`. Error if that's too large for an `f64`.
+pub fn exp(x: f64) -> Result<f64, CalcError> {
+    let result = x.exp();
+    if result.is_infinite() {
+        return Err(CalcError::overflow("exp"));
+    }
+    if result.is_nan() {
+        return Err(CalcError::domain("exp of NaN"));
+    }
+    Ok(result)
+}
+
+/// What percentage `part` is of `whole`. Error if `whole` is zero.
+/// E.g.: (30, 200) => 15
+pub fn percent_of(part: f64, whole: f64) -> Result<f64, CalcError> {
+    if whole == 0.0 {
+        return Err(CalcError::zero_divisor(2));
+    }
+    Ok(part / whole * 100.0)
+}
+
+/// `base` increased by `pct` percent.
+/// E.g.: (15, 200) => 230
+pub fn percent_on(pct: f64, base: f64) -> Result<f64, CalcError> {
+    Ok(base * (1.0 + pct / 100.0))
+}
+
+/// `base` decreased by `pct` percent.
+/// E.g.: (15, 200) => 170
+pub fn percent_off(pct: f64, base: f64) -> Result<f64, CalcError> {
+    Ok(base * (1.0 - pct / 100.0))
+}
+
+/// The change from `from` to `to` as a percentage of the size of `from`, so that a rise
+/// is positive even from a negative baseline. Error if `from` is zero.
+/// E.g.: (50, 75) => 50, (-50, -25) => 50, (80, 60) => -25
+pub fn percent_change(from: f64, to: f64) -> Result<f64, CalcError> {
+    if from == 0.0 {
+        return Err(CalcError::zero_divisor(1));
+    }
+    Ok((to - from) / from.abs() * 100.0)
+}
+
+/// `a` to `b`, from [`reduce_ratio`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ratio {
+    /// Both numbers were integers: `a:b` in lowest terms
+    Integers(i128, i128),
+    /// `a / b`, to 1
+    Decimal(f64),
+}
+
+/// `a:b` in lowest terms when both are integers (exactly, so up to 2^53), dividing both by
+/// their [`gcd`](intmath::gcd); otherwise `a / b`. Error if `b` is zero.
+/// E.g.: (1920, 1080) => 16:9, (1.5, 1) => 1.5
+pub fn reduce_ratio(a: f64, b: f64) -> Result<Ratio, CalcError> {
+    if b == 0.0 {
+        return Err(CalcError::zero_divisor(2));
+    }
+    const EXACT: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+    let integer = |x: f64| x.fract() == 0.0 && x.abs() <= EXACT;
+    if !integer(a) || !integer(b) {
+        return Ok(Ratio::Decimal(a / b));
+    }
+    let (a, b) = (a as i128, b as i128);
+    let divisor = intmath::gcd(&[a.unsigned_abs(), b.unsigned_abs()]) as i128;
+    Ok(Ratio::Integers(a / divisor, b / divisor))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,56 +671,438 @@ mod tests {
     #[test]
     fn test_add_basic() {
         let nums = [1.0, 2.5, 3.5];
-        let res = add(&nums);
+        let res = add(&nums, true).unwrap();
         assert!(approx_eq(res, 7.0));
     }
 
     #[test]
     fn test_add_with_negatives() {
         let nums = [10.0, -3.0, -2.0, 5.0];
-        let res = add(&nums);
+        let res = add(&nums, true).unwrap();
         assert!(approx_eq(res, 10.0));
     }
 
+    #[test]
+    fn test_add_compensates_cancellation() {
+        assert_eq!(add(&[1e16, 1.0, -1e16], true), Ok(1.0));
+        assert_eq!(add_naive(&[1e16, 1.0, -1e16], true), Ok(0.0));
+        assert_eq!(add(&[1.0, 1e100, 1.0, -1e100], true), Ok(2.0));
+        assert_eq!(add(&[0.1; 10], true), Ok(1.0));
+        assert_ne!(add_naive(&[0.1; 10], true), Ok(1.0));
+        let halves = [1.0, 1e-16, 1e-16, 1e-16, 1e-16, -1.0];
+        assert_eq!(add(&halves, true), Ok(4e-16));
+        assert_eq!(add(&[f64::INFINITY, 1.0], false), Ok(f64::INFINITY));
+        assert_eq!(add(&[f64::MAX, f64::MAX], false), Ok(f64::INFINITY));
+        assert!(add(&[f64::INFINITY, f64::NEG_INFINITY], false)
+            .unwrap()
+            .is_nan());
+    }
+
+    #[test]
+    fn test_add_matches_an_exact_sum() {
+        use num_rational::BigRational;
+        use num_traits::ToPrimitive;
+
+        // xorshift64, so the "random" numbers are the same on every run
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..10 {
+            let nums: Vec<f64> = (0..1000)
+                .map(|_| {
+                    let mantissa = (next() % 1_000_000) as f64 / 1000.0;
+                    let exponent = (next() % 40) as i32 - 20;
+                    let sign = if next() % 2 == 0 { 1.0 } else { -1.0 };
+                    sign * mantissa * 10f64.powi(exponent)
+                })
+                .collect();
+            let exact: BigRational = nums
+                .iter()
+                .map(|&x| BigRational::from_float(x).unwrap())
+                .sum();
+            let expected = exact.to_f64().unwrap();
+            let result = add(&nums, true).unwrap();
+            assert!(
+                (result - expected).abs() <= 2.0 * f64::EPSILON * expected.abs(),
+                "{} != {}",
+                result,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_strict_errors_name_the_operand() {
+        assert_eq!(
+            mul(&[2.0, 1e308, 10.0, 0.5], true),
+            Err(CalcError::Overflow {
+                operation: "mul",
+                at: None,
+                operand: Some(2)
+            })
+        );
+        assert_eq!(
+            mul(&[1e308, 10.0], true).unwrap_err().to_string(),
+            "overflow in mul at operand 2"
+        );
+        // inf goes on through sub, to NaN
+        assert_eq!(
+            sub(&[1.0, f64::INFINITY, 2.0], true)
+                .unwrap_err()
+                .to_string(),
+            "overflow in sub at operand 2"
+        );
+        assert_eq!(
+            sub(&[f64::INFINITY, f64::INFINITY], true)
+                .unwrap_err()
+                .to_string(),
+            "overflow in sub at operand 1"
+        );
+        assert_eq!(
+            sub(&[1.0, f64::INFINITY, 2.0], false),
+            Ok(f64::NEG_INFINITY)
+        );
+        assert!(sub(&[f64::INFINITY, f64::INFINITY], false)
+            .unwrap()
+            .is_nan());
+        assert_eq!(
+            add(&[1.0, f64::MAX, f64::MAX, -f64::MAX], true)
+                .unwrap_err()
+                .to_string(),
+            "overflow in add at operand 3"
+        );
+        assert_eq!(
+            add_naive(&[f64::INFINITY, f64::NEG_INFINITY], true)
+                .unwrap_err()
+                .to_string(),
+            "overflow in add at operand 1"
+        );
+        // Finite again after an overflow, but still an error
+        assert_eq!(
+            div(&[1e300, 1e-300, f64::INFINITY], true)
+                .unwrap_err()
+                .to_string(),
+            "overflow in div at operand 2"
+        );
+        assert_eq!(div(&[1e300, 1e-300, 1e308], false), Ok(f64::INFINITY));
+        assert_eq!(
+            pow(&[0.5, 10.0, 400.0], true).unwrap_err().to_string(),
+            "overflow in pow at operand 2"
+        );
+        assert_eq!(pow(&[0.5, 10.0, 400.0], false), Ok(0.0));
+        assert_eq!(
+            finite("mul", None, Some(3), f64::NAN)
+                .unwrap_err()
+                .to_string(),
+            "not a number (NaN) in mul at operand 3"
+        );
+    }
+
     #[test]
     fn test_sub_left_associative() {
         let nums = [10.0, 3.0, 2.0];
-        let res = sub(&nums);
+        let res = sub(&nums, true).unwrap();
         assert!(approx_eq(res, 5.0));
     }
 
     #[test]
     fn test_mul_basic() {
         let nums = [2.0, 3.0, 4.0];
-        let res = mul(&nums);
+        let res = mul(&nums, true).unwrap();
         assert!(approx_eq(res, 24.0));
     }
 
     #[test]
     fn test_mul_with_zero() {
         let nums = [2.0, 0.0, 100.0];
-        let res = mul(&nums);
+        let res = mul(&nums, true).unwrap();
         assert!(approx_eq(res, 0.0));
     }
 
     #[test]
     fn test_div_left_associative_ok() {
         let nums = [20.0, 2.0, 5.0];
-        let res = div(&nums).unwrap();
+        let res = div(&nums, true).unwrap();
         assert!(approx_eq(res, 2.0));
     }
 
     #[test]
     fn test_div_by_zero_in_rest_errors() {
-        let nums = [10.0, 0.0];
-        let err = div(&nums).unwrap_err();
-        assert!(err.contains("division by zero"));
+        let err = div(&[10.0, 0.0], true).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero: operand 2 is zero");
+        let err = div(&[10.0, 2.0, 5.0, 0.0], true).unwrap_err();
+        assert_eq!(err.offset(), None);
+        assert_eq!(err.to_string(), "division by zero: operand 4 is zero");
+    }
+
+    #[test]
+    fn test_too_few_operands_error_instead_of_panicking() {
+        for nums in [&[][..], &[5.0]] {
+            let got = nums.len();
+            for (function, result) in [
+                ("sub", sub(nums, true)),
+                ("div", div(nums, true)),
+                ("pow", pow(nums, true)),
+            ] {
+                assert_eq!(
+                    result.unwrap_err(),
+                    CalcError::TooFewNumbers {
+                        function,
+                        needed: 2,
+                        got
+                    }
+                );
+            }
+        }
+        assert_eq!(
+            sub(&[5.0], true).unwrap_err().to_string(),
+            "sub needs at least 2 numbers, got 1"
+        );
+        // An empty sum and product are well defined
+        assert!(approx_eq(add(&[], true).unwrap(), 0.0));
+        assert!(approx_eq(mul(&[], true).unwrap(), 1.0));
+        assert!(approx_eq(add(&[5.0], true).unwrap(), 5.0));
     }
 
     #[test]
     fn test_div_with_zero_first_is_ok() {
         let nums = [0.0, 2.0, 5.0];
-        let res = div(&nums).unwrap();
+        let res = div(&nums, true).unwrap();
         assert!(approx_eq(res, 0.0));
     }
+
+    #[test]
+    fn test_rem_negative_operands_by_mode() {
+        assert!(approx_eq(rem(-7.0, 3.0, RemMode::Truncated).unwrap(), -1.0));
+        assert!(approx_eq(rem(-7.0, 3.0, RemMode::Euclidean).unwrap(), 2.0));
+        assert!(approx_eq(rem(7.0, -3.0, RemMode::Truncated).unwrap(), 1.0));
+        assert!(approx_eq(rem(7.0, -3.0, RemMode::Euclidean).unwrap(), 1.0));
+        assert!(approx_eq(rem(-7.0, -3.0, RemMode::Euclidean).unwrap(), 2.0));
+        assert!(approx_eq(rem(7.5, 2.0, RemMode::Truncated).unwrap(), 1.5));
+    }
+
+    #[test]
+    fn test_divmod_keeps_a_equal_to_q_times_b_plus_r() {
+        for mode in [RemMode::Truncated, RemMode::Euclidean] {
+            for (a, b) in [
+                (-7.0, 3.0),
+                (7.0, -3.0),
+                (-7.0, -3.0),
+                (7.0, 3.0),
+                (9.0, 3.0),
+            ] {
+                let (q, r) = divmod(a, b, mode).unwrap();
+                assert!(approx_eq(q * b + r, a), "{:?} {} {}", mode, a, b);
+                assert!(approx_eq(q, q.trunc()));
+            }
+        }
+        assert_eq!(divmod(-7.0, 3.0, RemMode::Truncated).unwrap(), (-2.0, -1.0));
+        assert_eq!(divmod(-7.0, 3.0, RemMode::Euclidean).unwrap(), (-3.0, 2.0));
+    }
+
+    #[test]
+    fn test_rem_by_zero_errors() {
+        let err = rem(10.0, 0.0, RemMode::Truncated).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero: operand 2 is zero");
+        assert!(divmod(10.0, 0.0, RemMode::Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_nth_root() {
+        assert!(approx_eq(nth_root(16.0, 2).unwrap(), 4.0));
+        assert!(approx_eq(nth_root(27.0, 3).unwrap(), 3.0));
+        assert!(approx_eq(nth_root(32.0, 5).unwrap(), 2.0));
+        assert!(approx_eq(nth_root(5.0, 1).unwrap(), 5.0));
+        assert!(approx_eq(nth_root(0.0, 4).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_nth_root_of_negatives() {
+        assert!(approx_eq(nth_root(-8.0, 3).unwrap(), -2.0));
+        assert!(approx_eq(nth_root(-32.0, 5).unwrap(), -2.0));
+        assert_eq!(
+            nth_root(-4.0, 2).unwrap_err().to_string(),
+            "even root (2) of a negative number (-4) is not a real number"
+        );
+        assert!(nth_root(-16.0, 4).is_err());
+    }
+
+    #[test]
+    fn test_zeroth_root_is_rejected() {
+        let err = nth_root(8.0, 0).unwrap_err();
+        assert_eq!(err.to_string(), "the 0th root is undefined");
+    }
+
+    #[test]
+    fn test_percent_of_on_off() {
+        assert!(approx_eq(percent_of(30.0, 200.0).unwrap(), 15.0));
+        assert!(approx_eq(percent_of(300.0, 200.0).unwrap(), 150.0));
+        assert!(approx_eq(percent_on(15.0, 200.0).unwrap(), 230.0));
+        assert!(approx_eq(percent_off(15.0, 200.0).unwrap(), 170.0));
+        assert!(approx_eq(percent_off(100.0, 80.0).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_percent_negative_values() {
+        assert!(approx_eq(percent_of(-30.0, 200.0).unwrap(), -15.0));
+        assert!(approx_eq(percent_on(-15.0, 200.0).unwrap(), 170.0));
+        assert!(approx_eq(percent_off(-15.0, 200.0).unwrap(), 230.0));
+        assert!(approx_eq(percent_on(10.0, -50.0).unwrap(), -55.0));
+    }
+
+    #[test]
+    fn test_percent_of_zero_whole_errors() {
+        let err = percent_of(5.0, 0.0).unwrap_err();
+        assert_eq!(err.to_string(), "division by zero: operand 2 is zero");
+    }
+
+    #[test]
+    fn test_percent_change() {
+        assert!(approx_eq(percent_change(50.0, 75.0).unwrap(), 50.0));
+        assert!(approx_eq(percent_change(80.0, 60.0).unwrap(), -25.0));
+        assert!(approx_eq(percent_change(10.0, 10.0).unwrap(), 0.0));
+        assert!(approx_eq(percent_change(10.0, 0.0).unwrap(), -100.0));
+        // From a negative baseline, a rise is still positive
+        assert!(approx_eq(percent_change(-50.0, -25.0).unwrap(), 50.0));
+        assert!(approx_eq(percent_change(-50.0, -100.0).unwrap(), -100.0));
+        assert!(approx_eq(percent_change(-20.0, 10.0).unwrap(), 150.0));
+        assert_eq!(
+            percent_change(0.0, 5.0).unwrap_err().to_string(),
+            "division by zero: operand 1 is zero"
+        );
+    }
+
+    #[test]
+    fn test_reduce_ratio() {
+        assert_eq!(reduce_ratio(1920.0, 1080.0), Ok(Ratio::Integers(16, 9)));
+        assert_eq!(reduce_ratio(1080.0, 1920.0), Ok(Ratio::Integers(9, 16)));
+        assert_eq!(reduce_ratio(7.0, 3.0), Ok(Ratio::Integers(7, 3)));
+        assert_eq!(reduce_ratio(4.0, 4.0), Ok(Ratio::Integers(1, 1)));
+        assert_eq!(reduce_ratio(-4.0, 6.0), Ok(Ratio::Integers(-2, 3)));
+        assert_eq!(reduce_ratio(0.0, 5.0), Ok(Ratio::Integers(0, 1)));
+        assert_eq!(
+            reduce_ratio(9007199254740992.0, 2.0),
+            Ok(Ratio::Integers(4503599627370496, 1))
+        );
+        assert_eq!(
+            reduce_ratio(5.0, 0.0).unwrap_err().to_string(),
+            "division by zero: operand 2 is zero"
+        );
+    }
+
+    #[test]
+    fn test_reduce_ratio_of_non_integers() {
+        assert_eq!(reduce_ratio(1.5, 1.0), Ok(Ratio::Decimal(1.5)));
+        assert_eq!(reduce_ratio(3.0, 0.5), Ok(Ratio::Decimal(6.0)));
+        assert_eq!(reduce_ratio(-2.5, 2.0), Ok(Ratio::Decimal(-1.25)));
+        // Integers too large to be sure of
+        assert_eq!(reduce_ratio(1e20, 1e10), Ok(Ratio::Decimal(1e10)));
+        assert_eq!(
+            reduce_ratio(f64::INFINITY, 1.0),
+            Ok(Ratio::Decimal(f64::INFINITY))
+        );
+    }
+
+    #[test]
+    fn test_pow_tower_is_right_associative() {
+        assert!(approx_eq(pow(&[2.0, 3.0, 2.0], true).unwrap(), 512.0));
+        assert!(approx_eq(pow(&[2.0, 10.0], true).unwrap(), 1024.0));
+        assert!(approx_eq(pow(&[4.0, 0.5], true).unwrap(), 2.0));
+        assert!(approx_eq(pow(&[-2.0, 3.0], true).unwrap(), -8.0));
+        assert!(approx_eq(pow(&[2.0, -1.0], true).unwrap(), 0.5));
+    }
+
+    #[test]
+    fn test_pow_zero_to_the_zero_is_one() {
+        assert!(approx_eq(pow(&[0.0, 0.0], true).unwrap(), 1.0));
+        // 0^(0^0) = 0^1
+        assert!(approx_eq(pow(&[0.0, 0.0, 0.0], true).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_pow_negative_base_with_fractional_exponent_errors() {
+        let err = pow(&[-8.0, 0.5], true).unwrap_err();
+        assert_eq!(err.offset(), None);
+        assert_eq!(
+            err.to_string(),
+            "-8^0.5 is not a real number (negative base with a fractional exponent)"
+        );
+        // The exponent 1/2 comes from 2^-1 before -4 is raised to it
+        assert!(pow(&[-4.0, 2.0, -1.0], true).is_err());
+    }
+
+    #[test]
+    fn test_log_in_other_bases() {
+        assert!(approx_eq(log_base(1000.0, 10.0).unwrap(), 3.0));
+        assert!(approx_eq(log_base(8.0, 2.0).unwrap(), 3.0));
+        assert!(approx_eq(log_base(0.25, 2.0).unwrap(), -2.0));
+        assert!(approx_eq(log_base(9.0, 1.0 / 3.0).unwrap(), -2.0));
+        assert!(approx_eq(ln(std::f64::consts::E).unwrap(), 1.0));
+        assert!(approx_eq(ln(1.0).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_ln_of_exp_is_identity() {
+        for x in [-20.0, -1.5, 0.0, 0.5, 1.0, 10.0, 300.0] {
+            let round_trip = ln(exp(x).unwrap()).unwrap();
+            assert!((round_trip - x).abs() < 1e-12 * x.abs().max(1.0), "{}", x);
+        }
+    }
+
+    #[test]
+    fn test_log_and_exp_domain_errors() {
+        assert_eq!(
+            ln(0.0).unwrap_err().to_string(),
+            "log is only defined for positive numbers, got 0"
+        );
+        assert_eq!(
+            log_base(-3.0, 10.0).unwrap_err().to_string(),
+            "log is only defined for positive numbers, got -3"
+        );
+        assert_eq!(
+            log_base(8.0, 1.0).unwrap_err().to_string(),
+            "log base must be positive and not 1, got 1"
+        );
+        assert_eq!(
+            log_base(8.0, -2.0).unwrap_err().to_string(),
+            "log base must be positive and not 1, got -2"
+        );
+        assert!(log_base(8.0, 0.0).is_err());
+        assert_eq!(exp(1000.0).unwrap_err().to_string(), "overflow in exp");
+        assert_eq!(exp(-1000.0), Ok(0.0));
+    }
+
+    #[test]
+    fn test_caret_counts_characters() {
+        let err = eval("2 × 3").unwrap_err();
+        assert_eq!(err.offset(), Some(2));
+        assert_eq!(err.caret("2 × 3").unwrap(), "  ^");
+        assert_eq!(err.to_string(), "unexpected character `×` at offset 2");
+        assert_eq!(eval("1 +").unwrap_err().caret("1 +").unwrap(), "   ^");
+    }
+
+    #[test]
+    fn test_errors_serialize_with_their_kind() {
+        let json = |err: CalcError| serde_json::to_value(err).unwrap();
+        assert_eq!(
+            json(div(&[20.0, 0.0], true).unwrap_err()),
+            serde_json::json!({"kind": "DivisionByZero", "at": null, "operand": 2})
+        );
+        assert_eq!(
+            json(eval("pi = 3").unwrap_err())["reserved_for"],
+            "constant"
+        );
+        // Too big for a JSON value, but not for JSON text
+        let err = CalcError::InexactDivision {
+            dividend: i128::MAX,
+            divisor: 2,
+        };
+        assert!(serde_json::to_string(&err)
+            .unwrap()
+            .contains(&i128::MAX.to_string()));
+    }
 }
@@ -0,0 +1,297 @@
+//! Decimal and digit group separators for `--locale` and `--grouping`: reading `3,14` or
+//! `1.234,57` the way they're written in much of Europe, and printing results back that way.
+//!
+//! Every number read from the command line, stdin, a file or an expression goes through
+//! [`Separators::normalize`] first, so they all accept and reject the same forms.
+
+use crate::radix::group_digits;
+use crate::suffix;
+use std::borrow::Cow;
+
+/// Which character separates the decimals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    /// `1,234.5`
+    #[default]
+    Dot,
+    /// `1.234,5`
+    Comma,
+}
+
+/// The separators of the numbers read and printed, and whether those read may have an SI
+/// suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separators {
+    pub locale: Locale,
+    /// Whether numbers may have, and are printed with, digit group separators
+    pub grouping: bool,
+    /// Whether numbers may end in an SI suffix like `k` or `m` (see [`suffix::expand`])
+    pub suffixes: bool,
+}
+
+impl Default for Separators {
+    fn default() -> Separators {
+        Separators::new(Locale::Dot, false)
+    }
+}
+
+impl Separators {
+    /// Separators of `locale`, with suffixes allowed.
+    pub fn new(locale: Locale, grouping: bool) -> Separators {
+        Separators {
+            locale,
+            grouping,
+            suffixes: true,
+        }
+    }
+
+    /// The decimal separator: `.` or `,`.
+    pub fn decimal(self) -> char {
+        match self.locale {
+            Locale::Dot => '.',
+            Locale::Comma => ',',
+        }
+    }
+
+    /// The digit group separator, the other one of `.` and `,`.
+    pub fn group(self) -> char {
+        match self.locale {
+            Locale::Dot => ',',
+            Locale::Comma => '.',
+        }
+    }
+
+    /// Whether `,` separates the numbers of a list and the arguments of a function, which
+    /// it does when it's never part of a number; otherwise `;` does.
+    pub fn comma_separates(self) -> bool {
+        self.locale == Locale::Dot && !self.grouping
+    }
+
+    /// `token` written the way Rust parses numbers: with a `.` for decimals, no digit
+    /// groups, no `_` and no suffix. Error with the reason when the separators are
+    /// misplaced, mixed up, or, with grouping, ambiguous: `1,234` could be 1234, or 1.234
+    /// with a decimal comma. Anything else is passed through for the caller's parser to
+    /// judge.
+    /// E.g.: ("-1.234,5", comma locale with grouping) => "-1234.5", ("2,5k", comma) => "2500"
+    pub fn normalize(self, token: &str) -> Result<Cow<'_, str>, &'static str> {
+        let token = self.decimal_point(token)?;
+        Ok(match suffix::expand(&token, self.suffixes)? {
+            Some(expanded) => Cow::Owned(expanded),
+            None => token,
+        })
+    }
+
+    /// `token` with a `.` for decimals and no digit groups, for [`Separators::normalize`].
+    fn decimal_point(self, token: &str) -> Result<Cow<'_, str>, &'static str> {
+        if !token.contains([',', '.']) {
+            return Ok(Cow::Borrowed(token));
+        }
+        let (decimal, group) = (self.decimal(), self.group());
+        let (mantissa, exponent) = token.split_at(token.find(['e', 'E']).unwrap_or(token.len()));
+        let (sign, mantissa) = match mantissa.strip_prefix(['-', '+']) {
+            Some(rest) => (&mantissa[..1], rest),
+            None => ("", mantissa),
+        };
+        let (int, frac) = match mantissa.split_once(decimal) {
+            Some((int, frac)) => (int, Some(frac)),
+            None => (mantissa, None),
+        };
+        if let Some(frac) = frac {
+            if frac.contains(decimal) {
+                return Err("more than one decimal separator");
+            }
+            if frac.contains(group) {
+                return Err(match self.locale {
+                    Locale::Dot => "a `,` after the decimal `.`",
+                    Locale::Comma => "a `.` after the decimal `,`",
+                });
+            }
+        }
+        let mut digits = String::with_capacity(token.len());
+        digits.push_str(sign);
+        if int.contains(group) {
+            if !self.grouping {
+                return Err(match (self.locale, frac) {
+                    (_, Some(_)) => "digit group separators need --grouping",
+                    (Locale::Dot, None) => {
+                        "the decimal separator is `.`; use --locale comma for `,`"
+                    }
+                    (Locale::Comma, None) => "the decimal separator is `,` with --locale comma",
+                });
+            }
+            let groups: Vec<&str> = int.split(group).collect();
+            let (first, rest) = groups.split_first().expect("split yields a first part");
+            if !(1..=3).contains(&first.len()) || rest.iter().any(|g| g.len() != 3) {
+                return Err("digit groups must have 3 digits");
+            }
+            if groups.len() == 2 && frac.is_none() {
+                return Err(match self.locale {
+                    Locale::Dot => "ambiguous: a lone `,` could be a decimal comma",
+                    Locale::Comma => "ambiguous: a lone `.` could be a decimal point",
+                });
+            }
+            groups.iter().for_each(|g| digits.push_str(g));
+        } else {
+            digits.push_str(int);
+        }
+        if let Some(frac) = frac {
+            digits.push('.');
+            digits.push_str(frac);
+        }
+        digits.push_str(exponent);
+        Ok(Cow::Owned(digits))
+    }
+
+    /// `formatted`, a number as Rust prints it (`-1234.5`, `6.60e4`, `inf`), with the
+    /// decimal separator of the locale and, with grouping, the integer digits in groups of 3.
+    /// E.g.: ("1234.57", comma locale with grouping) => "1.234,57"
+    pub fn localize(self, formatted: &str) -> String {
+        let (sign, rest) = match formatted.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", formatted),
+        };
+        let (int, rest) = rest.split_at(
+            rest.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len()),
+        );
+        let int = if self.grouping && !int.is_empty() {
+            group_digits(int, 3).replace('_', &self.group().to_string())
+        } else {
+            int.to_string()
+        };
+        match rest.strip_prefix('.') {
+            Some(frac) => format!("{}{}{}{}", sign, int, self.decimal(), frac),
+            None => format!("{}{}{}", sign, int, rest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOT: Separators = Separators {
+        locale: Locale::Dot,
+        grouping: false,
+        suffixes: true,
+    };
+    const COMMA: Separators = Separators {
+        locale: Locale::Comma,
+        grouping: false,
+        suffixes: true,
+    };
+    const DOT_GROUPED: Separators = Separators {
+        locale: Locale::Dot,
+        grouping: true,
+        suffixes: true,
+    };
+    const COMMA_GROUPED: Separators = Separators {
+        locale: Locale::Comma,
+        grouping: true,
+        suffixes: true,
+    };
+
+    #[test]
+    fn test_normalize_each_locale() {
+        assert_eq!(DOT.normalize("3.14").unwrap(), "3.14");
+        assert_eq!(COMMA.normalize("3,14").unwrap(), "3.14");
+        assert_eq!(COMMA.normalize("-,5e3").unwrap(), "-.5e3");
+        assert_eq!(COMMA.normalize("+2,5E-1").unwrap(), "+2.5E-1");
+        assert_eq!(DOT_GROUPED.normalize("1,234,567.89").unwrap(), "1234567.89");
+        assert_eq!(COMMA_GROUPED.normalize("1.234,57").unwrap(), "1234.57");
+        assert_eq!(COMMA_GROUPED.normalize("-12.345.678").unwrap(), "-12345678");
+        // Nothing to do
+        assert_eq!(COMMA.normalize("1/3").unwrap(), "1/3");
+        assert_eq!(COMMA_GROUPED.normalize("inf").unwrap(), "inf");
+    }
+
+    #[test]
+    fn test_mixed_separators_are_rejected() {
+        assert_eq!(
+            COMMA.normalize("3.14"),
+            Err("the decimal separator is `,` with --locale comma")
+        );
+        assert_eq!(
+            DOT.normalize("3,14"),
+            Err("the decimal separator is `.`; use --locale comma for `,`")
+        );
+        assert_eq!(
+            COMMA.normalize("1.234,5"),
+            Err("digit group separators need --grouping")
+        );
+        assert_eq!(
+            COMMA_GROUPED.normalize("1,234.5"),
+            Err("a `.` after the decimal `,`")
+        );
+        assert_eq!(
+            DOT_GROUPED.normalize("1.5,0"),
+            Err("a `,` after the decimal `.`")
+        );
+        assert_eq!(
+            COMMA.normalize("1,2,3"),
+            Err("more than one decimal separator")
+        );
+        for misplaced in ["1,23,456", "1234,567", ",123", "1,,234"] {
+            assert_eq!(
+                DOT_GROUPED.normalize(misplaced),
+                Err("digit groups must have 3 digits"),
+                "{}",
+                misplaced
+            );
+        }
+    }
+
+    #[test]
+    fn test_a_single_group_separator_is_ambiguous_only_with_grouping() {
+        assert!(DOT_GROUPED
+            .normalize("1,234")
+            .unwrap_err()
+            .starts_with("ambiguous"));
+        assert!(COMMA_GROUPED
+            .normalize("1.234")
+            .unwrap_err()
+            .starts_with("ambiguous"));
+        assert_eq!(COMMA.normalize("1,234").unwrap(), "1.234");
+        assert_eq!(DOT_GROUPED.normalize("1,234.0").unwrap(), "1234.0");
+        assert_eq!(DOT_GROUPED.normalize("1,234,567").unwrap(), "1234567");
+    }
+
+    #[test]
+    fn test_underscores_and_suffixes_after_separators() {
+        assert_eq!(COMMA.normalize("2,5k").unwrap(), "2500");
+        assert_eq!(COMMA_GROUPED.normalize("1.234,5m").unwrap(), "1.2345");
+        assert_eq!(DOT.normalize("1_000.25").unwrap(), "1000.25");
+        assert_eq!(DOT.normalize("3M").unwrap(), "3000000");
+        let no_suffixes = Separators {
+            suffixes: false,
+            ..DOT
+        };
+        assert_eq!(no_suffixes.normalize("3M").unwrap(), "3M");
+        assert_eq!(no_suffixes.normalize("1_000").unwrap(), "1000");
+        assert_eq!(DOT.normalize("1__0"), Err("a `_` goes between two digits"));
+    }
+
+    #[test]
+    fn test_localize() {
+        assert_eq!(DOT.localize("-1234.50"), "-1234.50");
+        assert_eq!(COMMA.localize("-1234.50"), "-1234,50");
+        assert_eq!(COMMA_GROUPED.localize("1234.57"), "1.234,57");
+        assert_eq!(DOT_GROUPED.localize("-1234567"), "-1,234,567");
+        assert_eq!(DOT_GROUPED.localize("123.4"), "123.4");
+        assert_eq!(COMMA_GROUPED.localize("6.60e4"), "6,60e4");
+        assert_eq!(COMMA_GROUPED.localize("66.0e-3"), "66,0e-3");
+        assert_eq!(COMMA_GROUPED.localize("inf"), "inf");
+        assert_eq!(COMMA_GROUPED.localize("NaN"), "NaN");
+    }
+
+    #[test]
+    fn test_round_trip_in_comma_locale() {
+        for separators in [COMMA, COMMA_GROUPED] {
+            for x in [1234.57, -0.5, 1_000_000.0, 2.75] {
+                let printed = separators.localize(&format!("{:.2}", x));
+                let read: f64 = separators.normalize(&printed).unwrap().parse().unwrap();
+                assert_eq!(read, x, "{}", printed);
+            }
+        }
+    }
+}
@@ -1,11 +1,44 @@
-use clap::{Args, Parser, Subcommand};
-use mycalc::{add, div, mul, sub};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use mycalc::chain::eval_chain_localized;
+use mycalc::duration::{self, format_clock, format_hms, parse_duration};
+use mycalc::finance;
+use mycalc::functions::CONSTANTS;
+use mycalc::geometry::{distance, hypot};
+use mycalc::input::{parse_number, parse_numbers, read_numbers, Number};
+use mycalc::intmath::{factorial, gcd, lcm, MAX_FACTORIAL};
+use mycalc::locale::{Locale, Separators};
+use mycalc::radix::{detect_radix, from_radix, group_digits, radix_prefix, to_radix};
+use mycalc::rational::{add_r, div_r, format_decimal, format_fraction, mul_r, sub_r};
+use mycalc::readline;
+use mycalc::repl::Repl;
+use mycalc::stats::{self, Spread};
+use mycalc::trig::{self, AngleUnit};
+use mycalc::units;
+use mycalc::vector;
+use mycalc::{
+    add, add_d, add_i, add_naive, div, div_d, div_i, divmod, eval_localized, eval_rpn_localized,
+    exp, finite, format_d, format_number, format_rounded, ln, log_base, mul, mul_d, mul_i,
+    nth_root, percent_change, percent_of, percent_off, percent_on, pow, reduce_ratio, rem,
+    round_to, sub, sub_d, sub_i, trim_zeros, CalcError, Env, IntDiv, Notation, Ratio, RemMode,
+    RoundMode,
+};
+use num_rational::BigRational;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::env;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Parser, Debug)]
 #[command(
     name = "mycalc",
     version,
-    about = "Simple CLI calculator with clap (add, sub, mul, div)",
+    about = "Simple CLI calculator with clap (add, sub, mul, div, mod, pow, eval)",
     arg_required_else_help = true,
     propagate_version = true,
     disable_help_subcommand = true
@@ -15,10 +48,240 @@ struct Cli {
     #[arg(global = true, short, long, default_value_t = 2)]
     precision: usize,
 
+    /// How to print results: fixed decimals, sci (6.02e23) or eng (exponent a multiple of 3)
+    #[arg(global = true, long, default_value = "fixed", value_parser = notation_arg())]
+    notation: Notation,
+
+    /// Also read numbers from this file (separated by spaces, commas or newlines, semicolons
+    /// instead of commas with --locale comma or --grouping; # comments)
+    #[arg(global = true, long, value_name = "PATH")]
+    file: Option<PathBuf>,
+
+    /// Decimal separator of the numbers read and printed: dot (3.14) or comma (3,14)
+    #[arg(global = true, long, default_value = "dot", value_parser = locale_arg())]
+    locale: Locale,
+
+    /// Accept and print digit group separators: 1,234.5, or 1.234,5 with --locale comma
+    #[arg(global = true, long)]
+    grouping: bool,
+
+    /// Drop trailing zeros after the point, and the point itself: 3.00 => 3, 2.50 => 2.5
+    #[arg(global = true, long)]
+    trim: bool,
+
+    /// Read numbers like 2.5k or 750m as they are, rather than with SI suffixes (k, M, G,
+    /// T, m, u or µ, n)
+    #[arg(global = true, long)]
+    no_suffixes: bool,
+
+    /// Print results as text, or as one JSON object per run for scripts (errors to stderr)
+    #[arg(global = true, long, value_enum, default_value_t = Output::Text)]
+    output: Output,
+
+    /// Exact integer arithmetic for add, sub, mul and div: errors on overflow, no decimals
+    #[arg(global = true, long)]
+    int: bool,
+
+    /// With --int, round divisions down (-7 / 2 => -4) instead of requiring them to be exact
+    #[arg(global = true, long)]
+    int_floor: bool,
+
+    /// With --int, round divisions toward zero (-7 / 2 => -3)
+    #[arg(global = true, long)]
+    int_trunc: bool,
+
+    /// Exact fractions for add, sub, mul and div: takes 1/3, 0.25 or 7, prints 5/6
+    #[arg(global = true, long)]
+    exact: bool,
+
+    /// With --exact, print a decimal rounded to N digits instead of a fraction
+    #[arg(global = true, long, value_name = "N")]
+    decimal_digits: Option<usize>,
+
+    /// Base-10 arithmetic for add, sub, mul and div (0.1 + 0.2 is exactly 0.3), for money
+    #[arg(global = true, long)]
+    decimal: bool,
+
+    /// Angles in degrees for sin, cos and tan, and from asin, acos, atan and atan2
+    #[arg(global = true, long)]
+    degrees: bool,
+
+    /// Add by plain floating-point addition, without compensating for rounding, to compare:
+    /// 1e16 + 1 - 1e16 => 0 instead of 1
+    #[arg(global = true, long)]
+    naive_sum: bool,
+
+    /// Error on results that aren't finite numbers, like 1e308 * 10 or inf - inf, and on
+    /// results that are only numbers by rounding, like tan of 90 degrees (the default)
+    #[arg(global = true, long, overrides_with = "no_strict")]
+    strict: bool,
+
+    /// Print infinite and NaN results as inf and NaN, and tan of 90 degrees as the huge
+    /// number floating point gives
+    #[arg(global = true, long, overrides_with = "strict")]
+    no_strict: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+fn notation_arg() -> impl TypedValueParser<Value = Notation> {
+    PossibleValuesParser::new(["fixed", "sci", "eng"]).map(|raw| match raw.as_str() {
+        "sci" => Notation::Scientific,
+        "eng" => Notation::Engineering,
+        _ => Notation::Fixed,
+    })
+}
+
+fn locale_arg() -> impl TypedValueParser<Value = Locale> {
+    PossibleValuesParser::new(["dot", "comma"]).map(|raw| match raw.as_str() {
+        "comma" => Locale::Comma,
+        _ => Locale::Dot,
+    })
+}
+
+fn round_mode_arg() -> impl TypedValueParser<Value = RoundMode> {
+    PossibleValuesParser::new(["half-up", "half-even", "floor", "ceil", "trunc"]).map(|raw| {
+        match raw.as_str() {
+            "half-even" => RoundMode::HalfEven,
+            "floor" => RoundMode::Floor,
+            "ceil" => RoundMode::Ceil,
+            "trunc" => RoundMode::Trunc,
+            _ => RoundMode::HalfUp,
+        }
+    })
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Output {
+    Text,
+    Json,
+}
+
+/// How results are printed: as text, floating-point ones with `--precision` digits in
+/// `--notation`, trimmed with `--trim`, and the separators of `--locale` and `--grouping`;
+/// or as JSON objects naming the subcommand `op`.
+#[derive(Clone, Copy, Debug)]
+struct Style<'a> {
+    precision: usize,
+    notation: Notation,
+    trim: bool,
+    separators: Separators,
+    output: Output,
+    op: &'a str,
+    /// Results that aren't finite numbers are errors
+    strict: bool,
+}
+
+impl Style<'_> {
+    /// `x`, a result of `operation`; with `--strict`, an error if it isn't a finite number.
+    fn finite(self, operation: &'static str, x: f64) -> Result<f64, CalcError> {
+        if self.strict {
+            finite(operation, None, None, x)
+        } else {
+            Ok(x)
+        }
+    }
+
+    fn format(self, x: f64) -> String {
+        self.decimals(&format_number(x, self.precision, self.notation))
+    }
+
+    /// `formatted`, a number with `--precision` decimals, trimmed and localized.
+    fn decimals(self, formatted: &str) -> String {
+        if self.trim {
+            self.separators.localize(&trim_zeros(formatted))
+        } else {
+            self.separators.localize(formatted)
+        }
+    }
+
+    /// `values` formatted and separated by commas, or semicolons when commas are part of
+    /// numbers.
+    fn list(self, values: &[f64]) -> String {
+        let formatted: Vec<String> = values.iter().map(|&x| self.format(x)).collect();
+        formatted.join(if self.separators.comma_separates() {
+            ", "
+        } else {
+            "; "
+        })
+    }
+
+    /// Prints `text`, or with `--output json` an object with `op`, `operands`, `result`
+    /// and `precision`.
+    fn print(self, text: String, operands: Value, result: Value) {
+        match self.output {
+            Output::Text => println!("{}", text),
+            Output::Json => println!(
+                "{}",
+                json!({
+                    "op": self.op,
+                    "operands": operands,
+                    "result": result,
+                    "precision": self.precision,
+                })
+            ),
+        }
+    }
+
+    /// Prints `err` to stderr, as text or an object with `op` and `error`, and exits with
+    /// its [`exit_code`].
+    fn fail(self, err: CalcError) -> ! {
+        match self.output {
+            Output::Text => eprintln!("Error: {}", err),
+            Output::Json => {
+                #[derive(Serialize)]
+                struct Failure<'a> {
+                    op: &'a str,
+                    error: &'a CalcError,
+                }
+                let failure = Failure {
+                    op: self.op,
+                    error: &err,
+                };
+                eprintln!(
+                    "{}",
+                    serde_json::to_string(&failure).expect("errors serialize")
+                );
+            }
+        }
+        std::process::exit(exit_code(&err))
+    }
+}
+
+/// `x` as a JSON number, or as the string `"NaN"`, `"inf"` or `"-inf"`, which JSON has no
+/// numbers for.
+fn json_float(x: f64) -> Value {
+    if x.is_nan() {
+        json!("NaN")
+    } else if x.is_infinite() {
+        json!(if x > 0.0 { "inf" } else { "-inf" })
+    } else {
+        json!(x)
+    }
+}
+
+fn json_floats(xs: &[f64]) -> Value {
+    Value::Array(xs.iter().copied().map(json_float).collect())
+}
+
+/// `n` as a JSON number, or as a string of its digits beyond 64 bits, where JSON parsers
+/// commonly lose precision.
+fn json_int<T: Copy + TryInto<i64> + ToString>(n: T) -> Value {
+    match n.try_into() {
+        Ok(n) => json!(n),
+        Err(_) => json!(n.to_string()),
+    }
+}
+
+/// Where lists of numbers come from besides the command line, and how they're written
+/// and errors about them printed.
+#[derive(Clone, Copy, Debug)]
+struct Input<'a> {
+    file: Option<&'a Path>,
+    style: Style<'a>,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Add all numbers: mycalc add 1 2 3
@@ -29,38 +292,1469 @@ enum Commands {
     Mul(OpArgs),
     /// Divide is (left-associative): mycalc div 20 2 5 => (20 / 2 / 5)
     Div(OpArgs),
+    /// Remainder: mycalc mod 7 3 => 1
+    Mod(RemArgs),
+    /// Quotient and remainder on one line: mycalc divmod 7 3 => 2 1
+    Divmod(RemArgs),
+    /// Power tower (right-associative): mycalc pow 2 3 2 => 2^(3^2)
+    Pow(OpArgs),
+    /// Nth root (square root by default): mycalc root 27 --n 3 => 3
+    Root(RootArgs),
+    /// Square root: mycalc sqrt 16 => 4
+    Sqrt(ValueArgs),
+    /// Cube root: mycalc cbrt -- -8 => -2
+    Cbrt(ValueArgs),
+    /// Factorial: mycalc fact 5 => 120
+    Fact(FactArgs),
+    /// Greatest common divisor: mycalc gcd 12 18 27 => 3
+    Gcd(OpArgs),
+    /// Least common multiple: mycalc lcm 4 6 10 => 60
+    Lcm(OpArgs),
+    /// Round to --digits decimals: mycalc round 2.675 --digits 2 => 2.68
+    Round(RoundArgs),
+    /// Round down: mycalc floor -2.5 => -3
+    Floor(DigitsArgs),
+    /// Round up: mycalc ceil 2.1 => 3
+    Ceil(DigitsArgs),
+    /// Round toward zero: mycalc trunc -2.9 => -2
+    Trunc(DigitsArgs),
+    /// Logarithm, base 10 unless --base is given: mycalc log 8 --base 2 => 3
+    Log(LogArgs),
+    /// Natural logarithm: mycalc ln 1 => 0
+    Ln(ValueArgs),
+    /// e raised to X: mycalc exp 1 => 2.718...
+    Exp(ValueArgs),
+    /// Sine: mycalc --degrees sin 30 => 0.5
+    Sin(ValueArgs),
+    /// Cosine: mycalc --degrees cos 60 => 0.5
+    Cos(ValueArgs),
+    /// Tangent: mycalc --degrees tan 45 => 1
+    Tan(ValueArgs),
+    /// Arcsine: mycalc --degrees asin 0.5 => 30
+    Asin(ValueArgs),
+    /// Arccosine: mycalc --degrees acos 0.5 => 60
+    Acos(ValueArgs),
+    /// Arctangent: mycalc --degrees atan 1 => 45
+    Atan(ValueArgs),
+    /// Angle of the point (X, Y): mycalc --degrees atan2 1 -1 => 135
+    Atan2(Atan2Args),
+    /// Length of a vector, sqrt(x² + y² + ...): mycalc hypot 3 4 => 5
+    Hypot(OpArgs),
+    /// Distance between two points: mycalc dist --a 1,2,3 --b 4,6,3 => 5
+    Dist(DistArgs),
+    /// Vectors, their components separated by commas: mycalc vec dot --a 1,2,3 --b 4,5,6 => 32
+    #[command(name = "vec")]
+    Vector(VectorArgs),
+    /// Convert between units of length, mass, volume or data size: mycalc convert 5 km mi => 3.11
+    Convert(ConvertArgs),
+    /// Durations like 1h30m or 01:30:00: mycalc time add 1h30m 45m 90s => 2h16m30s
+    Time(TimeArgs),
+    /// Compound interest and loan payments: mycalc finance compound --principal 1000 --rate 5% --years 10 => 1628.89
+    Finance(FinanceArgs),
+    /// Percentages: mycalc percent 30 of 200 => 15, percent 15 on 200 => 230, 15 off 200 => 170
+    Percent(PercentArgs),
+    /// Percent change from FROM to TO: mycalc change 50 75 => 50, change 80 60 => -25
+    Change(ChangeArgs),
+    /// Ratio of A to B, in lowest terms for integers: mycalc ratio 1920 1080 => 16:9
+    Ratio(RatioArgs),
+    /// Convert an integer between bases: mycalc base 0xff --to bin => 11111111
+    Base(BaseArgs),
+    /// Summary statistics: mycalc stats 3 1 4 1 5 9 2 6
+    Stats(StatsArgs),
+    /// Evaluate an expression: mycalc eval "2 + 3 * (4 - 1) / 2"
+    Eval(EvalArgs),
+    /// Run operations one after another: mycalc chain "add 1 2 3 | mul 2 | sub 1" => 11
+    Chain(ChainArgs),
+    /// Evaluate Reverse Polish Notation: mycalc rpn 3 4 + 2 '*' => 14
+    Rpn(RpnArgs),
+    /// List the constants of expressions with their full values: mycalc constants
+    Constants,
+    /// Evaluate expressions interactively, one per line; :help lists the commands
+    Repl(ReplArgs),
 }
 
 #[derive(Args, Debug)]
 struct OpArgs {
-    /// Numbers to operate (at least 2)
-    #[arg(value_name = "NUM", num_args = 2..)]
-    nums: Vec<f64>,
+    /// Numbers to operate (at least 2); a lone - reads more from stdin
+    #[arg(value_name = "NUM", value_parser = num_arg, allow_negative_numbers = true)]
+    nums: Vec<NumArg>,
+}
+
+/// A number given on the command line, kept as written until the mode (float, `--int` or
+/// `--exact`) decides how to read it, or `-` for the numbers on stdin.
+#[derive(Clone, Debug)]
+enum NumArg {
+    Value(String),
+    Stdin,
+}
+
+fn num_arg(raw: &str) -> Result<NumArg, String> {
+    if raw == "-" {
+        return Ok(NumArg::Stdin);
+    }
+    Ok(NumArg::Value(raw.to_string()))
+}
+
+/// A single number given on the command line, kept as written until `--locale` decides
+/// how to read it.
+#[derive(Clone, Debug)]
+struct RawNum(String);
+
+impl FromStr for RawNum {
+    type Err = Infallible;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Ok(RawNum(raw.to_string()))
+    }
+}
+
+impl RawNum {
+    fn value(&self, separators: Separators) -> f64 {
+        parse_arg(&self.0, separators)
+    }
+
+    /// This number as a fraction, which it is unless it ends with `%`: `5%` is 0.05.
+    fn rate(&self, separators: Separators) -> f64 {
+        match self.0.strip_suffix('%') {
+            Some(percent) => parse_arg::<f64>(percent, separators) / 100.0,
+            None => self.value(separators),
+        }
+    }
+
+    /// Prints `f` of this number in `style`, or fails with the error.
+    fn apply(&self, f: impl FnOnce(f64) -> Result<f64, CalcError>, style: Style) {
+        let x = self.value(style.separators);
+        print_result(f(x), &[x], style)
+    }
+}
+
+/// `raw` read as a `T` with `separators`; one that isn't is a usage error.
+fn parse_arg<T: Number>(raw: &str, separators: Separators) -> T {
+    match parse_number(raw, separators) {
+        Ok(n) => n,
+        Err(reason) => {
+            let mut message = format!("invalid {} '{}'", T::NAME, raw);
+            if let Some(reason) = reason {
+                message = format!("{}: {}", message, reason);
+            }
+            Cli::command()
+                .error(ErrorKind::ValueValidation, message)
+                .exit()
+        }
+    }
+}
+
+/// The numbers of `args`, then those on stdin if one of them is `-`, then those of the
+/// input file. Fewer than `min` numbers, or one that isn't a `T`, is a usage error.
+fn numbers<T: Number>(args: &[NumArg], input: Input, min: usize) -> Vec<T> {
+    let (separators, style) = (input.style.separators, input.style);
+    let mut nums = Vec::new();
+    for arg in args {
+        if let NumArg::Value(raw) = arg {
+            nums.push(parse_arg(raw, separators));
+        }
+    }
+    if args.iter().any(|arg| matches!(arg, NumArg::Stdin)) {
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) {
+            style.fail(CalcError::Io {
+                source: "stdin".to_string(),
+                message: e.to_string(),
+            });
+        }
+        match parse_numbers(&text, "stdin", separators) {
+            Ok(more) => nums.extend(more),
+            Err(err) => style.fail(err),
+        }
+    }
+    if let Some(file) = input.file {
+        match read_numbers(file, separators) {
+            Ok(more) => nums.extend(more),
+            Err(err) => style.fail(err),
+        }
+    }
+    if nums.len() < min && style.output == Output::Json {
+        style.fail(CalcError::TooFewNumbers {
+            // The process exits right after, so the name may as well live as long
+            function: Box::leak(style.op.to_string().into_boxed_str()),
+            needed: min,
+            got: nums.len(),
+        });
+    }
+    if nums.len() < min {
+        Cli::command()
+            .error(
+                ErrorKind::TooFewValues,
+                format!(
+                    "at least {} number{} required, got {}",
+                    min,
+                    if min == 1 { " is" } else { "s are" },
+                    nums.len()
+                ),
+            )
+            .exit();
+    }
+    nums
+}
+
+#[derive(Args, Debug)]
+struct RemArgs {
+    /// Dividend
+    #[arg(allow_negative_numbers = true)]
+    a: RawNum,
+    /// Divisor
+    #[arg(allow_negative_numbers = true)]
+    b: RawNum,
+    /// Euclidean remainder, never negative (-7 mod 3 => 2) instead of truncated (=> -1)
+    #[arg(long)]
+    euclidean: bool,
+}
+
+impl RemArgs {
+    fn mode(&self) -> RemMode {
+        if self.euclidean {
+            RemMode::Euclidean
+        } else {
+            RemMode::Truncated
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct ValueArgs {
+    #[arg(value_name = "X", allow_negative_numbers = true)]
+    x: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct RootArgs {
+    #[arg(value_name = "X", allow_negative_numbers = true)]
+    x: RawNum,
+    /// Which root to take (2 = square root, 3 = cube root, ...)
+    #[arg(short, long, default_value_t = 2)]
+    n: u32,
+}
+
+#[derive(Args, Debug)]
+struct RoundArgs {
+    #[command(flatten)]
+    value: DigitsArgs,
+    /// half-up (ties away from zero), half-even (ties to even), floor, ceil or trunc
+    #[arg(long, default_value = "half-up", value_parser = round_mode_arg())]
+    mode: RoundMode,
+}
+
+#[derive(Args, Debug)]
+struct DigitsArgs {
+    #[arg(value_name = "X", allow_negative_numbers = true)]
+    x: RawNum,
+    /// Decimals to keep; negative rounds to tens (-1), hundreds (-2), ...
+    #[arg(short, long, default_value_t = 0, allow_negative_numbers = true)]
+    digits: i32,
+}
+
+impl DigitsArgs {
+    /// Prints `x` rounded with `mode`, showing `digits` decimals; in fixed notation they
+    /// come from the decimal form of the result, so `--digits 400` pads with zeros.
+    fn print(&self, mode: RoundMode, style: Style) {
+        let style = Style {
+            precision: self.digits.max(0) as usize,
+            ..style
+        };
+        let x = self.x.value(style.separators);
+        let operands = [x, self.digits.into()];
+        match style.finite("the result", round_to(x, self.digits, mode)) {
+            Ok(result) if style.notation == Notation::Fixed => style.print(
+                style.decimals(&format_rounded(result, self.digits)),
+                json_floats(&operands),
+                json_float(result),
+            ),
+            result => print_result(result, &operands, style),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct LogArgs {
+    #[arg(value_name = "X", allow_negative_numbers = true)]
+    x: RawNum,
+    /// Base of the logarithm (positive, not 1)
+    #[arg(short, long, default_value = "10", allow_negative_numbers = true)]
+    base: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct Atan2Args {
+    #[arg(allow_negative_numbers = true)]
+    y: RawNum,
+    #[arg(allow_negative_numbers = true)]
+    x: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    #[arg(allow_negative_numbers = true)]
+    value: RawNum,
+    /// Unit of VALUE: mm, cm, m, km, in, ft, yd, mi; mg, g, kg, oz, lb; ml, l, cup, gal;
+    /// B, KB, KiB, MB, MiB, GB, GiB; or a name like meter, in any case
+    from: String,
+    /// Unit to convert to, of the same dimension as FROM
+    to: String,
+}
+
+#[derive(Args, Debug)]
+struct DistArgs {
+    /// First point, its coordinates separated by commas (semicolons with --locale comma
+    /// or --grouping)
+    #[arg(long, value_name = "POINT", allow_hyphen_values = true)]
+    a: String,
+    /// Second point, with as many coordinates as the first
+    #[arg(long, value_name = "POINT", allow_hyphen_values = true)]
+    b: String,
+}
+
+impl DistArgs {
+    /// The coordinates of both points; one that isn't a number fails in `style`.
+    fn points(&self, style: Style) -> (Vec<f64>, Vec<f64>) {
+        (
+            components(&self.a, "--a", style),
+            components(&self.b, "--b", style),
+        )
+    }
+}
+
+/// The numbers of the list `text` given with the option `source`, separated by commas
+/// (semicolons when commas are part of numbers); one that isn't a number fails in `style`.
+fn components(text: &str, source: &str, style: Style) -> Vec<f64> {
+    parse_numbers(text, source, style.separators).unwrap_or_else(|err| style.fail(err))
+}
+
+#[derive(Args, Debug)]
+struct FinanceArgs {
+    #[command(subcommand)]
+    op: FinanceOp,
+    /// Also print a table of the interest, principal and balance of each year
+    #[arg(long, global = true)]
+    schedule: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum FinanceOp {
+    /// What PRINCIPAL grows to with compound interest
+    Compound {
+        #[command(flatten)]
+        money: Money,
+        /// How many times a year interest is added
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        compounds_per_year: u32,
+    },
+    /// Monthly payment of a loan of PRINCIPAL, paid off in YEARS
+    Payment {
+        #[command(flatten)]
+        money: Money,
+    },
+}
+
+#[derive(Args, Debug)]
+struct Money {
+    #[arg(long, allow_negative_numbers = true)]
+    principal: RawNum,
+    /// Yearly interest rate, as 5% or 0.05
+    #[arg(long, allow_hyphen_values = true)]
+    rate: RawNum,
+    #[arg(long, allow_negative_numbers = true)]
+    years: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct TimeArgs {
+    #[command(subcommand)]
+    op: TimeOp,
+    /// How to print the resulting duration
+    #[arg(long = "as", value_enum, default_value_t = TimeFormat::Hms, global = true)]
+    format: TimeFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum TimeOp {
+    /// Sum: mycalc time add 1h30m 45m 90s => 2h16m30s
+    Add {
+        /// Durations like 1h30m45s, 90m, 1.5h or 01:30:45.5; a leading - subtracts one
+        /// (after --)
+        #[arg(required = true)]
+        durations: Vec<String>,
+    },
+    /// A minus B, negative if B is longer: mycalc time diff 02:15:00 01:50:30 => 24m30s
+    Diff { a: String, b: String },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum TimeFormat {
+    /// 8130.00, with --precision decimals
+    Seconds,
+    /// 2h15m30s
+    Hms,
+    /// 02:15:30
+    Clock,
+}
+
+#[derive(Args, Debug)]
+struct VectorArgs {
+    #[command(subcommand)]
+    op: VectorOp,
+}
+
+#[derive(Subcommand, Debug)]
+enum VectorOp {
+    /// Dot product: mycalc vec dot --a 1,2,3 --b 4,5,6 => 32
+    Dot(TwoVectors),
+    /// Length: mycalc vec norm --a 3,4 => 5
+    Norm(OneVector),
+    /// Sum: mycalc vec add --a 1,2 --b 3,4 => 4, 6
+    Add(TwoVectors),
+    /// Difference: mycalc vec sub --a 1,2 --b 3,4 => -2, -2
+    Sub(TwoVectors),
+    /// Each component times K: mycalc vec scale --a 1,-2 --k 2 => 2, -4
+    Scale(ScaleArgs),
+}
+
+#[derive(Args, Debug)]
+struct OneVector {
+    /// Components separated by commas (semicolons with --locale comma or --grouping)
+    #[arg(long, value_name = "VECTOR", allow_hyphen_values = true)]
+    a: String,
+}
+
+#[derive(Args, Debug)]
+struct TwoVectors {
+    /// Components separated by commas (semicolons with --locale comma or --grouping)
+    #[arg(long, value_name = "VECTOR", allow_hyphen_values = true)]
+    a: String,
+    /// As many components as --a
+    #[arg(long, value_name = "VECTOR", allow_hyphen_values = true)]
+    b: String,
+}
+
+#[derive(Args, Debug)]
+struct ScaleArgs {
+    /// Components separated by commas (semicolons with --locale comma or --grouping)
+    #[arg(long, value_name = "VECTOR", allow_hyphen_values = true)]
+    a: String,
+    /// Factor to multiply each component by
+    #[arg(long, allow_negative_numbers = true)]
+    k: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct FactArgs {
+    /// Non-negative integer, up to 34
+    n: u32,
+}
+
+#[derive(Args, Debug)]
+struct PercentArgs {
+    /// The part (for of) or the percentage (for on and off)
+    #[arg(allow_negative_numbers = true)]
+    value: RawNum,
+    /// of: what percent VALUE is of WHOLE; on: WHOLE plus VALUE%; off: WHOLE minus VALUE%
+    #[arg(value_enum)]
+    op: PercentOp,
+    #[arg(allow_negative_numbers = true)]
+    whole: RawNum,
+    /// Append % to a result that is a percentage (the of form)
+    #[arg(long)]
+    symbol: bool,
+}
+
+#[derive(Args, Debug)]
+struct ChangeArgs {
+    /// The value before, which can't be zero
+    #[arg(allow_negative_numbers = true)]
+    from: RawNum,
+    /// The value after
+    #[arg(allow_negative_numbers = true)]
+    to: RawNum,
+}
+
+#[derive(Args, Debug)]
+struct RatioArgs {
+    #[arg(allow_negative_numbers = true)]
+    a: RawNum,
+    /// Can't be zero
+    #[arg(allow_negative_numbers = true)]
+    b: RawNum,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PercentOp {
+    Of,
+    On,
+    Off,
+}
+
+#[derive(Args, Debug)]
+struct BaseArgs {
+    /// Integer to convert; a 0x, 0b or 0o prefix sets its base unless --from is given
+    #[arg(allow_hyphen_values = true)]
+    value: String,
+    /// Base of VALUE (default: from its prefix, else dec)
+    #[arg(long, value_enum)]
+    from: Option<Radix>,
+    /// Base of VALUE, any from 2 to 36
+    #[arg(long, value_name = "N", conflicts_with = "from", value_parser = clap::value_parser!(u32).range(2..=36))]
+    from_radix: Option<u32>,
+    /// Base to print in
+    #[arg(long, value_enum, default_value_t = Radix::Dec)]
+    to: Radix,
+    /// Base to print in, any from 2 to 36
+    #[arg(long, value_name = "N", conflicts_with = "to", value_parser = clap::value_parser!(u32).range(2..=36))]
+    to_radix: Option<u32>,
+    /// Print the 0b, 0o or 0x prefix of the output base
+    #[arg(long)]
+    prefix: bool,
+    /// Separate every N digits with _, counting from the right
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    group: Option<u32>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Radix {
+    Bin,
+    Oct,
+    Dec,
+    Hex,
+}
+
+impl Radix {
+    fn value(self) -> u32 {
+        match self {
+            Radix::Bin => 2,
+            Radix::Oct => 8,
+            Radix::Dec => 10,
+            Radix::Hex => 16,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct StatsArgs {
+    /// Numbers to describe (at least 1); a lone - reads more from stdin
+    #[arg(
+        value_name = "NUM",
+        allow_negative_numbers = true,
+        value_parser = num_arg
+    )]
+    nums: Vec<NumArg>,
+    /// Print only these metrics, in this order: --only mean,median
+    #[arg(long, value_delimiter = ',')]
+    only: Vec<Metric>,
+    /// Also print this percentile (0 to 100); repeatable: --percentile 90 --percentile 99
+    #[arg(long, value_name = "P")]
+    percentile: Vec<RawNum>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    Median,
+    Mode,
+    /// 25th percentile (first quartile)
+    P25,
+    /// 50th percentile (the median)
+    P50,
+    /// 75th percentile (third quartile)
+    P75,
+    /// Interquartile range, p75 - p25
+    Iqr,
+    /// Sample standard deviation (divides by n - 1)
+    Stddev,
+    /// Population standard deviation (divides by n)
+    Pstddev,
+    /// Sample variance
+    Variance,
+    /// Population variance
+    Pvariance,
+}
+
+impl Metric {
+    fn label(self) -> &'static str {
+        match self {
+            Metric::Count => "count",
+            Metric::Sum => "sum",
+            Metric::Min => "min",
+            Metric::Max => "max",
+            Metric::Mean => "mean",
+            Metric::Median => "median",
+            Metric::Mode => "mode",
+            Metric::P25 => "p25",
+            Metric::P50 => "p50",
+            Metric::P75 => "p75",
+            Metric::Iqr => "iqr",
+            Metric::Stddev => "stddev (sample)",
+            Metric::Pstddev => "stddev (population)",
+            Metric::Variance => "variance (sample)",
+            Metric::Pvariance => "variance (population)",
+        }
+    }
+
+    /// The name of the metric in `--only` and in the JSON output.
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Stddev => "stddev",
+            Metric::Pstddev => "pstddev",
+            Metric::Variance => "variance",
+            Metric::Pvariance => "pvariance",
+            _ => self.label(),
+        }
+    }
+
+    /// The metric of `nums`: one number, or for the mode every one that's most common.
+    /// With `--strict`, an error if one isn't a finite number.
+    fn values(self, nums: &[f64], style: Style) -> Result<Vec<f64>, CalcError> {
+        let values = match self {
+            Metric::Count => vec![nums.len() as f64],
+            Metric::Sum => vec![add(nums, style.strict)?],
+            Metric::Min => vec![stats::min(nums)?],
+            Metric::Max => vec![stats::max(nums)?],
+            Metric::Mean => vec![stats::mean(nums)?],
+            Metric::Median => vec![stats::median(nums)?],
+            Metric::Mode => stats::mode(nums)?,
+            Metric::P25 => vec![stats::percentile(nums, 25.0)?],
+            Metric::P50 => vec![stats::percentile(nums, 50.0)?],
+            Metric::P75 => vec![stats::percentile(nums, 75.0)?],
+            Metric::Iqr => vec![stats::iqr(nums)?],
+            Metric::Stddev => vec![stats::stddev(nums, Spread::Sample)?],
+            Metric::Pstddev => vec![stats::stddev(nums, Spread::Population)?],
+            Metric::Variance => vec![stats::variance(nums, Spread::Sample)?],
+            Metric::Pvariance => vec![stats::variance(nums, Spread::Population)?],
+        };
+        for &x in &values {
+            style.finite(self.name(), x)?;
+        }
+        Ok(values)
+    }
+
+    /// The metric of `nums` formatted in `style`, and as JSON: a number, or an array of
+    /// them for the mode.
+    fn value(self, nums: &[f64], style: Style) -> Result<(String, Value), CalcError> {
+        let values = self.values(nums, style)?;
+        Ok(match self {
+            Metric::Count => (nums.len().to_string(), json!(nums.len())),
+            Metric::Mode => (style.list(&values), json_floats(&values)),
+            _ => (style.format(values[0]), json_float(values[0])),
+        })
+    }
+}
+
+#[derive(Args, Debug)]
+struct ReplArgs {
+    /// Don't load or save the history file ($XDG_DATA_HOME/mycalc/history)
+    #[arg(long)]
+    no_history: bool,
+}
+
+#[derive(Args, Debug)]
+struct ChainArgs {
+    /// Stages separated by |, each an operation (add, sub, mul, div, pow, mod, sqrt, cbrt,
+    /// ln or exp) and its numbers; a stage's result goes first in the next (quote it)
+    #[arg(value_name = "STAGES", allow_hyphen_values = true)]
+    chain: String,
+}
+
+#[derive(Args, Debug)]
+struct RpnArgs {
+    /// Numbers, constants, operators (+ - * / ^) and functions; a lone - reads them from
+    /// stdin
+    #[arg(value_name = "TOKEN", required = true, allow_hyphen_values = true)]
+    tokens: Vec<String>,
+}
+
+impl RpnArgs {
+    /// The tokens given, or those on stdin for a lone `-`.
+    fn tokens(&self, style: Style) -> Vec<String> {
+        if self.tokens != ["-"] {
+            return self.tokens.clone();
+        }
+        let mut text = String::new();
+        if let Err(e) = io::stdin().read_to_string(&mut text) {
+            style.fail(CalcError::Io {
+                source: "stdin".to_string(),
+                message: e.to_string(),
+            });
+        }
+        text.split_whitespace().map(str::to_string).collect()
+    }
+}
+
+#[derive(Args, Debug)]
+struct EvalArgs {
+    /// Expression with + - * /, parentheses and numbers like 1.5e3 (quote it)
+    #[arg(value_name = "EXPR", allow_hyphen_values = true)]
+    expr: String,
+    /// Set a variable for the expression; repeatable: --var x=3 --var y=2
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = var_arg)]
+    vars: Vec<(String, RawNum)>,
+}
+
+fn var_arg(raw: &str) -> Result<(String, RawNum), String> {
+    let (name, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", raw))?;
+    let name = name.trim();
+    let mut chars = name.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_identifier {
+        return Err(format!("invalid variable name '{}'", name));
+    }
+    Env::new().set(name, 0.0).map_err(|err| err.to_string())?;
+    Ok((name.to_string(), RawNum(value.trim().to_string())))
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    check_modes(&cli);
     let precision = cli.precision;
+    let separators = Separators {
+        suffixes: !cli.no_suffixes,
+        ..Separators::new(cli.locale, cli.grouping)
+    };
+    // `vec dot` and the like are named with their own subcommand
+    let (name, args) = matches.subcommand().expect("a subcommand is required");
+    let op = match args.subcommand_name() {
+        Some(inner) => format!("{} {}", name, inner),
+        None => name.to_string(),
+    };
+    let style = Style {
+        precision,
+        notation: cli.notation,
+        trim: cli.trim,
+        separators,
+        output: cli.output,
+        op: &op,
+        strict: !cli.no_strict,
+    };
+    let strict = style.strict;
+    let input = Input {
+        file: cli.file.as_deref(),
+        style,
+    };
+    let unit = if cli.degrees {
+        AngleUnit::Degrees
+    } else {
+        AngleUnit::Radians
+    };
+    if cli.int {
+        let mode = if cli.int_floor {
+            IntDiv::Floor
+        } else if cli.int_trunc {
+            IntDiv::Trunc
+        } else {
+            IntDiv::Exact
+        };
+        run_int(cli.command, input, mode);
+        return;
+    }
+    if cli.exact {
+        run_exact(cli.command, input, cli.decimal_digits);
+        return;
+    }
+    if cli.decimal {
+        run_decimal(cli.command, input);
+        return;
+    }
 
     match cli.command {
         Commands::Add(args) => {
-            let result = add(&args.nums);
-            println!("{:.*}", precision, result);
+            let nums = numbers(&args.nums, input, 2);
+            let add = if cli.naive_sum { add_naive } else { add };
+            print_result(add(&nums, strict), &nums, style)
         }
         Commands::Sub(args) => {
-            let result = sub(&args.nums);
-            println!("{:.*}", precision, result);
+            let nums = numbers(&args.nums, input, 2);
+            print_result(sub(&nums, strict), &nums, style)
         }
         Commands::Mul(args) => {
-            let result = mul(&args.nums);
-            println!("{:.*}", precision, result);
+            let nums = numbers(&args.nums, input, 2);
+            print_result(mul(&nums, strict), &nums, style)
         }
-        Commands::Div(args) => match div(&args.nums) {
-            Ok(result) => println!("{:.*}", precision, result),
-            Err(msg) => {
-                eprintln!("{}", msg);
-                std::process::exit(1);
+        Commands::Div(args) => {
+            let nums = numbers(&args.nums, input, 2);
+            print_result(div(&nums, strict), &nums, style)
+        }
+        Commands::Mod(args) => {
+            let (a, b) = (args.a.value(separators), args.b.value(separators));
+            print_result(rem(a, b, args.mode()), &[a, b], style)
+        }
+        Commands::Divmod(args) => {
+            let (a, b) = (args.a.value(separators), args.b.value(separators));
+            let result = divmod(a, b, args.mode())
+                .and_then(|(q, r)| Ok((style.finite("divmod", q)?, style.finite("divmod", r)?)));
+            match result {
+                Ok((q, r)) => style.print(
+                    format!("{} {}", style.format(q), style.format(r)),
+                    json_floats(&[a, b]),
+                    json_floats(&[q, r]),
+                ),
+                Err(err) => style.fail(err),
             }
+        }
+        Commands::Pow(args) => {
+            let nums = numbers(&args.nums, input, 2);
+            print_result(pow(&nums, strict), &nums, style)
+        }
+        Commands::Root(args) => {
+            let x = args.x.value(separators);
+            print_result(nth_root(x, args.n), &[x, args.n.into()], style)
+        }
+        Commands::Sqrt(args) => args.x.apply(|x| nth_root(x, 2), style),
+        Commands::Cbrt(args) => args.x.apply(|x| nth_root(x, 3), style),
+        Commands::Round(args) => args.value.print(args.mode, style),
+        Commands::Floor(args) => args.print(RoundMode::Floor, style),
+        Commands::Ceil(args) => args.print(RoundMode::Ceil, style),
+        Commands::Trunc(args) => args.print(RoundMode::Trunc, style),
+        Commands::Log(args) => {
+            let (x, base) = (args.x.value(separators), args.base.value(separators));
+            print_result(log_base(x, base), &[x, base], style)
+        }
+        Commands::Ln(args) => args.x.apply(ln, style),
+        Commands::Exp(args) => args.x.apply(exp, style),
+        Commands::Sin(args) => args.x.apply(|x| trig::sin(x, unit), style),
+        Commands::Cos(args) => args.x.apply(|x| trig::cos(x, unit), style),
+        Commands::Tan(args) => args.x.apply(|x| trig::tan(x, unit, strict), style),
+        Commands::Asin(args) => args.x.apply(|x| trig::asin(x, unit), style),
+        Commands::Acos(args) => args.x.apply(|x| trig::acos(x, unit), style),
+        Commands::Atan(args) => args.x.apply(|x| trig::atan(x, unit), style),
+        Commands::Atan2(args) => {
+            let (y, x) = (args.y.value(separators), args.x.value(separators));
+            print_result(trig::atan2(y, x, unit), &[y, x], style)
+        }
+        Commands::Fact(args) => match factorial(args.n) {
+            Ok(result) => style.print(
+                separators.localize(&result.to_string()),
+                json!([args.n]),
+                json_int(result),
+            ),
+            Err(err) if style.output == Output::Text => {
+                eprintln!(
+                    "Error: {}: {}! doesn't fit in 128 bits, the largest is {}!",
+                    err, args.n, MAX_FACTORIAL
+                );
+                std::process::exit(exit_code(&err));
+            }
+            Err(err) => style.fail(err),
         },
+        Commands::Gcd(args) => {
+            let nums = numbers(&args.nums, input, 2);
+            print_integer(Ok(gcd(&nums)), &nums, style)
+        }
+        Commands::Lcm(args) => {
+            let nums = numbers(&args.nums, input, 2);
+            print_integer(lcm(&nums), &nums, style)
+        }
+        Commands::Hypot(args) => {
+            let nums = numbers(&args.nums, input, 2);
+            print_result(hypot(&nums), &nums, style)
+        }
+        Commands::Dist(args) => {
+            let (a, b) = args.points(style);
+            match distance(&a, &b).and_then(|d| style.finite("dist", d)) {
+                Ok(d) => style.print(
+                    style.format(d),
+                    json!([json_floats(&a), json_floats(&b)]),
+                    json_float(d),
+                ),
+                Err(err) => style.fail(err),
+            }
+        }
+        Commands::Vector(args) => print_vector_op(&args.op, style),
+        Commands::Time(args) => print_duration(&args, style),
+        Commands::Finance(args) => print_finance(&args, style),
+        Commands::Convert(args) => {
+            let value = args.value.value(separators);
+            match units::convert(value, &args.from, &args.to)
+                .and_then(|x| style.finite("convert", x))
+            {
+                Ok(result) => style.print(
+                    style.format(result),
+                    json!([json_float(value), args.from, args.to]),
+                    json_float(result),
+                ),
+                Err(err) => style.fail(err),
+            }
+        }
+        Commands::Percent(args) => {
+            let (value, whole) = (args.value.value(separators), args.whole.value(separators));
+            let result = match args.op {
+                PercentOp::Of => percent_of(value, whole),
+                PercentOp::On => percent_on(value, whole),
+                PercentOp::Off => percent_off(value, whole),
+            };
+            match result {
+                Ok(result) if args.symbol && matches!(args.op, PercentOp::Of) => style.print(
+                    format!("{}%", style.format(result)),
+                    json_floats(&[value, whole]),
+                    json_float(result),
+                ),
+                result => print_result(result, &[value, whole], style),
+            }
+        }
+        Commands::Change(args) => {
+            let (from, to) = (args.from.value(separators), args.to.value(separators));
+            print_result(percent_change(from, to), &[from, to], style)
+        }
+        Commands::Ratio(args) => {
+            let (a, b) = (args.a.value(separators), args.b.value(separators));
+            match reduce_ratio(a, b) {
+                Ok(Ratio::Integers(x, y)) => style.print(
+                    format!(
+                        "{}:{}",
+                        separators.localize(&x.to_string()),
+                        separators.localize(&y.to_string())
+                    ),
+                    json_floats(&[a, b]),
+                    json!([json_int(x), json_int(y)]),
+                ),
+                Ok(Ratio::Decimal(x)) => match style.finite("ratio", x) {
+                    Ok(x) => style.print(
+                        format!("{}:1", style.format(x)),
+                        json_floats(&[a, b]),
+                        json!([json_float(x), 1]),
+                    ),
+                    Err(err) => style.fail(err),
+                },
+                Err(err) => style.fail(err),
+            }
+        }
+        Commands::Base(args) => print_base(&args, style),
+        Commands::Stats(args) => print_stats(&args, &numbers::<f64>(&args.nums, input, 1), style),
+        Commands::Eval(args) => {
+            let mut env = Env::new();
+            for (name, value) in &args.vars {
+                env.set(name, value.value(separators))
+                    .expect("checked by var_arg");
+            }
+            let result = eval_localized(&args.expr, &mut env, separators, strict);
+            print_expr_result(result, &args.expr, style);
+        }
+        Commands::Chain(args) => {
+            let result = eval_chain_localized(&args.chain, separators, strict);
+            print_expr_result(result, &args.chain, style);
+        }
+        Commands::Rpn(args) => {
+            let tokens = args.tokens(style);
+            let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+            match eval_rpn_localized(&tokens, separators, strict) {
+                Ok(result) => style.print(style.format(result), json!(tokens), json_float(result)),
+                Err(err) => style.fail(err),
+            }
+        }
+        Commands::Constants => print_constants(style),
+        Commands::Repl(_) if style.output == Output::Json => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--output json doesn't work with repl",
+            )
+            .exit(),
+        Commands::Repl(args) => {
+            let mut repl = Repl::new(precision, cli.notation);
+            repl.separators = separators;
+            repl.trim = cli.trim;
+            repl.strict = strict;
+            let stdin = io::stdin();
+            let result = if stdin.is_terminal() {
+                let history = if args.no_history {
+                    None
+                } else {
+                    readline::history_path(
+                        env::var_os("XDG_DATA_HOME").map(PathBuf::from),
+                        env::var_os("HOME").map(PathBuf::from),
+                    )
+                };
+                readline::run(&mut repl, history.as_deref()).map_err(|e| e.to_string())
+            } else {
+                repl.run(stdin.lock(), io::stdout().lock(), "")
+                    .map_err(|e| e.to_string())
+            };
+            if let Err(e) = result {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The exit status for `err`: 2 when the input can't be used, like clap's usage errors,
+/// and 1 when the calculation itself failed.
+fn exit_code(err: &CalcError) -> i32 {
+    match err {
+        CalcError::TooFewNumbers { .. }
+        | CalcError::InvalidNumber { .. }
+        | CalcError::InvalidDigit { .. }
+        | CalcError::UnknownUnit { .. }
+        | CalcError::InvalidDuration { .. } => 2,
+        CalcError::Stage { error, .. } => exit_code(error),
+        _ => 1,
+    }
+}
+
+/// Prints `result` of the expression `expr` in `style`, or fails with the error and, as
+/// text, a `^` under where in `expr` it is.
+fn print_expr_result(result: Result<f64, CalcError>, expr: &str, style: Style) {
+    match result {
+        Ok(result) => style.print(style.format(result), json!([expr]), json_float(result)),
+        Err(err) if style.output == Output::Text => {
+            eprintln!("Error: {}", err);
+            if let Some(caret) = err.caret(expr) {
+                eprintln!("  {}", expr);
+                eprintln!("  {}", caret);
+            }
+            std::process::exit(exit_code(&err));
+        }
+        Err(err) => style.fail(err),
+    }
+}
+
+/// Prints what `finance compound` or `finance payment` results in and, with `--schedule`, a
+/// table of its years. As JSON, the result is then an object with the `value` and the
+/// `schedule`.
+fn print_finance(args: &FinanceArgs, style: Style) {
+    let separators = style.separators;
+    let money = match &args.op {
+        FinanceOp::Compound { money, .. } | FinanceOp::Payment { money } => money,
+    };
+    let principal = money.principal.value(separators);
+    let rate = money.rate.rate(separators);
+    let years = money.years.value(separators);
+    let mut operands = vec![json_float(principal), json_float(rate), json_float(years)];
+    let (result, schedule) = match args.op {
+        FinanceOp::Compound {
+            compounds_per_year, ..
+        } => {
+            operands.push(json!(compounds_per_year));
+            (
+                finance::compound(principal, rate, years, compounds_per_year),
+                args.schedule.then(|| {
+                    finance::compound_schedule(principal, rate, years, compounds_per_year)
+                }),
+            )
+        }
+        FinanceOp::Payment { .. } => (
+            finance::payment(principal, rate, years),
+            args.schedule
+                .then(|| finance::payment_schedule(principal, rate, years)),
+        ),
+    };
+    let value = result
+        .and_then(|x| style.finite("the result", x))
+        .unwrap_or_else(|err| style.fail(err));
+    let Some(schedule) = schedule else {
+        return style.print(
+            style.format(value),
+            Value::Array(operands),
+            json_float(value),
+        );
+    };
+    let schedule = schedule.unwrap_or_else(|err| style.fail(err));
+    let loan = matches!(args.op, FinanceOp::Payment { .. });
+    let mut rows = vec![vec![
+        "Year".to_string(),
+        "Interest".to_string(),
+        "Principal".to_string(),
+        "Balance".to_string(),
+    ]];
+    for year in &schedule {
+        rows.push(vec![
+            year.year.to_string(),
+            style.format(year.interest),
+            style.format(year.principal),
+            style.format(year.balance),
+        ]);
+    }
+    if !loan {
+        // Savings pay off no principal
+        for row in &mut rows {
+            row.remove(2);
+        }
+    }
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut lines = vec![style.format(value)];
+    lines.extend(rows.iter().map(|row| {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:>width$}", cell))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }));
+    let schedule: Vec<Value> = schedule
+        .iter()
+        .map(|year| {
+            let mut object = json!({
+                "year": year.year,
+                "interest": json_float(year.interest),
+                "balance": json_float(year.balance),
+            });
+            if loan {
+                object["principal"] = json_float(year.principal);
+            }
+            object
+        })
+        .collect();
+    style.print(
+        lines.join("\n"),
+        Value::Array(operands),
+        json!({ "value": json_float(value), "schedule": schedule }),
+    )
+}
+
+/// Prints the duration `time add` or `time diff` results in, in the format of `--as`.
+fn print_duration(args: &TimeArgs, style: Style) {
+    let texts: Vec<&str> = match &args.op {
+        TimeOp::Add { durations } => durations.iter().map(String::as_str).collect(),
+        TimeOp::Diff { a, b } => vec![a, b],
+    };
+    let seconds: Vec<f64> = texts
+        .iter()
+        .map(|text| parse_duration(text).unwrap_or_else(|err| style.fail(err)))
+        .collect();
+    let result = match args.op {
+        TimeOp::Add { .. } => duration::total(&seconds),
+        TimeOp::Diff { .. } => seconds[0] - seconds[1],
+    };
+    match style.finite("the result", result) {
+        Ok(result) => {
+            let text = match args.format {
+                TimeFormat::Seconds => style.format(result),
+                TimeFormat::Hms => format_hms(result, style.precision),
+                TimeFormat::Clock => format_clock(result, style.precision),
+            };
+            style.print(text, json!(texts), json_float(result))
+        }
+        Err(err) => style.fail(err),
+    }
+}
+
+/// Prints the result of the `vec` operation `op`: a number, or a vector as a list.
+fn print_vector_op(op: &VectorOp, style: Style) {
+    let vectors = |args: &TwoVectors| {
+        let (a, b) = (
+            components(&args.a, "--a", style),
+            components(&args.b, "--b", style),
+        );
+        let operands = json!([json_floats(&a), json_floats(&b)]);
+        (a, b, operands)
+    };
+    let (operation, result, operands) = match op {
+        VectorOp::Dot(args) => {
+            let (a, b, operands) = vectors(args);
+            ("dot", vector::dot(&a, &b).map(|x| vec![x]), operands)
+        }
+        VectorOp::Norm(args) => {
+            let a = components(&args.a, "--a", style);
+            (
+                "norm",
+                vector::norm(&a).map(|x| vec![x]),
+                json!([json_floats(&a)]),
+            )
+        }
+        VectorOp::Add(args) => {
+            let (a, b, operands) = vectors(args);
+            ("add", vector::axpy(1.0, &a, &b), operands)
+        }
+        VectorOp::Sub(args) => {
+            let (a, b, operands) = vectors(args);
+            ("sub", vector::axpy(-1.0, &b, &a), operands)
+        }
+        VectorOp::Scale(args) => {
+            let a = components(&args.a, "--a", style);
+            let k = args.k.value(style.separators);
+            (
+                "scale",
+                vector::scale(k, &a),
+                json!([json_floats(&a), json_float(k)]),
+            )
+        }
+    };
+    let result = result.and_then(|values| {
+        for &x in &values {
+            style.finite(operation, x)?;
+        }
+        Ok(values)
+    });
+    match result {
+        Ok(values) if matches!(op, VectorOp::Dot(_) | VectorOp::Norm(_)) => {
+            style.print(style.format(values[0]), operands, json_float(values[0]))
+        }
+        Ok(values) => style.print(style.list(&values), operands, json_floats(&values)),
+        Err(err) => style.fail(err),
+    }
+}
+
+/// Prints `result` of `operands` in `style`, or fails with the error, which it is with
+/// `--strict` if the result isn't a finite number.
+fn print_result(result: Result<f64, CalcError>, operands: &[f64], style: Style) {
+    match result.and_then(|x| style.finite("the result", x)) {
+        Ok(result) => style.print(
+            style.format(result),
+            json_floats(operands),
+            json_float(result),
+        ),
+        Err(err) => style.fail(err),
+    }
+}
+
+/// Prints the integer `result` of `operands` without decimals, or fails with the error.
+fn print_integer<T>(result: Result<T, CalcError>, operands: &[T], style: Style)
+where
+    T: Copy + TryInto<i64> + ToString,
+{
+    match result {
+        Ok(result) => style.print(
+            style.separators.localize(&result.to_string()),
+            Value::Array(operands.iter().copied().map(json_int).collect()),
+            json_int(result),
+        ),
+        Err(err) => style.fail(err),
+    }
+}
+
+/// `args.value` converted to the output base, with its prefix and `_` separators if asked.
+fn print_base(args: &BaseArgs, style: Style) {
+    let from = args
+        .from
+        .map(Radix::value)
+        .or(args.from_radix)
+        .or_else(|| detect_radix(&args.value))
+        .unwrap_or(10);
+    let to = args.to_radix.unwrap_or(args.to.value());
+    let n = match from_radix(&args.value, from) {
+        Ok(n) => n,
+        Err(err) => style.fail(err),
+    };
+    let digits = to_radix(n, to);
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", digits.as_str()),
+    };
+    let digits = match args.group {
+        Some(size) => group_digits(digits, size as usize),
+        None => digits.to_string(),
+    };
+    let prefix = radix_prefix(to).filter(|_| args.prefix).unwrap_or("");
+    let result = format!("{}{}{}", sign, prefix, digits);
+    style.print(result.clone(), json!([args.value]), json!(result));
+}
+
+/// One `name value` line per constant, its value with every digit it has.
+fn print_constants(style: Style) {
+    let width = CONSTANTS
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let text = CONSTANTS
+        .iter()
+        .map(|(name, value)| {
+            let value = style.separators.localize(&value.to_string());
+            format!("{:<width$} {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let object = CONSTANTS
+        .iter()
+        .map(|&(name, value)| (name.to_string(), json_float(value)))
+        .collect();
+    style.print(text, json!([]), Value::Object(object));
+}
+
+/// One `label: value` line per metric, then one per `--percentile`. With `--only`, a
+/// metric that can't be computed (a sample deviation of one number) is an error; in the
+/// full summary it shows as n/a. As JSON, the result maps the metrics' names (`p90` for
+/// a percentile) to their values, null for n/a.
+fn print_stats(args: &StatsArgs, nums: &[f64], style: Style) {
+    let metrics = if args.only.is_empty() {
+        Metric::value_variants()
+    } else {
+        &args.only[..]
+    };
+    let mut lines = Vec::new();
+    let mut object = serde_json::Map::new();
+    for &metric in metrics {
+        let (text, value) = match metric.value(nums, style) {
+            Ok(value) => value,
+            Err(_) if args.only.is_empty() => ("n/a".to_string(), Value::Null),
+            Err(err) => style.fail(err),
+        };
+        lines.push((metric.label().to_string(), text));
+        object.insert(metric.name().to_string(), value);
+    }
+    for p in &args.percentile {
+        let p = p.value(style.separators);
+        match stats::percentile(nums, p).and_then(|x| style.finite("percentile", x)) {
+            Ok(value) => {
+                let label = format!("p{}", style.separators.localize(&p.to_string()));
+                lines.push((label, style.format(value)));
+                object.insert(format!("p{}", p), json_float(value));
+            }
+            Err(err) => style.fail(err),
+        }
+    }
+    let width = lines
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let text = lines
+        .into_iter()
+        .map(|(label, value)| format!("{:<width$} {}", format!("{}:", label), value))
+        .collect::<Vec<_>>()
+        .join("\n");
+    style.print(text, json_floats(nums), Value::Object(object));
+}
+
+/// Which mode flags need or exclude each other. clap's `requires` and `conflicts_with` only
+/// see the global flags given on one side of the subcommand, so `mycalc --int div 7 2
+/// --int-floor` would fail and `mycalc --int add 1 2 --exact` would pass.
+fn check_modes(cli: &Cli) {
+    let requires = [
+        (cli.int_floor, "--int-floor", cli.int, "--int"),
+        (cli.int_trunc, "--int-trunc", cli.int, "--int"),
+        (
+            cli.decimal_digits.is_some(),
+            "--decimal-digits",
+            cli.exact,
+            "--exact",
+        ),
+    ];
+    for (given, flag, needed, needs) in requires {
+        if given && !needed {
+            Cli::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    format!("{} only works with {}", flag, needs),
+                )
+                .exit();
+        }
+    }
+    let conflicts = [
+        (cli.int, "--int", cli.exact, "--exact"),
+        (cli.int, "--int", cli.decimal, "--decimal"),
+        (cli.int_floor, "--int-floor", cli.int_trunc, "--int-trunc"),
+        (cli.exact, "--exact", cli.decimal, "--decimal"),
+        (cli.naive_sum, "--naive-sum", cli.int, "--int"),
+        (cli.naive_sum, "--naive-sum", cli.exact, "--exact"),
+        (cli.naive_sum, "--naive-sum", cli.decimal, "--decimal"),
+    ];
+    for (a, flag_a, b, flag_b) in conflicts {
+        if a && b {
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("the argument '{}' cannot be used with '{}'", flag_a, flag_b),
+                )
+                .exit();
+        }
+    }
+}
+
+/// `--int`: add, sub, mul and div on exact integers, printed without decimals.
+fn run_int(command: Commands, input: Input, mode: IntDiv) {
+    let style = input.style;
+    let (result, nums) = match command {
+        Commands::Add(args) => with_operands(numbers(&args.nums, input, 2), add_i),
+        Commands::Sub(args) => with_operands(numbers(&args.nums, input, 2), sub_i),
+        Commands::Mul(args) => with_operands(numbers(&args.nums, input, 2), mul_i),
+        Commands::Div(args) => {
+            with_operands(numbers(&args.nums, input, 2), |nums| div_i(nums, mode))
+        }
+        _ => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--int only works with add, sub, mul and div",
+            )
+            .exit(),
+    };
+    match result {
+        Err(err @ CalcError::InexactDivision { .. }) if style.output == Output::Text => {
+            eprintln!("Error: {}; use --int-floor or --int-trunc to round", err);
+            std::process::exit(exit_code(&err));
+        }
+        result => print_integer(result, &nums, style),
+    }
+}
+
+/// `f` of `nums`, and `nums` to print it with.
+fn with_operands<T, R>(nums: Vec<T>, f: impl FnOnce(&[T]) -> R) -> (R, Vec<T>) {
+    (f(&nums), nums)
+}
+
+/// `--exact`: add, sub, mul and div on fractions, printed reduced (`5/6`) or, with
+/// `--decimal-digits`, as a rounded decimal. As JSON, the fractions are strings (`"5/6"`)
+/// to stay exact.
+fn run_exact(command: Commands, input: Input, digits: Option<usize>) {
+    let style = input.style;
+    let (result, nums) = match command {
+        Commands::Add(args) => with_operands(numbers(&args.nums, input, 2), add_r),
+        Commands::Sub(args) => with_operands(numbers(&args.nums, input, 2), sub_r),
+        Commands::Mul(args) => with_operands(numbers(&args.nums, input, 2), mul_r),
+        Commands::Div(args) => with_operands(numbers::<BigRational>(&args.nums, input, 2), div_r),
+        _ => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--exact only works with add, sub, mul and div",
+            )
+            .exit(),
+    };
+    match result {
+        Ok(result) => style.print(
+            match digits {
+                Some(digits) => style.decimals(&format_decimal(&result, digits)),
+                None => format_fraction(&result),
+            },
+            nums.iter().map(format_fraction).collect(),
+            json!(format_fraction(&result)),
+        ),
+        Err(err) => style.fail(err),
+    }
+}
+
+/// `--decimal`: add, sub, mul and div in base 10, printed with `--precision` decimals
+/// rounded half to even. As JSON, the decimals are strings, unrounded, to stay exact.
+fn run_decimal(command: Commands, input: Input) {
+    let style = input.style;
+    let (result, nums) = match command {
+        Commands::Add(args) => with_operands(numbers(&args.nums, input, 2), add_d),
+        Commands::Sub(args) => with_operands(numbers(&args.nums, input, 2), sub_d),
+        Commands::Mul(args) => with_operands(numbers(&args.nums, input, 2), mul_d),
+        Commands::Div(args) => with_operands(numbers::<Decimal>(&args.nums, input, 2), div_d),
+        _ => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--decimal only works with add, sub, mul and div",
+            )
+            .exit(),
+    };
+    match result {
+        Ok(result) => style.print(
+            style.decimals(&format_d(result, style.precision)),
+            nums.iter().map(|d| json!(d.to_string())).collect(),
+            json!(result.to_string()),
+        ),
+        Err(err) => style.fail(err),
     }
 }
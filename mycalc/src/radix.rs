@@ -0,0 +1,202 @@
+//! Integers written in other bases, e.g. `0xff`, `-1010` in binary or `zz` in base 36, for
+//! `mycalc base`.
+
+use crate::CalcError;
+
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// The conventional prefix of numbers in `radix`: `0b`, `0o` or `0x`.
+pub fn radix_prefix(radix: u32) -> Option<&'static str> {
+    match radix {
+        2 => Some("0b"),
+        8 => Some("0o"),
+        16 => Some("0x"),
+        _ => None,
+    }
+}
+
+/// The base that the prefix of `raw` (after any sign) stands for, e.g. 16 for `-0x1f`.
+pub fn detect_radix(raw: &str) -> Option<u32> {
+    let digits = raw.strip_prefix(['-', '+']).unwrap_or(raw);
+    [2, 8, 16].into_iter().find(|&radix| {
+        let prefix = radix_prefix(radix).expect("2, 8 and 16 have prefixes");
+        digits.len() > 2 && digits[..2].eq_ignore_ascii_case(prefix)
+    })
+}
+
+/// `n` in `radix` with lowercase digits and a leading `-` when negative.
+/// E.g.: (255, 16) => "ff", (-5, 2) => "-101"
+///
+/// Panics if `radix` isn't between 2 and 36.
+pub fn to_radix(n: i128, radix: u32) -> String {
+    assert!((2..=36).contains(&radix), "radix {} out of 2..=36", radix);
+    let mut magnitude = n.unsigned_abs();
+    let mut digits = Vec::new();
+    loop {
+        digits.push(DIGITS[(magnitude % radix as u128) as usize]);
+        magnitude /= radix as u128;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push(b'-');
+    }
+    digits.iter().rev().map(|&b| b as char).collect()
+}
+
+/// The integer that `raw` writes in `radix`, with an optional sign, the prefix of that base
+/// (`0x` for 16, ...) and `_` between digits. Digits are case-insensitive.
+/// E.g.: ("-0xFF", 16) => -255, ("1010_1010", 2) => 170
+pub fn from_radix(raw: &str, radix: u32) -> Result<i128, CalcError> {
+    if !(2..=36).contains(&radix) {
+        return Err(CalcError::domain(format!(
+            "base must be between 2 and 36, got {}",
+            radix
+        )));
+    }
+    let (negative, digits) = match raw.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    let digits = match radix_prefix(radix) {
+        Some(prefix) if digits.len() >= 2 && digits[..2].eq_ignore_ascii_case(prefix) => {
+            &digits[2..]
+        }
+        _ => digits,
+    };
+    if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') {
+        return Err(CalcError::domain(format!(
+            "`{}` is not a base {} number",
+            raw, radix
+        )));
+    }
+    let mut magnitude: u128 = 0;
+    for c in digits.chars().filter(|&c| c != '_') {
+        let digit = c
+            .to_digit(radix)
+            .ok_or(CalcError::InvalidDigit { digit: c, radix })?;
+        magnitude = magnitude
+            .checked_mul(radix as u128)
+            .and_then(|m| m.checked_add(digit as u128))
+            .ok_or(CalcError::overflow("base conversion"))?;
+    }
+    match (negative, i128::try_from(magnitude)) {
+        (false, Ok(n)) => Ok(n),
+        (true, Ok(n)) => Ok(-n),
+        (true, Err(_)) if magnitude == i128::MIN.unsigned_abs() => Ok(i128::MIN),
+        _ => Err(CalcError::overflow("base conversion")),
+    }
+}
+
+/// `digits` with a `_` between every `size` of them, counting from the right.
+/// E.g.: ("11111111", 4) => "1111_1111"
+pub fn group_digits(digits: &str, size: usize) -> String {
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(size) {
+            grouped.push('_');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_radix() {
+        assert_eq!(to_radix(255, 16), "ff");
+        assert_eq!(to_radix(255, 2), "11111111");
+        assert_eq!(to_radix(8, 8), "10");
+        assert_eq!(to_radix(0, 2), "0");
+        assert_eq!(to_radix(-5, 2), "-101");
+        assert_eq!(to_radix(35, 36), "z");
+        assert_eq!(to_radix(i128::MAX, 16), format!("7{}", "f".repeat(31)));
+        assert_eq!(to_radix(i128::MIN, 16), format!("-8{}", "0".repeat(31)));
+    }
+
+    #[test]
+    fn test_from_radix_signs_prefixes_and_separators() {
+        assert_eq!(from_radix("ff", 16), Ok(255));
+        assert_eq!(from_radix("0xFF", 16), Ok(255));
+        assert_eq!(from_radix("-0x1f", 16), Ok(-31));
+        assert_eq!(from_radix("+0b101", 2), Ok(5));
+        assert_eq!(from_radix("0o17", 8), Ok(15));
+        assert_eq!(from_radix("1010_1010", 2), Ok(170));
+        assert_eq!(from_radix("ZZ", 36), Ok(1295));
+        // Only the prefix of the base itself is skipped: 0b1 is a valid hex number
+        assert_eq!(from_radix("0b1", 16), Ok(0xb1));
+    }
+
+    #[test]
+    fn test_from_radix_errors() {
+        assert_eq!(
+            from_radix("12", 2).unwrap_err().to_string(),
+            "invalid digit `2` for base 2"
+        );
+        assert_eq!(
+            from_radix("0x", 16).unwrap_err().to_string(),
+            "`0x` is not a base 16 number"
+        );
+        for bad in ["", "-", "_1", "1_", "1 0", "--1"] {
+            assert!(from_radix(bad, 10).is_err(), "{:?}", bad);
+        }
+        assert_eq!(
+            from_radix("1", 37).unwrap_err().to_string(),
+            "base must be between 2 and 36, got 37"
+        );
+    }
+
+    #[test]
+    fn test_overflow_at_the_i128_boundaries() {
+        let max = to_radix(i128::MAX, 2);
+        assert_eq!(from_radix(&max, 2), Ok(i128::MAX));
+        assert_eq!(from_radix(&format!("-{}", max), 2), Ok(-i128::MAX));
+        assert_eq!(from_radix(&to_radix(i128::MIN, 7), 7), Ok(i128::MIN));
+        assert_eq!(
+            from_radix(&format!("8{}", "0".repeat(31)), 16)
+                .unwrap_err()
+                .to_string(),
+            "overflow in base conversion"
+        );
+        // Past u128 too
+        assert!(from_radix(&"z".repeat(30), 36).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_across_bases() {
+        let samples = [
+            0,
+            1,
+            -1,
+            36,
+            255,
+            -256,
+            1 << 64,
+            123_456_789_012_345_678_901_234_567,
+            i128::MAX,
+            i128::MIN,
+            i128::MIN + 1,
+        ];
+        for radix in 2..=36 {
+            for &n in &samples {
+                assert_eq!(from_radix(&to_radix(n, radix), radix), Ok(n), "{}", radix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_radix_and_group_digits() {
+        assert_eq!(detect_radix("0xff"), Some(16));
+        assert_eq!(detect_radix("-0B1"), Some(2));
+        assert_eq!(detect_radix("0o7"), Some(8));
+        assert_eq!(detect_radix("0x"), None);
+        assert_eq!(detect_radix("255"), None);
+        assert_eq!(group_digits("11111111", 4), "1111_1111");
+        assert_eq!(group_digits("101", 4), "101");
+        assert_eq!(group_digits("1234567", 3), "1_234_567");
+    }
+}
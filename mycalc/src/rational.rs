@@ -0,0 +1,161 @@
+//! Exact rational arithmetic for `--exact`: numbers like `1/3`, `0.25` or `7` become
+//! fractions of big integers, so `1/3 + 1/6` is exactly `1/2`.
+
+use crate::CalcError;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{Signed, Zero};
+
+/// `7`, `-1/3`, `0.25` or `1.5e3` as an exact fraction; `None` when it's none of those.
+pub fn parse_rational(raw: &str) -> Option<BigRational> {
+    if let Some((numer, denom)) = raw.split_once('/') {
+        let numer: BigInt = numer.parse().ok()?;
+        let denom: BigInt = denom.parse().ok()?;
+        return (!denom.is_zero()).then(|| BigRational::new(numer, denom));
+    }
+    let (mantissa, exp) = match raw.split_once(['e', 'E']) {
+        Some((mantissa, exp)) => (mantissa, exp.parse::<i32>().ok()?),
+        None => (raw, 0),
+    };
+    let (sign, digits) = match mantissa.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if int.len() + frac.len() == 0 || !all_digits(int) || !all_digits(frac) {
+        return None;
+    }
+    let numer: BigInt = format!("{}{}", int, frac).parse().ok()?;
+    let scale = exp - i32::try_from(frac.len()).ok()?;
+    let ten = BigRational::from_integer(BigInt::from(10));
+    Some(BigRational::from_integer(numer * sign) * ten.pow(scale))
+}
+
+/// Add all numbers.
+pub fn add_r(nums: &[BigRational]) -> Result<BigRational, CalcError> {
+    Ok(nums.iter().sum())
+}
+
+/// Subtraction is left-associative.
+/// E.g.: [1, 1/3, 1/6] => (1 - 1/3 - 1/6) = 1/2
+pub fn sub_r(nums: &[BigRational]) -> Result<BigRational, CalcError> {
+    let (first, rest) = crate::operands("sub", nums)?;
+    Ok(rest.iter().fold(first.clone(), |acc, x| acc - x))
+}
+
+/// Multiply all numbers.
+pub fn mul_r(nums: &[BigRational]) -> Result<BigRational, CalcError> {
+    Ok(nums.iter().product())
+}
+
+/// Divide is left-associative. Error if any divisor is zero.
+pub fn div_r(nums: &[BigRational]) -> Result<BigRational, CalcError> {
+    let (first, rest) = crate::operands("div", nums)?;
+    rest.iter()
+        .enumerate()
+        .try_fold(first.clone(), |acc, (i, x)| {
+            if x.is_zero() {
+                return Err(CalcError::zero_divisor(i + 2));
+            }
+            Ok(acc / x)
+        })
+}
+
+/// The reduced fraction, e.g. `5/6`, or just the integer when the denominator is 1.
+pub fn format_fraction(r: &BigRational) -> String {
+    if r.is_integer() {
+        r.numer().to_string()
+    } else {
+        format!("{}/{}", r.numer(), r.denom())
+    }
+}
+
+/// The decimal expansion of `r` with `digits` decimals, rounded half away from zero.
+/// E.g.: (2/3, 4) => "0.6667"
+pub fn format_decimal(r: &BigRational, digits: usize) -> String {
+    let scale = BigInt::from(10).pow(digits as u32);
+    let scaled = (r * BigRational::from_integer(scale)).round().to_integer();
+    let magnitude = scaled.abs().to_string();
+    let sign = if scaled.is_negative() { "-" } else { "" };
+    if digits == 0 {
+        return format!("{}{}", sign, magnitude);
+    }
+    let padded = format!("{:0>width$}", magnitude, width = digits + 1);
+    let (int, frac) = padded.split_at(padded.len() - digits);
+    format!("{}{}.{}", sign, int, frac)
+}
+
+impl crate::input::Number for BigRational {
+    const NAME: &'static str = "rational number";
+
+    fn parse(token: &str) -> Option<Self> {
+        parse_rational(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::One;
+
+    fn r(raw: &str) -> BigRational {
+        parse_rational(raw).unwrap_or_else(|| panic!("{:?}", raw))
+    }
+
+    #[test]
+    fn test_parse_fractions_decimals_and_integers() {
+        assert_eq!(format_fraction(&r("7")), "7");
+        assert_eq!(format_fraction(&r("-7")), "-7");
+        assert_eq!(format_fraction(&r("2/6")), "1/3");
+        assert_eq!(format_fraction(&r("-1/3")), "-1/3");
+        assert_eq!(format_fraction(&r("0.25")), "1/4");
+        assert_eq!(format_fraction(&r("-1.5")), "-3/2");
+        assert_eq!(format_fraction(&r(".5")), "1/2");
+        assert_eq!(format_fraction(&r("1.5e3")), "1500");
+        assert_eq!(format_fraction(&r("25e-2")), "1/4");
+        for bad in ["", "1/0", "1/x", "abc", "1.2.3", "-", ".", "1e", "0x10"] {
+            assert!(parse_rational(bad).is_none(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        assert_eq!(add_r(&[r("1/3"), r("1/6")]), Ok(r("1/2")));
+        assert_eq!(add_r(&[r("0.1"), r("0.2")]), Ok(r("3/10")));
+        assert_eq!(
+            sub_r(&[BigRational::one(), r("1/3"), r("1/6")]),
+            Ok(r("1/2"))
+        );
+        assert_eq!(mul_r(&[r("2/3"), r("3/4")]), Ok(r("1/2")));
+        assert_eq!(
+            div_r(&[r("1"), r("3"), r("1/3")]).unwrap(),
+            BigRational::one()
+        );
+        assert_eq!(
+            div_r(&[r("1"), r("0.0")]).unwrap_err().to_string(),
+            "division by zero: operand 2 is zero"
+        );
+        assert_eq!(
+            sub_r(&[r("1/2")]).unwrap_err().to_string(),
+            "sub needs at least 2 numbers, got 1"
+        );
+        assert!(div_r(&[]).is_err());
+    }
+
+    #[test]
+    fn test_decimal_expansion_rounds_half_away_from_zero() {
+        assert_eq!(format_decimal(&r("2/3"), 4), "0.6667");
+        assert_eq!(format_decimal(&r("-2/3"), 4), "-0.6667");
+        assert_eq!(format_decimal(&r("1/3"), 2), "0.33");
+        assert_eq!(format_decimal(&r("1/8"), 2), "0.13");
+        assert_eq!(format_decimal(&r("-1/8"), 2), "-0.13");
+        assert_eq!(format_decimal(&r("1/200"), 2), "0.01");
+        assert_eq!(format_decimal(&r("-1/1000"), 2), "0.00");
+        assert_eq!(format_decimal(&r("22/7"), 0), "3");
+        assert_eq!(format_decimal(&r("5/2"), 0), "3");
+        assert_eq!(format_decimal(&r("10"), 3), "10.000");
+        // 1/7 = 0.142857 142857...
+        assert_eq!(format_decimal(&r("1/7"), 12), "0.142857142857");
+    }
+}
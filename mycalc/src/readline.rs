@@ -0,0 +1,195 @@
+//! Line editing for `mycalc repl` on a terminal, with rustyline: arrow-key history kept
+//! across sessions, Ctrl-R search, and Tab completion of functions, constants, variables
+//! and `:` commands.
+
+use crate::functions::{CONSTANTS, FUNCTIONS};
+use crate::repl::{Flow, Repl, COMMANDS};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{Config, Context, Editor, Helper, Highlighter, Hinter, Validator};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Most lines kept in the history file.
+pub const HISTORY_SIZE: usize = 1000;
+
+/// Where the history is kept: `$XDG_DATA_HOME/mycalc/history`, or
+/// `$HOME/.local/share/mycalc/history` when `XDG_DATA_HOME` is unset, empty or relative
+/// (which the XDG spec says to ignore). `None` without either.
+pub fn history_path(xdg_data_home: Option<PathBuf>, home: Option<PathBuf>) -> Option<PathBuf> {
+    let data = xdg_data_home
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home.map(|home| home.join(".local").join("share")))?;
+    Some(data.join("mycalc").join("history"))
+}
+
+/// Where the word ending at `pos` starts, and the completions of it: `:` commands at the
+/// start of the line, otherwise functions (with their `(`), constants and `variables`.
+/// E.g.: ("1 + sq", 6) => (4, ["sqrt("])
+pub fn complete(line: &str, pos: usize, variables: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let trimmed = before.trim_start();
+    if trimmed.starts_with(':') {
+        if trimmed.contains(' ') {
+            // The argument of a command
+            return (pos, Vec::new());
+        }
+        let start = pos - trimmed.len();
+        let candidates = COMMANDS
+            .iter()
+            .filter(|command| command.starts_with(trimmed))
+            .map(|command| command.to_string())
+            .collect();
+        return (start, candidates);
+    }
+    let start = pos
+        - before
+            .bytes()
+            .rev()
+            .take_while(|&b| b.is_ascii_alphanumeric() || b == b'_')
+            .count();
+    let prefix = &before[start..];
+    if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+        return (pos, Vec::new());
+    }
+    let functions = FUNCTIONS.iter().map(|f| format!("{}(", f.name));
+    let constants = CONSTANTS.iter().map(|&(name, _)| name.to_string());
+    let mut candidates: Vec<String> = functions
+        .chain(constants)
+        .chain(variables.iter().cloned())
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+/// Completes with [`complete`] from the variables of the session so far.
+#[derive(Helper, Hinter, Highlighter, Validator)]
+pub struct ReplHelper {
+    pub variables: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.variables))
+    }
+}
+
+/// Runs `repl` on the terminal until `:quit` or Ctrl-D; Ctrl-C clears the line. With a
+/// `history` file, earlier sessions' lines are loaded from it and this session's saved.
+pub fn run(repl: &mut Repl, history: Option<&Path>) -> rustyline::Result<()> {
+    let config = Config::builder()
+        .max_history_size(HISTORY_SIZE)?
+        .auto_add_history(true)
+        .build();
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(ReplHelper {
+        variables: Vec::new(),
+    }));
+    if let Some(path) = history {
+        // Nothing to load on the first session
+        let _ = editor.load_history(path);
+    }
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        };
+        let flow = repl.line(line.trim(), &mut io::stdout().lock())?;
+        if let Some(helper) = editor.helper_mut() {
+            helper.variables = repl.env.names().map(str::to_string).collect();
+        }
+        if let Flow::Quit = flow {
+            break;
+        }
+    }
+    if let Some(path) = history {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        editor.save_history(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(line: &str, variables: &[&str]) -> (usize, Vec<String>) {
+        let variables: Vec<String> = variables.iter().map(|v| v.to_string()).collect();
+        complete(line, line.len(), &variables)
+    }
+
+    #[test]
+    fn test_completes_functions_constants_and_variables() {
+        assert_eq!(candidates("1 + sq", &[]), (4, vec!["sqrt(".to_string()]));
+        assert_eq!(
+            candidates("m", &["mass"]),
+            (
+                0,
+                vec!["mass".to_string(), "max(".to_string(), "min(".to_string()]
+            )
+        );
+        assert_eq!(
+            candidates("2*p", &[]),
+            (2, vec!["phi".to_string(), "pi".to_string()])
+        );
+        assert_eq!(
+            candidates("x = an", &["ans", "angle"]),
+            (4, vec!["angle".to_string(), "ans".to_string()])
+        );
+        assert_eq!(candidates("sqrt(co", &[]).1, vec!["cos(".to_string()]);
+    }
+
+    #[test]
+    fn test_nothing_to_complete() {
+        assert_eq!(candidates("", &["x"]), (0, vec![]));
+        assert_eq!(candidates("1 + ", &["x"]), (4, vec![]));
+        assert_eq!(candidates("12", &[]), (2, vec![]));
+        assert_eq!(candidates("zz", &["x"]), (0, vec![]));
+        // Only what's before the cursor counts
+        assert_eq!(complete("sqx + 1", 2, &[]), (0, vec!["sqrt(".to_string()]));
+    }
+
+    #[test]
+    fn test_completes_commands() {
+        assert_eq!(candidates(":p", &[]), (0, vec![":precision".to_string()]));
+        assert_eq!(candidates("  :q", &[]).0, 2);
+        assert_eq!(candidates(":", &[]).1.len(), COMMANDS.len());
+        assert_eq!(candidates(":mode s", &[]), (7, vec![]));
+    }
+
+    #[test]
+    fn test_history_path() {
+        assert_eq!(
+            history_path(Some("/data".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/data/mycalc/history"))
+        );
+        assert_eq!(
+            history_path(None, Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.local/share/mycalc/history"))
+        );
+        assert_eq!(
+            history_path(Some("".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.local/share/mycalc/history"))
+        );
+        assert_eq!(
+            history_path(Some("relative".into()), Some("/home/me".into())),
+            Some(PathBuf::from("/home/me/.local/share/mycalc/history"))
+        );
+        assert_eq!(history_path(None, None), None);
+    }
+}
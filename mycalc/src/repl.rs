@@ -0,0 +1,241 @@
+//! `mycalc repl`: evaluates expressions line by line, keeping variables and `ans` between
+//! lines, with `:` commands to change how results are printed. It reads any `BufRead` and writes any `Write`, so a session can be
+//! replayed from a string.
+
+use crate::locale::Separators;
+use crate::{eval_localized, format_number, trim_zeros, Env, Notation};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Type an expression, e.g. 2 + 3 * (4 - 1), to evaluate it, or x = ... to also store
+the result in x. ans is the last result. Commands:
+  :precision N         print N decimals
+  :mode fixed|sci|eng  print in fixed, scientific or engineering notation
+  :help                show this help
+  :quit                leave (so does Ctrl-D)";
+
+/// The state of a session: its variables and how numbers are read and printed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repl {
+    pub precision: usize,
+    pub notation: Notation,
+    pub separators: Separators,
+    /// Drop trailing zeros from results, as `--trim` does
+    pub trim: bool,
+    /// Error on results that aren't finite numbers, as `--strict` does
+    pub strict: bool,
+    pub env: Env,
+}
+
+/// The `:` commands, for completion.
+pub const COMMANDS: &[&str] = &[":precision", ":mode", ":help", ":quit"];
+
+/// What to do after a line.
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+impl Repl {
+    pub fn new(precision: usize, notation: Notation) -> Repl {
+        Repl {
+            precision,
+            notation,
+            separators: Separators::default(),
+            trim: false,
+            strict: true,
+            env: Env::new(),
+        }
+    }
+
+    /// Evaluates each line of `input` and writes its result or error to `output`, until
+    /// `:quit` or the end of `input`. `prompt` is written before each line; it's empty when
+    /// the input isn't a terminal.
+    pub fn run(
+        &mut self,
+        input: impl BufRead,
+        mut output: impl Write,
+        prompt: &str,
+    ) -> io::Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => {
+                    // Ctrl-D: end the prompt's line
+                    if !prompt.is_empty() {
+                        writeln!(output)?;
+                    }
+                    return Ok(());
+                }
+            };
+            if let Flow::Quit = self.line(line.trim(), &mut output)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Evaluates one trimmed line or runs its `:` command, writing the result to `output`.
+    pub fn line(&mut self, line: &str, output: &mut impl Write) -> io::Result<Flow> {
+        if line.is_empty() {
+            return Ok(Flow::Continue);
+        }
+        if let Some(command) = line.strip_prefix(':') {
+            return self.command(command, output);
+        }
+        match eval_localized(line, &mut self.env, self.separators, self.strict) {
+            Ok(result) => {
+                let mut formatted = format_number(result, self.precision, self.notation);
+                if self.trim {
+                    formatted = trim_zeros(&formatted);
+                }
+                writeln!(output, "{}", self.separators.localize(&formatted))?
+            }
+            Err(err) => {
+                writeln!(output, "Error: {}", err)?;
+                if let Some(caret) = err.caret(line) {
+                    writeln!(output, "  {}", line)?;
+                    writeln!(output, "  {}", caret)?;
+                }
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn command(&mut self, command: &str, output: &mut impl Write) -> io::Result<Flow> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+        match (name, arg) {
+            ("quit", None) => return Ok(Flow::Quit),
+            ("help", None) => writeln!(output, "{}", HELP)?,
+            ("precision", Some(n)) => match n.parse() {
+                Ok(n) => self.precision = n,
+                Err(_) => writeln!(output, "Error: invalid precision `{}`", n)?,
+            },
+            ("mode", Some(mode)) => match mode {
+                "fixed" => self.notation = Notation::Fixed,
+                "sci" => self.notation = Notation::Scientific,
+                "eng" => self.notation = Notation::Engineering,
+                _ => writeln!(
+                    output,
+                    "Error: unknown mode `{}`, expected fixed, sci or eng",
+                    mode
+                )?,
+            },
+            ("precision", None) => writeln!(output, "Error: usage: :precision N")?,
+            ("mode", None) => writeln!(output, "Error: usage: :mode fixed|sci|eng")?,
+            _ => writeln!(
+                output,
+                "Error: unknown command `:{}`, try :help",
+                command.trim()
+            )?,
+        }
+        Ok(Flow::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The output of a session fed `input`, without a prompt.
+    fn transcript(input: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new(2, Notation::Fixed)
+            .run(input.as_bytes(), &mut output, "")
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_evaluates_each_line() {
+        assert_eq!(
+            transcript("1 + 2\n\n2 ^ 10\n  3 * (4 - 1)  \n"),
+            "3.00\n1024.00\n9.00\n"
+        );
+        assert_eq!(transcript(""), "");
+    }
+
+    #[test]
+    fn test_errors_do_not_end_the_session() {
+        assert_eq!(
+            transcript("1 / 0\n2 + * 3\n4\n"),
+            "Error: division by zero at offset 2\n  1 / 0\n    ^\n\
+             Error: expected a number, found `*` at offset 4\n  2 + * 3\n      ^\n\
+             4.00\n"
+        );
+    }
+
+    #[test]
+    fn test_commands_change_the_output() {
+        assert_eq!(
+            transcript("1 / 3\n:precision 4\n1 / 3\n:mode sci\n66000\n:mode eng\n66000\n"),
+            "0.33\n0.3333\n6.6000e4\n66.000e3\n"
+        );
+        assert_eq!(
+            transcript(":precision x\n:mode hex\n:mode\n:frobnicate\n"),
+            "Error: invalid precision `x`\n\
+             Error: unknown mode `hex`, expected fixed, sci or eng\n\
+             Error: usage: :mode fixed|sci|eng\n\
+             Error: unknown command `:frobnicate`, try :help\n"
+        );
+        assert!(transcript(":help\n").contains(":precision N"));
+    }
+
+    #[test]
+    fn test_variables_and_ans_persist_between_lines() {
+        assert_eq!(
+            transcript("x = 3 * 4\ny = x / 2\nx + y\nans * 2\nz\nans\npi = 3\n"),
+            "12.00\n6.00\n18.00\n36.00\n\
+             Error: undefined variable `z` at offset 0\n  z\n  ^\n\
+             36.00\n\
+             Error: can't assign to the constant `pi` at offset 0\n  pi = 3\n  ^\n"
+        );
+    }
+
+    #[test]
+    fn test_decimal_comma() {
+        let mut output = Vec::new();
+        let mut repl = Repl::new(2, Notation::Fixed);
+        repl.separators = Separators::new(crate::locale::Locale::Comma, true);
+        repl.run(
+            "x = 1234,5\nmax(x; 2) / 2\n1.5\n".as_bytes(),
+            &mut output,
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "1.234,50\n617,25\n\
+             Error: the decimal separator is `,` with --locale comma at offset 0\n  1.5\n  ^\n"
+        );
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut output = Vec::new();
+        let mut repl = Repl::new(2, Notation::Fixed);
+        repl.trim = true;
+        repl.run("1 + 2\n5 / 2\n-0.001\n".as_bytes(), &mut output, "")
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "3\n2.5\n0\n");
+    }
+
+    #[test]
+    fn test_quit_stops_reading() {
+        assert_eq!(transcript("1\n:quit\n2\n"), "1.00\n");
+    }
+
+    #[test]
+    fn test_prompt_before_each_line() {
+        let mut output = Vec::new();
+        let mut repl = Repl::new(0, Notation::Fixed);
+        repl.run("1 + 1\n:precision 1\n".as_bytes(), &mut output, "> ")
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "> 2\n> > \n");
+        assert_eq!(repl.precision, 1);
+    }
+}
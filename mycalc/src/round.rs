@@ -0,0 +1,170 @@
+//! Rounding to a number of decimals for `mycalc round`, `floor`, `ceil` and `trunc`.
+//!
+//! It rounds the shortest decimal that reads back as the `f64` (what `println!("{}")`
+//! shows), not its exact binary value: 2.675 is stored as 2.67499999..., yet rounds to
+//! 2.68 like it's written.
+
+/// Which way [`round_to`] goes when digits are dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundMode {
+    /// Nearest, ties away from zero: 2.5 => 3, -2.5 => -3
+    HalfUp,
+    /// Nearest, ties to the even digit (banker's rounding): 2.5 => 2, 3.5 => 4
+    HalfEven,
+    /// Toward negative infinity: -2.1 => -3
+    Floor,
+    /// Toward positive infinity: 2.1 => 3
+    Ceil,
+    /// Toward zero: -2.9 => -2
+    Trunc,
+}
+
+/// `x` rounded to `digits` decimals; a negative `digits` rounds to tens (-1), hundreds
+/// (-2), ... Infinities and NaN are returned as they are.
+/// E.g.: (2.675, 2, HalfUp) => 2.68, (1250, -2, HalfEven) => 1200
+pub fn round_to(x: f64, digits: i32, mode: RoundMode) -> f64 {
+    if !x.is_finite() {
+        return x;
+    }
+    // The shortest digits d1 d2 ... and exponent such that |x| = d1.d2... * 10^exp
+    let sci = format!("{:e}", x.abs());
+    let (mantissa, exp) = sci
+        .split_once('e')
+        .expect("scientific notation has an exponent");
+    let exp: i64 = exp.parse().expect("the exponent is an integer");
+    let all: Vec<u8> = mantissa
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|b| b - b'0')
+        .collect();
+    // How many of those digits are before the last one kept
+    let keep = exp + 1 + digits as i64;
+    if keep >= all.len() as i64 {
+        return x;
+    }
+    let (kept, dropped): (&[u8], &[u8]) = if keep <= 0 {
+        (&[], &all)
+    } else {
+        all.split_at(keep as usize)
+    };
+    // The first dropped digit is an implicit 0 when the number is below the kept range
+    let first = if keep < 0 { 0 } else { dropped[0] };
+    let rest_nonzero = if keep < 0 {
+        dropped.iter().any(|&d| d != 0)
+    } else {
+        dropped[1..].iter().any(|&d| d != 0)
+    };
+    let nonzero = first != 0 || rest_nonzero;
+    let odd = kept.last().is_some_and(|d| d % 2 == 1);
+    let negative = x < 0.0;
+    let away_from_zero = match mode {
+        RoundMode::HalfUp => first >= 5,
+        RoundMode::HalfEven => first > 5 || (first == 5 && (rest_nonzero || odd)),
+        RoundMode::Floor => negative && nonzero,
+        RoundMode::Ceil => !negative && nonzero,
+        RoundMode::Trunc => false,
+    };
+    let mut units = kept.iter().fold(0u128, |acc, &d| acc * 10 + d as u128);
+    if away_from_zero {
+        units += 1;
+    }
+    if units == 0 {
+        return 0.0;
+    }
+    let rounded: f64 = format!("{}e{}", units, -(digits as i64))
+        .parse()
+        .expect("a valid float literal");
+    if negative {
+        -rounded
+    } else {
+        rounded
+    }
+}
+
+/// `x` written with `digits` decimals (none for a negative `digits`), from its shortest
+/// decimal form: past the digits a float holds, the decimals are zeros rather than the
+/// binary expansion of `x`.
+/// E.g.: (1.23, 5) => "1.23000", (0.1, 20) => "0.10000000000000000000"
+pub fn format_rounded(x: f64, digits: i32) -> String {
+    let decimals = digits.max(0) as usize;
+    let shortest = x.to_string();
+    match shortest.split_once('.') {
+        _ if !x.is_finite() => shortest,
+        None if decimals == 0 => shortest,
+        None => format!("{}.{}", shortest, "0".repeat(decimals)),
+        Some((_, frac)) if frac.len() <= decimals => {
+            format!("{}{}", shortest, "0".repeat(decimals - frac.len()))
+        }
+        Some(_) => format!("{:.*}", decimals, x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RoundMode::{Ceil, Floor, HalfEven, HalfUp, Trunc};
+
+    #[test]
+    fn test_ties_under_every_mode() {
+        let modes = [HalfUp, HalfEven, Floor, Ceil, Trunc];
+        let cases: [(f64, i32, [f64; 5]); 5] = [
+            (2.5, 0, [3.0, 2.0, 2.0, 3.0, 2.0]),
+            (-2.5, 0, [-3.0, -2.0, -3.0, -2.0, -2.0]),
+            (3.5, 0, [4.0, 4.0, 3.0, 4.0, 3.0]),
+            (2.675, 2, [2.68, 2.68, 2.67, 2.68, 2.67]),
+            (-2.665, 2, [-2.67, -2.66, -2.67, -2.66, -2.66]),
+        ];
+        for (x, digits, expected) in cases {
+            for (mode, want) in modes.into_iter().zip(expected) {
+                assert_eq!(round_to(x, digits, mode), want, "{} {:?}", x, mode);
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_binary_float_surprises() {
+        // f64::round(2.675 * 100.0) / 100.0 gives 2.67
+        assert_eq!(round_to(2.675, 2, HalfUp), 2.68);
+        assert_eq!(round_to(1.005, 2, HalfUp), 1.01);
+        assert_eq!(round_to(0.1 + 0.2, 1, Trunc), 0.3);
+        assert_eq!(round_to(1.15, 1, HalfEven), 1.2);
+    }
+
+    #[test]
+    fn test_negative_digits_round_to_tens_and_hundreds() {
+        assert_eq!(round_to(1234.0, -1, HalfUp), 1230.0);
+        assert_eq!(round_to(1250.0, -2, HalfUp), 1300.0);
+        assert_eq!(round_to(1250.0, -2, HalfEven), 1200.0);
+        assert_eq!(round_to(-1250.0, -2, Floor), -1300.0);
+        assert_eq!(round_to(49.0, -2, HalfUp), 0.0);
+        assert_eq!(round_to(51.0, -2, HalfUp), 100.0);
+        // Far below the rounding unit
+        assert_eq!(round_to(7.0, -3, Ceil), 1000.0);
+        assert_eq!(round_to(-7.0, -3, Floor), -1000.0);
+        assert_eq!(round_to(-7.0, -3, HalfUp), 0.0);
+    }
+
+    #[test]
+    fn test_nothing_to_round() {
+        assert_eq!(round_to(2.5, 1, Trunc), 2.5);
+        assert_eq!(round_to(0.0, 2, Ceil), 0.0);
+        assert_eq!(round_to(1e300, 2, HalfUp), 1e300);
+        assert_eq!(round_to(9.99, 1, HalfUp), 10.0);
+        assert_eq!(round_to(1e-300, 2, Ceil), 0.01);
+        assert!(round_to(f64::NAN, 2, HalfUp).is_nan());
+        assert_eq!(round_to(f64::NEG_INFINITY, 0, Floor), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_format_rounded() {
+        assert_eq!(format_rounded(1.23, 5), "1.23000");
+        assert_eq!(format_rounded(2.68, 2), "2.68");
+        assert_eq!(format_rounded(1200.0, -2), "1200");
+        assert_eq!(format_rounded(3.0, 1), "3.0");
+        assert_eq!(format_rounded(-0.0, 2), "-0.00");
+        assert_eq!(format_rounded(f64::INFINITY, 2), "inf");
+        let wide = format_rounded(round_to(1.23, 400, HalfUp), 400);
+        assert_eq!(wide, format!("1.23{}", "0".repeat(398)));
+        assert_eq!(format_rounded(1.5e-20, 21), "0.000000000000000000015");
+    }
+}
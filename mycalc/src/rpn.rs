@@ -0,0 +1,185 @@
+//! Reverse Polish Notation for `mycalc rpn 3 4 + 2 '*'`: numbers and constants are pushed
+//! on a stack, `+ - * / ^` pop two numbers and push the result, and functions pop as many
+//! numbers as they take at most (two for `log`, `min` and `max`) and push theirs.
+
+use crate::functions;
+use crate::input::parse_number;
+use crate::locale::Separators;
+use crate::{finite, power, CalcError};
+
+/// Evaluates the RPN `tokens`, which must leave exactly one number on the stack. Errors
+/// name the token (counted from 1) that found too few numbers on the stack; a result that
+/// isn't a finite number is one.
+/// E.g.: ["3", "4", "+", "2", "*"] => (3 + 4) * 2 = 14
+pub fn eval_rpn(tokens: &[&str]) -> Result<f64, CalcError> {
+    eval_rpn_localized(tokens, Separators::default(), true)
+}
+
+/// Evaluates `tokens` like [`eval_rpn`], reading its numbers with `separators`; without
+/// `strict`, results may be infinite or NaN.
+pub fn eval_rpn_localized(
+    tokens: &[&str],
+    separators: Separators,
+    strict: bool,
+) -> Result<f64, CalcError> {
+    let mut stack: Vec<f64> = Vec::new();
+    for (i, &token) in tokens.iter().enumerate() {
+        let index = i + 1;
+        let needed = match token {
+            "+" | "-" | "*" | "/" | "^" | "**" => 2,
+            _ => match functions::lookup(token) {
+                Some(function) => function.max_args.unwrap_or(2),
+                None => {
+                    let value = functions::constant(token)
+                        .or_else(|| parse_number(token, separators).ok())
+                        .ok_or_else(|| CalcError::UnknownToken {
+                            index,
+                            token: token.to_string(),
+                        })?;
+                    stack.push(value);
+                    continue;
+                }
+            },
+        };
+        if stack.len() < needed {
+            return Err(CalcError::StackUnderflow {
+                index,
+                token: token.to_string(),
+                needed,
+                got: stack.len(),
+            });
+        }
+        let args = stack.split_off(stack.len() - needed);
+        let (operation, result) = apply(token, &args, strict)?;
+        stack.push(if strict {
+            finite(operation, None, None, result)?
+        } else {
+            result
+        });
+    }
+    match stack[..] {
+        [result] if strict => finite("the expression", None, None, result),
+        [result] => Ok(result),
+        _ => Err(CalcError::StackLeftover { left: stack.len() }),
+    }
+}
+
+/// The operator or function `token` applied to `args`, as many as it takes, with its
+/// name for errors.
+fn apply(token: &str, args: &[f64], strict: bool) -> Result<(&'static str, f64), CalcError> {
+    let (a, b) = (args[0], args.get(1).copied().unwrap_or_default());
+    match token {
+        "+" => Ok(("`+`", a + b)),
+        "-" => Ok(("`-`", a - b)),
+        "*" => Ok(("`*`", a * b)),
+        "/" if b == 0.0 => Err(CalcError::DivisionByZero {
+            at: None,
+            operand: None,
+        }),
+        "/" => Ok(("`/`", a / b)),
+        "^" | "**" => Ok(("`^`", power(a, b)?)),
+        _ => {
+            let function = functions::lookup(token).expect("only operators and functions");
+            let result = (function.apply)(args, strict).map_err(CalcError::domain)?;
+            Ok((function.name, result))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::Locale;
+
+    fn rpn(text: &str) -> Result<f64, CalcError> {
+        eval_rpn(&text.split_whitespace().collect::<Vec<_>>())
+    }
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(rpn("3 4 +"), Ok(7.0));
+        assert_eq!(rpn("3 4 + 2 *"), Ok(14.0));
+        assert_eq!(rpn("10 3 -"), Ok(7.0));
+        assert_eq!(rpn("20 2 / 5 /"), Ok(2.0));
+        assert_eq!(rpn("2 3 2 ^ ^"), Ok(512.0));
+        assert_eq!(rpn("2 10 **"), Ok(1024.0));
+        assert_eq!(rpn("-3 -2 *"), Ok(6.0));
+        assert_eq!(rpn("42"), Ok(42.0));
+    }
+
+    #[test]
+    fn test_longer_mixed_expression() {
+        // (5 + sqrt(16)) * 2 - max(1, 3) / log(100) + pi = 18 - 1.5 + pi
+        let result = rpn("5 16 sqrt + 2 * 1 3 max 100 10 log / - pi +").unwrap();
+        assert!(approx_eq(result, 16.5 + std::f64::consts::PI));
+        assert!(approx_eq(rpn("pi cos").unwrap(), -1.0));
+        assert!(approx_eq(rpn("1.5k 2_000 + 750m *").unwrap(), 2625.0));
+        let comma = Separators::new(Locale::Comma, false);
+        assert_eq!(eval_rpn_localized(&["1,5", "2", "*"], comma, true), Ok(3.0));
+    }
+
+    #[test]
+    fn test_underflow_names_the_token() {
+        assert_eq!(
+            rpn("3 +"),
+            Err(CalcError::StackUnderflow {
+                index: 2,
+                token: "+".to_string(),
+                needed: 2,
+                got: 1,
+            })
+        );
+        assert_eq!(
+            rpn("1 2 + * 4").unwrap_err().to_string(),
+            "`*` at token 4 needs 2 numbers on the stack, got 1"
+        );
+        assert_eq!(
+            rpn("sqrt").unwrap_err().to_string(),
+            "`sqrt` at token 1 needs 1 number on the stack, got 0"
+        );
+    }
+
+    #[test]
+    fn test_extra_operands() {
+        assert_eq!(rpn("1 2 3 +"), Err(CalcError::StackLeftover { left: 2 }));
+        assert_eq!(
+            rpn("1 2").unwrap_err().to_string(),
+            "expected one number left on the stack, got 2"
+        );
+        assert_eq!(rpn(""), Err(CalcError::StackLeftover { left: 0 }));
+    }
+
+    #[test]
+    fn test_bad_tokens_and_domains() {
+        assert_eq!(
+            rpn("1 x +").unwrap_err().to_string(),
+            "unknown token `x` at token 2"
+        );
+        assert_eq!(rpn("1 0 /").unwrap_err().to_string(), "division by zero");
+        assert!(matches!(
+            rpn("-4 sqrt"),
+            Err(CalcError::Domain { at: None, .. })
+        ));
+        assert!(rpn("-8 0.5 ^").is_err());
+        assert_eq!(
+            rpn("1e308 10 * 0 *").unwrap_err().to_string(),
+            "overflow in `*`"
+        );
+        assert_eq!(
+            rpn("inf").unwrap_err().to_string(),
+            "overflow in the expression"
+        );
+        let lenient = eval_rpn_localized(&["1e308", "10", "*"], Separators::default(), false);
+        assert_eq!(lenient, Ok(f64::INFINITY));
+        assert_eq!(
+            rpn("pi 2 / tan").unwrap_err().to_string(),
+            "tan is undefined at 1.5707963267948966 radians"
+        );
+        let lenient = eval_rpn_localized(&["pi", "2", "/", "tan"], Separators::default(), false);
+        assert!(lenient.unwrap() > 1e15);
+    }
+}
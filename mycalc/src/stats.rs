@@ -0,0 +1,239 @@
+//! Descriptive statistics for `mycalc stats`. Every function errors on too few numbers
+//! instead of returning NaN, and none of them reorders the caller's slice.
+
+use crate::CalcError;
+
+/// Whether a variance describes the numbers themselves or estimates it from a sample of
+/// a larger population (dividing by n - 1 instead of n).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Spread {
+    Population,
+    Sample,
+}
+
+fn at_least(function: &'static str, nums: &[f64], needed: usize) -> Result<(), CalcError> {
+    if nums.len() < needed {
+        return Err(CalcError::TooFewNumbers {
+            function,
+            needed,
+            got: nums.len(),
+        });
+    }
+    Ok(())
+}
+
+/// A sorted copy of `nums`.
+fn sorted(nums: &[f64]) -> Vec<f64> {
+    let mut sorted = nums.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+pub fn min(nums: &[f64]) -> Result<f64, CalcError> {
+    at_least("min", nums, 1)?;
+    Ok(nums.iter().copied().fold(f64::INFINITY, f64::min))
+}
+
+pub fn max(nums: &[f64]) -> Result<f64, CalcError> {
+    at_least("max", nums, 1)?;
+    Ok(nums.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+}
+
+pub fn mean(nums: &[f64]) -> Result<f64, CalcError> {
+    at_least("mean", nums, 1)?;
+    Ok(crate::sum(nums) / nums.len() as f64)
+}
+
+/// The middle number, or the average of the middle two for an even count.
+/// E.g.: [3, 1, 4, 1] => (1 + 3) / 2 = 2
+pub fn median(nums: &[f64]) -> Result<f64, CalcError> {
+    at_least("median", nums, 1)?;
+    let sorted = sorted(nums);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        Ok((sorted[mid - 1] + sorted[mid]) / 2.0)
+    } else {
+        Ok(sorted[mid])
+    }
+}
+
+/// The most frequent numbers, smallest first; several when they tie.
+/// E.g.: [1, 2, 2, 3, 3] => [2, 3]
+pub fn mode(nums: &[f64]) -> Result<Vec<f64>, CalcError> {
+    at_least("mode", nums, 1)?;
+    let sorted = sorted(nums);
+    let runs: Vec<&[f64]> = sorted.chunk_by(|a, b| a == b).collect();
+    let most = runs.iter().map(|run| run.len()).max().unwrap_or(0);
+    Ok(runs
+        .into_iter()
+        .filter(|run| run.len() == most)
+        .map(|run| run[0])
+        .collect())
+}
+
+/// The `p`th percentile (0 to 100) by linear interpolation between the closest ranks, as
+/// numpy's default: the value at rank `p / 100 * (n - 1)` of the sorted numbers, blending
+/// the two neighbours when that rank falls between them.
+/// E.g.: 25th of [1, 2, 3, 4] => rank 0.75 => 1 + 0.75 * (2 - 1) = 1.75
+pub fn percentile(nums: &[f64], p: f64) -> Result<f64, CalcError> {
+    if !(0.0..=100.0).contains(&p) {
+        return Err(CalcError::domain(format!(
+            "percentile {} is outside 0 to 100",
+            p
+        )));
+    }
+    at_least("percentile", nums, 1)?;
+    let sorted = sorted(nums);
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    Ok(sorted[lo] + (sorted[hi] - sorted[lo]) * (rank - lo as f64))
+}
+
+/// Interquartile range: the 75th percentile minus the 25th.
+pub fn iqr(nums: &[f64]) -> Result<f64, CalcError> {
+    Ok(percentile(nums, 75.0)? - percentile(nums, 25.0)?)
+}
+
+/// Mean squared distance from the mean; a sample variance needs at least 2 numbers.
+pub fn variance(nums: &[f64], spread: Spread) -> Result<f64, CalcError> {
+    let divisor = match spread {
+        Spread::Population => {
+            at_least("population variance", nums, 1)?;
+            nums.len()
+        }
+        Spread::Sample => {
+            at_least("sample variance", nums, 2)?;
+            nums.len() - 1
+        }
+    };
+    let mean = mean(nums)?;
+    let squares: Vec<f64> = nums.iter().map(|x| (x - mean).powi(2)).collect();
+    let squares = crate::sum(&squares);
+    Ok(squares / divisor as f64)
+}
+
+/// Standard deviation, the square root of the [`variance`].
+pub fn stddev(nums: &[f64], spread: Spread) -> Result<f64, CalcError> {
+    variance(nums, spread).map(f64::sqrt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f64 = 1e-9;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < EPS
+    }
+
+    const DIGITS: [f64; 8] = [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0, 6.0];
+
+    #[test]
+    fn test_mean_min_max() {
+        assert!(approx_eq(mean(&DIGITS).unwrap(), 31.0 / 8.0));
+        assert!(approx_eq(min(&DIGITS).unwrap(), 1.0));
+        assert!(approx_eq(max(&DIGITS).unwrap(), 9.0));
+        assert!(approx_eq(mean(&[-2.0, 2.0]).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_median_odd_and_even_counts() {
+        // 1 1 2 3 4 5 6 9
+        assert!(approx_eq(median(&DIGITS).unwrap(), 3.5));
+        assert!(approx_eq(median(&[7.0, 1.0, 3.0]).unwrap(), 3.0));
+        assert!(approx_eq(median(&[4.0]).unwrap(), 4.0));
+        assert!(approx_eq(median(&[-1.0, -5.0]).unwrap(), -3.0));
+    }
+
+    #[test]
+    fn test_median_leaves_the_input_order_alone() {
+        let nums = DIGITS;
+        median(&nums).unwrap();
+        mode(&nums).unwrap();
+        assert_eq!(nums, DIGITS);
+    }
+
+    #[test]
+    fn test_mode_single_and_multimodal() {
+        assert_eq!(mode(&DIGITS).unwrap(), [1.0]);
+        assert_eq!(mode(&[3.0, 2.0, 3.0, 2.0, 1.0]).unwrap(), [2.0, 3.0]);
+        assert_eq!(mode(&[5.0, 4.0]).unwrap(), [4.0, 5.0]);
+        assert_eq!(mode(&[0.5, 0.5, 0.5, -1.0]).unwrap(), [0.5]);
+    }
+
+    #[test]
+    fn test_percentiles_match_numpy() {
+        // numpy.percentile([3, 1, 4, 1, 5, 9, 2, 6], p)
+        for (p, expected) in [
+            (0.0, 1.0),
+            (10.0, 1.0),
+            (25.0, 1.75),
+            (50.0, 3.5),
+            (75.0, 5.25),
+            (90.0, 6.9),
+            (99.0, 8.79),
+            (100.0, 9.0),
+        ] {
+            let got = percentile(&DIGITS, p).unwrap();
+            assert!(approx_eq(got, expected), "p{}: {} != {}", p, got, expected);
+        }
+        assert!(approx_eq(iqr(&DIGITS).unwrap(), 3.5));
+    }
+
+    #[test]
+    fn test_percentile_edge_cases() {
+        // Exact ranks need no interpolation: rank 0.5 * 4 = 2
+        assert!(approx_eq(
+            percentile(&[10.0, 30.0, 20.0, 50.0, 40.0], 50.0).unwrap(),
+            30.0
+        ));
+        assert!(approx_eq(
+            percentile(&[10.0, 30.0, 20.0, 50.0, 40.0], 75.0).unwrap(),
+            40.0
+        ));
+        for p in [0.0, 25.0, 50.0, 99.0, 100.0] {
+            assert!(approx_eq(percentile(&[7.0], p).unwrap(), 7.0));
+        }
+        assert!(approx_eq(iqr(&[7.0]).unwrap(), 0.0));
+        assert_eq!(
+            percentile(&DIGITS, 100.5).unwrap_err().to_string(),
+            "percentile 100.5 is outside 0 to 100"
+        );
+        assert!(percentile(&DIGITS, -1.0).is_err());
+        assert!(percentile(&[], 50.0).is_err());
+    }
+
+    #[test]
+    fn test_variance_and_stddev() {
+        // Deviations from 5: -3 -1 -1 -1 0 0 2 4, squares sum to 32
+        let nums = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert!(approx_eq(variance(&nums, Spread::Population).unwrap(), 4.0));
+        assert!(approx_eq(stddev(&nums, Spread::Population).unwrap(), 2.0));
+        assert!(approx_eq(
+            variance(&nums, Spread::Sample).unwrap(),
+            32.0 / 7.0
+        ));
+        assert!(approx_eq(
+            stddev(&nums, Spread::Sample).unwrap(),
+            (32.0f64 / 7.0).sqrt()
+        ));
+        assert!(approx_eq(stddev(&[5.0], Spread::Population).unwrap(), 0.0));
+    }
+
+    #[test]
+    fn test_too_few_numbers() {
+        assert_eq!(
+            mean(&[]).unwrap_err().to_string(),
+            "mean needs at least 1 number, got 0"
+        );
+        for err in [median(&[]), min(&[]), max(&[])] {
+            assert!(err.is_err());
+        }
+        assert!(mode(&[]).is_err());
+        assert_eq!(
+            stddev(&[5.0], Spread::Sample).unwrap_err().to_string(),
+            "sample variance needs at least 2 numbers, got 1"
+        );
+    }
+}
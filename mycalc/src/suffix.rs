@@ -0,0 +1,158 @@
+//! `_` between digits (`1_000_000`) and SI suffixes (`2.5k`, `750m`) in numbers, expanded
+//! to plain digits before they're parsed. [`Separators::normalize`] calls [`expand`] last,
+//! so every number read from the command line, stdin, a file or an expression takes them.
+//!
+//! [`Separators::normalize`]: crate::locale::Separators::normalize
+
+/// The SI suffixes and the power of 10 they stand for.
+pub const SUFFIXES: &[(char, i32)] = &[
+    ('T', 12),
+    ('G', 9),
+    ('M', 6),
+    ('k', 3),
+    ('m', -3),
+    ('u', -6),
+    ('µ', -6),
+    ('n', -9),
+];
+
+/// `token`, a number with a `.` for decimals, without its `_` separators and with its SI
+/// suffix, when `suffixes` allows one, expanded by moving the point; `None` when there's
+/// nothing to expand. Error when a `_` isn't between two digits, or a suffix follows an
+/// exponent. A token that doesn't look like a number with a suffix (`nan`, `1/3k`) is
+/// passed through for the caller's parser to judge.
+/// E.g.: ("1_000", _) => "1000", ("2.5k", true) => "2500", ("-750m", true) => "-0.750"
+pub fn expand(token: &str, suffixes: bool) -> Result<Option<String>, &'static str> {
+    let mut expanded = None;
+    if token.contains('_') {
+        let chars: Vec<char> = token.chars().collect();
+        let between_digits = |i: usize| {
+            i > 0
+                && chars[i - 1].is_ascii_digit()
+                && chars.get(i + 1).is_some_and(char::is_ascii_digit)
+        };
+        if (0..chars.len()).any(|i| chars[i] == '_' && !between_digits(i)) {
+            return Err("a `_` goes between two digits");
+        }
+        expanded = Some(token.replace('_', ""));
+    }
+    let token = expanded.as_deref().unwrap_or(token);
+    if !suffixes {
+        return Ok(expanded);
+    }
+    let mut chars = token.chars();
+    let power = match chars.next_back() {
+        Some(last) => match SUFFIXES.iter().find(|(suffix, _)| *suffix == last) {
+            Some(&(_, power)) => power,
+            None => return Ok(expanded),
+        },
+        None => return Ok(expanded),
+    };
+    let mantissa = chars.as_str();
+    if mantissa.contains(['e', 'E']) && mantissa.ends_with(|c: char| c.is_ascii_digit()) {
+        return Err("a suffix can't follow an exponent");
+    }
+    let (sign, unsigned) = match mantissa.strip_prefix(['-', '+']) {
+        Some(rest) => (&mantissa[..1], rest),
+        None => ("", mantissa),
+    };
+    let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if int.len() + frac.len() == 0 || !is_digits(int) || !is_digits(frac) {
+        return Ok(expanded);
+    }
+    Ok(Some(format!("{}{}", sign, shift_point(int, frac, power))))
+}
+
+/// The digits `int.frac` times 10^`power`, written without an exponent.
+fn shift_point(int: &str, frac: &str, power: i32) -> String {
+    let digits = format!("{}{}", int, frac);
+    let point = int.len() as i32 + power;
+    let shifted = if point <= 0 {
+        format!("0.{}{}", "0".repeat(point.unsigned_abs() as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}", digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        let (int, frac) = digits.split_at(point as usize);
+        format!("{}.{}", int, frac)
+    };
+    // One leading zero at most, before the point
+    let trimmed = shifted.trim_start_matches('0');
+    if trimmed.is_empty() || trimmed.starts_with('.') {
+        format!("0{}", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_forms() {
+        let cases = [
+            ("1_000_000", "1000000"),
+            ("-1_234.567_8", "-1234.5678"),
+            ("1_0e1_0", "10e10"),
+            ("2.5k", "2500"),
+            ("3M", "3000000"),
+            ("1.2G", "1200000000"),
+            ("4T", "4000000000000"),
+            ("750m", "0.750"),
+            ("-750m", "-0.750"),
+            ("+2u", "+0.000002"),
+            ("2µ", "0.000002"),
+            ("15n", "0.000000015"),
+            (".5k", "500"),
+            ("5.k", "5000"),
+            ("0.25k", "250"),
+            ("0m", "0.000"),
+            ("1_500k", "1500000"),
+            ("123456.789k", "123456789"),
+            ("1.23456789k", "1234.56789"),
+        ];
+        for (token, expected) in cases {
+            assert_eq!(
+                expand(token, true),
+                Ok(Some(expected.to_string())),
+                "{}",
+                token
+            );
+        }
+    }
+
+    #[test]
+    fn test_nothing_to_expand() {
+        for token in [
+            "12", "-1.5e3", "inf", "nan", "NaN", "1/3k", "k", "-m", "0x1k", "",
+        ] {
+            assert_eq!(expand(token, true), Ok(None), "{}", token);
+        }
+        // Without suffixes, underscores still go
+        assert_eq!(expand("2k", false), Ok(None));
+        assert_eq!(expand("1_000k", false), Ok(Some("1000k".to_string())));
+    }
+
+    #[test]
+    fn test_invalid_forms() {
+        for token in [
+            "_1", "1_", "1__000", "-_1", "1_.5", "1._5", "1_e3", "2_k", "_",
+        ] {
+            assert_eq!(
+                expand(token, true),
+                Err("a `_` goes between two digits"),
+                "{}",
+                token
+            );
+        }
+        for token in ["1e3k", "2.5E-3m"] {
+            assert_eq!(
+                expand(token, true),
+                Err("a suffix can't follow an exponent"),
+                "{}",
+                token
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Trigonometry in radians or degrees for `mycalc sin`, `asin`, `atan2`, ... An argument
+//! outside a function's domain is an error instead of `NaN`.
+
+use crate::CalcError;
+
+/// How angles are given to `sin`, `cos` and `tan`, and returned by the inverse functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+}
+
+impl AngleUnit {
+    fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleUnit::Radians => angle,
+            AngleUnit::Degrees => angle.to_radians(),
+        }
+    }
+
+    fn in_unit(self, radians: f64) -> f64 {
+        match self {
+            AngleUnit::Radians => radians,
+            AngleUnit::Degrees => radians.to_degrees(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "radians",
+            AngleUnit::Degrees => "degrees",
+        }
+    }
+}
+
+/// Below this, `cos(x)` counts as zero for `tan` in strict mode.
+const SINGULAR_EPS: f64 = 1e-12;
+
+fn finite_angle(function: &str, x: f64) -> Result<f64, CalcError> {
+    if x.is_finite() {
+        Ok(x)
+    } else {
+        Err(CalcError::domain(format!(
+            "{} needs a finite angle, got {}",
+            function, x
+        )))
+    }
+}
+
+fn unit_interval(function: &str, x: f64) -> Result<f64, CalcError> {
+    if (-1.0..=1.0).contains(&x) {
+        Ok(x)
+    } else {
+        Err(CalcError::domain(format!(
+            "{} is only defined from -1 to 1, got {}",
+            function, x
+        )))
+    }
+}
+
+/// Sine of the angle `x`.
+pub fn sin(x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    Ok(unit.to_radians(finite_angle("sin", x)?).sin())
+}
+
+/// Cosine of the angle `x`.
+pub fn cos(x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    Ok(unit.to_radians(finite_angle("cos", x)?).cos())
+}
+
+/// Tangent of the angle `x`. Where it's undefined (90°, 270°, ...) floating point still
+/// gives a huge number; with `strict` that's an error instead.
+pub fn tan(x: f64, unit: AngleUnit, strict: bool) -> Result<f64, CalcError> {
+    let radians = unit.to_radians(finite_angle("tan", x)?);
+    if strict && radians.cos().abs() < SINGULAR_EPS {
+        return Err(CalcError::domain(format!(
+            "tan is undefined at {} {}",
+            x,
+            unit.name()
+        )));
+    }
+    Ok(radians.tan())
+}
+
+/// The angle whose sine is `x`, from -90° to 90°.
+pub fn asin(x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    Ok(unit.in_unit(unit_interval("asin", x)?.asin()))
+}
+
+/// The angle whose cosine is `x`, from 0° to 180°.
+pub fn acos(x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    Ok(unit.in_unit(unit_interval("acos", x)?.acos()))
+}
+
+/// The angle whose tangent is `x`, between -90° and 90°.
+pub fn atan(x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    if x.is_nan() {
+        return Err(CalcError::domain("atan of NaN"));
+    }
+    Ok(unit.in_unit(x.atan()))
+}
+
+/// The angle of the point (`x`, `y`) from the positive x axis, from -180° to 180°.
+/// E.g.: (1, -1) in degrees => 135
+pub fn atan2(y: f64, x: f64, unit: AngleUnit) -> Result<f64, CalcError> {
+    if y.is_nan() || x.is_nan() {
+        return Err(CalcError::domain("atan2 of NaN"));
+    }
+    Ok(unit.in_unit(y.atan2(x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use AngleUnit::{Degrees, Radians};
+
+    const EPS: f64 = 1e-12;
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() < EPS
+    }
+
+    #[test]
+    fn test_exact_values_in_degrees() {
+        let half_sqrt3 = 3f64.sqrt() / 2.0;
+        let half_sqrt2 = 2f64.sqrt() / 2.0;
+        let cases = [
+            (0.0, 0.0, 1.0),
+            (30.0, 0.5, half_sqrt3),
+            (45.0, half_sqrt2, half_sqrt2),
+            (60.0, half_sqrt3, 0.5),
+            (90.0, 1.0, 0.0),
+        ];
+        for (angle, s, c) in cases {
+            assert!(approx_eq(sin(angle, Degrees).unwrap(), s), "sin {}", angle);
+            assert!(approx_eq(cos(angle, Degrees).unwrap(), c), "cos {}", angle);
+            assert!(approx_eq(asin(s, Degrees).unwrap(), angle), "asin {}", s);
+            assert!(approx_eq(acos(c, Degrees).unwrap(), angle), "acos {}", c);
+        }
+        assert!(approx_eq(tan(0.0, Degrees, true).unwrap(), 0.0));
+        assert!(approx_eq(
+            tan(30.0, Degrees, true).unwrap(),
+            1.0 / 3f64.sqrt()
+        ));
+        assert!(approx_eq(tan(45.0, Degrees, true).unwrap(), 1.0));
+        assert!(approx_eq(tan(60.0, Degrees, true).unwrap(), 3f64.sqrt()));
+        assert!(approx_eq(atan(1.0, Degrees).unwrap(), 45.0));
+        assert!(approx_eq(atan2(1.0, -1.0, Degrees).unwrap(), 135.0));
+        assert!(approx_eq(atan2(-1.0, 0.0, Degrees).unwrap(), -90.0));
+    }
+
+    #[test]
+    fn test_radians_by_default() {
+        assert!(approx_eq(sin(FRAC_PI_2, Radians).unwrap(), 1.0));
+        assert!(approx_eq(cos(PI, Radians).unwrap(), -1.0));
+        assert!(approx_eq(tan(FRAC_PI_4, Radians, false).unwrap(), 1.0));
+        assert!(approx_eq(asin(1.0, Radians).unwrap(), FRAC_PI_2));
+        assert!(approx_eq(atan2(1.0, 1.0, Radians).unwrap(), FRAC_PI_4));
+    }
+
+    #[test]
+    fn test_inverse_functions_outside_their_domain_error() {
+        assert_eq!(
+            asin(2.0, Degrees).unwrap_err().to_string(),
+            "asin is only defined from -1 to 1, got 2"
+        );
+        assert_eq!(
+            acos(-1.5, Radians).unwrap_err().to_string(),
+            "acos is only defined from -1 to 1, got -1.5"
+        );
+        assert!(asin(f64::NAN, Radians).is_err());
+        assert!(sin(f64::INFINITY, Radians).is_err());
+        assert!(atan2(f64::NAN, 1.0, Radians).is_err());
+    }
+
+    #[test]
+    fn test_tan_near_singular_points() {
+        // Without strict, floating point gives a huge but finite number
+        assert!(tan(90.0, Degrees, false).unwrap().abs() > 1e15);
+        assert_eq!(
+            tan(90.0, Degrees, true).unwrap_err().to_string(),
+            "tan is undefined at 90 degrees"
+        );
+        assert!(tan(-270.0, Degrees, true).is_err());
+        assert!(tan(FRAC_PI_2, Radians, true).is_err());
+        assert!(tan(89.9, Degrees, true).is_ok());
+    }
+}
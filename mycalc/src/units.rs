@@ -0,0 +1,312 @@
+//! Units for `mycalc convert 5 km mi`: lengths, masses, volumes and data sizes. Each
+//! dimension has its units with how many of its base unit (metre, gram, litre, byte) one
+//! is, so converting is a multiplication and a division; adding a unit is one entry in
+//! [`UNITS`].
+
+use crate::CalcError;
+
+/// What a unit measures; only units of the same one convert to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Volume,
+    Data,
+}
+
+impl Dimension {
+    pub fn name(self) -> &'static str {
+        match self {
+            Dimension::Length => "length",
+            Dimension::Mass => "mass",
+            Dimension::Volume => "volume",
+            Dimension::Data => "data size",
+        }
+    }
+}
+
+pub struct Unit {
+    pub symbol: &'static str,
+    /// Other names accepted for the unit, like its symbol in any case
+    pub aliases: &'static [&'static str],
+    /// How many of the base unit of its dimension one of it is
+    pub factor: f64,
+}
+
+/// The units of each dimension, the base unit being the one with a factor of 1.
+pub const UNITS: &[(Dimension, &[Unit])] = &[
+    (
+        Dimension::Length,
+        &[
+            Unit {
+                symbol: "mm",
+                aliases: &["millimeter", "millimeters", "millimetre", "millimetres"],
+                factor: 0.001,
+            },
+            Unit {
+                symbol: "cm",
+                aliases: &["centimeter", "centimeters", "centimetre", "centimetres"],
+                factor: 0.01,
+            },
+            Unit {
+                symbol: "m",
+                aliases: &["meter", "meters", "metre", "metres"],
+                factor: 1.0,
+            },
+            Unit {
+                symbol: "km",
+                aliases: &["kilometer", "kilometers", "kilometre", "kilometres"],
+                factor: 1000.0,
+            },
+            Unit {
+                symbol: "in",
+                aliases: &["inch", "inches"],
+                factor: 0.0254,
+            },
+            Unit {
+                symbol: "ft",
+                aliases: &["foot", "feet"],
+                factor: 0.3048,
+            },
+            Unit {
+                symbol: "yd",
+                aliases: &["yard", "yards"],
+                factor: 0.9144,
+            },
+            Unit {
+                symbol: "mi",
+                aliases: &["mile", "miles"],
+                factor: 1609.344,
+            },
+        ],
+    ),
+    (
+        Dimension::Mass,
+        &[
+            Unit {
+                symbol: "mg",
+                aliases: &["milligram", "milligrams"],
+                factor: 0.001,
+            },
+            Unit {
+                symbol: "g",
+                aliases: &["gram", "grams"],
+                factor: 1.0,
+            },
+            Unit {
+                symbol: "kg",
+                aliases: &["kilogram", "kilograms"],
+                factor: 1000.0,
+            },
+            Unit {
+                symbol: "oz",
+                aliases: &["ounce", "ounces"],
+                factor: 28.349523125,
+            },
+            Unit {
+                symbol: "lb",
+                aliases: &["lbs", "pound", "pounds"],
+                factor: 453.59237,
+            },
+        ],
+    ),
+    (
+        Dimension::Volume,
+        &[
+            Unit {
+                symbol: "ml",
+                aliases: &["milliliter", "milliliters", "millilitre", "millilitres"],
+                factor: 0.001,
+            },
+            Unit {
+                symbol: "l",
+                aliases: &["liter", "liters", "litre", "litres"],
+                factor: 1.0,
+            },
+            // US customary
+            Unit {
+                symbol: "cup",
+                aliases: &["cups"],
+                factor: 0.2365882365,
+            },
+            Unit {
+                symbol: "gal",
+                aliases: &["gallon", "gallons"],
+                factor: 3.785411784,
+            },
+        ],
+    ),
+    (
+        Dimension::Data,
+        &[
+            Unit {
+                symbol: "B",
+                aliases: &["byte", "bytes"],
+                factor: 1.0,
+            },
+            Unit {
+                symbol: "KB",
+                aliases: &["kilobyte", "kilobytes"],
+                factor: 1e3,
+            },
+            Unit {
+                symbol: "KiB",
+                aliases: &["kibibyte", "kibibytes"],
+                factor: 1024.0,
+            },
+            Unit {
+                symbol: "MB",
+                aliases: &["megabyte", "megabytes"],
+                factor: 1e6,
+            },
+            Unit {
+                symbol: "MiB",
+                aliases: &["mebibyte", "mebibytes"],
+                factor: 1048576.0,
+            },
+            Unit {
+                symbol: "GB",
+                aliases: &["gigabyte", "gigabytes"],
+                factor: 1e9,
+            },
+            Unit {
+                symbol: "GiB",
+                aliases: &["gibibyte", "gibibytes"],
+                factor: 1073741824.0,
+            },
+        ],
+    ),
+];
+
+/// The unit called `name`, by its symbol or an alias in any case, with its dimension.
+/// E.g.: "Metre" => (Length, m), "kib" => (Data, KiB)
+pub fn find(name: &str) -> Option<(Dimension, &'static Unit)> {
+    UNITS.iter().find_map(|&(dimension, units)| {
+        units
+            .iter()
+            .find(|unit| {
+                unit.symbol.eq_ignore_ascii_case(name)
+                    || unit.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+            })
+            .map(|unit| (dimension, unit))
+    })
+}
+
+/// `value` in the unit `from` converted to the unit `to`. Error if either isn't a unit, or
+/// they measure different dimensions.
+/// E.g.: (5, "km", "mi") => 3.10686, (1, "GiB", "MB") => 1073.741824
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, CalcError> {
+    let unit = |name: &str| {
+        find(name).ok_or_else(|| CalcError::UnknownUnit {
+            name: name.to_string(),
+        })
+    };
+    let (from_dimension, from_unit) = unit(from)?;
+    let (to_dimension, to_unit) = unit(to)?;
+    if from_dimension != to_dimension {
+        return Err(CalcError::IncompatibleUnits {
+            from: from_unit.symbol,
+            from_dimension: from_dimension.name(),
+            to: to_unit.symbol,
+            to_dimension: to_dimension.name(),
+        });
+    }
+    Ok(value * from_unit.factor / to_unit.factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Within the 10 significant digits the expected values are given with.
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-10 * b.abs()
+    }
+
+    #[test]
+    fn test_length() {
+        assert!(close(convert(5.0, "km", "mi").unwrap(), 3.106855961));
+        assert_eq!(convert(1.0, "mi", "ft"), Ok(5280.0));
+        assert!(close(convert(1.0, "ft", "in").unwrap(), 12.0));
+        assert!(close(convert(3.0, "ft", "yd").unwrap(), 1.0));
+        assert!(close(convert(2.54, "cm", "in").unwrap(), 1.0));
+        assert_eq!(convert(1500.0, "mm", "m"), Ok(1.5));
+        assert_eq!(convert(-2.0, "m", "cm"), Ok(-200.0));
+    }
+
+    #[test]
+    fn test_mass() {
+        assert!(close(convert(1.0, "kg", "lb").unwrap(), 2.2046226218));
+        assert!(close(convert(1.0, "lb", "oz").unwrap(), 16.0));
+        assert_eq!(convert(2500.0, "mg", "g"), Ok(2.5));
+        assert_eq!(convert(0.5, "kg", "g"), Ok(500.0));
+    }
+
+    #[test]
+    fn test_volume() {
+        assert!(close(convert(1.0, "gal", "cup").unwrap(), 16.0));
+        assert!(close(convert(1.0, "gal", "l").unwrap(), 3.785411784));
+        assert_eq!(convert(250.0, "ml", "l"), Ok(0.25));
+    }
+
+    #[test]
+    fn test_data_sizes() {
+        assert_eq!(convert(1.0, "KiB", "B"), Ok(1024.0));
+        assert_eq!(convert(1.0, "GiB", "MiB"), Ok(1024.0));
+        assert_eq!(convert(1.0, "GiB", "MB"), Ok(1073.741824));
+        assert_eq!(convert(1500.0, "KB", "MB"), Ok(1.5));
+    }
+
+    #[test]
+    fn test_aliases_in_any_case() {
+        for name in ["m", "M", "meter", "Metre", "METRES"] {
+            assert_eq!(
+                find(name).map(|(_, unit)| unit.symbol),
+                Some("m"),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(find("kib").map(|(_, unit)| unit.symbol), Some("KiB"));
+        assert_eq!(find("Pounds").map(|(_, unit)| unit.symbol), Some("lb"));
+        assert_eq!(convert(1.0, "Kilometre", "METERS"), Ok(1000.0));
+        assert!(find("furlong").is_none());
+        assert!(find("").is_none());
+    }
+
+    #[test]
+    fn test_symbols_and_aliases_are_unique() {
+        let names: Vec<String> = UNITS
+            .iter()
+            .flat_map(|(_, units)| units.iter())
+            .flat_map(|unit| std::iter::once(&unit.symbol).chain(unit.aliases))
+            .map(|name| name.to_lowercase())
+            .collect();
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[..i].contains(name), "{} is taken twice", name);
+        }
+    }
+
+    #[test]
+    fn test_rejected_conversions() {
+        let err = convert(5.0, "km", "kg").unwrap_err();
+        assert_eq!(
+            err,
+            CalcError::IncompatibleUnits {
+                from: "km",
+                from_dimension: "length",
+                to: "kg",
+                to_dimension: "mass",
+            }
+        );
+        assert_eq!(err.to_string(), "can't convert km (length) to kg (mass)");
+        assert_eq!(
+            convert(1.0, "GB", "gal").unwrap_err().to_string(),
+            "can't convert GB (data size) to gal (volume)"
+        );
+        assert_eq!(
+            convert(1.0, "km", "furlong").unwrap_err().to_string(),
+            "unknown unit `furlong`"
+        );
+    }
+}
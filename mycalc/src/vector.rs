@@ -0,0 +1,123 @@
+//! Vectors for `mycalc vec`: the dot product, the length and linear combinations of lists
+//! of components. Vectors combined must have as many components as each other, and none
+//! may be empty.
+
+use crate::geometry::length;
+use crate::CalcError;
+
+/// Error if `a` and `b` have different numbers of components, or none, for `operation`.
+fn same_dimensions(operation: &'static str, a: &[f64], b: &[f64]) -> Result<(), CalcError> {
+    if a.len() != b.len() {
+        return Err(CalcError::domain(format!(
+            "the vectors have different dimensions: {} and {}",
+            a.len(),
+            b.len()
+        )));
+    }
+    not_empty(operation, a)
+}
+
+/// Error if `a` has no components.
+fn not_empty(operation: &'static str, a: &[f64]) -> Result<(), CalcError> {
+    if a.is_empty() {
+        return Err(CalcError::TooFewNumbers {
+            function: operation,
+            needed: 1,
+            got: 0,
+        });
+    }
+    Ok(())
+}
+
+/// The dot product, the sum of the products of the components of `a` and `b`.
+/// E.g.: ([1, 2, 3], [4, 5, 6]) => 4 + 10 + 18 = 32
+pub fn dot(a: &[f64], b: &[f64]) -> Result<f64, CalcError> {
+    same_dimensions("dot", a, b)?;
+    let products: Vec<f64> = a.iter().zip(b).map(|(x, y)| x * y).collect();
+    Ok(crate::sum(&products))
+}
+
+/// The Euclidean length of `a`, without overflowing for huge components, like
+/// [`hypot`](crate::geometry::hypot).
+/// E.g.: [3, 4] => 5
+pub fn norm(a: &[f64]) -> Result<f64, CalcError> {
+    not_empty("norm", a)?;
+    Ok(length(a.iter().copied()))
+}
+
+/// `alpha * x + y`, component by component.
+/// E.g.: (2, [1, 2], [10, 20]) => [12, 24]
+pub fn axpy(alpha: f64, x: &[f64], y: &[f64]) -> Result<Vec<f64>, CalcError> {
+    same_dimensions("axpy", x, y)?;
+    Ok(x.iter().zip(y).map(|(x, y)| alpha * x + y).collect())
+}
+
+/// `k * a`, each component times `k`.
+/// E.g.: (2, [1, -2]) => [2, -4]
+pub fn scale(k: f64, a: &[f64]) -> Result<Vec<f64>, CalcError> {
+    not_empty("scale", a)?;
+    Ok(a.iter().map(|x| k * x).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), Ok(32.0));
+        assert_eq!(dot(&[-2.0], &[3.5]), Ok(-7.0));
+        // Orthogonal vectors
+        assert_eq!(dot(&[1.0, 0.0], &[0.0, 1.0]), Ok(0.0));
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[-3.0, 0.0, 1.0]), Ok(0.0));
+        assert_eq!(dot(&[1e16, 1.0, -1e16], &[1.0, 1.0, 1.0]), Ok(1.0));
+    }
+
+    #[test]
+    fn test_norm() {
+        assert_eq!(norm(&[3.0, 4.0]), Ok(5.0));
+        assert_eq!(norm(&[-7.0]), Ok(7.0));
+        assert_eq!(norm(&[0.0, 0.0]), Ok(0.0));
+        assert_eq!(norm(&[3e300, 4e300]), Ok(5e300));
+    }
+
+    #[test]
+    fn test_axpy_and_scale() {
+        assert_eq!(axpy(2.0, &[1.0, 2.0], &[10.0, 20.0]), Ok(vec![12.0, 24.0]));
+        assert_eq!(axpy(1.0, &[1.0, 2.0], &[3.0, 4.0]), Ok(vec![4.0, 6.0]));
+        assert_eq!(axpy(-1.0, &[3.0, 4.0], &[1.0, 2.0]), Ok(vec![-2.0, -2.0]));
+        assert_eq!(scale(2.0, &[1.0, -2.0]), Ok(vec![2.0, -4.0]));
+        assert_eq!(scale(-0.5, &[4.0]), Ok(vec![-2.0]));
+        // Scaling by zero is the zero vector, keeping the dimension
+        assert_eq!(scale(0.0, &[1.0, -2.0, 3.0]), Ok(vec![0.0, 0.0, 0.0]));
+        assert_eq!(scale(0.0, &[5.0]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_mismatched_and_empty_vectors() {
+        assert_eq!(
+            dot(&[1.0, 2.0, 3.0], &[4.0, 5.0]).unwrap_err().to_string(),
+            "the vectors have different dimensions: 3 and 2"
+        );
+        assert!(axpy(1.0, &[1.0], &[1.0, 2.0]).is_err());
+        for err in [
+            dot(&[], &[]),
+            norm(&[]),
+            axpy(1.0, &[], &[]).map(|_| 0.0),
+            scale(2.0, &[]).map(|_| 0.0),
+        ] {
+            assert!(matches!(
+                err,
+                Err(CalcError::TooFewNumbers {
+                    needed: 1,
+                    got: 0,
+                    ..
+                })
+            ));
+        }
+        assert_eq!(
+            norm(&[]).unwrap_err().to_string(),
+            "norm needs at least 1 number, got 0"
+        );
+    }
+}
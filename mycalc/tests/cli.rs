@@ -1,5 +1,6 @@
 use predicates::prelude::*;
 use std::error::Error;
+use std::fs;
 
 #[test]
 fn add_default_precision() -> Result<(), Box<dyn Error>> {
@@ -28,6 +29,12 @@ fn sub_left_associative() -> Result<(), Box<dyn Error>> {
         .assert()
         .success()
         .stdout(predicate::str::starts_with("5.00"));
+    // Negative operands need no `--`
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["sub", "10", "-3", "-2.5"])
+        .assert()
+        .success()
+        .stdout("15.50\n");
     Ok(())
 }
 
@@ -55,8 +62,1483 @@ fn div_ok() -> Result<(), Box<dyn Error>> {
 fn div_by_zero_errors() -> Result<(), Box<dyn Error>> {
     let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
     cmd.args(["div", "10", "0"]) // error
+        .assert()
+        .code(1)
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["div", "100", "5", "0", "2"])
+        .assert()
+        .code(1)
+        .stderr("Error: division by zero: operand 3 is zero\n");
+    Ok(())
+}
+
+#[test]
+fn too_few_operands_never_panic() -> Result<(), Box<dyn Error>> {
+    let commands = [
+        "add", "sub", "mul", "div", "mod", "divmod", "pow", "root", "sqrt", "cbrt", "fact", "gcd",
+        "lcm", "round", "floor", "ceil", "trunc", "log", "ln", "exp", "sin", "cos", "tan", "asin",
+        "acos", "atan", "atan2", "percent", "base", "stats", "eval", "hypot", "dist", "vec",
+        "convert", "time", "finance", "change", "ratio",
+    ];
+    for command in commands {
+        for operands in [&[][..], &["5"]] {
+            let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+            let output = cmd.arg(command).args(operands).write_stdin("").output()?;
+            let stderr = String::from_utf8(output.stderr)?;
+            assert!(
+                !stderr.contains("panicked"),
+                "{} {:?}: {}",
+                command,
+                operands,
+                stderr
+            );
+            assert!(
+                matches!(output.status.code(), Some(0..=2)),
+                "{} {:?}: {:?}",
+                command,
+                operands,
+                output.status
+            );
+        }
+    }
+    for mode in ["--int", "--exact", "--decimal"] {
+        for command in ["add", "sub", "mul", "div"] {
+            let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+            cmd.args([mode, command, "5"])
+                .assert()
+                .code(2)
+                .stderr(predicate::str::contains(
+                    "at least 2 numbers are required, got 1",
+                ));
+        }
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "-"])
+        .write_stdin("")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "at least 1 number is required, got 0",
+        ));
+    Ok(())
+}
+
+#[test]
+fn eval_expression() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "1", "eval", "2 + 3 * (4 - 1) / 2"]) // 2 + 4.5
+        .assert()
+        .success()
+        .stdout("6.5\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "-1.5e3 / 3"])
+        .assert()
+        .success()
+        .stdout("-500.00\n");
+    Ok(())
+}
+
+#[test]
+fn eval_error_points_at_the_token() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "2 + * 3"])
+        .assert()
+        .failure()
+        .stderr("Error: expected a number, found `*` at offset 4\n  2 + * 3\n      ^\n");
+    Ok(())
+}
+
+#[test]
+fn eval_functions_and_power() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "-2^2 + max(1, sqrt(16), 3) * 2**3"]) // -4 + 4 * 8
+        .assert()
+        .success()
+        .stdout("28.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "1 + sqr(4)"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "unknown function `sqr` at offset 4 (did you mean `sqrt`?)",
+        ));
+    Ok(())
+}
+
+#[test]
+fn pow_tower_right_associative() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "pow", "2", "3", "2"]) // 2^(3^2) = 512
+        .assert()
+        .success()
+        .stdout("512\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["pow", "-8", "0.5"])
+        .assert()
+        .failure()
+        .stderr("Error: -8^0.5 is not a real number (negative base with a fractional exponent)\n");
+    Ok(())
+}
+
+#[test]
+fn mod_truncated_and_euclidean() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "mod", "-7", "3"])
+        .assert()
+        .success()
+        .stdout("-1\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "mod", "--euclidean", "-7", "3"])
+        .assert()
+        .success()
+        .stdout("2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["mod", "10", "0"])
+        .assert()
+        .failure()
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    Ok(())
+}
+
+#[test]
+fn divmod_prints_quotient_and_remainder() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "divmod", "-7", "3", "--euclidean"])
+        .assert()
+        .success()
+        .stdout("-3 2\n");
+    Ok(())
+}
+
+#[test]
+fn root_sqrt_and_cbrt() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["root", "27", "--n", "3"])
+        .assert()
+        .success()
+        .stdout("3.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "3", "sqrt", "2"])
+        .assert()
+        .success()
+        .stdout("1.414\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["cbrt", "-8"])
+        .assert()
+        .success()
+        .stdout("-2.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["root", "-4"])
+        .assert()
+        .failure()
+        .stderr("Error: even root (2) of a negative number (-4) is not a real number\n");
+    Ok(())
+}
+
+#[test]
+fn stats_summary() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "3", "1", "4", "1", "5", "9", "2", "6"])
+        .assert()
+        .success()
+        .stdout(
+            "count:                 8
+sum:                   31.00
+min:                   1.00
+max:                   9.00
+mean:                  3.88
+median:                3.50
+mode:                  1.00
+p25:                   1.75
+p50:                   3.50
+p75:                   5.25
+iqr:                   3.50
+stddev (sample):       2.75
+stddev (population):   2.57
+variance (sample):     7.55
+variance (population): 6.61
+",
+        );
+    Ok(())
+}
+
+#[test]
+fn stats_only_selected_metrics() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "-p",
+        "1",
+        "stats",
+        "--only",
+        "median,mode",
+        "1",
+        "2",
+        "2",
+        "3",
+        "3",
+    ])
+    .assert()
+    .success()
+    .stdout("median: 2.0\nmode:   2.0, 3.0\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "--only", "stddev", "5"])
+        .assert()
+        .failure()
+        .stderr("Error: sample variance needs at least 2 numbers, got 1\n");
+    Ok(())
+}
+
+#[test]
+fn stats_custom_percentiles() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "stats",
+        "--only",
+        "iqr",
+        "--percentile",
+        "90",
+        "--percentile",
+        "99.9",
+    ])
+    .args(["3", "1", "4", "1", "5", "9", "2", "6"])
+    .assert()
+    .success()
+    .stdout("iqr:   3.50\np90:   6.90\np99.9: 8.98\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "--percentile", "101", "1", "2"])
+        .assert()
+        .failure()
+        .stderr("Error: percentile 101 is outside 0 to 100\n");
+    Ok(())
+}
+
+#[test]
+fn numbers_from_stdin() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "-"])
+        .write_stdin("1 2 3.5\n")
+        .assert()
+        .success()
+        .stdout("6.50\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["mul", "-", "10"]) // stdin goes last: 10 * 2 * 3
+        .write_stdin("2\n3\n")
+        .assert()
+        .success()
+        .stdout("60.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["sub", "100", "-"])
+        .write_stdin("  50\n\n 20  ")
+        .assert()
+        .success()
+        .stdout("30.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "--only", "count,median", "-"])
+        .write_stdin("3 1 4\n1 5 9\n")
+        .assert()
+        .success()
+        .stdout("count:  6\nmedian: 3.50\n");
+    Ok(())
+}
+
+#[test]
+fn numbers_from_stdin_errors() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "1", "-"])
+        .write_stdin("")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "at least 2 numbers are required, got 1",
+        ))
+        .stderr(predicate::str::contains("panicked").not());
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "-"])
+        .write_stdin("1 2\n3 x4\n")
+        .assert()
+        .failure()
+        .stderr("Error: stdin: invalid number `x4` at line 2, column 3\n");
+    Ok(())
+}
+
+#[test]
+fn numbers_from_a_file() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("mycalc-file-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let values = dir.join("values.txt");
+    fs::write(&values, "# readings\r\n1, 2, 3,\r\n\r\n4\r\n")?;
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "--file"])
+        .arg(&values)
+        .assert()
+        .success()
+        .stdout("10.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.arg("--file")
+        .arg(&values)
+        .args(["stats", "--only", "count,max", "10"]) // 10 first, then the file
+        .assert()
+        .success()
+        .stdout("count: 5\nmax:   10.00\n");
+
+    let bad = dir.join("bad.txt");
+    fs::write(&bad, "1 2\n3 4,five\n")?;
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["mul", "--file"])
+        .arg(&bad)
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: {}: invalid number `five` at line 2, column 5\n",
+            bad.display()
+        ));
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn int_mode_is_exact() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "-p", "4", "add", "9007199254740993", "1"]) // f64 would round
+        .assert()
+        .success()
+        .stdout("9007199254740994\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "mul",
+        "--int",
+        "170141183460469231731687303715884105727",
+        "2",
+    ])
+    .assert()
+    .failure()
+    .stderr("Error: overflow in mul\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "add", "0.1", "0.2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid integer '0.1'"));
+    Ok(())
+}
+
+#[test]
+fn int_division_modes() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "div", "7", "2"])
+        .assert()
+        .failure()
+        .stderr("Error: 7 / 2 is not a whole number; use --int-floor or --int-trunc to round\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "--int-floor", "div", "-7", "2"])
+        .assert()
+        .success()
+        .stdout("-4\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "--int-trunc", "div", "-7", "2"])
+        .assert()
+        .success()
+        .stdout("-3\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int-floor", "div", "7", "2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "--int-floor only works with --int",
+        ));
+    // Global flags work on either side of the subcommand
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "div", "7", "2", "--int-floor"])
+        .assert()
+        .success()
+        .stdout("3\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int-trunc", "div", "-7", "2", "--int"])
+        .assert()
+        .success()
+        .stdout("-3\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "--int-floor", "div", "7", "2", "--int-trunc"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "'--int-floor' cannot be used with '--int-trunc'",
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "add", "1", "2", "--exact"])
+        .assert()
+        .code(2);
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--int", "stats", "1", "2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "--int only works with add, sub, mul and div",
+        ));
+    Ok(())
+}
+
+#[test]
+fn exact_mode_with_fractions() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "add", "1/3", "1/6"])
+        .assert()
+        .success()
+        .stdout("1/2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "add", "0.1", "0.2", "7"])
+        .assert()
+        .success()
+        .stdout("73/10\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "--decimal-digits", "5", "div", "2", "3"])
+        .assert()
+        .success()
+        .stdout("0.66667\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "div", "1/2", "0"])
+        .assert()
+        .failure()
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "--int", "add", "1", "2"])
+        .assert()
+        .code(2);
+    // Global flags work on either side of the subcommand
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "add", "1/3", "1/6", "--decimal-digits", "5"])
+        .assert()
+        .success()
+        .stdout("0.50000\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal-digits", "5", "div", "2", "3", "--exact"])
+        .assert()
+        .success()
+        .stdout("0.66667\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal-digits", "5", "div", "2", "3"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "--decimal-digits only works with --exact",
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--exact", "add", "1", "2", "--decimal"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "'--exact' cannot be used with '--decimal'",
+        ));
+    Ok(())
+}
+
+#[test]
+fn decimal_mode_is_base_ten() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal", "-p", "20", "add", "0.1", "0.2"])
+        .assert()
+        .success()
+        .stdout("0.30000000000000000000\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal", "-p", "3", "div", "10", "3"])
+        .assert()
+        .success()
+        .stdout("3.333\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal", "mul", "0.125", "1"]) // half to even
+        .assert()
+        .success()
+        .stdout("0.12\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--decimal", "div", "5", "0"])
+        .assert()
+        .failure()
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    Ok(())
+}
+
+#[test]
+fn percent_of_on_and_off() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["percent", "30", "of", "200", "--symbol"])
+        .assert()
+        .success()
+        .stdout("15.00%\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["percent", "15", "on", "200", "--symbol"]) // not a percentage: no %
+        .assert()
+        .success()
+        .stdout("230.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "percent", "-15", "off", "200"])
+        .assert()
+        .success()
+        .stdout("230\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["percent", "5", "of", "0"])
+        .assert()
+        .failure()
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["percent", "5", "in", "10"]).assert().code(2);
+    Ok(())
+}
+
+#[test]
+fn base_conversion() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 7] = [
+        (&["base", "0xff"], "255\n"),
+        (
+            &["base", "ff", "--from", "hex", "--to", "bin"],
+            "11111111\n",
+        ),
+        (
+            &["base", "255", "--to", "bin", "--prefix", "--group", "4"],
+            "0b1111_1111\n",
+        ),
+        (&["base", "-0x1f", "--to", "oct", "--prefix"], "-0o37\n"),
+        (&["base", "zz", "--from-radix", "36"], "1295\n"),
+        (&["base", "1295", "--to-radix", "36"], "zz\n"),
+        (&["base", "1000000", "--group", "3"], "1_000_000\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["base", "12", "--from", "bin"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Error: división por cero"));
+        .stderr("Error: invalid digit `2` for base 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["base", "1", "--from-radix", "37"])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn factorial_gcd_and_lcm() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 5] = [
+        (&["fact", "5"], "120\n"),
+        (&["fact", "0"], "1\n"),
+        (&["gcd", "12", "18", "27"], "3\n"),
+        (&["lcm", "4", "6", "10"], "60\n"),
+        (&["lcm", "0", "5"], "0\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["fact", "35"])
+        .assert()
+        .failure()
+        .stderr("Error: overflow in factorial: 35! doesn't fit in 128 bits, the largest is 34!\n");
+    for args in [&["fact", "-1"][..], &["gcd", "12", "1.5"]] {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().code(2);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["lcm", "-4", "6"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "invalid non-negative integer '-4'",
+        ));
+    Ok(())
+}
+
+#[test]
+fn trigonometry_in_radians_and_degrees() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 7] = [
+        (&["sin", "0"], "0.00\n"),
+        (&["-p", "4", "cos", "3.14159265358979"], "-1.0000\n"),
+        (&["--degrees", "sin", "30"], "0.50\n"),
+        (&["--degrees", "tan", "45"], "1.00\n"),
+        (&["--degrees", "-p", "0", "asin", "0.5"], "30\n"),
+        (&["--degrees", "-p", "0", "atan2", "1", "-1"], "135\n"),
+        (&["-p", "4", "atan", "1"], "0.7854\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["asin", "2"])
+        .assert()
+        .failure()
+        .stderr("Error: asin is only defined from -1 to 1, got 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--degrees", "--no-strict", "tan", "90"])
+        .assert()
+        .success();
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--degrees", "tan", "90"])
+        .assert()
+        .failure()
+        .stderr("Error: tan is undefined at 90 degrees\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--degrees", "--no-strict", "--strict", "tan", "90"])
+        .assert()
+        .failure()
+        .stderr("Error: tan is undefined at 90 degrees\n");
+    Ok(())
+}
+
+#[test]
+fn logarithms_and_exponentials() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 5] = [
+        (&["log", "1000"], "3.00\n"),
+        (&["log", "8", "--base", "2"], "3.00\n"),
+        (&["-p", "4", "ln", "2.718281828459045"], "1.0000\n"),
+        (&["-p", "4", "exp", "1"], "2.7183\n"),
+        (&["exp", "-1000"], "0.00\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let errors: [(&[&str], &str); 4] = [
+        (
+            &["log", "0"],
+            "log is only defined for positive numbers, got 0",
+        ),
+        (
+            &["ln", "-2"],
+            "log is only defined for positive numbers, got -2",
+        ),
+        (
+            &["log", "8", "--base", "1"],
+            "log base must be positive and not 1, got 1",
+        ),
+        (&["exp", "1000"], "overflow in exp"),
+    ];
+    for (args, message) in errors {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args)
+            .assert()
+            .failure()
+            .stderr(format!("Error: {}\n", message));
+    }
+    Ok(())
+}
+
+#[test]
+fn scientific_and_engineering_notation() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 6] = [
+        (
+            &["--notation", "sci", "-p", "3", "mul", "6.022", "1e23"],
+            "6.022e23\n",
+        ),
+        (&["--notation", "eng", "mul", "66", "1000"], "66.0e3\n"),
+        (
+            &["--notation", "sci", "-p", "1", "add", "998", "1"],
+            "1.0e3\n",
+        ),
+        (
+            &["--notation", "eng", "-p", "1", "div", "1", "2000"],
+            "500e-6\n",
+        ),
+        (&["--notation", "fixed", "add", "1", "2"], "3.00\n"),
+        (
+            &["--notation", "sci", "divmod", "7", "2"],
+            "3.00e0 1.00e0\n",
+        ),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "--notation",
+        "sci",
+        "stats",
+        "--only",
+        "mean",
+        "1000",
+        "3000",
+    ])
+    .assert()
+    .success()
+    .stdout("mean: 2.00e3\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--notation", "hex", "add", "1", "2"])
+        .assert()
+        .code(2);
+    Ok(())
+}
+
+#[test]
+fn rounding_modes() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 10] = [
+        (&["round", "2.5"], "3\n"),
+        (&["round", "-2.5"], "-3\n"),
+        (&["round", "2.5", "--mode", "half-even"], "2\n"),
+        (&["round", "2.675", "--digits", "2"], "2.68\n"),
+        (
+            &["round", "1250", "--digits", "-2", "--mode", "half-even"],
+            "1200\n",
+        ),
+        (&["floor", "-2.5"], "-3\n"),
+        (&["ceil", "2.1"], "3\n"),
+        (&["trunc", "-2.9"], "-2\n"),
+        (&["floor", "2.679", "-d", "2"], "2.67\n"),
+        (
+            &["round", "1.23", "--digits", "25"],
+            "1.2300000000000000000000000\n",
+        ),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["round", "2.5", "--mode", "up"]).assert().code(2);
+    Ok(())
+}
+
+#[test]
+fn repl_reads_expressions_until_eof() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "1", "repl"])
+        .write_stdin("1 + 2\n1 / 0\n:precision 3\n2 / 3\n")
+        .assert()
+        .success()
+        .stdout("3.0\nError: division by zero at offset 2\n  1 / 0\n    ^\n0.667\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.arg("repl")
+        .write_stdin(":quit\n1 + 1\n")
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn eval_with_variables() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "x * y + 1", "--var", "x=3", "--var", "y=2"])
+        .assert()
+        .success()
+        .stdout("7.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "r = 2", "-p", "0"])
+        .assert()
+        .success()
+        .stdout("2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "1 + z"])
+        .assert()
+        .failure()
+        .stderr("Error: undefined variable `z` at offset 4\n  1 + z\n      ^\n");
+    for var in ["pi=3", "sqrt=1", "x", "1x=2", "x=abc"] {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(["eval", "1", "--var", var]).assert().code(2);
+    }
+    Ok(())
+}
+
+#[test]
+fn decimal_comma_locale() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "add", "3,14", "1"])
+        .assert()
+        .success()
+        .stdout("4,14\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "--grouping", "mul", "1.234,567", "1"])
+        .assert()
+        .success()
+        .stdout("1.234,57\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "-p", "1", "eval", "max(1,5; x) * 2"])
+        .args(["--var", "x=2,5"])
+        .assert()
+        .success()
+        .stdout("5,0\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "add", "-", "1"])
+        .write_stdin("1,5 2,5\n0,5;0,5\n")
+        .assert()
+        .success()
+        .stdout("6,00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "--locale",
+        "comma",
+        "--grouping",
+        "--int",
+        "add",
+        "1.999.999",
+        "1",
+    ])
+    .assert()
+    .success()
+    .stdout("2.000.000\n");
+    // What it prints reads back the same
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    let output = cmd
+        .args(["--locale", "comma", "--grouping", "div", "1234567", "100"])
+        .output()?;
+    let printed = String::from_utf8(output.stdout)?;
+    assert_eq!(printed, "12.345,67\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "--locale",
+        "comma",
+        "--grouping",
+        "sub",
+        printed.trim(),
+        "0,67",
+    ])
+    .assert()
+    .success()
+    .stdout("12.345,00\n");
+    Ok(())
+}
+
+#[test]
+fn mixed_separators_are_rejected() -> Result<(), Box<dyn Error>> {
+    let cases = [
+        (
+            vec!["--locale", "comma", "add", "1.5", "1"],
+            "the decimal separator is `,`",
+        ),
+        (
+            vec!["--locale", "comma", "add", "1.234,5", "1"],
+            "need --grouping",
+        ),
+        (
+            vec!["--locale", "comma", "--grouping", "add", "1,234.5", "1"],
+            "a `.` after the decimal `,`",
+        ),
+        (vec!["--grouping", "add", "1,234", "1"], "ambiguous"),
+        (vec!["--grouping", "add", "12,34.5", "1"], "3 digits"),
+        (
+            vec!["--locale", "comma", "sqrt", "2.25"],
+            "the decimal separator is `,`",
+        ),
+    ];
+    for (args, reason) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(&args)
+            .assert()
+            .code(2)
+            .stderr(predicate::str::contains(reason));
+    }
+    // Without --grouping a lone comma is just a decimal one
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "-p", "3", "add", "1,234", "0"])
+        .assert()
+        .success()
+        .stdout("1,234\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "add", "-"])
+        .write_stdin("1,5\n2.5\n")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "stdin: invalid number `2.5` at line 2, column 1",
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "eval", "1.5 + 1"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("the decimal separator is `,`"));
+    Ok(())
+}
+
+fn json_run(args: &[&str]) -> Result<(Option<i32>, serde_json::Value), Box<dyn Error>> {
+    let output = assert_cmd::Command::cargo_bin("mycalc")?
+        .args(args)
+        .output()?;
+    let text = if output.status.success() {
+        output.stdout
+    } else {
+        output.stderr
+    };
+    Ok((output.status.code(), serde_json::from_slice(&text)?))
+}
+
+#[test]
+fn json_output() -> Result<(), Box<dyn Error>> {
+    let (code, json) = json_run(&["--output", "json", "div", "20", "2", "5"])?;
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        json,
+        serde_json::json!({"op": "div", "operands": [20.0, 2.0, 5.0], "result": 2.0, "precision": 2})
+    );
+    let (_, json) = json_run(&[
+        "--output",
+        "json",
+        "--no-strict",
+        "add",
+        "inf",
+        "--",
+        "-inf",
+    ])?;
+    assert_eq!(json["result"], "NaN");
+    let (code, json) = json_run(&["--output", "json", "sub", "1"])?;
+    assert_eq!(code, Some(2));
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "op": "sub",
+            "error": {"kind": "TooFewNumbers", "function": "sub", "needed": 2, "got": 1}
+        })
+    );
+    let (code, json) = json_run(&["--output", "json", "stats"])?;
+    assert_eq!(code, Some(2));
+    assert_eq!(json["error"]["kind"], "TooFewNumbers");
+    let (_, json) = json_run(&["--output", "json", "fact", "34"])?;
+    assert_eq!(json["result"], "295232799039604140847618609643520000000");
+    let (_, json) = json_run(&["--output", "json", "--exact", "add", "1/3", "1/2"])?;
+    assert_eq!(json["result"], "5/6");
+    let (_, json) = json_run(&["--output", "json", "stats", "1", "2", "2", "5"])?;
+    assert_eq!(json["result"]["count"], 4);
+    assert_eq!(json["result"]["mode"], serde_json::json!([2.0]));
+    // Locale settings only change the text output
+    let (_, json) = json_run(&["--output", "json", "--locale", "comma", "add", "1,5", "1"])?;
+    assert_eq!(json["result"], 2.5);
+    Ok(())
+}
+
+#[test]
+fn json_errors() -> Result<(), Box<dyn Error>> {
+    let (code, json) = json_run(&["--output", "json", "div", "20", "0"])?;
+    assert_eq!(code, Some(1));
+    assert_eq!(
+        json,
+        serde_json::json!({"op": "div", "error": {"kind": "DivisionByZero", "at": null, "operand": 2}})
+    );
+    let (code, json) = json_run(&["--output", "json", "eval", "1 + foo"])?;
+    assert_eq!(code, Some(1));
+    assert_eq!(json["error"]["kind"], "UndefinedVariable");
+    assert_eq!(json["error"]["at"], 4);
+    let (code, json) = json_run(&["--output", "json", "--int", "div", "7", "2"])?;
+    assert_eq!(code, Some(1));
+    assert_eq!(json["error"]["kind"], "InexactDivision");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    let output = cmd
+        .args(["--output", "json", "add", "1", "-"])
+        .write_stdin("1 x")
+        .output()?;
+    assert_eq!(output.status.code(), Some(2));
+    let json: serde_json::Value = serde_json::from_slice(&output.stderr)?;
+    assert_eq!(json["error"]["kind"], "InvalidNumber");
+    assert_eq!(json["error"]["token"], "x");
+    Ok(())
+}
+
+#[test]
+fn trim_trailing_zeros() -> Result<(), Box<dyn Error>> {
+    let cases = [
+        (vec!["--trim", "add", "1", "2"], "3\n"),
+        (vec!["--trim", "div", "5", "2"], "2.5\n"),
+        (vec!["--trim", "-p", "0", "div", "500", "2"], "250\n"),
+        (vec!["--trim", "mul", "-0.001", "1"], "0\n"),
+        (
+            vec!["--trim", "--notation", "sci", "add", "1", "2"],
+            "3.00e0\n",
+        ),
+        (vec!["--trim", "--decimal", "add", "0.1", "0.2"], "0.3\n"),
+        (
+            vec!["--trim", "--locale", "comma", "div", "5", "2"],
+            "2,5\n",
+        ),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(&args).assert().success().stdout(expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn suffixes_and_underscores() -> Result<(), Box<dyn Error>> {
+    let cases = [
+        (vec!["add", "2.5k", "1_000"], "3500.00\n"),
+        (vec!["-p", "4", "mul", "750m", "2"], "1.5000\n"),
+        (vec!["--int", "add", "3M", "2k"], "3002000\n"),
+        (vec!["eval", "1.2G / 3M"], "400.00\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(&args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "-"])
+        .write_stdin("1_000 2k\n5m\n")
+        .assert()
+        .success()
+        .stdout("3000.01\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "1__000", "1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("a `_` goes between two digits"));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--no-suffixes", "add", "2k", "1"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("invalid number '2k'"));
+    Ok(())
+}
+
+#[test]
+fn constants_list_and_shadowing() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.arg("constants")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("pi  3.141592653589793\n"))
+        .stdout(predicate::str::contains("phi 1.618033988749895\n"))
+        .stdout(predicate::str::contains("inf inf\n"));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "4", "eval", "cos(pi) + tau / pi"])
+        .assert()
+        .success()
+        .stdout("1.0000\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "tau = 6"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "can't assign to the constant `tau` at offset 0",
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "phi", "--var", "phi=2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("constant `phi`"));
+    Ok(())
+}
+
+#[test]
+fn rpn_from_args_and_stdin() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["rpn", "3", "4", "+", "2", "*"])
+        .assert()
+        .success()
+        .stdout("14.00\n");
+    // A lone - is stdin; after numbers it's a subtraction
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["rpn", "-"])
+        .write_stdin("5 1 2 + 4 * + 3 -\n")
+        .assert()
+        .success()
+        .stdout("14.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["rpn", "5", "-3", "-"])
+        .assert()
+        .success()
+        .stdout("8.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["rpn", "1", "+", "2"])
+        .assert()
+        .code(1)
+        .stderr("Error: `+` at token 2 needs 2 numbers on the stack, got 1\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["rpn", "1", "2", "3", "+"])
+        .assert()
+        .code(1)
+        .stderr("Error: expected one number left on the stack, got 2\n");
+    Ok(())
+}
+
+#[test]
+fn chain_pipelines() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["chain", "add 1 2 3 | mul 2 | sub 1"])
+        .assert()
+        .success()
+        .stdout("11.00\n");
+    // Intermediate results aren't rounded to --precision
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["-p", "0", "chain", "div 1 3 | mul 3"])
+        .assert()
+        .success()
+        .stdout("1\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["chain", "add 1 2 | times 2"])
+        .assert()
+        .code(1)
+        .stderr(
+            "Error: stage 2: unknown operation `times` at offset 10 \
+             (add, sub, mul, div, pow, mod, sqrt, cbrt, ln or exp)\n  \
+             add 1 2 | times 2\n            ^\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["chain", "add 1 | sub"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "stage 2: sub needs at least 2 numbers",
+        ));
+    Ok(())
+}
+
+#[test]
+fn compensated_and_naive_sums() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["add", "1e16", "1", "-1e16"])
+        .assert()
+        .success()
+        .stdout("1.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--naive-sum", "add", "1e16", "1", "-1e16"])
+        .assert()
+        .success()
+        .stdout("0.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--naive-sum", "--int", "add", "1", "2"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--naive-sum", "add", "1", "2", "--decimal"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+            "'--naive-sum' cannot be used with '--decimal'",
+        ));
+    Ok(())
+}
+
+#[test]
+fn strict_results_that_are_not_finite() -> Result<(), Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["mul", "1e308", "10"])
+        .assert()
+        .code(1)
+        .stderr("Error: overflow in mul at operand 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--no-strict", "mul", "1e308", "10"])
+        .assert()
+        .success()
+        .stdout("inf\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["sub", "1", "inf", "2"])
+        .assert()
+        .code(1)
+        .stderr("Error: overflow in sub at operand 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "0/0"])
+        .assert()
+        .code(1)
+        .stderr("Error: division by zero at offset 1\n  0/0\n   ^\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "1 / (inf - inf)"])
+        .assert()
+        .code(1)
+        .stderr(
+            "Error: `inf` is not a finite number (strict mode) at offset 5\n  \
+             1 / (inf - inf)\n       ^\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["eval", "tan(pi / 2)"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::starts_with(
+            "Error: tan is undefined at 1.5707963267948966 radians at offset 0\n",
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--no-strict", "eval", "1 / (inf - inf)"])
+        .assert()
+        .success()
+        .stdout("NaN\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "exp", "1000"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            r#""kind":"Overflow","operation":"exp""#,
+        ));
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["stats", "--only", "mean", "1e308", "1e308"])
+        .assert()
+        .code(1)
+        .stderr("Error: overflow in mean\n");
+    Ok(())
+}
+
+#[test]
+fn hypot_and_distance() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 5] = [
+        (&["hypot", "3", "4"], "5.00\n"),
+        (&["hypot", "1", "2", "2"], "3.00\n"),
+        (
+            &["--notation", "sci", "hypot", "3e300", "4e300"],
+            "5.00e300\n",
+        ),
+        (&["dist", "--a", "1,2,3", "--b", "4,6,3"], "5.00\n"),
+        (&["dist", "--a", "-1,2", "--b", "2,-2"], "5.00\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--locale", "comma", "dist", "--a", "1,5;2", "--b", "4,5;6"])
+        .assert()
+        .success()
+        .stdout("5,00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["dist", "--a", "1,2,3", "--b", "4,6"])
+        .assert()
+        .code(1)
+        .stderr("Error: the points have different dimensions: 3 and 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["dist", "--a", "1,x", "--b", "1,2"])
+        .assert()
+        .code(2)
+        .stderr("Error: --a: invalid number `x` at line 1, column 3\n");
+    Ok(())
+}
+
+#[test]
+fn unit_conversions() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 6] = [
+        (&["convert", "5", "km", "mi"], "3.11\n"),
+        (
+            &["--precision", "4", "convert", "1", "mi", "km"],
+            "1.6093\n",
+        ),
+        (&["--trim", "convert", "1", "GiB", "MiB"], "1024\n"),
+        (&["convert", "1", "kg", "lb"], "2.20\n"),
+        (&["convert", "2", "Gallons", "litres"], "7.57\n"),
+        (&["convert", "-40", "Metre", "FT"], "-131.23\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "convert", "250", "ml", "l"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"op\":\"convert\",\"operands\":[250.0,\"ml\",\"l\"],\"precision\":2,\
+             \"result\":0.25}\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["convert", "5", "km", "kg"])
+        .assert()
+        .code(1)
+        .stderr("Error: can't convert km (length) to kg (mass)\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["convert", "5", "km", "furlong"])
+        .assert()
+        .code(2)
+        .stderr("Error: unknown unit `furlong`\n");
+    Ok(())
+}
+
+#[test]
+fn percent_change_and_ratio() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 8] = [
+        (&["change", "50", "75"], "50.00\n"),
+        (&["change", "80", "60"], "-25.00\n"),
+        (&["change", "-50", "-25"], "50.00\n"),
+        (&["--precision", "1", "change", "3", "4"], "33.3\n"),
+        (&["ratio", "1920", "1080"], "16:9\n"),
+        (&["ratio", "-4", "6"], "-2:3\n"),
+        (&["ratio", "1.5", "1"], "1.50:1\n"),
+        (&["--trim", "ratio", "3", "0.5"], "6:1\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "ratio", "1920", "1080"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"op\":\"ratio\",\"operands\":[1920.0,1080.0],\"precision\":2,\
+             \"result\":[16,9]}\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "change", "50", "75"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"op\":\"change\",\"operands\":[50.0,75.0],\"precision\":2,\
+             \"result\":50.0}\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["change", "0", "5"])
+        .assert()
+        .code(1)
+        .stderr("Error: division by zero: operand 1 is zero\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["ratio", "5", "0"])
+        .assert()
+        .code(1)
+        .stderr("Error: division by zero: operand 2 is zero\n");
+    Ok(())
+}
+
+#[test]
+fn compound_interest_and_loan_payments() -> Result<(), Box<dyn Error>> {
+    let money = ["--principal", "1000", "--rate", "5%", "--years", "10"];
+    let cases: [(&[&str], &str); 3] = [
+        (&["compound"], "1628.89\n"),
+        (&["compound", "--compounds-per-year", "12"], "1647.01\n"),
+        (&["payment"], "10.61\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.arg("finance")
+            .args(args)
+            .args(money)
+            .assert()
+            .success()
+            .stdout(expected);
+    }
+    // A rate as a fraction
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "payment", "--principal", "250000"])
+        .args(["--rate", "0.045", "--years", "30"])
+        .assert()
+        .success()
+        .stdout("1266.71\n");
+    // With no interest, the formula would divide by zero
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "payment", "--principal", "12000"])
+        .args(["--rate", "0%", "--years", "1"])
+        .assert()
+        .success()
+        .stdout("1000.00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "payment", "--principal", "10000"])
+        .args(["--rate", "6%", "--years", "3", "--schedule"])
+        .assert()
+        .success()
+        .stdout(
+            "304.22\n\
+             Year  Interest  Principal  Balance\n\
+            \x20  1    514.69    3135.94  6864.06\n\
+            \x20  2    321.28    3329.36  3534.70\n\
+            \x20  3    115.93    3534.70     0.00\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "compound", "--principal", "1000"])
+        .args(["--rate", "5%", "--years", "2", "--schedule"])
+        .assert()
+        .success()
+        .stdout(
+            "1102.50\n\
+             Year  Interest  Balance\n\
+            \x20  1     50.00  1050.00\n\
+            \x20  2     52.50  1102.50\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "payment", "--principal", "1000"])
+        .args(["--rate", "-1%", "--years", "1"])
+        .assert()
+        .code(1)
+        .stderr("Error: a negative rate (-0.01)\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["finance", "payment", "--principal", "1000"])
+        .args(["--rate", "1%", "--years", "0"])
+        .assert()
+        .code(1)
+        .stderr("Error: a loan needs a positive number of years, got 0\n");
+    Ok(())
+}
+
+#[test]
+fn duration_arithmetic() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 10] = [
+        (&["time", "add", "1h30m", "45m", "90s"], "2h16m30s\n"),
+        (&["time", "add", "1:2:3.5", "00:00:00.5"], "1h2m4s\n"),
+        (&["time", "diff", "02:15:00", "01:50:30"], "24m30s\n"),
+        (&["time", "diff", "01:50:30", "02:15:00"], "-24m30s\n"),
+        (
+            &["time", "diff", "01:50:30", "02:15:00", "--as", "clock"],
+            "-00:24:30\n",
+        ),
+        (
+            &["time", "add", "20h", "10h", "--as", "clock"],
+            "30:00:00\n",
+        ),
+        (&["time", "--as", "seconds", "add", "90m"], "5400.00\n"),
+        (&["time", "add", "00:00:01.25", "0.5s"], "1.75s\n"),
+        (&["--precision", "0", "time", "add", "1.6s"], "2s\n"),
+        (&["time", "add", "--", "1h", "-15m"], "45m\n"),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "time", "diff", "1h", "2h"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"op\":\"time diff\",\"operands\":[\"1h\",\"2h\"],\"precision\":2,\
+             \"result\":-3600.0}\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["time", "add", "1h", "30m1h"])
+        .assert()
+        .code(2)
+        .stderr("Error: invalid duration `30m1h`: h, m and s go in that order, each once\n");
+    Ok(())
+}
+
+#[test]
+fn vector_operations() -> Result<(), Box<dyn Error>> {
+    let cases: [(&[&str], &str); 7] = [
+        (&["vec", "dot", "--a", "1,2,3", "--b", "4,5,6"], "32.00\n"),
+        // Orthogonal vectors
+        (&["vec", "dot", "--a", "1,2,3", "--b", "-3,0,1"], "0.00\n"),
+        (&["vec", "norm", "--a", "3,4"], "5.00\n"),
+        (&["vec", "add", "--a", "1,2", "--b", "3,4"], "4.00, 6.00\n"),
+        (
+            &["vec", "sub", "--a", "1,2", "--b", "3,4"],
+            "-2.00, -2.00\n",
+        ),
+        (
+            &["vec", "scale", "--a", "1,-2", "--k", "-0.5"],
+            "-0.50, 1.00\n",
+        ),
+        (
+            &["vec", "scale", "--a", "1,2,3", "--k", "0"],
+            "0.00, 0.00, 0.00\n",
+        ),
+    ];
+    for (args, expected) in cases {
+        let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+        cmd.args(args).assert().success().stdout(expected);
+    }
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args([
+        "--locale", "comma", "vec", "add", "--a", "1,5;2", "--b", "1;1",
+    ])
+    .assert()
+    .success()
+    .stdout("2,50; 3,00\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["--output", "json", "vec", "sub", "--a", "1,2", "--b", "3,4"])
+        .assert()
+        .success()
+        .stdout(
+            "{\"op\":\"vec sub\",\"operands\":[[1.0,2.0],[3.0,4.0]],\"precision\":2,\
+             \"result\":[-2.0,-2.0]}\n",
+        );
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["vec", "dot", "--a", "1,2,3", "--b", "4,5"])
+        .assert()
+        .code(1)
+        .stderr("Error: the vectors have different dimensions: 3 and 2\n");
+    let mut cmd = assert_cmd::Command::cargo_bin("mycalc")?;
+    cmd.args(["vec", "norm", "--a", ""])
+        .assert()
+        .code(2)
+        .stderr("Error: norm needs at least 1 number, got 0\n");
     Ok(())
 }
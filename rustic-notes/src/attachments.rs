@@ -0,0 +1,184 @@
+//! Files attached to notes, copied into `attachments/<id>/` next to the store. Notes record
+//! the path relative to the store's directory so the whole folder can be moved around.
+//!
+//! Ids of removed notes get reused, and a soft-removed note keeps its files (for `undo`), so a
+//! note only starts a directory that doesn't exist yet: `attachments/<id>-1/` and so on when
+//! `attachments/<id>/` is taken.
+
+use crate::Note;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DIR: &str = "attachments";
+
+fn store_dir(store: &Path) -> &Path {
+    store.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+}
+
+/// The note's attachment directory relative to the store's directory: where its files already
+/// are, or else the first free one of `attachments/<id>`, `attachments/<id>-1`, …
+pub fn relative_dir(store: &Path, note: &Note) -> PathBuf {
+    if let Some(dir) = note.attachments.first().and_then(|p| p.parent()) {
+        return dir.to_path_buf();
+    }
+    Path::new(DIR).join(free_name(&absolute(store, Path::new(DIR)), &note.id.to_string()))
+}
+
+/// Where a recorded attachment path lives on disk.
+pub fn absolute(store: &Path, relative: &Path) -> PathBuf {
+    store_dir(store).join(relative)
+}
+
+/// `name`, or `stem-1.ext`, `stem-2.ext`, … if the note already has a file with that name.
+fn free_name(dir: &Path, name: &str) -> String {
+    if !dir.join(name).exists() {
+        return name.to_string();
+    }
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let ext = path.extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e)).unwrap_or_default();
+    (1..).map(|i| format!("{}-{}{}", stem, i, ext)).find(|n| !dir.join(n).exists()).expect("some suffix is free")
+}
+
+/// Copies `file` into the note's attachment directory and records it; returns the relative path.
+pub fn attach(store: &Path, note: &mut Note, file: &Path) -> anyhow::Result<PathBuf> {
+    if !file.is_file() {
+        anyhow::bail!("{} is not a file", file.display());
+    }
+    let name = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow::anyhow!("{} has no usable file name", file.display()))?;
+    let relative_dir = relative_dir(store, note);
+    let dir = absolute(store, &relative_dir);
+    fs::create_dir_all(&dir)?;
+    let name = free_name(&dir, name);
+    fs::copy(file, dir.join(&name))?;
+    let relative = relative_dir.join(name);
+    note.attachments.push(relative.clone());
+    Ok(relative)
+}
+
+/// Deletes the attachment called `name` from the note and from disk.
+pub fn detach(store: &Path, note: &mut Note, name: &str) -> anyhow::Result<PathBuf> {
+    let pos = note
+        .attachments
+        .iter()
+        .position(|p| p.file_name().is_some_and(|n| n == name))
+        .ok_or_else(|| anyhow::anyhow!("note #{} has no attachment named {:?}", note.id, name))?;
+    let relative = note.attachments.remove(pos);
+    delete(store, &relative)?;
+    Ok(relative)
+}
+
+/// Deletes an attachment file, and its directory once that's empty.
+fn delete(store: &Path, relative: &Path) -> anyhow::Result<()> {
+    let path = absolute(store, relative);
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    if let Some(dir) = path.parent()
+        && fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+    {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+/// Deletes the note's attachments from disk (`remove --hard`, once the removal is saved). Only
+/// the files the note records go, so another note's files never do.
+pub fn remove_all(store: &Path, note: &Note) -> anyhow::Result<()> {
+    for relative in &note.attachments {
+        delete(store, relative)?;
+    }
+    Ok(())
+}
+
+/// Copies every attachment of `notes` from next to `from` to next to `to`; returns how many were copied.
+pub fn copy_all(from: &Path, to: &Path, notes: &[Note]) -> anyhow::Result<usize> {
+    if store_dir(from) == store_dir(to) {
+        return Ok(0);
+    }
+    let mut copied = 0;
+    for relative in notes.iter().flat_map(|n| &n.attachments) {
+        let (src, dst) = (absolute(from, relative), absolute(to, relative));
+        if src.exists() {
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(src, dst)?;
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// Size for listings: `512 B`, `1.5 KiB`, `3.0 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn note(id: u64) -> Note {
+        Note::new(id, "with files")
+    }
+
+    #[test]
+    fn colliding_names_get_a_numeric_suffix() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+        let file = dir.path().join("minutes.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let mut n = note(3);
+        attach(&store, &mut n, &file).unwrap();
+        attach(&store, &mut n, &file).unwrap();
+        let paths: Vec<String> = n.attachments.iter().map(|p| p.display().to_string()).collect();
+        let expected = [Path::new(DIR).join("3").join("minutes.txt"), Path::new(DIR).join("3").join("minutes-1.txt")];
+        assert_eq!(paths, expected.map(|p| p.display().to_string()));
+
+        detach(&store, &mut n, "minutes.txt").unwrap();
+        detach(&store, &mut n, "minutes-1.txt").unwrap();
+        assert!(!dir.path().join(DIR).join("3").exists());
+    }
+
+    #[test]
+    fn a_reused_id_gets_a_directory_of_its_own() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "hello").unwrap();
+
+        let mut removed = note(2);
+        attach(&store, &mut removed, &file).unwrap();
+        // #2 was soft-removed (its files stay for undo) and a new note got id 2
+        let mut reused = note(2);
+        assert_eq!(attach(&store, &mut reused, &file).unwrap(), Path::new(DIR).join("2-1").join("a.txt"));
+        assert_eq!(relative_dir(&store, &reused), Path::new(DIR).join("2-1"));
+
+        remove_all(&store, &reused).unwrap();
+        assert!(!dir.path().join(DIR).join("2-1").exists());
+        assert!(dir.path().join(DIR).join("2").join("a.txt").exists());
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{crypto, load, Format};
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A `<store>.bak.N` file found next to the store.
+#[derive(Debug)]
+pub struct BackupInfo {
+    pub which: usize,
+    pub path: PathBuf,
+    pub modified: DateTime<Utc>,
+    /// `None` when the backup can't be parsed with the store format (or is encrypted)
+    pub notes: Option<usize>,
+    pub encrypted: bool,
+}
+
+fn is_encrypted_file(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|data| crypto::is_encrypted(&data))
+}
+
+pub fn backup_path(store: &Path, which: usize) -> PathBuf {
+    let mut name = store.as_os_str().to_owned();
+    name.push(format!(".bak.{}", which));
+    PathBuf::from(name)
+}
+
+/// Shifts `.bak.1..` up by one (dropping the oldest) and copies the current store to `.bak.1`.
+pub fn rotate(store: &Path, keep: usize) -> anyhow::Result<()> {
+    if keep == 0 || !store.exists() {
+        return Ok(());
+    }
+    let oldest = backup_path(store, keep);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for which in (1..keep).rev() {
+        let from = backup_path(store, which);
+        if from.exists() {
+            fs::rename(&from, backup_path(store, which + 1))?;
+        }
+    }
+    fs::copy(store, backup_path(store, 1))?;
+    Ok(())
+}
+
+/// Existing backups, newest (`.bak.1`) first.
+pub fn list(store: &Path, format: Format) -> anyhow::Result<Vec<BackupInfo>> {
+    let mut out = Vec::new();
+    let mut which = 1;
+    loop {
+        let path = backup_path(store, which);
+        if !path.exists() {
+            break;
+        }
+        let modified: DateTime<Utc> = fs::metadata(&path)?.modified()?.into();
+        // Counting notes of an encrypted backup would mean asking for its passphrase
+        let encrypted = is_encrypted_file(&path);
+        let notes = if encrypted { None } else { load(&path, format).ok().map(|s| s.notes.len()) };
+        out.push(BackupInfo { which, path, modified, notes, encrypted });
+        which += 1;
+    }
+    Ok(out)
+}
+
+/// Puts backup `which` back in place of the store; the replaced store is rotated into the backups.
+pub fn restore(store: &Path, format: Format, which: usize, keep: usize) -> anyhow::Result<()> {
+    let path = backup_path(store, which);
+    if !path.exists() {
+        anyhow::bail!("backup {} not found: {}", which, path.display());
+    }
+    let raw = fs::read(&path)?;
+    // Refuse to restore something the store format can't read
+    if !is_encrypted_file(&path) {
+        load(&path, format)?;
+    }
+    rotate(store, keep)?;
+    fs::write(store, raw)?;
+    Ok(())
+}
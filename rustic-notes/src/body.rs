@@ -0,0 +1,154 @@
+//! Note bodies. A `Body` derefs to its text, so the rest of the code treats it as a `String`;
+//! only (de)serialization knows that long bodies may be kept in a file store as
+//! `{ "compressed": true, "data": "<base64 zstd>" }` (`compress_bodies` in the config file).
+//! Plain and compressed bodies can be mixed in one store.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// Bodies longer than this many bytes are compressed unless `compress_threshold` says otherwise.
+pub const DEFAULT_THRESHOLD: usize = 4096;
+
+thread_local! {
+    /// Set by [`compressing`] while a store is being serialized
+    static COMPRESS_ABOVE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with bodies longer than `above` bytes serialized compressed (`None`: all plain).
+/// Everything else that serializes notes (export, convert, the journal) writes plain text.
+pub fn compressing<T>(above: Option<usize>, f: impl FnOnce() -> T) -> T {
+    let previous = COMPRESS_ABOVE.replace(above);
+    let result = f();
+    COMPRESS_ABOVE.set(previous);
+    result
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Body(String);
+
+impl Body {
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for Body {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.0
+    }
+}
+
+impl DerefMut for Body {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.0
+    }
+}
+
+impl From<String> for Body {
+    fn from(s: String) -> Self {
+        Body(s)
+    }
+}
+
+impl From<&str> for Body {
+    fn from(s: &str) -> Self {
+        Body(s.to_string())
+    }
+}
+
+impl fmt::Display for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<str> for Body {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Body {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Compressed {
+    compressed: bool,
+    data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Plain(String),
+    Compressed(Compressed),
+}
+
+impl Serialize for Body {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match COMPRESS_ABOVE.get() {
+            Some(above) if self.0.len() > above => {
+                let packed = zstd::encode_all(self.0.as_bytes(), 0).map_err(serde::ser::Error::custom)?;
+                Compressed { compressed: true, data: STANDARD.encode(packed) }.serialize(serializer)
+            }
+            _ => serializer.serialize_str(&self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Stored::deserialize(deserializer)? {
+            Stored::Plain(text) => Ok(Body(text)),
+            Stored::Compressed(Compressed { compressed: false, data }) => Ok(Body(data)),
+            Stored::Compressed(Compressed { compressed: true, data }) => {
+                use serde::de::Error;
+                let packed = STANDARD.decode(data).map_err(|e| D::Error::custom(format!("compressed body: {}", e)))?;
+                let bytes = zstd::decode_all(packed.as_slice()).map_err(|e| D::Error::custom(format!("compressed body: {}", e)))?;
+                String::from_utf8(bytes).map(Body).map_err(|e| D::Error::custom(format!("compressed body: {}", e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Doc {
+        bodies: Vec<Body>,
+    }
+
+    #[test]
+    fn long_bodies_are_compressed_only_while_compressing() {
+        let doc = Doc { bodies: vec!["short".into(), "x".repeat(100).into()] };
+        let plain = serde_json::to_string(&doc).unwrap();
+        assert!(!plain.contains("compressed"));
+
+        let packed = compressing(Some(50), || serde_json::to_string(&doc).unwrap());
+        assert!(packed.starts_with(r#"{"bodies":["short",{"compressed":true,"data":""#));
+        assert!(packed.len() < plain.len());
+        assert_eq!(serde_json::from_str::<Doc>(&packed).unwrap(), doc);
+        // The setting doesn't leak out of the closure
+        assert_eq!(serde_json::to_string(&doc).unwrap(), plain);
+
+        let toml = compressing(Some(50), || toml::to_string(&doc).unwrap());
+        assert_eq!(toml::from_str::<Doc>(&toml).unwrap(), doc);
+    }
+
+    #[test]
+    fn broken_data_is_an_error() {
+        assert!(serde_json::from_str::<Body>(r#"{"compressed":true,"data":"not base64!"}"#).is_err());
+        assert_eq!(serde_json::from_str::<Body>(r#"{"compressed":false,"data":"as is"}"#).unwrap(), "as is");
+    }
+}
@@ -0,0 +1,119 @@
+//! Task lists inside bodies: lines like `- [ ] call the bank` or `  - [x] done`. Items are
+//! numbered from 1 in document order, nested ones included; fenced code blocks are skipped.
+
+use regex::Regex;
+use serde::Serialize;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// A bullet (`-`, `*` or `+`), then the box with an optional `x`/`X`, spaces allowed around both.
+static ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[ \t]*[-*+][ \t]+\[[ \t]*([xX]?)[ \t]*\]").expect("valid checklist regex"));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub done: bool,
+    /// The text after the box
+    pub text: String,
+    /// Byte range of what's between the brackets, within the whole body
+    mark: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    pub done: usize,
+    pub total: usize,
+}
+
+impl Progress {
+    pub fn open(&self) -> usize {
+        self.total - self.done
+    }
+}
+
+pub fn items(body: &str) -> Vec<Item> {
+    let mut items = Vec::new();
+    let mut in_code = false;
+    let mut offset = 0;
+    for line in body.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let Some(caps) = ITEM.captures(line) else { continue };
+        let whole = caps.get(0).expect("group 0 always matches");
+        let open = line[..whole.end()].rfind('[').expect("the pattern has a [");
+        items.push(Item {
+            done: !caps[1].is_empty(),
+            text: line[whole.end()..].trim().to_string(),
+            mark: start + open + 1..start + whole.end() - 1,
+        });
+    }
+    items
+}
+
+/// How many items are done, `None` when the body has none.
+pub fn progress(body: &str) -> Option<Progress> {
+    let items = items(body);
+    if items.is_empty() {
+        return None;
+    }
+    Some(Progress { done: items.iter().filter(|i| i.done).count(), total: items.len() })
+}
+
+/// Flips item `index` (1-based), changing nothing but what's between its brackets.
+/// Returns the new body and the item as it is now.
+pub fn toggle(body: &str, index: usize) -> anyhow::Result<(String, Item)> {
+    let items = items(body);
+    let Some(item) = index.checked_sub(1).and_then(|i| items.get(i)) else {
+        anyhow::bail!("there is no checklist item {} (the note has {})", index, items.len());
+    };
+    let mark = if item.done { " " } else { "x" };
+    let mut toggled = body.to_string();
+    toggled.replace_range(item.mark.clone(), mark);
+    let mark = item.mark.start..item.mark.start + mark.len();
+    Ok((toggled, Item { done: !item.done, text: item.text.clone(), mark }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "Trip\n- [ ] passport\n-  [X]  tickets\n  - [x] train\n  * [ ] hotel \r\n```\n- [ ] not a task\n```\n- [] charger";
+
+    #[test]
+    fn items_tolerate_spacing_and_nesting() {
+        let items = items(BODY);
+        let found: Vec<(bool, &str)> = items.iter().map(|i| (i.done, i.text.as_str())).collect();
+        assert_eq!(found, [(false, "passport"), (true, "tickets"), (true, "train"), (false, "hotel"), (false, "charger")]);
+        assert_eq!(progress(BODY), Some(Progress { done: 2, total: 5 }));
+        assert_eq!(progress("- not a task\n[x] neither"), None);
+    }
+
+    #[test]
+    fn toggling_rewrites_only_the_box() {
+        // Nested item: checked, then unchecked again
+        let (once, item) = toggle(BODY, 4).unwrap();
+        assert_eq!((item.done, item.text.as_str()), (true, "hotel"));
+        assert_eq!(once, BODY.replace("* [ ] hotel", "* [x] hotel"));
+        let (twice, item) = toggle(&once, 4).unwrap();
+        assert!(!item.done);
+        assert_eq!(twice, BODY);
+
+        // Last item, an empty box without a trailing newline
+        let (last, item) = toggle(BODY, 5).unwrap();
+        assert!(last.ends_with("- [x] charger") && item.done);
+        assert_eq!(last.len(), BODY.len() + 1);
+
+        // Uppercase X is unchecked to a space, keeping the surrounding spaces
+        let (tickets, _) = toggle(BODY, 2).unwrap();
+        assert!(tickets.contains("-  [ ]  tickets\n"));
+
+        assert_eq!(toggle(BODY, 6).unwrap_err().to_string(), "there is no checklist item 6 (the note has 5)");
+        assert!(toggle(BODY, 0).is_err());
+    }
+}
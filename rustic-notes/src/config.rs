@@ -0,0 +1,102 @@
+//! Optional user settings in `config.toml` (in the platform config directory, or wherever
+//! RUSTIC_NOTES_CONFIG points). Command-line flags and environment variables win over it.
+
+use directories::ProjectDirs;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+
+pub const CONFIG_ENV: &str = "RUSTIC_NOTES_CONFIG";
+
+/// Unknown keys are an error, so a typo doesn't silently fall back to the default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Store path used when neither --store/--cwd nor $RUSTIC_NOTES_STORE is given
+    pub store: Option<PathBuf>,
+    /// Store format (json, toml, ...) used when neither --format nor $RUSTIC_NOTES_FORMAT is given
+    pub format: Option<String>,
+    /// Encrypt the store when saving, like --encrypt
+    pub encrypt: bool,
+    /// Editor command used when neither --editor, $VISUAL nor $EDITOR is set
+    pub editor: Option<String>,
+    /// `"local"` displays timestamps in the local time zone, like --local
+    pub timezone: Option<Timezone>,
+    /// Commit the store after every change when it lives in a git repository
+    pub auto_commit: bool,
+    /// Archive notes not updated for this many days, at most once a day (see `prune`)
+    pub auto_prune_days: Option<u64>,
+    /// Make `add` refuse titles that are already taken, like --unique-title
+    pub unique_titles: bool,
+    /// Make `search` case-sensitive by default, like --case-sensitive (-i turns it off again)
+    pub search_case_sensitive: bool,
+    /// Write long bodies zstd-compressed in JSON/TOML/YAML stores
+    pub compress_bodies: bool,
+    /// Bodies longer than this many bytes are compressed (default 4096)
+    pub compress_threshold: Option<usize>,
+    /// ASCII-only output like --plain; `false` keeps emoji even when stdout is not a terminal
+    pub plain: Option<bool>,
+    /// Keep a full-text index next to the store to speed up `search` (see `reindex`)
+    pub search_index: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Timezone {
+    Utc,
+    Local,
+}
+
+/// `$RUSTIC_NOTES_CONFIG`, else `config.toml` in the platform config directory.
+pub fn config_path() -> Option<PathBuf> {
+    if let Some(p) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        return Some(PathBuf::from(p));
+    }
+    ProjectDirs::from("", "", "rustic-notes").map(|dirs| dirs.config_dir().join("config.toml"))
+}
+
+impl Config {
+    /// The `StoreOptions::compress_bodies` these settings ask for.
+    pub fn compress_above(&self) -> Option<usize> {
+        self.compress_bodies.then(|| self.compress_threshold.unwrap_or(crate::body::DEFAULT_THRESHOLD))
+    }
+
+    /// Reads the config file; a missing file is the same as an empty one.
+    pub fn load() -> anyhow::Result<Config> {
+        let Some(path) = config_path().filter(|p| p.exists()) else {
+            return Ok(Config::default());
+        };
+        let raw = fs::read_to_string(&path)?;
+        toml::from_str(&raw).map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn misspelled_keys_are_rejected() {
+        let err = toml::from_str::<Config>("editr = \"vim\"").unwrap_err().to_string();
+        assert!(err.contains("unknown field `editr`"), "{}", err);
+    }
+
+    #[test]
+    fn empty_and_partial_files_parse() {
+        assert!(toml::from_str::<Config>("").unwrap().editor.is_none());
+        let c: Config = toml::from_str("editor = \"code -w\"\n").unwrap();
+        assert_eq!(c.editor.as_deref(), Some("code -w"));
+        assert_eq!(toml::from_str::<Config>("timezone = \"local\"").unwrap().timezone, Some(Timezone::Local));
+        assert!(toml::from_str::<Config>("timezone = \"Europe/Paris\"").is_err());
+        assert!(toml::from_str::<Config>("auto_commit = true").unwrap().auto_commit);
+        assert_eq!(toml::from_str::<Config>("auto_prune_days = 90").unwrap().auto_prune_days, Some(90));
+        assert!(toml::from_str::<Config>("unique_titles = true").unwrap().unique_titles);
+        assert!(toml::from_str::<Config>("search_case_sensitive = true").unwrap().search_case_sensitive);
+        let c: Config = toml::from_str("compress_bodies = true\ncompress_threshold = 100").unwrap();
+        assert_eq!((c.compress_bodies, c.compress_threshold), (true, Some(100)));
+        assert_eq!(toml::from_str::<Config>("plain = false").unwrap().plain, Some(false));
+        assert!(toml::from_str::<Config>("search_index = true").unwrap().search_index);
+        let c: Config = toml::from_str("store = \"/data/notes.db\"\nformat = \"sqlite\"\nencrypt = true").unwrap();
+        assert_eq!((c.store, c.format.as_deref(), c.encrypt), (Some(PathBuf::from("/data/notes.db")), Some("sqlite"), true));
+    }
+}
@@ -0,0 +1,69 @@
+//! Confirmation before destructive commands.
+
+use std::fmt;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Exit status when a destructive command needs --yes/--force and there is nobody to ask.
+pub const EXIT_NOT_CONFIRMED: i32 = 3;
+
+/// Returned by [`confirm`] when stdout is not a terminal and the command wasn't forced.
+#[derive(Debug)]
+pub struct ConfirmationRequired {
+    pub prompt: String,
+}
+
+impl fmt::Display for ConfirmationRequired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" needs confirmation; pass --yes (or --force) when not running on a terminal", self.prompt)
+    }
+}
+
+impl std::error::Error for ConfirmationRequired {}
+
+/// Asks `prompt [y/N]` on the terminal; anything but y/yes is a no. `force` skips the question,
+/// and without a terminal the command fails with [`ConfirmationRequired`] instead of hanging.
+pub fn confirm(prompt: &str, force: bool) -> anyhow::Result<bool> {
+    if force {
+        return Ok(true);
+    }
+    confirm_with(prompt, io::stdout().is_terminal(), &mut io::stdin().lock(), &mut io::stdout())
+}
+
+/// [`confirm`] with the terminal check, input and output supplied by the caller.
+pub fn confirm_with(prompt: &str, interactive: bool, input: &mut impl BufRead, output: &mut impl Write) -> anyhow::Result<bool> {
+    if !interactive {
+        return Err(ConfirmationRequired { prompt: prompt.to_string() }.into());
+    }
+    write!(output, "{} [y/N] ", prompt)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ask(answer: &str) -> bool {
+        let mut out = Vec::new();
+        let yes = confirm_with("Continue?", true, &mut answer.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, b"Continue? [y/N] ");
+        yes
+    }
+
+    #[test]
+    fn only_y_or_yes_confirms() {
+        assert!(ask("y\n"));
+        assert!(ask(" YES \n"));
+        assert!(!ask("n\n"));
+        assert!(!ask("yep\n"));
+        assert!(!ask(""));
+    }
+
+    #[test]
+    fn without_a_terminal_it_refuses() {
+        let err = confirm_with("Continue?", false, &mut "y\n".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert!(err.is::<ConfirmationRequired>());
+    }
+}
@@ -0,0 +1,114 @@
+//! Encryption at rest: the serialized store is sealed with ChaCha20-Poly1305 using a key
+//! derived from a passphrase with Argon2id.
+//!
+//! File layout: `MAGIC (8) | salt (16) | nonce (12) | ciphertext + tag`.
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::env;
+
+pub const MAGIC: &[u8; 8] = b"RNOTES\x00\x01";
+pub const PASSPHRASE_ENV: &str = "RUSTIC_NOTES_PASSPHRASE";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + SALT_LEN + NONCE_LEN;
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Seals `plaintext` with a fresh salt and nonce, so two saves never produce the same bytes.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+
+    let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+pub fn decrypt(data: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < HEADER_LEN {
+        anyhow::bail!("not an encrypted rustic-notes store");
+    }
+    let salt = &data[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = Nonce::from_slice(&data[MAGIC.len() + SALT_LEN..HEADER_LEN]);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+    cipher
+        .decrypt(nonce, &data[HEADER_LEN..])
+        .map_err(|_| anyhow::anyhow!("wrong passphrase or corrupted store"))
+}
+
+/// Reads the passphrase from RUSTIC_NOTES_PASSPHRASE, or asks for it on the terminal.
+pub fn ask_passphrase(prompt: &str) -> anyhow::Result<String> {
+    if let Ok(p) = env::var(PASSPHRASE_ENV)
+        && !p.is_empty()
+    {
+        return Ok(p);
+    }
+    let p = rpassword::prompt_password(prompt)
+        .map_err(|e| anyhow::anyhow!("couldn't read the passphrase (set {}): {}", PASSPHRASE_ENV, e))?;
+    if p.is_empty() {
+        anyhow::bail!("empty passphrase");
+    }
+    Ok(p)
+}
+
+/// Passphrase for a store being encrypted: RUSTIC_NOTES_PASSPHRASE, or asked twice on the terminal.
+pub fn ask_new_passphrase() -> anyhow::Result<String> {
+    if let Ok(p) = env::var(PASSPHRASE_ENV)
+        && !p.is_empty()
+    {
+        return Ok(p);
+    }
+    let first = ask_passphrase("New passphrase: ")?;
+    let again = ask_passphrase("Repeat passphrase: ")?;
+    if first != again {
+        anyhow::bail!("passphrases don't match");
+    }
+    Ok(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_with_fixed_passphrase() {
+        let sealed = encrypt(b"{\"notes\": []}", "correct horse").unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(decrypt(&sealed, "correct horse").unwrap(), b"{\"notes\": []}");
+    }
+
+    #[test]
+    fn ciphertext_differs_between_saves() {
+        let a = encrypt(b"same content", "pw").unwrap();
+        let b = encrypt(b"same content", "pw").unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn wrong_passphrase_is_a_clean_error() {
+        let sealed = encrypt(b"secret", "right").unwrap();
+        let err = decrypt(&sealed, "wrong").unwrap_err();
+        assert_eq!(err.to_string(), "wrong passphrase or corrupted store");
+    }
+}
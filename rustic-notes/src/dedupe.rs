@@ -0,0 +1,86 @@
+//! Exact duplicates: notes whose title, body and tags are the same once normalized. `merge`
+//! skips incoming notes that the store already has, and `dedupe` removes the copies a store
+//! already holds.
+
+use crate::{normalize_tags, Note};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// sha256 (hex) of the trimmed title and body and of the tags (normalized, case-folded and
+/// sorted). Notes that only differ in id, dates, pinning or history hash the same.
+pub fn content_hash(n: &Note) -> String {
+    let mut tags: Vec<String> = normalize_tags(n.tags.clone()).iter().map(|t| t.to_lowercase()).collect();
+    tags.sort();
+    let key = serde_json::to_vec(&(n.title.trim(), n.body.trim(), tags)).expect("strings always serialize");
+    Sha256::digest(key).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Notes with the same content: the oldest is kept, the rest are duplicates of it.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Duplicates {
+    pub kept: u64,
+    pub removed: Vec<u64>,
+}
+
+/// Every group of notes sharing a [`content_hash`], in the store order of their first note.
+/// The note kept is the one created first (the earliest in the store on a tie).
+pub fn find(notes: &[Note]) -> Vec<Duplicates> {
+    let mut groups: Vec<Vec<&Note>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for n in notes {
+        let at = *index.entry(content_hash(n)).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[at].push(n);
+    }
+    groups
+        .into_iter()
+        .filter(|g| g.len() > 1)
+        .map(|g| {
+            let kept = g.iter().min_by_key(|n| n.created_at).expect("groups are never empty").id;
+            Duplicates { kept, removed: g.iter().map(|n| n.id).filter(|id| *id != kept).collect() }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn note(id: u64, title: &str, body: &str, tags: &[&str], day: u32) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            body: body.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, day, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn hash_ignores_spacing_tag_case_and_order() {
+        let a = note(1, "Bread", "buy it", &["Home", "errands"], 1);
+        assert_eq!(content_hash(&a), content_hash(&note(9, " Bread ", "buy it\n", &["errands", "home", "HOME"], 5)));
+        assert_ne!(content_hash(&a), content_hash(&note(1, "Bread", "buy it", &["errands"], 1)));
+        assert_ne!(content_hash(&a), content_hash(&note(1, "bread", "buy it", &["Home", "errands"], 1)));
+    }
+
+    #[test]
+    fn keeps_the_oldest_of_each_group() {
+        let notes = [
+            note(1, "a", "x", &[], 3),
+            note(2, "b", "", &[], 1),
+            note(3, "a", "x", &[], 2),
+            // Near duplicate: same text, other tags
+            note(4, "a", "x", &["work"], 1),
+            note(5, "a", "x ", &[], 2),
+            note(6, "b", "", &[], 1),
+        ];
+        assert_eq!(find(&notes), [Duplicates { kept: 3, removed: vec![1, 5] }, Duplicates { kept: 2, removed: vec![6] }]);
+        assert!(find(&notes[..2]).is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Consistency checks for stores that were edited by hand.
+
+use crate::links::{self, Target};
+use crate::dedupe::content_hash;
+use crate::{normalize_tags, normalize_title, Note, Storage};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A problem found by [`check`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    /// The notes changed since the store was last saved by rustic-notes
+    ChecksumMismatch,
+    DuplicateId { id: u64, titles: Vec<String> },
+    ZeroId { title: String },
+    IdenticalContent { ids: Vec<u64> },
+    /// Titles that are the same once normalized, see [`normalize_title`]
+    DuplicateTitle { title: String, ids: Vec<u64> },
+    /// Tags that are empty, padded with spaces or repeated with a different case
+    MalformedTags { id: u64, tags: Vec<String> },
+    FutureDate { id: u64, created_at: DateTime<Utc> },
+    /// A `[[...]]` reference to a note that doesn't exist
+    DanglingLink { id: u64, link: String },
+}
+
+impl Issue {
+    /// Whether `doctor --fix` repairs it (the rest needs a human decision).
+    pub fn fixable(&self) -> bool {
+        matches!(self, Issue::ChecksumMismatch | Issue::DuplicateId { .. } | Issue::ZeroId { .. } | Issue::MalformedTags { .. })
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::ChecksumMismatch => write!(f, "the store's checksum doesn't match its notes (edited outside rustic-notes or corrupted)"),
+            Issue::DuplicateId { id, titles } => write!(f, "id {} is used by {} notes: {:?}", id, titles.len(), titles),
+            Issue::ZeroId { title } => write!(f, "note {:?} has id 0", title),
+            Issue::IdenticalContent { ids } => {
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                write!(f, "notes {} have identical content", ids.join(", "))
+            }
+            Issue::DuplicateTitle { title, ids } => {
+                let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                write!(f, "notes {} share the title {:?}", ids.join(", "), title)
+            }
+            Issue::MalformedTags { id, tags } => write!(f, "note #{} has malformed tags {:?}", id, tags),
+            Issue::FutureDate { id, created_at } => {
+                write!(f, "note #{} was created in the future ({})", id, created_at.format("%Y-%m-%d %H:%M:%S UTC"))
+            }
+            Issue::DanglingLink { id, link } => write!(f, "note #{} links to [[{}]], which doesn't exist", id, link),
+        }
+    }
+}
+
+fn tags_are_clean(n: &Note) -> bool {
+    normalize_tags(n.tags.clone()) == n.tags
+}
+
+/// Lists every issue in `storage`, in note order.
+pub fn check(storage: &Storage, now: DateTime<Utc>) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if storage.checksum_mismatch() {
+        issues.push(Issue::ChecksumMismatch);
+    }
+
+    let mut by_id: HashMap<u64, Vec<String>> = HashMap::new();
+    for n in storage.notes.iter().filter(|n| n.id != 0) {
+        by_id.entry(n.id).or_default().push(n.title.clone());
+    }
+    let mut reported = HashSet::new();
+    for n in &storage.notes {
+        if n.id == 0 {
+            issues.push(Issue::ZeroId { title: n.title.clone() });
+        } else if by_id[&n.id].len() > 1 && reported.insert(n.id) {
+            issues.push(Issue::DuplicateId { id: n.id, titles: by_id[&n.id].clone() });
+        }
+    }
+
+    let mut by_content: HashMap<_, Vec<u64>> = HashMap::new();
+    let mut order = Vec::new();
+    for n in &storage.notes {
+        let ids = by_content.entry(content_hash(n)).or_insert_with(|| {
+            order.push(content_hash(n));
+            Vec::new()
+        });
+        ids.push(n.id);
+    }
+    for key in order {
+        let ids = &by_content[&key];
+        if ids.len() > 1 {
+            issues.push(Issue::IdenticalContent { ids: ids.clone() });
+        }
+    }
+
+    let mut by_title: HashMap<String, Vec<u64>> = HashMap::new();
+    for n in &storage.notes {
+        by_title.entry(normalize_title(&n.title)).or_default().push(n.id);
+    }
+    let mut reported = HashSet::new();
+    for n in &storage.notes {
+        let key = normalize_title(&n.title);
+        if by_title[&key].len() > 1 && reported.insert(key.clone()) {
+            issues.push(Issue::DuplicateTitle { title: n.title.clone(), ids: by_title[&key].clone() });
+        }
+    }
+
+    for n in &storage.notes {
+        if !tags_are_clean(n) {
+            issues.push(Issue::MalformedTags { id: n.id, tags: n.tags.clone() });
+        }
+        if n.created_at > now {
+            issues.push(Issue::FutureDate { id: n.id, created_at: n.created_at });
+        }
+        for link in links::links(n, storage).into_iter().filter(|l| l.target == Target::Dangling) {
+            issues.push(Issue::DanglingLink { id: n.id, link: link.text });
+        }
+    }
+    issues
+}
+
+/// Repairs the fixable issues: duplicate ids and id 0 get fresh ids (the first holder of an id
+/// keeps it) and tags go through `normalize_tags`. A checksum that doesn't match is cleared, so
+/// the next save writes the current one. Returns how many notes were changed.
+pub fn fix(storage: &mut Storage) -> usize {
+    if storage.checksum_mismatch() {
+        storage.checksum = None;
+    }
+    let mut changed = HashSet::new();
+    let mut next = storage.next_id();
+    let mut seen = HashSet::new();
+    for (i, n) in storage.notes.iter_mut().enumerate() {
+        if n.id == 0 || !seen.insert(n.id) {
+            n.id = next;
+            next += 1;
+            changed.insert(i);
+        }
+        if !tags_are_clean(n) {
+            n.tags = normalize_tags(std::mem::take(&mut n.tags));
+            changed.insert(i);
+        }
+    }
+    changed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn note(id: u64, title: &str, tags: &[&str]) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, 1, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn clean_store_has_no_issues() {
+        let s = Storage { notes: vec![note(1, "a", &["x"]), note(2, "b", &[])], ..Default::default() };
+        assert!(check(&s, now()).is_empty());
+    }
+
+    #[test]
+    fn finds_every_kind_of_issue() {
+        let mut future = note(4, "d", &[]);
+        future.created_at = Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap();
+        let s = Storage {
+            notes: vec![note(1, "a", &[]), note(1, "b", &[]), note(0, "c", &["Work", "work", " "]), future, note(5, "a", &[])],
+            ..Default::default()
+        };
+        let issues = check(&s, now());
+        assert_eq!(
+            issues,
+            [
+                Issue::DuplicateId { id: 1, titles: vec!["a".into(), "b".into()] },
+                Issue::ZeroId { title: "c".into() },
+                Issue::IdenticalContent { ids: vec![1, 5] },
+                Issue::DuplicateTitle { title: "a".into(), ids: vec![1, 5] },
+                Issue::MalformedTags { id: 0, tags: vec!["Work".into(), "work".into(), " ".into()] },
+                Issue::FutureDate { id: 4, created_at: Utc.with_ymd_and_hms(2099, 1, 1, 0, 0, 0).unwrap() },
+            ]
+        );
+    }
+
+    #[test]
+    fn titles_differing_only_in_case_and_spacing_are_duplicates() {
+        let s = Storage {
+            notes: vec![note(1, "Weekly  sync", &[]), note(2, "Budget", &["x"]), note(3, " weekly SYNC", &["y"])],
+            ..Default::default()
+        };
+        assert_eq!(check(&s, now()), [Issue::DuplicateTitle { title: "Weekly  sync".into(), ids: vec![1, 3] }]);
+        assert_eq!(check(&s, now())[0].to_string(), "notes #1, #3 share the title \"Weekly  sync\"");
+    }
+
+    #[test]
+    fn reports_dangling_links() {
+        let mut linking = note(1, "a", &[]);
+        linking.body = "see [[2]], [[b]] and [[c]]".into();
+        let s = Storage { notes: vec![linking, note(2, "b", &[])], ..Default::default() };
+        assert_eq!(check(&s, now()), [Issue::DanglingLink { id: 1, link: "c".into() }]);
+    }
+
+    #[test]
+    fn fix_reassigns_ids_and_normalizes_tags() {
+        let mut s = Storage { notes: vec![note(2, "a", &[]), note(2, "b", &[" x ", "X"]), note(0, "c", &[])], ..Default::default() };
+        assert_eq!(fix(&mut s), 2);
+        let ids: Vec<u64> = s.notes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, [2, 3, 4]);
+        assert_eq!(s.notes[1].tags, ["x"]);
+        assert!(check(&s, now()).is_empty());
+    }
+
+    #[test]
+    fn fix_clears_a_checksum_that_does_not_match() {
+        let mut s = Storage { notes: vec![note(1, "a", &[])], ..Default::default() };
+        s.checksum = Some(crate::journal::hash(&s));
+        assert!(check(&s, now()).is_empty());
+        s.notes[0].title = "b".into();
+        assert_eq!(check(&s, now()), [Issue::ChecksumMismatch]);
+        assert_eq!(fix(&mut s), 0);
+        assert_eq!(s.checksum, None);
+        assert!(check(&s, now()).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Due dates and `due --notify`: a note can be due at a time, and `due --notify` (meant to run
+//! from cron) sends a reminder for each note due within a window, overdue ones included. A
+//! note is reminded of at most once a day, remembered in its `notified_at`.
+
+use crate::style::DateFormat;
+use crate::{Note, Storage};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use std::process::{Command, Stdio};
+
+/// How long after a reminder the same note stays quiet.
+pub const QUIET: TimeDelta = TimeDelta::hours(24);
+
+/// A due date from the command line: RFC 3339 (`2025-10-20T09:00:00Z`), or a day
+/// (`2025-10-20`), meaning its start in UTC.
+pub fn parse(s: &str) -> Result<DateTime<Utc>, String> {
+    let s = s.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|day| day.and_time(Default::default()).and_utc())
+        .map_err(|_| format!("invalid due date \"{}\" (expected e.g. 2025-10-20 or 2025-10-20T09:00:00Z)", s))
+}
+
+/// The notes due before `now + within`, soonest first.
+pub fn upcoming(notes: &[Note], now: DateTime<Utc>, within: TimeDelta) -> Vec<&Note> {
+    let mut due: Vec<&Note> = notes.iter().filter(|n| n.due.is_some_and(|at| at <= now + within)).collect();
+    due.sort_by_key(|n| (n.due, n.id));
+    due
+}
+
+/// Whether `n` was due before `now`.
+pub fn overdue(n: &Note, now: DateTime<Utc>) -> bool {
+    n.due.is_some_and(|at| at < now)
+}
+
+/// Whether a reminder for `n` would be a duplicate of one sent less than [`QUIET`] ago.
+pub fn recently_notified(n: &Note, now: DateTime<Utc>) -> bool {
+    n.notified_at.is_some_and(|at| now - at < QUIET)
+}
+
+/// Where reminders go.
+pub trait Notifier {
+    fn notify(&mut self, note: &Note) -> anyhow::Result<()>;
+}
+
+/// What a reminder says about `n`, its due date shown like every other timestamp.
+pub fn message(n: &Note, dates: &DateFormat) -> String {
+    match n.due {
+        Some(at) => format!("#{} {} (due {})", n.id, n.title, dates.format(&at)),
+        None => format!("#{} {}", n.id, n.title),
+    }
+}
+
+/// Prints reminders to stderr, so they never end up in `--output json` or `--porcelain` output.
+pub struct Print {
+    pub dates: DateFormat,
+}
+
+impl Notifier for Print {
+    fn notify(&mut self, note: &Note) -> anyhow::Result<()> {
+        eprintln!("Reminder: {}", message(note, &self.dates));
+        Ok(())
+    }
+}
+
+/// System notifications through `notify-send` on Linux and `osascript` on macOS; elsewhere,
+/// or when the command is missing or fails, reminders are printed instead.
+pub struct Desktop {
+    pub dates: DateFormat,
+}
+
+impl Desktop {
+    fn print(&self, note: &Note) -> anyhow::Result<()> {
+        Print { dates: self.dates.clone() }.notify(note)
+    }
+}
+
+impl Notifier for Desktop {
+    fn notify(&mut self, note: &Note) -> anyhow::Result<()> {
+        let body = message(note, &self.dates);
+        let mut command = if cfg!(target_os = "macos") {
+            let script = format!("display notification {:?} with title \"rustic-notes\"", body);
+            let mut c = Command::new("osascript");
+            c.args(["-e", &script]);
+            c
+        } else if cfg!(target_os = "linux") {
+            let mut c = Command::new("notify-send");
+            c.args(["rustic-notes", &body]);
+            c
+        } else {
+            return self.print(note);
+        };
+        match command.stdout(Stdio::null()).stderr(Stdio::null()).status() {
+            Ok(status) if status.success() => Ok(()),
+            _ => self.print(note),
+        }
+    }
+}
+
+/// Sends a reminder for each note due within `within` that wasn't reminded of in the last
+/// [`QUIET`], and records when in its `notified_at`. Returns the ids reminded of, soonest
+/// due first.
+pub fn notify(storage: &mut Storage, now: DateTime<Utc>, within: TimeDelta, notifier: &mut dyn Notifier) -> anyhow::Result<Vec<u64>> {
+    let ids: Vec<u64> =
+        upcoming(&storage.notes, now, within).into_iter().filter(|n| !recently_notified(n, now)).map(|n| n.id).collect();
+    for &id in &ids {
+        let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else { continue };
+        notifier.notify(n)?;
+        n.notified_at = Some(now);
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// Keeps the ids it was asked to remind of.
+    #[derive(Default)]
+    struct Recording {
+        sent: Vec<u64>,
+    }
+
+    impl Notifier for Recording {
+        fn notify(&mut self, note: &Note) -> anyhow::Result<()> {
+            self.sent.push(note.id);
+            Ok(())
+        }
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 10, day, hour, 0, 0).unwrap()
+    }
+
+    fn note(id: u64, due: Option<DateTime<Utc>>) -> Note {
+        Note { id, title: format!("note {}", id), due, ..Default::default() }
+    }
+
+    #[test]
+    fn due_dates_parse_as_days_or_times() {
+        assert_eq!(parse("2025-10-20"), Ok(at(20, 0)));
+        assert_eq!(parse("2025-10-20T09:00:00Z"), Ok(at(20, 9)));
+        assert_eq!(parse("2025-10-20T11:00:00+02:00"), Ok(at(20, 9)));
+        assert!(parse("next friday").unwrap_err().contains("invalid due date \"next friday\""));
+    }
+
+    #[test]
+    fn upcoming_includes_overdue_notes_soonest_first() {
+        let notes = vec![note(1, Some(at(16, 12))), note(2, None), note(3, Some(at(14, 8))), note(4, Some(at(20, 0)))];
+        let ids: Vec<u64> = upcoming(&notes, at(15, 12), TimeDelta::days(1)).iter().map(|n| n.id).collect();
+        assert_eq!(ids, [3, 1]);
+    }
+
+    #[test]
+    fn overdue_notes_and_messages() {
+        let n = note(1, Some(at(15, 9)));
+        assert!(overdue(&n, at(15, 10)));
+        assert!(!overdue(&n, at(15, 9)));
+        assert!(!overdue(&note(2, None), at(15, 10)));
+        let dates = DateFormat::new(false, Some("%d/%m %H:%M".to_string())).unwrap();
+        assert_eq!(message(&n, &dates), "#1 note 1 (due 15/10 09:00)");
+        assert_eq!(message(&n, &DateFormat::default()), "#1 note 1 (due 2025-10-15 09:00:00 UTC)");
+    }
+
+    #[test]
+    fn a_note_is_reminded_of_once_a_day() {
+        let mut storage = Storage { notes: vec![note(1, Some(at(15, 18))), note(2, Some(at(16, 9)))], ..Default::default() };
+        let mut notifier = Recording::default();
+
+        assert_eq!(notify(&mut storage, at(15, 12), TimeDelta::days(1), &mut notifier).unwrap(), [1, 2]);
+        assert_eq!(storage.notes[0].notified_at, Some(at(15, 12)));
+        // Run again from cron an hour later: nothing new
+        assert!(notify(&mut storage, at(15, 13), TimeDelta::days(1), &mut notifier).unwrap().is_empty());
+        assert_eq!(notifier.sent, [1, 2]);
+        // A day later, both are still due (overdue by then)
+        assert_eq!(notify(&mut storage, at(16, 12), TimeDelta::days(1), &mut notifier).unwrap(), [1, 2]);
+        assert_eq!(notifier.sent, [1, 2, 1, 2]);
+    }
+}
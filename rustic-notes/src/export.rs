@@ -0,0 +1,118 @@
+//! `export note`: a single note written on its own, as markdown with a YAML front matter or as
+//! the whole `Note` in the same JSON/TOML/YAML form the stores use.
+
+use crate::store::ensure_parent;
+use crate::{serialize_document, Format, Note};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum NoteFormat {
+    /// Front matter (id, title, tags, dates) followed by the body
+    Markdown,
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl NoteFormat {
+    /// Format implied by the output file's extension (`.md`, `.json`, `.yaml`/`.yml`, `.toml`)
+    pub fn from_extension(path: &Path) -> Option<NoteFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "md" | "markdown" => Some(NoteFormat::Markdown),
+            "json" => Some(NoteFormat::Json),
+            "yaml" | "yml" => Some(NoteFormat::Yaml),
+            "toml" => Some(NoteFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FrontMatter<'a> {
+    id: u64,
+    title: &'a str,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated_at: Option<DateTime<Utc>>,
+}
+
+/// The exported text of `n`.
+pub fn render(n: &Note, format: NoteFormat) -> anyhow::Result<String> {
+    let mut text = match format {
+        NoteFormat::Markdown => {
+            let front = FrontMatter { id: n.id, title: &n.title, tags: &n.tags, created_at: n.created_at, updated_at: n.updated_at };
+            format!("---\n{}---\n\n{}", serialize_document(&front, Format::Yaml)?, n.body)
+        }
+        NoteFormat::Json => serialize_document(n, Format::Json)?,
+        NoteFormat::Yaml => serialize_document(n, Format::Yaml)?,
+        NoteFormat::Toml => serialize_document(n, Format::Toml)?,
+    };
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// Writes `text` to `out` (`-` is stdout), creating the missing directories. An existing file
+/// is only replaced with `force`.
+pub fn write(out: &Path, text: &str, force: bool) -> anyhow::Result<()> {
+    if out == Path::new("-") {
+        std::io::stdout().write_all(text.as_bytes())?;
+        return Ok(());
+    }
+    if out.exists() && !force {
+        anyhow::bail!("{} already exists (use --force to overwrite it)", out.display());
+    }
+    ensure_parent(out)?;
+    fs::write(out, text)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn note() -> Note {
+        Note {
+            id: 7,
+            title: "Kickoff: \"Q3\"".to_string(),
+            body: "# Agenda\n\n- [ ] budget".into(),
+            tags: vec!["work".to_string()],
+            created_at: Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap(),
+            pinned: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn markdown_has_front_matter_then_the_body() {
+        let text = render(&note(), NoteFormat::Markdown).unwrap();
+        assert_eq!(
+            text,
+            "---\nid: 7\ntitle: 'Kickoff: \"Q3\"'\ntags:\n- work\ncreated_at: 2025-03-01T09:00:00Z\n---\n\n# Agenda\n\n- [ ] budget\n"
+        );
+    }
+
+    #[test]
+    fn serde_formats_round_trip() {
+        for format in [NoteFormat::Json, NoteFormat::Yaml, NoteFormat::Toml] {
+            let text = render(&note(), format).unwrap();
+            let back: Note = match format {
+                NoteFormat::Json => serde_json::from_str(&text).unwrap(),
+                NoteFormat::Yaml => serde_yaml::from_str(&text).unwrap(),
+                _ => toml::from_str(&text).unwrap(),
+            };
+            assert_eq!(back, note());
+        }
+        assert_eq!(NoteFormat::from_extension(Path::new("a/b.YML")), Some(NoteFormat::Yaml));
+        assert_eq!(NoteFormat::from_extension(Path::new("notes.txt")), None);
+    }
+}
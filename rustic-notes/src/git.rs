@@ -0,0 +1,64 @@
+//! Keeping a store that lives in a git repository committed (`auto_commit`) and in sync (`sync`).
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Directory git runs in: the store's parent directory.
+fn work_dir(store: &Path) -> PathBuf {
+    match store.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Runs git in `dir`; a non-zero exit is an error carrying git's stderr.
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("couldn't run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("`git {}` failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Whether the store's directory is inside a git work tree (false when git isn't installed).
+pub fn is_repo(store: &Path) -> bool {
+    let dir = work_dir(store);
+    dir.is_dir() && git(&dir, &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|out| out.trim() == "true")
+}
+
+/// Commits the store file alone as `notes: <summary>`, leaving anything else staged untouched.
+/// Returns false when there was nothing to commit or the store isn't in a repository.
+pub fn commit_store(store: &Path, summary: &str) -> anyhow::Result<bool> {
+    if !is_repo(store) || !store.exists() {
+        return Ok(false);
+    }
+    let dir = work_dir(store);
+    let file = store.file_name().and_then(|f| f.to_str()).ok_or_else(|| anyhow::anyhow!("invalid store path"))?;
+    git(&dir, &["add", "--", file])?;
+    // `diff --cached --quiet` exits with 1 when the file has staged changes
+    if git(&dir, &["diff", "--cached", "--quiet", "--", file]).is_ok() {
+        return Ok(false);
+    }
+    git(&dir, &["commit", "--quiet", "-m", &format!("notes: {}", summary), "--only", "--", file])?;
+    Ok(true)
+}
+
+fn require_repo(store: &Path) -> anyhow::Result<PathBuf> {
+    if !is_repo(store) {
+        anyhow::bail!("{} is not inside a git repository", work_dir(store).display());
+    }
+    Ok(work_dir(store))
+}
+
+pub fn pull(store: &Path) -> anyhow::Result<()> {
+    git(&require_repo(store)?, &["pull", "--quiet"]).map(|_| ())
+}
+
+pub fn push(store: &Path) -> anyhow::Result<()> {
+    git(&require_repo(store)?, &["push", "--quiet"]).map(|_| ())
+}
@@ -0,0 +1,100 @@
+//! `grep`: line-by-line regex search inside note bodies, with context lines.
+
+use crate::Note;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+/// Builds the pattern like mini-grep does: `-w` wraps it in word boundaries, `-i` ignores case.
+pub fn pattern(pattern: &str, ignore_case: bool, word: bool) -> anyhow::Result<Regex> {
+    let wrapped = if word { format!(r"\b(?:{})\b", pattern) } else { pattern.to_string() };
+    RegexBuilder::new(&wrapped)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| anyhow::anyhow!("invalid regex \"{}\": {}", pattern, e))
+}
+
+/// A body line that is printed: a hit, or context around one.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Line {
+    /// 1-based
+    pub number: usize,
+    pub text: String,
+    /// Byte ranges of the matches; empty for context lines
+    pub matches: Vec<(usize, usize)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NoteHits<'a> {
+    pub id: u64,
+    pub title: &'a str,
+    pub lines: Vec<Line>,
+}
+
+/// Matching lines of `body` with up to `context` lines around each; overlapping windows are merged,
+/// so every line appears once and in order.
+pub fn search_body(re: &Regex, body: &str, context: usize) -> Vec<Line> {
+    let lines: Vec<&str> = body.lines().collect();
+    let hits: Vec<usize> = (0..lines.len()).filter(|&i| re.is_match(lines[i])).collect();
+    let mut shown = Vec::new();
+    let mut next = 0;
+    for &hit in &hits {
+        let start = hit.saturating_sub(context).max(next);
+        let end = (hit + context).min(lines.len() - 1);
+        for (i, text) in lines.iter().enumerate().take(end + 1).skip(start) {
+            let matches = re.find_iter(text).map(|m| (m.start(), m.end())).collect();
+            shown.push(Line { number: i + 1, text: text.to_string(), matches });
+        }
+        next = next.max(end + 1);
+    }
+    shown
+}
+
+/// Runs [`search_body`] over every note, keeping the notes with at least one hit.
+pub fn search<'a>(re: &Regex, notes: impl IntoIterator<Item = &'a Note>, context: usize) -> Vec<NoteHits<'a>> {
+    notes
+        .into_iter()
+        .filter_map(|n| {
+            let lines = search_body(re, &n.body, context);
+            (!lines.is_empty()).then_some(NoteHits { id: n.id, title: &n.title, lines })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "alpha\nbeta TODO\ngamma\ndelta\nepsilon todo\nzeta\neta\ntheta\niota TODO";
+
+    fn numbers(lines: &[Line]) -> Vec<usize> {
+        lines.iter().map(|l| l.number).collect()
+    }
+
+    #[test]
+    fn finds_every_matching_line() {
+        let re = pattern("TODO", false, false).unwrap();
+        let lines = search_body(&re, BODY, 0);
+        assert_eq!(numbers(&lines), [2, 9]);
+        assert_eq!(lines[0].matches, [(5, 9)]);
+    }
+
+    #[test]
+    fn context_windows_merge_when_they_overlap() {
+        let re = pattern("todo", true, false).unwrap();
+        let lines = search_body(&re, BODY, 2);
+        // 2 → 1..=4, 5 → 3..=7 (overlaps), 9 → 7..=9 (touches)
+        assert_eq!(numbers(&lines), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let hits: Vec<usize> = lines.iter().filter(|l| !l.matches.is_empty()).map(|l| l.number).collect();
+        assert_eq!(hits, [2, 5, 9]);
+
+        let lines = search_body(&re, BODY, 1);
+        assert_eq!(numbers(&lines), [1, 2, 3, 4, 5, 6, 8, 9]);
+    }
+
+    #[test]
+    fn word_flag_needs_whole_words() {
+        let re = pattern("eta", false, true).unwrap();
+        assert_eq!(numbers(&search_body(&re, BODY, 0)), [7]);
+        assert!(pattern("(", false, false).unwrap_err().to_string().contains("invalid regex"));
+    }
+}
@@ -0,0 +1,68 @@
+//! `list --group-by tag`: the listed notes under one heading per tag.
+
+use crate::Note;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+pub struct Group<'a> {
+    /// `None` for the notes without tags
+    pub tag: Option<String>,
+    pub notes: Vec<&'a Note>,
+}
+
+/// Groups `notes` by tag, keeping their order inside each group. Tags are compared without
+/// case and sorted alphabetically, spelled as in the first note that has them; a note with
+/// several tags is in each of their groups, or only in its first tag's with `first_only`.
+/// Untagged notes come last.
+pub fn by_tag<'a>(notes: &[&'a Note], first_only: bool) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group<'a>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut untagged = Vec::new();
+    for &n in notes {
+        let tags = if first_only { &n.tags[..n.tags.len().min(1)] } else { &n.tags[..] };
+        if tags.is_empty() {
+            untagged.push(n);
+        }
+        for tag in tags {
+            let at = *index.entry(tag.to_lowercase()).or_insert_with(|| {
+                groups.push(Group { tag: Some(tag.clone()), notes: vec![] });
+                groups.len() - 1
+            });
+            // Tags differing only in case are one tag, so the note is listed once
+            if groups[at].notes.last().is_none_or(|last| last.id != n.id) {
+                groups[at].notes.push(n);
+            }
+        }
+    }
+    groups.sort_by_key(|g| g.tag.as_ref().map(|t| t.to_lowercase()));
+    if !untagged.is_empty() {
+        groups.push(Group { tag: None, notes: untagged });
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: u64, tags: &[&str]) -> Note {
+        Note { tags: tags.iter().map(|t| t.to_string()).collect(), ..Note::new(id, format!("note {}", id)) }
+    }
+
+    fn shown<'a>(groups: &'a [Group]) -> Vec<(Option<&'a str>, Vec<u64>)> {
+        groups.iter().map(|g| (g.tag.as_deref(), g.notes.iter().map(|n| n.id).collect())).collect()
+    }
+
+    #[test]
+    fn notes_appear_under_each_tag_in_list_order() {
+        let notes = [note(3, &["work", "Ideas"]), note(1, &[]), note(2, &["ideas"]), note(4, &["Work", "work"]), note(5, &["a"])];
+        let listed: Vec<&Note> = notes.iter().collect();
+        assert_eq!(
+            shown(&by_tag(&listed, false)),
+            [(Some("a"), vec![5]), (Some("Ideas"), vec![3, 2]), (Some("work"), vec![3, 4]), (None, vec![1])]
+        );
+        assert_eq!(shown(&by_tag(&listed, true)), [(Some("a"), vec![5]), (Some("ideas"), vec![2]), (Some("work"), vec![3, 4]), (None, vec![1])]);
+        assert!(by_tag(&[], false).is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! Per-note revisions: every edit keeps the previous title/body/tags/meta in `Note::history`.
+
+use crate::Note;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How many revisions each note keeps unless --history-depth says otherwise.
+pub const DEFAULT_DEPTH: usize = 5;
+
+/// A past state of a note, saved when it was replaced by an edit.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Revision {
+    pub at: DateTime<Utc>,
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, String>,
+}
+
+impl Revision {
+    pub fn of(n: &Note, at: DateTime<Utc>) -> Self {
+        Revision { at, title: n.title.clone(), body: n.body.to_string(), tags: n.tags.clone(), meta: n.meta.clone() }
+    }
+}
+
+/// Whether title, body, tags or meta fields differ (history, dates, pinning are ignored).
+pub fn content_changed(a: &Note, b: &Note) -> bool {
+    a.title != b.title || a.body != b.body || a.tags != b.tags || a.meta != b.meta
+}
+
+/// Appends `revision` to the note, keeping only the newest `depth` (0 keeps none), and marks
+/// the note as updated when the revision was replaced.
+pub fn push(n: &mut Note, revision: Revision, depth: usize) {
+    n.updated_at = Some(revision.at);
+    n.history.push(revision);
+    let excess = n.history.len().saturating_sub(depth);
+    n.history.drain(..excess);
+}
+
+/// Puts revision `which` (1 = oldest) back, saving the current state as a new revision first.
+pub fn restore(n: &mut Note, which: usize, depth: usize, at: DateTime<Utc>) -> anyhow::Result<()> {
+    let Some(rev) = which.checked_sub(1).and_then(|i| n.history.get(i)).cloned() else {
+        anyhow::bail!("note #{} has no revision {} (it has {})", n.id, which, n.history.len());
+    };
+    let current = Revision::of(n, at);
+    push(n, current, depth);
+    n.title = rev.title;
+    n.body = rev.body.into();
+    n.tags = rev.tags;
+    n.meta = rev.meta;
+    Ok(())
+}
+
+/// Words added and removed going from `old` to `new`, ignoring order and whitespace.
+pub fn word_diff(old: &str, new: &str) -> (usize, usize) {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for w in old.split_whitespace() {
+        *counts.entry(w).or_default() -= 1;
+    }
+    for w in new.split_whitespace() {
+        *counts.entry(w).or_default() += 1;
+    }
+    let added = counts.values().filter(|c| **c > 0).sum::<i64>() as usize;
+    let removed = counts.values().filter(|c| **c < 0).map(|c| -c).sum::<i64>() as usize;
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn note(body: &str) -> Note {
+        Note {
+            id: 1,
+            title: "t".to_string(),
+            body: body.into(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn push_keeps_the_newest_revisions() {
+        let mut n = note("v0");
+        for i in 1..=4 {
+            let rev = Revision::of(&n, Utc::now());
+            push(&mut n, rev, 3);
+            n.body = format!("v{}", i).into();
+        }
+        let bodies: Vec<&str> = n.history.iter().map(|r| r.body.as_str()).collect();
+        assert_eq!(bodies, ["v1", "v2", "v3"]);
+    }
+
+    #[test]
+    fn restore_saves_the_current_state() {
+        let mut n = note("second");
+        n.history.push(Revision::of(&note("first"), Utc::now()));
+        restore(&mut n, 1, 5, Utc::now()).unwrap();
+        assert_eq!(n.body, "first");
+        assert_eq!(n.history.last().unwrap().body, "second");
+        assert!(restore(&mut n, 9, 5, Utc::now()).is_err());
+    }
+
+    #[test]
+    fn word_diff_counts_changes() {
+        assert_eq!(word_diff("buy milk and bread", "buy oat milk and eggs"), (2, 1));
+        assert_eq!(word_diff("", ""), (0, 0));
+    }
+}
@@ -0,0 +1,184 @@
+//! `export html`: a static site with an index, one page per note and one page per tag.
+
+use crate::{links, Note, Storage};
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const CSS: &str = "body{font-family:system-ui,sans-serif;max-width:46rem;margin:2rem auto;padding:0 1rem;line-height:1.5;color:#222}\
+a{color:#0b62c4;text-decoration:none}a:hover{text-decoration:underline}\
+.tag{display:inline-block;background:#eef3fa;border-radius:.6rem;padding:0 .5rem;margin-right:.3rem;font-size:.85em}\
+time{color:#777;font-size:.85em}pre{background:#f5f5f5;padding:.6rem;overflow-x:auto}\
+code{background:#f5f5f5}nav{margin-bottom:1.5rem}ul.notes li{margin:.3rem 0}";
+
+/// What [`export`] wrote.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Report {
+    pub notes: usize,
+    pub tags: usize,
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Markdown to HTML; raw HTML in the body is shown as text and `javascript:` links are dropped.
+pub fn markdown(body: &str) -> String {
+    let events = Parser::new_ext(body, Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES).map(|e| match e {
+        Event::Html(h) | Event::InlineHtml(h) => Event::Text(h),
+        Event::Start(Tag::Link { link_type, dest_url, title, id })
+            if dest_url.trim_start().to_lowercase().starts_with("javascript:") =>
+        {
+            Event::Start(Tag::Link { link_type, dest_url: CowStr::Borrowed("#"), title, id })
+        }
+        e => e,
+    });
+    let mut out = String::new();
+    pulldown_cmark::html::push_html(&mut out, events);
+    out
+}
+
+/// File name of a tag page: lowercase letters and digits, other runs of characters become `-`.
+fn slug(tag: &str) -> String {
+    let mut out = String::new();
+    for c in tag.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            out.push(c);
+        } else if !out.ends_with('-') {
+            out.push('-');
+        }
+    }
+    let out = out.trim_matches('-');
+    if out.is_empty() { "tag".to_string() } else { out.to_string() }
+}
+
+fn page(title: &str, root: &str, content: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<nav><a href=\"{}index.html\">All notes</a></nav>\n{}</body>\n</html>\n",
+        escape(title),
+        CSS,
+        root,
+        content
+    )
+}
+
+/// Tags grouped case-insensitively, keyed by slug: (first spelling seen, notes sorted by title).
+fn group_by_tag<'a>(notes: &[&'a Note]) -> BTreeMap<String, (String, Vec<&'a Note>)> {
+    let mut by_lowercase: BTreeMap<String, (String, Vec<&Note>)> = BTreeMap::new();
+    for n in notes {
+        for t in &n.tags {
+            by_lowercase.entry(t.to_lowercase()).or_insert_with(|| (t.clone(), Vec::new())).1.push(n);
+        }
+    }
+    // Distinct tags can share a slug ("a b" and "a-b"); later ones get a number
+    let mut by_slug = BTreeMap::new();
+    for (_, group) in by_lowercase {
+        let base = slug(&group.0);
+        let mut key = base.clone();
+        let mut i = 1;
+        while by_slug.contains_key(&key) {
+            key = format!("{}-{}", base, i);
+            i += 1;
+        }
+        by_slug.insert(key, group);
+    }
+    by_slug
+}
+
+fn note_list(notes: &[&Note], root: &str) -> String {
+    let mut out = String::from("<ul class=\"notes\">\n");
+    for n in notes {
+        let _ = writeln!(
+            out,
+            "<li><a href=\"{}notes/{}.html\">{}</a> <time>{}</time></li>",
+            root,
+            n.id,
+            escape(&n.title),
+            n.created_at.format("%Y-%m-%d")
+        );
+    }
+    out.push_str("</ul>\n");
+    out
+}
+
+/// Writes `index.html`, `notes/<id>.html` and `tags/<slug>.html` under `dir` for `notes`
+/// (`storage` is only used to spell out [[links]]). Existing pages are overwritten.
+pub fn export(storage: &Storage, notes: &[&Note], dir: &Path) -> anyhow::Result<Report> {
+    let mut sorted = notes.to_vec();
+    sorted.sort_by_key(|n| (n.title.to_lowercase(), n.id));
+    let tags = group_by_tag(&sorted);
+    let slug_of = |tag: &str| {
+        tags.iter().find(|(_, (name, _))| name.to_lowercase() == tag.to_lowercase()).map(|(s, _)| s.clone())
+    };
+
+    fs::create_dir_all(dir.join("notes"))?;
+    fs::create_dir_all(dir.join("tags"))?;
+
+    for n in &sorted {
+        let mut content = format!("<h1>{}</h1>\n<p><time>{}</time> ", escape(&n.title), n.created_at.format("%Y-%m-%d %H:%M UTC"));
+        for t in &n.tags {
+            let slug = slug_of(t).unwrap_or_else(|| slug(t));
+            let _ = write!(content, "<a class=\"tag\" href=\"../tags/{}.html\">{}</a>", slug, escape(t));
+        }
+        content.push_str("</p>\n");
+        content.push_str(&markdown(&links::render(&n.body, storage)));
+        fs::write(dir.join("notes").join(format!("{}.html", n.id)), page(&n.title, "../", &content))?;
+    }
+
+    for (slug, (name, tagged)) in &tags {
+        let content = format!("<h1>Tag: {}</h1>\n{}", escape(name), note_list(tagged, "../"));
+        fs::write(dir.join("tags").join(format!("{}.html", slug)), page(&format!("Tag: {}", name), "../", &content))?;
+    }
+
+    let mut index = format!("<h1>Notes</h1>\n{}", note_list(&sorted, ""));
+    if !tags.is_empty() {
+        index.push_str("<h2>By tag</h2>\n");
+        for (slug, (name, tagged)) in &tags {
+            let _ = writeln!(index, "<h3><a class=\"tag\" href=\"tags/{}.html\">{}</a></h3>", slug, escape(name));
+            index.push_str(&note_list(tagged, ""));
+        }
+    }
+    fs::write(dir.join("index.html"), page("Notes", "", &index))?;
+
+    Ok(Report { notes: sorted.len(), tags: tags.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(escape("<b>\"Tom\" & 'Jerry'</b>"), "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn markdown_renders_but_raw_html_stays_text() {
+        let html = markdown("# Plan\n\n*soon* <script>alert(1)</script>\n\n[x](javascript:alert(1))");
+        assert!(html.contains("<h1>Plan</h1>"));
+        assert!(html.contains("<em>soon</em>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("<a href=\"#\">x</a>"));
+    }
+
+    #[test]
+    fn slugs_are_file_name_safe() {
+        assert_eq!(slug("Work/Home Office"), "work-home-office");
+        assert_eq!(slug("会議"), "会議");
+        assert_eq!(slug("../"), "tag");
+    }
+}
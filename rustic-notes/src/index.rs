@@ -0,0 +1,214 @@
+//! Optional full-text index for `search` (`search_index = true` in the config file): every word
+//! of the titles, bodies and tags, lowercased, with the ids of the notes that contain it, kept
+//! in `<store>.idx`.
+//!
+//! The index only narrows down which notes can match a case-insensitive substring query; those
+//! are still checked with the usual matcher, so the results are exactly the linear scan's. It
+//! remembers the [`journal::hash`] of the notes it was built from and is rebuilt whenever the
+//! store no longer has it.
+
+use crate::{journal, Note, Storage};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn index_path(store: &Path) -> PathBuf {
+    let mut name = store.as_os_str().to_owned();
+    name.push(".idx");
+    PathBuf::from(name)
+}
+
+/// The lowercased runs of letters and digits in `text`.
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase().split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_string).collect()
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    /// [`journal::hash`] of the notes the index describes
+    pub checksum: String,
+    words: BTreeMap<String, BTreeSet<u64>>,
+}
+
+impl Index {
+    pub fn build(storage: &Storage) -> Index {
+        let mut index = Index { checksum: journal::hash(storage), ..Default::default() };
+        storage.notes.iter().for_each(|n| index.insert(n));
+        index
+    }
+
+    pub fn load(store: &Path) -> anyhow::Result<Option<Index>> {
+        let path = index_path(store);
+        if !path.exists() {
+            return Ok(None);
+        }
+        // A broken index is only a cache miss
+        Ok(serde_json::from_str(&fs::read_to_string(path)?).ok())
+    }
+
+    pub fn save(&self, store: &Path) -> anyhow::Result<()> {
+        // Written aside and renamed, since searches running side by side may rebuild it
+        let path = index_path(store);
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_string(self)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    /// Number of distinct words.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    fn insert(&mut self, n: &Note) {
+        let fields = [n.title.as_str(), n.body.as_str()].into_iter().chain(n.tags.iter().map(String::as_str));
+        for word in fields.flat_map(words) {
+            self.words.entry(word).or_default().insert(n.id);
+        }
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.words.retain(|_, ids| {
+            ids.remove(&id);
+            !ids.is_empty()
+        });
+    }
+
+    /// Re-indexes the notes `changed` (added, edited or removed) now that the store is `after`.
+    pub fn update(&mut self, after: &Storage, changed: &[u64]) {
+        for &id in changed {
+            self.remove(id);
+            after.notes.iter().filter(|n| n.id == id).for_each(|n| self.insert(n));
+        }
+        self.checksum = journal::hash(after);
+    }
+
+    /// Ids of the notes that may contain `query` ignoring case: every word of the query is part
+    /// of one of their words. `None` when the query has no word to look up.
+    pub fn candidates(&self, query: &str) -> Option<HashSet<u64>> {
+        let mut result: Option<HashSet<u64>> = None;
+        for word in words(query) {
+            let ids: HashSet<u64> =
+                self.words.iter().filter(|(w, _)| w.contains(&word)).flat_map(|(_, ids)| ids.iter().copied()).collect();
+            result = Some(match result {
+                Some(so_far) => so_far.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+        result
+    }
+}
+
+/// The index of `storage`, loaded from next to the store or rebuilt (and saved) when it is
+/// missing or stale.
+pub fn fresh(store: &Path, storage: &Storage) -> anyhow::Result<Index> {
+    if let Some(index) = Index::load(store)?
+        && index.checksum == journal::hash(storage)
+    {
+        return Ok(index);
+    }
+    let index = Index::build(storage);
+    index.save(store)?;
+    Ok(index)
+}
+
+/// After a change from `before` (the [`journal::hash`] of the notes it was made to) to `after`,
+/// updates an index that was current for `changed` notes only. A stale or missing index is left
+/// for the next search to rebuild.
+pub fn update(store: &Path, before: &str, after: &Storage, changed: &[u64]) -> anyhow::Result<()> {
+    match Index::load(store)? {
+        Some(mut index) if index.checksum == before => {
+            index.update(after, changed);
+            index.save(store)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FieldMask, Matcher, SearchFilter, TagMode};
+    use tempfile::TempDir;
+
+    const VOCABULARY: [&str; 24] = [
+        "meeting", "Budget", "roadmap", "grocery", "milk", "Rust", "release", "déjà", "vu", "日本語", "ship", "it",
+        "Q3", "2025", "follow-up", "TODO", "bread", "standup", "sprint", "retro", "café", "plan", "draft", "ok",
+    ];
+
+    /// Deterministic pseudo-random notes built from [`VOCABULARY`].
+    fn generated(count: u64) -> Storage {
+        let mut seed: u64 = 42;
+        let mut next = move |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        let mut phrase = |len: usize| (0..len).map(|_| VOCABULARY[next(VOCABULARY.len())]).collect::<Vec<_>>();
+        let notes = (1..=count)
+            .map(|id| Note {
+                body: phrase(12).join(if id % 2 == 0 { " " } else { ", " }).into(),
+                tags: phrase(2).into_iter().map(str::to_lowercase).collect(),
+                ..Note::new(id, phrase(3).join(" "))
+            })
+            .collect();
+        Storage { notes, ..Default::default() }
+    }
+
+    fn search<'a>(storage: &'a Storage, query: &str, index: Option<&Index>) -> Vec<&'a Note> {
+        let filter = SearchFilter {
+            matcher: Matcher::new(query, false, false).unwrap(),
+            mask: FieldMask::from_fields(&[]),
+            mode: TagMode::All,
+            tags: vec![],
+            not_tags: vec![],
+        };
+        let ids = index.and_then(|i| i.candidates(query));
+        storage.notes.iter().filter(|n| ids.as_ref().is_none_or(|ids| ids.contains(&n.id)) && filter.matches(n)).collect()
+    }
+
+    #[test]
+    fn indexed_search_finds_what_the_linear_scan_finds() {
+        let storage = generated(5000);
+        let index = Index::build(&storage);
+        for query in [
+            "meeting", "MEET", "eting bud", "rust release", "q3 2025", "follow-up", "ja vu", "日本", "it, ship",
+            "café plan", "nothing here", "a", "p r", "TODO bread sprint",
+        ] {
+            let linear = search(&storage, query, None);
+            let indexed = search(&storage, query, Some(&index));
+            assert_eq!(indexed, linear, "{:?}", query);
+            assert!(index.candidates(query).is_some(), "{:?}", query);
+        }
+        assert!(index.candidates("  --  ").is_none());
+    }
+
+    #[test]
+    fn updates_match_a_rebuild_and_stale_indexes_are_rebuilt() {
+        let tmp = TempDir::new().unwrap();
+        let store = tmp.path().join("notes.json");
+        let mut storage = generated(50);
+        let before = journal::hash(&storage);
+        assert_eq!(fresh(&store, &storage).unwrap(), Index::build(&storage));
+
+        storage.notes[3].body = "rewritten entirely".into();
+        storage.notes.remove(7);
+        let mut added = storage.notes[0].clone();
+        added.id = 51;
+        storage.notes.push(added);
+        update(&store, &before, &storage, &[4, 8, 51]).unwrap();
+        let updated = Index::load(&store).unwrap().unwrap();
+        assert_eq!(updated, Index::build(&storage));
+
+        // Changed without an update: the stale index is rebuilt on the next search
+        storage.notes[0].title = "brand new words".into();
+        update(&store, "not the hash it was built from", &storage, &[1]).unwrap();
+        assert_ne!(Index::load(&store).unwrap().unwrap(), Index::build(&storage));
+        assert_eq!(fresh(&store, &storage).unwrap(), Index::build(&storage));
+    }
+}
@@ -0,0 +1,213 @@
+//! `ingest`: the markdown files of a directory as notes, e.g. an inbox written in another
+//! editor. A file is read the way `export note` writes one: an optional YAML front matter with
+//! `title` and `tags`, then the body. Without a title there, a leading `# Heading` line or else
+//! the file name is the title.
+//!
+//! Each note remembers its file and the file's modification time in its meta fields, so
+//! unchanged files are skipped and edited ones update their note instead of adding another.
+
+use crate::history::{self, Revision};
+use crate::{normalize_tags, Note, Storage};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Meta field holding the file a note was ingested from
+pub const SOURCE: &str = "source";
+/// Meta field holding that file's modification time when it was last read
+pub const MTIME: &str = "source-mtime";
+/// Subdirectory that `--move` puts the ingested files in
+pub const PROCESSED: &str = "processed";
+
+#[derive(Deserialize, Default)]
+struct FrontMatter {
+    title: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Parsed {
+    pub title: String,
+    pub body: String,
+    pub tags: Vec<String>,
+}
+
+/// Title, body and tags of a markdown file named `stem`.
+pub fn parse(text: &str, stem: &str) -> anyhow::Result<Parsed> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text).replace("\r\n", "\n");
+    let (front, rest) = match text.strip_prefix("---\n") {
+        Some(after) => {
+            let (yaml, rest) = after
+                .split_once("\n---\n")
+                .or_else(|| after.strip_suffix("\n---").map(|yaml| (yaml, "")))
+                .ok_or_else(|| anyhow::anyhow!("front matter is never closed with ---"))?;
+            let front = if yaml.trim().is_empty() {
+                FrontMatter::default()
+            } else {
+                serde_yaml::from_str(yaml).map_err(|e| anyhow::anyhow!("invalid front matter: {}", e))?
+            };
+            (front, rest)
+        }
+        None => (FrontMatter::default(), text.as_str()),
+    };
+    let rest = rest.trim_start_matches('\n');
+    let (title, body) = match front.title.filter(|t| !t.trim().is_empty()) {
+        Some(title) => (title, rest),
+        None => match rest.strip_prefix("# ") {
+            Some(heading) => {
+                let (title, body) = heading.split_once('\n').unwrap_or((heading, ""));
+                (title.to_string(), body.trim_start_matches('\n'))
+            }
+            None => (stem.to_string(), rest),
+        },
+    };
+    Ok(Parsed { title: title.trim().to_string(), body: body.trim_end().to_string(), tags: front.tags })
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Ingested {
+    pub id: u64,
+    pub title: String,
+    pub file: PathBuf,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Failed {
+    pub file: PathBuf,
+    pub error: String,
+}
+
+/// What one pass over a directory did.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub added: Vec<Ingested>,
+    pub updated: Vec<Ingested>,
+    /// Files left out of this pass, e.g. with a broken front matter
+    pub failed: Vec<Failed>,
+    /// Every file whose note is now up to date, for `--move` once the store is saved
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
+}
+
+impl Report {
+    /// Whether the pass added, updated or skipped anything worth telling.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.failed.is_empty()
+    }
+}
+
+/// The `.md` files directly inside `dir`, by name.
+fn markdown_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir).map_err(|e| anyhow::anyhow!("can't read {}: {}", dir.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let markdown = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("md"));
+        if markdown && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn modified(path: &Path) -> anyhow::Result<String> {
+    let at: DateTime<Utc> = fs::metadata(path)?.modified()?.into();
+    Ok(at.to_rfc3339_opts(SecondsFormat::Nanos, true))
+}
+
+/// Adds a note for every new markdown file of `dir` and updates the notes of the files changed
+/// since they were last ingested; an update keeps the previous version in the note's history
+/// (at most `depth` revisions).
+pub fn ingest(dir: &Path, storage: &mut Storage, depth: usize, now: DateTime<Utc>) -> anyhow::Result<Report> {
+    let dir = dir.canonicalize().map_err(|e| anyhow::anyhow!("can't read {}: {}", dir.display(), e))?;
+    let mut report = Report::default();
+    for path in markdown_files(&dir)? {
+        let source = path.to_string_lossy().into_owned();
+        let mtime = modified(&path)?;
+        let existing = storage.notes.iter().position(|n| n.meta.get(SOURCE) == Some(&source));
+        if let Some(i) = existing
+            && storage.notes[i].meta.get(MTIME) == Some(&mtime)
+        {
+            report.files.push(path);
+            continue;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let parsed = match fs::read_to_string(&path).map_err(anyhow::Error::from).and_then(|text| parse(&text, &stem)) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                report.failed.push(Failed { file: path, error: e.to_string() });
+                continue;
+            }
+        };
+        match existing {
+            Some(i) => {
+                let n = &mut storage.notes[i];
+                let original = n.clone();
+                n.title = parsed.title;
+                n.body = parsed.body.into();
+                n.tags = normalize_tags(parsed.tags);
+                n.meta.insert(MTIME.to_string(), mtime);
+                // A file saved again without changes only refreshes the time
+                if n.title != original.title || n.body != original.body || n.tags != original.tags {
+                    history::push(n, Revision::of(&original, now), depth);
+                    report.updated.push(Ingested { id: n.id, title: n.title.clone(), file: path.clone() });
+                }
+            }
+            None => {
+                let id = storage.next_id();
+                report.added.push(Ingested { id, title: parsed.title.clone(), file: path.clone() });
+                storage.notes.push(Note {
+                    id,
+                    title: parsed.title,
+                    body: parsed.body.into(),
+                    tags: normalize_tags(parsed.tags),
+                    created_at: now,
+                    meta: BTreeMap::from([(SOURCE.to_string(), source), (MTIME.to_string(), mtime)]),
+                    ..Default::default()
+                });
+            }
+        }
+        report.files.push(path);
+    }
+    Ok(report)
+}
+
+/// Moves `files` into the `processed/` subdirectory of their directory.
+pub fn move_processed(files: &[PathBuf]) -> anyhow::Result<()> {
+    for file in files {
+        let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else { continue };
+        let processed = dir.join(PROCESSED);
+        fs::create_dir_all(&processed)?;
+        fs::rename(file, processed.join(name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(title: &str, body: &str, tags: &[&str]) -> Parsed {
+        Parsed { title: title.to_string(), body: body.to_string(), tags: tags.iter().map(|t| t.to_string()).collect() }
+    }
+
+    #[test]
+    fn reads_what_export_writes() {
+        let exported = "---\nid: 7\ntitle: 'Kickoff: \"Q3\"'\ntags:\n- work\ncreated_at: 2025-03-01T09:00:00Z\n---\n\n# Agenda\n\n- [ ] budget\n";
+        assert_eq!(parse(exported, "note-7").unwrap(), parsed("Kickoff: \"Q3\"", "# Agenda\n\n- [ ] budget", &["work"]));
+    }
+
+    #[test]
+    fn title_falls_back_to_the_heading_then_the_file_name() {
+        assert_eq!(parse("# Groceries\n\nmilk\r\nbread\n", "inbox").unwrap(), parsed("Groceries", "milk\nbread", &[]));
+        assert_eq!(parse("---\ntags: [home]\n---\n# Chores\n", "x").unwrap(), parsed("Chores", "", &["home"]));
+        assert_eq!(parse("just a thought\n", "2025-10-01 idea").unwrap(), parsed("2025-10-01 idea", "just a thought", &[]));
+        assert_eq!(parse("", "empty").unwrap(), parsed("empty", "", &[]));
+        assert!(parse("---\ntitle: never closed\n", "x").is_err());
+        assert!(parse("---\ntitle: [oops\n---\nbody", "x").is_err());
+    }
+}
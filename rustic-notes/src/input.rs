@@ -0,0 +1,80 @@
+//! Note bodies that come from somewhere other than the command line.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Largest --body-file accepted unless --max-body-size says otherwise (1 MiB).
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Reads a body until EOF (e.g. `--body -`), rejecting binary data and dropping exactly one
+/// trailing newline, so `echo text | rustic-notes add t --body -` stores `text`.
+pub fn read_body(mut reader: impl Read) -> anyhow::Result<String> {
+    let mut raw = Vec::new();
+    reader.read_to_end(&mut raw)?;
+    to_body(raw, "stdin")
+}
+
+/// Reads a body from a UTF-8 text file of at most `max_size` bytes (a leading BOM is dropped).
+pub fn read_body_file(path: &Path, max_size: u64) -> anyhow::Result<String> {
+    let meta = fs::metadata(path).map_err(|e| anyhow::anyhow!("can't read body file {}: {}", path.display(), e))?;
+    if meta.len() > max_size {
+        anyhow::bail!(
+            "{} is {} bytes, more than the {} byte limit (raise it with --max-body-size)",
+            path.display(),
+            meta.len(),
+            max_size
+        );
+    }
+    let mut raw = fs::read(path).map_err(|e| anyhow::anyhow!("can't read body file {}: {}", path.display(), e))?;
+    if raw.starts_with(BOM) {
+        raw.drain(..BOM.len());
+    }
+    to_body(raw, &path.display().to_string())
+}
+
+fn to_body(raw: Vec<u8>, source: &str) -> anyhow::Result<String> {
+    if raw.contains(&0) {
+        anyhow::bail!("{} looks like binary data; only text can be a note body", source);
+    }
+    let mut text = String::from_utf8(raw)
+        .map_err(|_| anyhow::anyhow!("{} is not valid UTF-8; only text can be a note body", source))?;
+    if text.ends_with('\n') {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_exactly_one_trailing_newline() {
+        assert_eq!(read_body("line 1\nline 2\n\n".as_bytes()).unwrap(), "line 1\nline 2\n");
+        assert_eq!(read_body("crlf\r\n".as_bytes()).unwrap(), "crlf");
+        assert_eq!(read_body("none".as_bytes()).unwrap(), "none");
+    }
+
+    #[test]
+    fn body_file_drops_bom_and_enforces_the_size_limit() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("minutes.txt");
+        fs::write(&path, b"\xEF\xBB\xBFAttendees: Ana, Luis\n").unwrap();
+        assert_eq!(read_body_file(&path, DEFAULT_MAX_BODY_SIZE).unwrap(), "Attendees: Ana, Luis");
+        let err = read_body_file(&path, 10).unwrap_err().to_string();
+        assert!(err.contains("more than the 10 byte limit"), "{}", err);
+        assert!(read_body_file(&dir.path().join("missing.txt"), DEFAULT_MAX_BODY_SIZE).is_err());
+    }
+
+    #[test]
+    fn rejects_binary_input() {
+        assert!(read_body(&b"PK\x03\x04\x00\x00"[..]).is_err());
+        assert!(read_body(&b"\xff\xfe"[..]).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Undo journal: every mutating command appends the operation that reverts it to `<store>.journal`.
+//!
+//! The journal remembers a hash of the notes it was written against; if the store no longer
+//! matches (edited by hand, restored from a backup, …) the journal is discarded rather than
+//! applied to content it doesn't describe.
+
+use crate::{Note, Storage};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How many operations are kept unless --journal-size says otherwise.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// The change that reverts an operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Inverse {
+    /// Undo an add
+    Remove { id: u64 },
+    /// Undo a remove: put the note back where it was
+    Restore { note: Note, position: usize },
+    /// Undo an edit: the note as it was before
+    Replace { note: Note },
+    /// Undo a bulk change (merge, doctor --fix): every note as it was before
+    Snapshot { notes: Vec<Note> },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    /// Short description for `undo --list`, e.g. `remove #3 "Groceries"`
+    pub command: String,
+    pub inverse: Inverse,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    /// Hash of the notes after the last recorded operation
+    pub hash: String,
+    pub entries: Vec<Entry>,
+}
+
+pub fn journal_path(store: &Path) -> PathBuf {
+    let mut name = store.as_os_str().to_owned();
+    name.push(".journal");
+    PathBuf::from(name)
+}
+
+/// Content hash of the notes, independent of the store format, encryption and version.
+pub fn hash(storage: &Storage) -> String {
+    // Same bytes as a version 0 store, so journals written before versions existed still match
+    #[derive(Serialize)]
+    struct Notes<'a> {
+        notes: &'a [Note],
+    }
+    let json = serde_json::to_vec(&Notes { notes: &storage.notes }).expect("notes always serialize");
+    Sha256::digest(json).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl Journal {
+    pub fn load(store: &Path) -> anyhow::Result<Journal> {
+        let path = journal_path(store);
+        if !path.exists() {
+            return Ok(Journal::default());
+        }
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| anyhow::anyhow!("unreadable undo journal {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, store: &Path) -> anyhow::Result<()> {
+        let path = journal_path(store);
+        if self.entries.is_empty() {
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+            return Ok(());
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the journal was written against exactly these notes.
+    pub fn matches(&self, storage: &Storage) -> bool {
+        self.hash == hash(storage)
+    }
+}
+
+/// Appends `entry` after an operation turned the notes hashed as `before` into `after`,
+/// keeping the newest `limit` entries (0 disables the journal).
+pub fn record(store: &Path, before: &str, after: &Storage, entry: Entry, limit: usize) -> anyhow::Result<()> {
+    let mut journal = Journal::load(store).unwrap_or_default();
+    if journal.hash != before || limit == 0 {
+        journal.entries.clear();
+    }
+    if limit > 0 {
+        journal.entries.push(entry);
+        let excess = journal.entries.len().saturating_sub(limit);
+        journal.entries.drain(..excess);
+    }
+    journal.hash = hash(after);
+    journal.save(store)
+}
+
+/// Applies an inverse operation to the notes.
+pub fn apply(storage: &mut Storage, inverse: Inverse) {
+    match inverse {
+        Inverse::Remove { id } => storage.notes.retain(|n| n.id != id),
+        Inverse::Restore { note, position } => {
+            let position = position.min(storage.notes.len());
+            storage.notes.insert(position, note);
+        }
+        Inverse::Replace { note } => {
+            if let Some(n) = storage.notes.iter_mut().find(|n| n.id == note.id) {
+                *n = note;
+            }
+        }
+        Inverse::Snapshot { notes } => storage.notes = notes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn note(id: u64, title: &str) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn entry(command: &str, inverse: Inverse) -> Entry {
+        Entry { at: Utc::now(), command: command.to_string(), inverse }
+    }
+
+    #[test]
+    fn record_caps_entries_and_resets_on_foreign_hash() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+        let mut s = Storage::default();
+        for id in 1..=4 {
+            let before = hash(&s);
+            s.notes.push(note(id, "n"));
+            record(&store, &before, &s, entry(&format!("add #{}", id), Inverse::Remove { id }), 3).unwrap();
+        }
+        let journal = Journal::load(&store).unwrap();
+        let commands: Vec<&str> = journal.entries.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(commands, ["add #2", "add #3", "add #4"]);
+        assert!(journal.matches(&s));
+
+        // Written against other content: the old entries are dropped
+        record(&store, "something else", &s, entry("edit #1", Inverse::Replace { note: note(1, "n") }), 3).unwrap();
+        assert_eq!(Journal::load(&store).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn apply_restores_at_the_original_position() {
+        let mut s = Storage { notes: vec![note(1, "a"), note(3, "c")], ..Default::default() };
+        apply(&mut s, Inverse::Restore { note: note(2, "b"), position: 1 });
+        apply(&mut s, Inverse::Replace { note: note(3, "C") });
+        let titles: Vec<&str> = s.notes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, ["a", "b", "C"]);
+    }
+}
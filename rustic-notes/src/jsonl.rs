@@ -0,0 +1,337 @@
+use crate::store::{ensure_parent, stamped, Store, Versions};
+use crate::{backup, Note, Storage};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// How far from the end of the file to look for the last version record before replaying it all.
+const TAIL_BYTES: u64 = 512;
+
+/// One line of the log: a note (new or replacing the previous record with its id), a tombstone,
+/// or the store version, appended after each save's records.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum Record {
+    Deleted {
+        id: u64,
+        deleted: bool,
+    },
+    Version {
+        version: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        saved_at: Option<DateTime<Utc>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_prune: Option<DateTime<Utc>>,
+    },
+    Note(Note),
+}
+
+/// Replays a log: the last record per id wins, notes keep the position of their first record
+/// (until deleted). A torn last line (no trailing newline, as left by an interrupted append)
+/// is ignored; the returned count is the number of records that no longer contribute.
+pub fn replay(raw: &str) -> anyhow::Result<(Storage, usize)> {
+    let mut slots: Vec<Option<Note>> = Vec::new();
+    let mut index: HashMap<u64, usize> = HashMap::new();
+    let mut version = None;
+    let mut records = 0;
+    let lines: Vec<&str> = raw.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = match serde_json::from_str::<Record>(line) {
+            Ok(r) => r,
+            Err(_) if i + 1 == lines.len() && !raw.ends_with('\n') => break,
+            Err(e) => anyhow::bail!("line {}: {}", i + 1, e),
+        };
+        records += 1;
+        match record {
+            Record::Note(n) => match index.get(&n.id) {
+                Some(&slot) => slots[slot] = Some(n),
+                None => {
+                    index.insert(n.id, slots.len());
+                    slots.push(Some(n));
+                }
+            },
+            Record::Deleted { id, .. } => {
+                if let Some(slot) = index.remove(&id) {
+                    slots[slot] = None;
+                }
+            }
+            Record::Version { version: v, saved_at, last_prune } => version = Some((v, saved_at, last_prune)),
+        }
+    }
+    let notes: Vec<Note> = slots.into_iter().flatten().collect();
+    let superseded = records - notes.len() - usize::from(version.is_some());
+    let (version, saved_at, last_prune) = version.unwrap_or_default();
+    Ok((Storage { version, saved_at, last_prune, checksum: None, notes }, superseded))
+}
+
+fn version_record(storage: &Storage) -> Record {
+    Record::Version { version: storage.version, saved_at: storage.saved_at, last_prune: storage.last_prune }
+}
+
+/// The compacted log: the version, then one line per note.
+pub fn serialize(storage: &Storage) -> anyhow::Result<String> {
+    let mut out = String::new();
+    if storage.version > 0 {
+        out.push_str(&serde_json::to_string(&version_record(storage))?);
+        out.push('\n');
+    }
+    for n in &storage.notes {
+        out.push_str(&serde_json::to_string(n)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Records turning `before` into `after`, or `None` when replaying them wouldn't give `after`'s
+/// order (e.g. `undo` putting a removed note back in the middle) and the file must be rewritten.
+fn diff(before: &Storage, after: &Storage) -> Option<Vec<Record>> {
+    let old: HashMap<u64, &Note> = before.notes.iter().map(|n| (n.id, n)).collect();
+    let new_ids: HashSet<u64> = after.notes.iter().map(|n| n.id).collect();
+    let kept = before.notes.iter().map(|n| n.id).filter(|id| new_ids.contains(id));
+    let added = after.notes.iter().map(|n| n.id).filter(|id| !old.contains_key(id));
+    if !kept.chain(added).eq(after.notes.iter().map(|n| n.id)) {
+        return None;
+    }
+    let mut records: Vec<Record> = before
+        .notes
+        .iter()
+        .filter(|n| !new_ids.contains(&n.id))
+        .map(|n| Record::Deleted { id: n.id, deleted: true })
+        .collect();
+    for n in &after.notes {
+        if old.get(&n.id).is_none_or(|o| *o != n) {
+            records.push(Record::Note(n.clone()));
+        }
+    }
+    Some(records)
+}
+
+/// Append-only log with one JSON record per line. Saving appends the added, changed and
+/// removed notes instead of rewriting the file; `compact` drops the superseded records.
+pub struct JsonlStore {
+    pub path: PathBuf,
+    /// Rotated copies kept when the whole file is rewritten (appends don't rotate)
+    pub backups: usize,
+    /// Notes as of the last load or save, so a save doesn't have to replay the file again
+    last: RefCell<Option<Storage>>,
+    torn: Cell<bool>,
+    versions: Versions,
+}
+
+impl JsonlStore {
+    pub fn new(path: PathBuf, backups: usize) -> Self {
+        JsonlStore { path, backups, last: RefCell::new(None), torn: Cell::new(false), versions: Versions::default() }
+    }
+
+    /// Saves even when another process appended to the log since it was loaded.
+    pub fn force_save(mut self, force: bool) -> Self {
+        self.versions.force = force;
+        self
+    }
+
+    /// The version on disk, read from the last line when that is a version record
+    /// (replaying the whole file otherwise, e.g. for logs written before versions existed).
+    fn disk_version(&self) -> anyhow::Result<u64> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let mut file = File::open(&self.path)?;
+        let len = file.metadata()?.len();
+        let start = len.saturating_sub(TAIL_BYTES);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = String::new();
+        // Cutting into a multi-byte character fails the read; that only happens inside note lines
+        if file.read_to_string(&mut tail).is_ok()
+            && let Some(rest) = tail.strip_suffix('\n')
+            && let Some(line) = match rest.rfind('\n') {
+                Some(i) => Some(&rest[i + 1..]),
+                None => (start == 0).then_some(rest),
+            }
+            && let Ok(Record::Version { version, .. }) = serde_json::from_str(line)
+        {
+            return Ok(version);
+        }
+        Ok(self.read()?.0.version)
+    }
+
+    fn read(&self) -> anyhow::Result<(Storage, usize)> {
+        if !self.path.exists() {
+            return Ok((Storage::default(), 0));
+        }
+        let raw = fs::read_to_string(&self.path)?;
+        self.torn.set(!raw.is_empty() && !raw.ends_with('\n'));
+        replay(&raw).map_err(|e| anyhow::anyhow!("{}: {}", self.path.display(), e))
+    }
+
+    /// Writes the whole file through a temporary file, so an interrupted rewrite leaves the old one.
+    fn rewrite(&self, storage: &Storage) -> anyhow::Result<()> {
+        ensure_parent(&self.path)?;
+        if self.path.exists() {
+            backup::rotate(&self.path, self.backups)?;
+        }
+        let dir = self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(".".as_ref());
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        tmp.write_all(serialize(storage)?.as_bytes())?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(&self.path)?;
+        self.torn.set(false);
+        Ok(())
+    }
+
+    fn append(&self, records: &[Record]) -> anyhow::Result<()> {
+        ensure_parent(&self.path)?;
+        let mut lines = String::new();
+        for r in records {
+            lines.push_str(&serde_json::to_string(r)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+impl Store for JsonlStore {
+    fn load_all(&self) -> anyhow::Result<Storage> {
+        let (storage, _) = self.read()?;
+        self.versions.seen(storage.version);
+        *self.last.borrow_mut() = Some(storage.clone());
+        Ok(storage)
+    }
+
+    fn save_all(&self, storage: &Storage) -> anyhow::Result<()> {
+        let on_disk = self.disk_version()?;
+        // The cached notes are only what's on disk if nobody appended since
+        let cached = self.last.borrow_mut().take().filter(|s| s.version == on_disk);
+        let before = match cached {
+            Some(s) => s,
+            None => self.read()?.0,
+        };
+        if before.same_content(storage) {
+            *self.last.borrow_mut() = Some(before);
+            return Ok(());
+        }
+        let saved = stamped(storage, self.versions.next(on_disk, storage)?);
+        match diff(&before, storage) {
+            Some(mut records) if !self.torn.get() => {
+                records.push(version_record(&saved));
+                self.append(&records)?
+            }
+            _ => self.rewrite(&saved)?,
+        }
+        self.versions.seen(saved.version);
+        *self.last.borrow_mut() = Some(saved);
+        Ok(())
+    }
+
+    fn compact(&self) -> anyhow::Result<Option<usize>> {
+        let (storage, superseded) = self.read()?;
+        if superseded > 0 || self.torn.get() {
+            self.rewrite(&storage)?;
+        }
+        *self.last.borrow_mut() = Some(storage.clone());
+        Ok(Some(superseded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use tempfile::TempDir;
+
+    fn note(id: u64, title: &str) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            created_at: DateTime::parse_from_rfc3339("2025-09-14T12:34:56Z").unwrap().into(),
+            ..Default::default()
+        }
+    }
+
+    fn line_count(store: &JsonlStore) -> usize {
+        fs::read_to_string(&store.path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn interleaved_adds_edits_and_removes_replay_to_the_last_state() {
+        let dir = TempDir::new().unwrap();
+        let store = JsonlStore::new(dir.path().join("notes.jsonl"), 0);
+        let mut s = Storage::default();
+        for (id, title) in [(1, "one"), (2, "two"), (3, "three")] {
+            s.notes.push(note(id, title));
+            store.save_all(&s).unwrap();
+        }
+        s.notes[1].title = "two, edited".to_string();
+        store.save_all(&s).unwrap();
+        s.notes.remove(0);
+        store.save_all(&s).unwrap();
+        s.notes.push(note(4, "four"));
+        s.notes[1].title = "three, edited".to_string();
+        store.save_all(&s).unwrap();
+
+        // 3 adds, 1 edit, 1 tombstone, 1 add + 1 edit, each save followed by its version:
+        // nothing was rewritten
+        assert_eq!(line_count(&store), 13);
+        let fresh = JsonlStore::new(store.path.clone(), 0).load_all().unwrap();
+        assert_eq!(fresh.notes, s.notes);
+        assert_eq!(fresh.version, 6);
+        let titles: Vec<String> = s.notes.iter().map(|n| n.title.clone()).collect();
+        assert_eq!(titles, ["two, edited", "three, edited", "four"]);
+    }
+
+    #[test]
+    fn compact_keeps_the_logical_state() {
+        let dir = TempDir::new().unwrap();
+        let store = JsonlStore::new(dir.path().join("notes.jsonl"), 1);
+        let mut s = Storage { notes: vec![note(1, "a"), note(2, "b")], ..Default::default() };
+        store.save_all(&s).unwrap();
+        s.notes[0].title = "a2".to_string();
+        store.save_all(&s).unwrap();
+        s.notes.remove(1);
+        store.save_all(&s).unwrap();
+        assert_eq!(line_count(&store), 7);
+
+        // Two older versions of note 1, note 2 and its tombstone, two older version records
+        assert_eq!(store.compact().unwrap(), Some(5));
+        assert_eq!(line_count(&store), 2);
+        let compacted = JsonlStore::new(store.path.clone(), 0).load_all().unwrap();
+        assert_eq!((compacted.notes, compacted.version), (s.notes, 3));
+        assert!(dir.path().join("notes.jsonl.bak.1").exists());
+        assert_eq!(store.compact().unwrap(), Some(0));
+    }
+
+    #[test]
+    fn reordering_rewrites_and_a_torn_line_is_ignored() {
+        let dir = TempDir::new().unwrap();
+        let store = JsonlStore::new(dir.path().join("notes.jsonl"), 0);
+        let s = Storage { notes: vec![note(1, "a"), note(2, "b")], ..Default::default() };
+        store.save_all(&s).unwrap();
+        // Undo putting a note back at the front can't be expressed as appends
+        let reordered = Storage { notes: vec![note(3, "c"), note(1, "a"), note(2, "b")], ..Default::default() };
+        store.save_all(&reordered).unwrap();
+        assert_eq!(line_count(&store), 4);
+        assert_eq!(JsonlStore::new(store.path.clone(), 0).load_all().unwrap().notes, reordered.notes);
+
+        let mut raw = fs::read_to_string(&store.path).unwrap();
+        raw.push_str("{\"id\": 4, \"tit");
+        fs::write(&store.path, raw).unwrap();
+        let torn = JsonlStore::new(store.path.clone(), 0);
+        assert_eq!(torn.load_all().unwrap().notes, reordered.notes);
+        let mut more = reordered.clone();
+        more.notes.push(note(4, "d"));
+        torn.save_all(&more).unwrap();
+        assert_eq!(JsonlStore::new(store.path.clone(), 0).load_all().unwrap().notes, more.notes);
+
+        fs::write(&store.path, "not json\n").unwrap();
+        assert!(JsonlStore::new(store.path.clone(), 0).load_all().unwrap_err().to_string().contains("line 1"));
+    }
+}
@@ -0,0 +1,743 @@
+pub use body::Body;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io::Write, process::Command as ProcCommand};
+use tempfile::Builder as TempBuilder;
+
+pub mod attachments;
+pub mod backup;
+pub mod body;
+pub mod checklist;
+pub mod config;
+pub mod confirm;
+pub mod crypto;
+pub mod dedupe;
+pub mod doctor;
+pub mod due;
+pub mod export;
+pub mod git;
+pub mod group;
+pub mod grep;
+pub mod history;
+pub mod html;
+pub mod index;
+pub mod ingest;
+pub mod input;
+pub mod journal;
+pub mod jsonl;
+pub mod links;
+pub mod location;
+pub mod lock;
+pub mod markdown;
+pub mod merge;
+pub mod meta;
+pub mod msg;
+pub mod picker;
+pub mod porcelain;
+pub mod prune;
+pub mod rank;
+pub mod resolve;
+pub mod server;
+pub mod session;
+pub mod snapshot;
+pub mod split;
+pub mod sqlite;
+pub mod stats;
+pub mod store;
+pub mod suggest;
+pub mod style;
+pub mod table;
+pub mod tui;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+    Sqlite,
+    /// One JSON record per line, appended to on save (also `.ndjson`)
+    Jsonl,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum EditorFmt {
+    Json,
+    Toml,
+    Yaml,
+}
+
+/// Note field that a search can be restricted to
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Field {
+    Title,
+    Body,
+    Tags,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
+pub struct Storage {
+    /// Bumped by every save; stores written before versions existed are at 0
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saved_at: Option<DateTime<Utc>>,
+    /// When notes were last archived by `prune` (see `auto_prune_days`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_prune: Option<DateTime<Utc>>,
+    /// sha256 of the notes written by every save of a JSON/TOML/YAML store, see
+    /// [`Storage::checksum_mismatch`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    pub notes: Vec<Note>,
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct Note {
+    pub id: u64,
+    pub title: String,
+    // Missing body/tags are tolerated so hand-written stores still load (see `doctor`)
+    #[serde(default)]
+    pub body: Body,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// Last change to title, body or tags; absent until the first edit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Previous versions, oldest first (see `history`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<history::Revision>,
+    /// Files copied next to the store, relative to its directory (see `attachments`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<PathBuf>,
+    /// Pinned notes are listed first
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// Position in `list --sort manual`, lowest first (see `move`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<u32>,
+    /// Custom `key=value` fields, keys lowercase (see `meta`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, String>,
+    /// When the note is due (see `due`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    /// Last reminder sent by `due --notify`, so it isn't repeated within a day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)] // a typo such as `titel:` must not silently drop the change
+pub struct EditableNote {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub meta: Option<BTreeMap<String, String>>,
+    // Tags stay last, so a list item appended to the buffer lands in them
+    pub tags: Option<Vec<String>>, // if omitted, they remain
+}
+
+/// How the search query is compared against a note's fields. Tags are always compared
+/// case-insensitively, since `Dev` and `dev` are the same tag.
+pub enum Matcher {
+    Substring { needle: String, case_sensitive: bool },
+    Regex { re: Regex, tags: Regex },
+}
+
+impl Matcher {
+    pub fn new(query: &str, regex: bool, case_sensitive: bool) -> anyhow::Result<Self> {
+        if regex {
+            let build = |case_insensitive| {
+                RegexBuilder::new(query)
+                    .case_insensitive(case_insensitive)
+                    .build()
+                    .map_err(|e| anyhow::anyhow!("invalid regex \"{}\": {}", query, e))
+            };
+            Ok(Matcher::Regex { re: build(!case_sensitive)?, tags: build(true)? })
+        } else {
+            let needle = if case_sensitive { query.to_string() } else { query.to_lowercase() };
+            Ok(Matcher::Substring { needle, case_sensitive })
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Substring { needle, case_sensitive: true } => text.contains(needle.as_str()),
+            Matcher::Substring { needle, case_sensitive: false } => text.to_lowercase().contains(needle.as_str()),
+            Matcher::Regex { re, .. } => re.is_match(text),
+        }
+    }
+
+    /// Byte offset of the first match in `text` (in its lowercased form when case is ignored).
+    pub fn find(&self, text: &str) -> Option<usize> {
+        match self {
+            Matcher::Substring { needle, case_sensitive: true } => text.find(needle.as_str()),
+            Matcher::Substring { needle, case_sensitive: false } => text.to_lowercase().find(needle.as_str()),
+            Matcher::Regex { re, .. } => re.find(text).map(|m| m.start()),
+        }
+    }
+
+    pub fn is_tag_match(&self, tag: &str) -> bool {
+        match self {
+            Matcher::Substring { needle, .. } => tag.to_lowercase().contains(&needle.to_lowercase()),
+            Matcher::Regex { tags, .. } => tags.is_match(tag),
+        }
+    }
+}
+
+/// How the positive tag list of a search is combined.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TagMode {
+    /// The note must contain every tag
+    All,
+    /// The note must contain at least one tag
+    Any,
+}
+
+impl TagMode {
+    pub fn matches(self, n: &Note, wanted: &[String]) -> bool {
+        if wanted.is_empty() {
+            return true;
+        }
+        match self {
+            TagMode::All => has_all_tags(n, wanted),
+            TagMode::Any => has_any_tag(n, wanted),
+        }
+    }
+}
+
+/// Which note fields a search inspects.
+pub struct FieldMask {
+    pub title: bool,
+    pub body: bool,
+    pub tags: bool,
+}
+
+impl FieldMask {
+    pub fn from_fields(fields: &[Field]) -> Self {
+        if fields.is_empty() {
+            return FieldMask { title: true, body: true, tags: true };
+        }
+        FieldMask {
+            title: fields.contains(&Field::Title),
+            body: fields.contains(&Field::Body),
+            tags: fields.contains(&Field::Tags),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut names = Vec::new();
+        if self.title { names.push("title"); }
+        if self.body { names.push("body"); }
+        if self.tags { names.push("tags"); }
+        names.join(",")
+    }
+}
+
+/// What `search` looks for: the query in one of the masked fields, the wanted tags
+/// (combined per `mode`) and none of `not_tags`.
+pub struct SearchFilter {
+    pub matcher: Matcher,
+    pub mask: FieldMask,
+    pub mode: TagMode,
+    pub tags: Vec<String>,
+    pub not_tags: Vec<String>,
+}
+
+impl SearchFilter {
+    pub fn matches(&self, n: &Note) -> bool {
+        let text_match = (self.mask.title && self.matcher.is_match(&n.title))
+            || (self.mask.body && self.matcher.is_match(&n.body))
+            || (self.mask.tags && n.tags.iter().any(|t| self.matcher.is_tag_match(t)));
+
+        // Exclusion is evaluated after the positive tag requirement
+        let tags_match = self.mode.matches(n, &self.tags) && !has_any_tag(n, &self.not_tags);
+
+        text_match && tags_match
+    }
+
+    /// Relevance of `n` for `search`, `None` when it doesn't match: a match in the title beats
+    /// one in the tags, which beats one in the body, and within a field the earlier match wins.
+    pub fn score(&self, n: &Note) -> Option<u32> {
+        if !self.matches(n) {
+            return None;
+        }
+        let title = self.mask.title.then(|| self.matcher.find(&n.title)).flatten().map(|at| (3, at));
+        let tags = self.mask.tags.then(|| n.tags.iter().position(|t| self.matcher.is_tag_match(t))).flatten().map(|at| (2, at));
+        let body = self.mask.body.then(|| self.matcher.find(&n.body)).flatten().map(|at| (1, at));
+        let (field, at) = title.or(tags).or(body)?;
+        let at = u32::try_from(at).unwrap_or(u32::MAX).min(SCORE_STEP - 1);
+        Some(field * SCORE_STEP + (SCORE_STEP - 1 - at))
+    }
+}
+
+/// Score range of each field in [`SearchFilter::score`]; later match positions all score as
+/// the last one.
+const SCORE_STEP: u32 = 1 << 24;
+
+impl Storage {
+    pub fn next_id(&self) -> u64 {
+        self.notes.iter().map(|n| n.id).max().unwrap_or(0) + 1
+    }
+
+    /// The first note whose title is the same as `title` once both are normalized.
+    pub fn find_title(&self, title: &str) -> Option<&Note> {
+        let wanted = normalize_title(title);
+        self.notes.iter().find(|n| normalize_title(&n.title) == wanted)
+    }
+
+    /// Whether writing `other` over this storage would change anything besides the version.
+    pub fn same_content(&self, other: &Storage) -> bool {
+        self.notes == other.notes && self.last_prune == other.last_prune
+    }
+
+    /// Whether the notes no longer have the checksum they were saved with, i.e. the file was
+    /// edited outside rustic-notes or corrupted. The checksum is [`journal::hash`], computed over
+    /// JSON whatever the store format, so converting a store doesn't change it. Stores without
+    /// one (hand-written, SQLite, JSON Lines) never mismatch.
+    pub fn checksum_mismatch(&self) -> bool {
+        self.checksum.as_ref().is_some_and(|c| *c != journal::hash(self))
+    }
+}
+
+impl Note {
+    /// A note created now, with nothing but its title; set the rest with
+    /// `Note { tags, ..Note::new(id, title) }`.
+    pub fn new(id: u64, title: impl Into<String>) -> Self {
+        Note { id, title: title.into(), created_at: Utc::now(), ..Default::default() }
+    }
+
+    /// When the note's content last changed.
+    pub fn last_updated(&self) -> DateTime<Utc> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Toml => "toml",
+            Format::Yaml => "yaml",
+            Format::Sqlite => "sqlite",
+            Format::Jsonl => "jsonl",
+        }
+    }
+
+    /// Format implied by a store file extension (`.json`, `.toml`, `.yaml`/`.yml`, `.db`, `.jsonl`)
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "db" | "sqlite" | "sqlite3" => Some(Format::Sqlite),
+            "jsonl" | "ndjson" => Some(Format::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+pub fn default_store_for(format: Format) -> &'static str {
+    match format {
+        Format::Json => "notes.json",
+        Format::Toml => "notes.toml",
+        Format::Yaml => "notes.yaml",
+        Format::Sqlite => "notes.db",
+        Format::Jsonl => "notes.jsonl",
+    }
+}
+
+/// Loads every note through the backend matching `format`.
+pub fn load(path: &Path, format: Format) -> anyhow::Result<Storage> {
+    store::open(path, format, 0).load_all()
+}
+
+/// Saves every note through the backend matching `format`, first rotating the previous
+/// store into up to `backups` `<store>.bak.N` copies.
+pub fn save(path: &Path, format: Format, storage: &Storage, backups: usize) -> anyhow::Result<()> {
+    store::open(path, format, backups).save_all(storage)
+}
+
+pub fn parse_storage(raw: &str, format: Format) -> anyhow::Result<Storage> {
+    if raw.trim().is_empty() {
+        return Ok(Storage::default());
+    }
+    let storage = match format {
+        Format::Json => serde_json::from_str(raw)?,
+        Format::Toml => toml::from_str(raw)?,
+        Format::Yaml => serde_yaml::from_str(raw)?,
+        Format::Sqlite => anyhow::bail!("sqlite stores are not text files"),
+        Format::Jsonl => jsonl::replay(raw)?.0,
+    };
+    Ok(storage)
+}
+
+pub fn serialize_storage(storage: &Storage, format: Format) -> anyhow::Result<String> {
+    Ok(match format {
+        Format::Sqlite => anyhow::bail!("sqlite stores are not text files"),
+        Format::Jsonl => jsonl::serialize(storage)?,
+        Format::Json | Format::Toml | Format::Yaml => serialize_document(storage, format)?,
+    })
+}
+
+/// `value` as a single JSON, TOML or YAML document, the way stores are written.
+pub fn serialize_document<T: Serialize>(value: &T, format: Format) -> anyhow::Result<String> {
+    Ok(match format {
+        Format::Json => serde_json::to_string_pretty(value)?,
+        Format::Toml => toml::to_string_pretty(value)?,
+        Format::Yaml => serde_yaml::to_string(value)?,
+        Format::Sqlite | Format::Jsonl => anyhow::bail!("{} is not a single-document format", format.name()),
+    })
+}
+
+pub fn normalize_tags(mut tags: Vec<String>) -> Vec<String> {
+    // Trim + dedup (case-insensitive), without empty
+    tags.iter_mut().for_each(|t| *t = t.trim().to_string());
+    tags.retain(|t| !t.is_empty());
+    let mut seen = HashSet::new();
+    tags.into_iter()
+        .filter(|t| seen.insert(t.to_lowercase()))
+        .collect()
+}
+
+/// Titles as compared for uniqueness: trimmed, case-folded, inner whitespace collapsed.
+pub fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+pub fn add_tags(existing: &mut Vec<String>, additions: Vec<String>) {
+    let to_add = normalize_tags(additions);
+    let mut seen: HashSet<String> = existing.iter().map(|t| t.to_lowercase()).collect();
+    for t in to_add {
+        if seen.insert(t.to_lowercase()) {
+            existing.push(t);
+        }
+    }
+}
+
+pub fn remove_tags(existing: &mut Vec<String>, removals: Vec<String>) {
+    let remset: HashSet<String> = removals
+        .into_iter()
+        .map(|s| s.to_lowercase().trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    existing.retain(|t| !remset.contains(&t.to_lowercase()));
+}
+
+pub fn has_all_tags(n: &Note, wanted: &[String]) -> bool {
+    let ntags: Vec<String> = n.tags.iter().map(|t| t.to_lowercase()).collect();
+    wanted.iter().all(|t| ntags.contains(&t.trim().to_lowercase()))
+}
+
+pub fn has_any_tag(n: &Note, wanted: &[String]) -> bool {
+    let ntags: Vec<String> = n.tags.iter().map(|t| t.to_lowercase()).collect();
+    wanted.iter().any(|t| ntags.contains(&t.trim().to_lowercase()))
+}
+
+pub fn editable_from_note(n: &Note) -> EditableNote {
+    EditableNote {
+        title: Some(n.title.clone()),
+        body: Some(n.body.to_string()),
+        meta: Some(n.meta.clone()),
+        tags: Some(n.tags.clone()),
+    }
+}
+
+pub fn to_text(fmt: EditorFmt, e: &EditableNote) -> anyhow::Result<String> {
+    Ok(match fmt {
+        EditorFmt::Json => serde_json::to_string_pretty(e)?,
+        EditorFmt::Toml => toml::to_string_pretty(e)?,
+        EditorFmt::Yaml => serde_yaml::to_string(e)?,
+    })
+}
+
+pub fn from_text(fmt: EditorFmt, s: &str) -> anyhow::Result<EditableNote> {
+    let mut e: EditableNote = match fmt {
+        EditorFmt::Json => serde_json::from_str(s)?,
+        EditorFmt::Toml => toml::from_str(s)?,
+        EditorFmt::Yaml => serde_yaml::from_str(s).map_err(|e| match e.location() {
+            Some(loc) if !e.to_string().contains("line") => {
+                anyhow::anyhow!("{} at line {} column {}", e, loc.line(), loc.column())
+            }
+            _ => e.into(),
+        })?,
+    };
+    e.meta = e.meta.map(meta::normalize).transpose()?;
+    Ok(e)
+}
+
+/// Extra editor sessions granted after an invalid buffer (unless --no-retry).
+pub const EDITOR_RETRIES: usize = 3;
+
+const BANNER_MARK: &str = "rustic-notes: ";
+
+fn comment_prefix(fmt: EditorFmt) -> &'static str {
+    // JSON has no comments; the banner is stripped again before parsing
+    match fmt { EditorFmt::Json => "// ", EditorFmt::Toml | EditorFmt::Yaml => "# " }
+}
+
+/// Puts `error` on top of the buffer as comment lines, for the next editor session.
+pub fn with_error_banner(fmt: EditorFmt, text: &str, error: &anyhow::Error, retries_left: usize) -> String {
+    let prefix = format!("{}{}", comment_prefix(fmt), BANNER_MARK);
+    let mut out = String::new();
+    for line in format!("{}", error).lines().filter(|l| !l.trim().is_empty()) {
+        out.push_str(&format!("{}{}\n", prefix, line));
+    }
+    out.push_str(&format!("{}fix it and save again ({} retries left)\n", prefix, retries_left));
+    out.push_str(text);
+    out
+}
+
+/// Drops the leading lines added by [`with_error_banner`].
+pub fn strip_error_banner(fmt: EditorFmt, text: &str) -> String {
+    let prefix = format!("{}{}", comment_prefix(fmt), BANNER_MARK);
+    let mut rest = text;
+    while rest.starts_with(&prefix) {
+        rest = rest.split_once('\n').map_or("", |(_, r)| r);
+    }
+    rest.to_string()
+}
+
+/// Runs `editor` on `initial` until the result parses, reopening it with the error on top
+/// at most `retries` times. Returns the edited text (without banner) and what it parsed to.
+pub fn edit_with_retries(
+    initial: &str,
+    fmt: EditorFmt,
+    retries: usize,
+    mut editor: impl FnMut(&str) -> anyhow::Result<String>,
+) -> anyhow::Result<(String, EditableNote)> {
+    let mut buffer = initial.to_string();
+    let mut left = retries;
+    loop {
+        let edited = strip_error_banner(fmt, &editor(&buffer)?);
+        match from_text(fmt, &edited) {
+            Ok(e) => return Ok((edited, e)),
+            Err(err) if left > 0 => {
+                buffer = with_error_banner(fmt, &edited, &err, left);
+                left -= 1;
+            }
+            Err(err) => anyhow::bail!("invalid editor content, note left unchanged: {}", err),
+        }
+    }
+}
+
+/// Copies the fields present in `e` into the note (missing ones stay as they are).
+pub fn apply_editable(n: &mut Note, e: EditableNote) {
+    if let Some(t) = e.title { n.title = t; }
+    if let Some(b) = e.body { n.body = b.into(); }
+    if let Some(ts) = e.tags { n.tags = normalize_tags(ts); }
+    if let Some(m) = e.meta { n.meta = m; }
+}
+
+/// Editor command line and which setting provided it (named in errors).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorCommand {
+    pub command: String,
+    pub source: String,
+}
+
+/// Picks the editor: --editor > $VISUAL > $EDITOR > `editor` in the config file > vi/notepad.
+pub fn resolve_editor(flag: Option<&str>, config: Option<&str>) -> EditorCommand {
+    let non_empty = |v: Option<String>| v.filter(|v| !v.trim().is_empty());
+    let candidates = [
+        (non_empty(flag.map(str::to_string)), "--editor"),
+        (non_empty(env::var("VISUAL").ok()), "$VISUAL"),
+        (non_empty(env::var("EDITOR").ok()), "$EDITOR"),
+        (non_empty(config.map(str::to_string)), "the config file"),
+    ];
+    for (command, source) in candidates {
+        if let Some(command) = command {
+            return EditorCommand { command, source: source.to_string() };
+        }
+    }
+    // Select default editor based on OS
+    let default = if cfg!(windows) { "notepad" } else { "vi" };
+    EditorCommand { command: default.to_string(), source: "the default".to_string() }
+}
+
+pub fn open_in_editor(initial: &str, fmt: EditorFmt, editor: &EditorCommand) -> anyhow::Result<String> {
+    let ext = match fmt { EditorFmt::Json => "json", EditorFmt::Toml => "toml", EditorFmt::Yaml => "yaml" };
+    let mut tmp = TempBuilder::new().suffix(&format!(".{}", ext)).tempfile()?;
+    tmp.write_all(initial.as_bytes())?;
+    tmp.flush()?;
+
+    let path = tmp.path().to_path_buf();
+
+    // Allow commands with flags, e.g. "code -w"
+    let mut parts = shell_words::split(&editor.command).unwrap_or_else(|_| vec![editor.command.clone()]);
+    if parts.is_empty() { parts.push(editor.command.clone()); }
+
+    let status = ProcCommand::new(&parts[0])
+        .args(&parts[1..])
+        .arg(&path)
+        .status();
+
+    match status {
+        Ok(s) if s.success() => {
+            let edited = fs::read_to_string(&path)?;
+            Ok(edited)
+        }
+        Ok(s) => anyhow::bail!("The editor ended with status: {:?}", s.code()),
+        Err(e) => anyhow::bail!("Couldn't run the editor `{}` (from {}): {}", editor.command, editor.source, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn note() -> Note {
+        Note {
+            id: 1,
+            title: "Plan".to_string(),
+            body: "draft".into(),
+            tags: vec!["work".to_string()],
+            created_at: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// Editor session as main runs it; the note is only touched when the buffer parses.
+    fn edit(n: &mut Note, fmt: EditorFmt, retries: usize, editor: impl FnMut(&str) -> anyhow::Result<String>) -> anyhow::Result<()> {
+        let initial = to_text(fmt, &editable_from_note(n))?;
+        let (_, edited) = edit_with_retries(&initial, fmt, retries, editor)?;
+        apply_editable(n, edited);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_field_is_rejected_with_its_position() {
+        for (fmt, typo) in [
+            (EditorFmt::Yaml, "titel: New\nbody: draft\n"),
+            (EditorFmt::Json, "{\"titel\": \"New\"}"),
+            (EditorFmt::Toml, "titel = \"New\"\n"),
+        ] {
+            let mut n = note();
+            let err = edit(&mut n, fmt, 0, |_| Ok(typo.to_string())).unwrap_err().to_string();
+            assert!(err.contains("unknown field `titel`"), "{}", err);
+            assert!(err.contains("line 1"), "{}", err);
+            assert_eq!(n, note());
+        }
+    }
+
+    #[test]
+    fn meta_round_trips_through_the_editor() {
+        for fmt in [EditorFmt::Yaml, EditorFmt::Json, EditorFmt::Toml] {
+            let mut n = note();
+            n.meta.insert("project".to_string(), "apollo".to_string());
+            edit(&mut n, fmt, 0, |buffer| {
+                assert!(buffer.contains("apollo"), "{}", buffer);
+                let mut e = from_text(fmt, buffer)?;
+                e.meta.as_mut().unwrap().insert("Source".to_string(), "email = yes".to_string());
+                to_text(fmt, &e)
+            })
+            .unwrap();
+            let expected = [("project", "apollo"), ("source", "email = yes")];
+            assert_eq!(n.meta, expected.map(|(k, v)| (k.to_string(), v.to_string())).into());
+
+            let before = n.clone();
+            let err = edit(&mut n, fmt, 0, |buffer| {
+                let mut e = from_text(fmt, buffer)?;
+                e.meta.as_mut().unwrap().insert("bad key".to_string(), "x".to_string());
+                to_text(fmt, &e)
+            })
+            .unwrap_err();
+            assert!(err.to_string().contains("invalid meta key \"bad key\""), "{}", err);
+            assert_eq!(n, before);
+        }
+    }
+
+    #[test]
+    fn gives_up_after_the_retries_and_leaves_the_note_alone() {
+        let mut n = note();
+        let mut sessions = 0;
+        let result = edit(&mut n, EditorFmt::Yaml, EDITOR_RETRIES, |_| {
+            sessions += 1;
+            Ok("titel: New\n".to_string())
+        });
+        assert!(result.is_err());
+        assert_eq!(sessions, 1 + EDITOR_RETRIES);
+        assert_eq!(n, note());
+    }
+
+    #[test]
+    fn reopens_with_a_banner_that_is_stripped_again() {
+        for fmt in [EditorFmt::Yaml, EditorFmt::Json, EditorFmt::Toml] {
+            let mut n = note();
+            let fixed = to_text(fmt, &EditableNote { title: Some("New".into()), ..Default::default() }).unwrap();
+            let mut buffers = Vec::new();
+            edit(&mut n, fmt, 1, |buffer| {
+                buffers.push(buffer.to_string());
+                // First session: a typo; second: keep the banner and fix the field
+                Ok(if buffers.len() == 1 { "{bad".to_string() } else { format!("{}{}", buffer.lines().next().unwrap(), "\n") + &fixed })
+            })
+            .unwrap();
+            assert!(buffers[1].starts_with(comment_prefix(fmt)), "{}", buffers[1]);
+            assert!(buffers[1].ends_with("{bad"));
+            assert_eq!(n.title, "New");
+            assert_eq!(n.body, "draft");
+        }
+    }
+
+    #[test]
+    fn score_ranks_title_over_tags_over_body_then_position() {
+        let filter = |query: &str, fields: &[Field]| SearchFilter {
+            matcher: Matcher::new(query, false, false).unwrap(),
+            mask: FieldMask::from_fields(fields),
+            mode: TagMode::All,
+            tags: vec![],
+            not_tags: vec![],
+        };
+        let with = |title: &str, body: &str, tags: &[&str]| Note {
+            title: title.to_string(),
+            body: body.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..note()
+        };
+        let rust = filter("rust", &[]);
+        let ranked = [
+            with("Rust tips", "", &[]),
+            with("Learning rust", "rust everywhere", &[]),
+            with("Tips", "", &["rusty", "misc"]),
+            with("Tips", "", &["misc", "rust"]),
+            with("Tips", "Rust first", &[]),
+            with("Tips", "all about rust", &[]),
+        ];
+        let scores: Vec<u32> = ranked.iter().map(|n| rust.score(n).unwrap()).collect();
+        assert!(scores.windows(2).all(|w| w[0] > w[1]), "{:?}", scores);
+        assert_eq!(rust.score(&with("Tips", "nothing here", &["misc"])), None);
+        // Fields left out of the search don't count
+        let bodies = filter("rust", &[Field::Body]);
+        assert_eq!(bodies.score(&ranked[0]), None);
+        assert!(bodies.score(&ranked[1]).unwrap() > bodies.score(&ranked[5]).unwrap());
+    }
+
+    #[test]
+    fn titles_are_compared_without_case_and_spacing() {
+        assert_eq!(normalize_title("  Weekly \t  SYNC\n"), "weekly sync");
+        let storage = Storage { notes: vec![note()], ..Default::default() };
+        assert_eq!(storage.find_title(" plan ").map(|n| n.id), Some(1));
+        assert!(storage.find_title("Plans").is_none());
+    }
+
+    #[test]
+    fn case_sensitive_queries_still_match_tags_in_any_case() {
+        for regex in [false, true] {
+            let m = Matcher::new("RUST", regex, true).unwrap();
+            assert!(m.is_match("the RUST acronym") && !m.is_match("learning rust"));
+            assert!(m.is_tag_match("rust"));
+            let m = Matcher::new("RUST", regex, false).unwrap();
+            assert!(m.is_match("learning rust") && m.is_tag_match("Rust"));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Wiki-style references between notes: `[[12]]` points at a note id, `[[Project kickoff]]`
+//! at a title (compared case-insensitively).
+
+use crate::{Note, Storage};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::LazyLock;
+
+static LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\[([^\[\]]+)\]\]").expect("valid link regex"));
+
+/// What a link points at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Target {
+    Resolved { id: u64 },
+    /// Several notes share the title
+    Ambiguous { ids: Vec<u64> },
+    Dangling,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Link {
+    /// Text between the brackets, as written
+    pub text: String,
+    #[serde(flatten)]
+    pub target: Target,
+}
+
+impl Target {
+    /// Whether the link may refer to note `id` (ambiguous links count for every candidate).
+    pub fn points_to(&self, id: u64) -> bool {
+        match self {
+            Target::Resolved { id: target } => *target == id,
+            Target::Ambiguous { ids } => ids.contains(&id),
+            Target::Dangling => false,
+        }
+    }
+}
+
+/// Texts of the `[[...]]` references in a body, in order.
+pub fn parse(body: &str) -> Vec<&str> {
+    LINK.captures_iter(body).map(|c| c.get(1).map_or("", |m| m.as_str().trim())).collect()
+}
+
+pub fn resolve(text: &str, storage: &Storage) -> Target {
+    if let Ok(id) = text.parse::<u64>() {
+        return match storage.notes.iter().any(|n| n.id == id) {
+            true => Target::Resolved { id },
+            false => Target::Dangling,
+        };
+    }
+    let wanted = text.to_lowercase();
+    let ids: Vec<u64> = storage.notes.iter().filter(|n| n.title.trim().to_lowercase() == wanted).map(|n| n.id).collect();
+    match ids.as_slice() {
+        [] => Target::Dangling,
+        [id] => Target::Resolved { id: *id },
+        _ => Target::Ambiguous { ids },
+    }
+}
+
+/// Outgoing links of a note with their resolution.
+pub fn links(note: &Note, storage: &Storage) -> Vec<Link> {
+    parse(&note.body)
+        .into_iter()
+        .map(|text| Link { text: text.to_string(), target: resolve(text, storage) })
+        .collect()
+}
+
+/// Notes whose body links to `id`, each with the links that do.
+pub fn backlinks(id: u64, storage: &Storage) -> Vec<(&Note, Vec<Link>)> {
+    storage
+        .notes
+        .iter()
+        .filter(|n| n.id != id)
+        .filter_map(|n| {
+            let hits: Vec<Link> = links(n, storage).into_iter().filter(|l| l.target.points_to(id)).collect();
+            (!hits.is_empty()).then_some((n, hits))
+        })
+        .collect()
+}
+
+/// The body with resolved links spelled out as `[[12: Project kickoff]]`.
+pub fn render(body: &str, storage: &Storage) -> String {
+    LINK.replace_all(body, |c: &regex::Captures| match resolve(c[1].trim(), storage) {
+        Target::Resolved { id } => {
+            let title = storage.notes.iter().find(|n| n.id == id).map_or("", |n| n.title.as_str());
+            format!("[[{}: {}]]", id, title)
+        }
+        _ => c[0].to_string(),
+    })
+    .into_owned()
+}
+
+/// The body with every link resolving to note `from` (by id or title) rewritten as `[[to]]`,
+/// or `None` when it has no such link.
+pub fn retarget(body: &str, from: u64, to: u64, storage: &Storage) -> Option<String> {
+    let mut found = false;
+    let rewritten = LINK.replace_all(body, |c: &regex::Captures| match resolve(c[1].trim(), storage) {
+        Target::Resolved { id } if id == from => {
+            found = true;
+            format!("[[{}]]", to)
+        }
+        _ => c[0].to_string(),
+    });
+    found.then(|| rewritten.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: u64, title: &str, body: &str) -> Note {
+        Note { body: body.into(), ..Note::new(id, title) }
+    }
+
+    fn storage() -> Storage {
+        Storage {
+            notes: vec![
+                note(1, "Project kickoff", "See [[2]] and [[plan]] and [[Nowhere]]"),
+                note(2, "Plan", "v1"),
+                note(3, "plan", "v2, back to [[project KICKOFF]]"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolves_ids_titles_and_reports_the_rest() {
+        let s = storage();
+        let found = links(&s.notes[0], &s);
+        let targets: Vec<&Target> = found.iter().map(|l| &l.target).collect();
+        assert_eq!(
+            targets,
+            [&Target::Resolved { id: 2 }, &Target::Ambiguous { ids: vec![2, 3] }, &Target::Dangling]
+        );
+        assert_eq!(resolve("42", &s), Target::Dangling);
+    }
+
+    #[test]
+    fn backlinks_include_title_links() {
+        let s = storage();
+        let ids: Vec<u64> = backlinks(1, &s).iter().map(|(n, _)| n.id).collect();
+        assert_eq!(ids, [3]);
+        let ids: Vec<u64> = backlinks(3, &s).iter().map(|(n, _)| n.id).collect();
+        assert_eq!(ids, [1]);
+    }
+
+    #[test]
+    fn render_spells_out_resolved_links() {
+        let s = storage();
+        assert_eq!(render(&s.notes[0].body, &s), "See [[2: Plan]] and [[plan]] and [[Nowhere]]");
+    }
+
+    #[test]
+    fn retarget_rewrites_id_and_title_links() {
+        let s = storage();
+        assert_eq!(retarget(&s.notes[2].body, 1, 9, &s).as_deref(), Some("v2, back to [[9]]"));
+        // Ambiguous and dangling links are left alone
+        assert_eq!(retarget(&s.notes[0].body, 2, 9, &s).as_deref(), Some("See [[9]] and [[plan]] and [[Nowhere]]"));
+        assert_eq!(retarget(&s.notes[0].body, 3, 9, &s), None);
+    }
+}
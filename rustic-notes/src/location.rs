@@ -0,0 +1,156 @@
+//! Where the store lives and in which format, resolved with the precedence
+//! command-line flag > environment variable > config file > store extension / data-directory default.
+
+use crate::{default_store_for, Format};
+use directories::ProjectDirs;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const STORE_ENV: &str = "RUSTIC_NOTES_STORE";
+pub const FORMAT_ENV: &str = "RUSTIC_NOTES_FORMAT";
+
+/// Which step of the precedence chain provided a setting (shown by --debug).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Source {
+    Flag,
+    Env,
+    Config,
+    Extension,
+    Default,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag => write!(f, "command-line flag"),
+            Source::Env => write!(f, "environment variable"),
+            Source::Config => write!(f, "config file"),
+            Source::Extension => write!(f, "store file extension"),
+            Source::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// `origin` names where `raw` came from in the error.
+fn parse_format(raw: &str, origin: &str) -> anyhow::Result<Format> {
+    match raw.trim().to_lowercase().as_str() {
+        "json" => Ok(Format::Json),
+        "toml" => Ok(Format::Toml),
+        "yaml" | "yml" => Ok(Format::Yaml),
+        "sqlite" | "db" => Ok(Format::Sqlite),
+        "jsonl" | "ndjson" => Ok(Format::Jsonl),
+        _ => anyhow::bail!("{}: unknown format \"{}\" (expected json, toml, yaml, sqlite or jsonl)", origin, raw),
+    }
+}
+
+/// Picks the store format: --format, then RUSTIC_NOTES_FORMAT, then `format` in the config file,
+/// then the store extension, then JSON. Returns a warning when an explicit format disagrees with
+/// the store extension.
+pub fn resolve_format(
+    flag: Option<Format>,
+    env: Option<&str>,
+    config: Option<&str>,
+    store: Option<&Path>,
+) -> anyhow::Result<(Format, Source, Option<String>)> {
+    let explicit = match (flag, env, config) {
+        (Some(f), _, _) => Some((f, Source::Flag)),
+        (None, Some(raw), _) => Some((parse_format(raw, FORMAT_ENV)?, Source::Env)),
+        (None, None, Some(raw)) => Some((parse_format(raw, "config file format")?, Source::Config)),
+        (None, None, None) => None,
+    };
+    let inferred = store.and_then(Format::from_extension);
+    Ok(match (explicit, inferred) {
+        (Some((f, source)), Some(ext)) if f != ext => {
+            let warning = format!(
+                "format {} (from {}) doesn't match the extension of {} ({}); using {}",
+                f.name(),
+                match source {
+                    Source::Flag => "--format",
+                    Source::Env => FORMAT_ENV,
+                    _ => "the config file",
+                },
+                store.map(|p| p.display().to_string()).unwrap_or_default(),
+                ext.name(),
+                f.name()
+            );
+            (f, source, Some(warning))
+        }
+        (Some((f, source)), _) => (f, source, None),
+        (None, Some(ext)) => (ext, Source::Extension, None),
+        (None, None) => (Format::Json, Source::Default, None),
+    })
+}
+
+/// Default store when no path is given: `$XDG_DATA_HOME/rustic-notes/notes.<ext>` (or the
+/// platform equivalent), falling back to the current directory if there is no home directory.
+pub fn default_store_path(format: Format) -> PathBuf {
+    match ProjectDirs::from("", "", "rustic-notes") {
+        Some(dirs) => dirs.data_dir().join(default_store_for(format)),
+        None => PathBuf::from(default_store_for(format)),
+    }
+}
+
+/// Picks the store file: --store / --cwd, then RUSTIC_NOTES_STORE, then `store` in the config
+/// file, then the data directory. A directory means `<DIR>/notes.<ext>`.
+pub fn resolve_store(
+    flag: Option<PathBuf>,
+    cwd: bool,
+    env: Option<PathBuf>,
+    config: Option<PathBuf>,
+    format: Format,
+) -> (PathBuf, Source) {
+    let in_dir = |p: PathBuf| if p.is_dir() { p.join(default_store_for(format)) } else { p };
+    match (flag, env, config) {
+        (Some(p), _, _) => (in_dir(p), Source::Flag),
+        (None, _, _) if cwd => (PathBuf::from(default_store_for(format)), Source::Flag),
+        (None, Some(p), _) => (in_dir(p), Source::Env),
+        (None, None, Some(p)) => (in_dir(p), Source::Config),
+        (None, None, None) => (default_store_path(format), Source::Default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_inferred_from_extension() {
+        assert_eq!(resolve_format(None, None, None, Some(Path::new("a/notes.yml"))).unwrap().0, Format::Yaml);
+        assert_eq!(resolve_format(None, None, None, Some(Path::new("notes.TOML"))).unwrap().0, Format::Toml);
+        let (f, source, _) = resolve_format(None, None, None, Some(Path::new("notes.txt"))).unwrap();
+        assert_eq!((f, source), (Format::Json, Source::Default));
+    }
+
+    #[test]
+    fn explicit_format_wins_with_warning() {
+        let (f, source, warning) = resolve_format(Some(Format::Toml), None, None, Some(Path::new("notes.yaml"))).unwrap();
+        assert_eq!((f, source), (Format::Toml, Source::Flag));
+        assert!(warning.unwrap().contains("using toml"));
+        assert!(resolve_format(Some(Format::Yaml), None, None, Some(Path::new("notes.yaml"))).unwrap().2.is_none());
+    }
+
+    #[test]
+    fn format_flag_beats_env_beats_config_beats_extension() {
+        let yaml = Some(Path::new("notes.yaml"));
+        assert_eq!(resolve_format(Some(Format::Json), Some("toml"), Some("yaml"), yaml).unwrap().1, Source::Flag);
+        let (f, source, warning) = resolve_format(None, Some("TOML"), Some("yaml"), yaml).unwrap();
+        assert_eq!((f, source), (Format::Toml, Source::Env));
+        assert!(warning.unwrap().contains(FORMAT_ENV));
+        assert!(resolve_format(None, Some("xml"), None, yaml).is_err());
+        let (f, source, _) = resolve_format(None, None, Some("toml"), None).unwrap();
+        assert_eq!((f, source), (Format::Toml, Source::Config));
+        let err = resolve_format(None, None, Some("xml"), None).unwrap_err().to_string();
+        assert!(err.contains("config file format: unknown format"), "{}", err);
+    }
+
+    #[test]
+    fn store_flag_beats_env_beats_config_beats_default() {
+        let env = Some(PathBuf::from("from-env.json"));
+        let config = Some(PathBuf::from("from-config.json"));
+        assert_eq!(resolve_store(Some("cli.json".into()), false, env.clone(), config.clone(), Format::Json).1, Source::Flag);
+        assert_eq!(resolve_store(None, true, env.clone(), config.clone(), Format::Json), (PathBuf::from("notes.json"), Source::Flag));
+        assert_eq!(resolve_store(None, false, env, config.clone(), Format::Json), (PathBuf::from("from-env.json"), Source::Env));
+        assert_eq!(resolve_store(None, false, None, config, Format::Json), (PathBuf::from("from-config.json"), Source::Config));
+        assert_eq!(resolve_store(None, false, None, None, Format::Json).1, Source::Default);
+    }
+}
@@ -0,0 +1,145 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Shared locks for read-only commands, exclusive ones for load-modify-save cycles.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+/// Advisory OS-level lock on `<store>.lock`, released when dropped (or when the process dies,
+/// so a leftover lock file never blocks anyone).
+#[derive(Debug)]
+pub struct StoreLock {
+    file: File,
+}
+
+const RETRY_EVERY: Duration = Duration::from_millis(50);
+
+/// Longest `--lock-timeout` accepted: a day.
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `--lock-timeout <SECS>`: a number of seconds, fractions allowed, from 0 up to [`MAX_TIMEOUT`].
+pub fn parse_timeout(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.trim().parse().map_err(|_| format!("invalid number of seconds \"{}\"", s))?;
+    if !(0.0..=MAX_TIMEOUT.as_secs_f64()).contains(&secs) {
+        return Err(format!("expected between 0 and {} seconds, got \"{}\"", MAX_TIMEOUT.as_secs(), s));
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+pub fn lock_path(store: &Path) -> PathBuf {
+    let mut name = store.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+impl StoreLock {
+    /// Waits up to `timeout` for the lock, then fails with a "store is locked" error.
+    pub fn acquire(store: &Path, kind: LockKind, timeout: Duration) -> anyhow::Result<Self> {
+        let path = lock_path(store);
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+
+        let start = Instant::now();
+        loop {
+            let res = match kind {
+                LockKind::Shared => FileExt::try_lock_shared(&file),
+                LockKind::Exclusive => FileExt::try_lock_exclusive(&file),
+            };
+            match res {
+                Ok(()) => return Ok(StoreLock { file }),
+                Err(e) if e.kind() == fs2::lock_contended_error().kind() => {
+                    if start.elapsed() >= timeout {
+                        anyhow::bail!(
+                            "store is locked by another process: {} (gave up after {}s)",
+                            store.display(),
+                            timeout.as_secs_f64()
+                        );
+                    }
+                    thread::sleep(RETRY_EVERY);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load, save, Format, Note};
+    use tempfile::TempDir;
+
+    #[test]
+    fn lock_path_appends_suffix() {
+        assert_eq!(lock_path(Path::new("data/notes.json")), PathBuf::from("data/notes.json.lock"));
+    }
+
+    #[test]
+    fn timeouts_are_finite_and_bounded() {
+        assert_eq!(parse_timeout("0.25"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_timeout("86400"), Ok(MAX_TIMEOUT));
+        for bad in ["inf", "NaN", "-0.5", "86401", "five"] {
+            assert!(parse_timeout(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn exclusive_lock_times_out_while_held() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+        let _held = StoreLock::acquire(&store, LockKind::Exclusive, Duration::ZERO).unwrap();
+        let err = StoreLock::acquire(&store, LockKind::Shared, Duration::from_millis(100)).unwrap_err();
+        assert!(err.to_string().contains("store is locked by another process"));
+    }
+
+    #[test]
+    fn shared_locks_coexist() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+        let _a = StoreLock::acquire(&store, LockKind::Shared, Duration::ZERO).unwrap();
+        let _b = StoreLock::acquire(&store, LockKind::Shared, Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn concurrent_adds_do_not_lose_notes() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("notes.json");
+
+        let workers: Vec<_> = (0..2)
+            .map(|w| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for i in 0..25 {
+                        let _lock = StoreLock::acquire(&store, LockKind::Exclusive, Duration::from_secs(10)).unwrap();
+                        let mut storage = load(&store, Format::Json).unwrap();
+                        storage.notes.push(Note::new(storage.next_id(), format!("worker {} note {}", w, i)));
+                        save(&store, Format::Json, &storage, 0).unwrap();
+                    }
+                })
+            })
+            .collect();
+        workers.into_iter().for_each(|h| h.join().unwrap());
+
+        let storage = load(&store, Format::Json).unwrap();
+        assert_eq!(storage.notes.len(), 50);
+        let mut ids: Vec<u64> = storage.notes.iter().map(|n| n.id).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 50);
+    }
+}
@@ -1,50 +1,262 @@
-use clap::{Parser, Subcommand, ValueEnum};
-use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
-use std::{env, fs, io::Write, path::{Path, PathBuf}, process::Command as ProcCommand};
-use std::collections::HashSet;
-use tempfile::Builder as TempBuilder;
+use clap::parser::ValueSource;
+use clap::error::ErrorKind;
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::Shell;
+use chrono::{DateTime, TimeDelta, Utc};
+use rustic_notes::{
+    add_tags, apply_editable, edit_with_retries, editable_from_note, has_any_tag, load, normalize_tags,
+    open_in_editor, remove_tags, resolve_editor, save, to_text, EditableNote, EditorCommand, EditorFmt,
+    Field, FieldMask, Format, Matcher, Note, SearchFilter, Storage, TagMode, EDITOR_RETRIES,
+};
+use rustic_notes::{attachments, backup, checklist, confirm, crypto, dedupe, doctor, due, git, grep, group, history, html, index, ingest, input, journal, links, markdown, merge, meta, picker, porcelain, prune, rank, resolve, server, snapshot, split, stats, suggest};
+use rustic_notes::config::{Config, Timezone};
+use rustic_notes::export::{self, NoteFormat};
+use rustic_notes::history::Revision;
+use rustic_notes::session::Session;
+use rustic_notes::tui::{self, Action, Outcome};
+use rustic_notes::journal::{Entry, Inverse, Journal};
+use rustic_notes::links::Target;
+use rustic_notes::location::{self, resolve_format, resolve_store};
+use rustic_notes::lock::{self, LockKind, StoreLock};
+use rustic_notes::store::{self, Encryption, StoreOptions};
+use rustic_notes::msg::{Icon, Msg};
+use rustic_notes::style::{DateFormat, Style};
+use rustic_notes::table::{self, Column};
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
-enum Format {
+enum OutputFmt {
+    Human,
     Json,
-    Toml,
     Yaml,
+    /// --porcelain (see the `porcelain` module)
+    #[value(skip)]
+    Porcelain,
 }
 
+/// When `list --table` renders the aligned table
 #[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
-enum EditorFmt {
-    Json,
-    Toml,
-    Yaml,
+enum TableMode {
+    /// Only when stdout is a terminal
+    Auto,
+    /// Even when piped
+    Always,
+}
+
+/// What `list --group-by` groups on
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+enum GroupBy {
+    Tag,
+}
+
+/// Order of `list`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+enum SortOrder {
+    /// Store order, pinned notes first
+    Store,
+    /// By rank (see `move`), unranked notes after the ranked ones by id
+    Manual,
+}
+
+/// Order of `search` results
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+enum SearchSort {
+    /// Best matches first: in the title, then in the tags, then in the body; earlier matches first
+    Relevance,
+    /// Newest first
+    Created,
+    /// Most recently edited first (notes never edited count from their creation)
+    Updated,
 }
 
 #[derive(Debug, Parser)]
-#[command(name = "rustic-notes", version, about = "Note manager (JSON/TOML/YAML) with serde")]
+#[command(
+    name = "rustic-notes",
+    version,
+    about = "Note manager (JSON/TOML/YAML) with serde",
+    after_help = "Store and format precedence (first match wins):\n  \
+        store:  --store / --cwd > $RUSTIC_NOTES_STORE > config `store` > $XDG_DATA_HOME/rustic-notes/notes.<ext>\n  \
+        format: --format > $RUSTIC_NOTES_FORMAT > config `format` > store file extension > json"
+)]
 struct Cli {
-    /// Storage format: json | toml | yaml
+    /// Storage format: json | toml | yaml | sqlite | jsonl (default: $RUSTIC_NOTES_FORMAT, the config file, the store extension, else json)
     #[arg(short = 'f', long = "format", value_enum, default_value_t = Format::Json)]
     format: Format,
 
-    /// File path for the notes (default: notes.{json|toml|yaml})
+    /// File path for the notes; a directory means <DIR>/notes.{json|toml|yaml}
+    /// (default: $RUSTIC_NOTES_STORE, the config file, else $XDG_DATA_HOME/rustic-notes/notes.{json|toml|yaml})
     #[arg(short = 's', long = "store")]
     store: Option<PathBuf>,
 
+    /// Use notes.{json|toml|yaml} in the current directory instead of the data directory
+    #[arg(long = "cwd", conflicts_with = "store")]
+    cwd: bool,
+
+    /// Print where the store path and format came from
+    #[arg(short = 'v', long = "debug")]
+    debug: bool,
+
+    /// Output format: human | json | yaml (json/yaml print results to stdout, messages to stderr)
+    #[arg(short = 'o', long = "output", value_enum, default_value_t = OutputFmt::Human)]
+    output: OutputFmt,
+
+    /// Stable output for scripts: ids from add/edit/remove, id<TAB>title<TAB>tags<TAB>created_at
+    /// rows from list/search, messages on stderr
+    #[arg(long = "porcelain", conflicts_with = "output")]
+    porcelain: bool,
+
+    /// Disable ANSI colors (also disabled by NO_COLOR or when stdout is not a terminal)
+    #[arg(long = "no-color", global = true)]
+    no_color: bool,
+
+    /// ASCII-only output: [ok], [warn] .. instead of emoji, no alignment padding
+    /// (also `plain = true` in the config file; the default when stdout is not a terminal)
+    #[arg(long = "plain", visible_alias = "ascii", global = true)]
+    plain: bool,
+
+    /// Show timestamps in the local time zone instead of UTC (also `timezone = "local"` in the config file)
+    #[arg(long = "local", global = true)]
+    local: bool,
+
+    /// strftime pattern for displayed timestamps, e.g. "%d/%m/%Y %H:%M" (default: %Y-%m-%d %H:%M:%S)
+    #[arg(long = "date-format", value_name = "STRFTIME", global = true)]
+    date_format: Option<String>,
+
+    /// Seconds to wait for another rustic-notes process to release the store
+    #[arg(long = "lock-timeout", value_name = "SECS", default_value = "5", value_parser = lock::parse_timeout)]
+    lock_timeout: Duration,
+
+    /// Save even if another process changed the store since it was loaded (its changes are lost)
+    #[arg(long = "force-save")]
+    force_save: bool,
+
+    /// Don't warn when the store's checksum doesn't match its notes (for stores edited by hand)
+    #[arg(long = "no-verify")]
+    no_verify: bool,
+
+    /// Number of rotated backups (<store>.bak.1 ..) kept on every save; 0 disables them
+    #[arg(long = "backups", value_name = "N", default_value_t = 3)]
+    backups: usize,
+
+    /// Number of operations kept in the undo journal (<store>.journal); 0 disables it
+    #[arg(long = "journal-size", value_name = "N", default_value_t = journal::DEFAULT_LIMIT)]
+    journal_size: usize,
+
+    /// Revisions kept per note by `edit` (default 5; 0 disables history)
+    #[arg(long = "history-depth", value_name = "N", default_value_t = history::DEFAULT_DEPTH)]
+    history_depth: usize,
+
+    /// Encrypt the store when saving (passphrase from $RUSTIC_NOTES_PASSPHRASE or a prompt; also
+    /// `encrypt = true` in the config file)
+    #[arg(long = "encrypt")]
+    encrypt: bool,
+
+    /// Print the man page (roff) to stdout
+    #[arg(long = "generate-man", hide = true, exclusive = true)]
+    generate_man: bool,
+
+    /// Always present, except with --generate-man
     #[command(subcommand)]
-    command: Command,
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ExportTarget {
+    /// Static site: index.html, one page per note (markdown rendered) and one per tag
+    Html {
+        /// Output directory (created if needed; existing pages are overwritten)
+        #[arg(long = "dir", value_name = "DIR", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+        /// Only export the notes having ALL these tags (comma-separated)
+        #[arg(long = "tag", value_delimiter = ',', value_hint = ValueHint::Other)]
+        tag: Vec<String>,
+    },
+    /// A single note, as markdown with front matter or as JSON/YAML/TOML
+    Note {
+        /// ID, or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE")]
+        note: String,
+        /// File to write (missing directories are created), or - for stdout
+        #[arg(long = "out", value_name = "PATH", value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// Format of the file (default: from the --out extension, else markdown)
+        #[arg(long = "format", value_enum)]
+        format: Option<NoteFormat>,
+        /// Replace the file if it already exists
+        #[arg(long = "force")]
+        force: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotAction {
+    /// List the snapshots, oldest first
+    List,
+    /// Copy a snapshot back over the store (the current store goes to the backups)
+    Restore {
+        /// File name of the snapshot, as shown by `snapshot list`
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long = "yes", visible_alias = "force")]
+        yes: bool,
+    },
+    /// Delete all but the newest snapshots
+    Prune {
+        /// Number of snapshots to keep
+        #[arg(long = "keep", value_name = "N")]
+        keep: usize,
+    },
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Adds a new note
     Add {
-        title: String,
-        /// Body of the note (use -b "long text")
-        #[arg(short = 'b', long = "body", default_value = "")]
-        body: String,
+        #[arg(required_unless_present = "open_editor")]
+        title: Option<String>,
+        /// Body of the note (use -b "long text"; "-" reads it from stdin)
+        #[arg(short = 'b', long = "body")]
+        body: Option<String>,
+        /// Read the body from stdin until EOF (same as --body -)
+        #[arg(long = "stdin", conflicts_with = "body")]
+        stdin: bool,
+        /// Read the body from a UTF-8 text file
+        #[arg(long = "body-file", value_name = "PATH", conflicts_with_all = ["body", "stdin"])]
+        body_file: Option<PathBuf>,
+        /// Largest --body-file accepted, in bytes
+        #[arg(long = "max-body-size", value_name = "BYTES", default_value_t = input::DEFAULT_MAX_BODY_SIZE)]
+        max_body_size: u64,
         /// Tags separated by commas, e.g.: -t work,ideas
-        #[arg(short = 't', long = "tags", value_delimiter = ',')]
+        #[arg(short = 't', long = "tags", value_delimiter = ',', value_hint = ValueHint::Other)]
         tags: Vec<String>,
+        /// Custom field (repeatable), e.g.: --meta project=apollo --meta source=email
+        #[arg(long = "meta", value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+        /// Write the note in $VISUAL or $EDITOR, starting from the other flags
+        #[arg(long = "open-editor")]
+        open_editor: bool,
+        /// Format of the temporary file opened in the editor
+        #[arg(long = "editor-format", value_enum, default_value_t = EditorFmt::Yaml)]
+        editor_format: EditorFmt,
+        /// Editor command, e.g. "code -w" (default: $VISUAL, $EDITOR, then `editor` in the config file)
+        #[arg(long = "editor", value_name = "CMD")]
+        editor: Option<String>,
+        /// Abort on the first invalid editor buffer instead of reopening it with the error
+        #[arg(long = "no-retry")]
+        no_retry: bool,
+        /// Apply the editor changes without showing the diff and asking first
+        #[arg(long = "no-confirm")]
+        no_confirm: bool,
+        /// Refuse the note if another one has the same title (ignoring case and spacing)
+        #[arg(long = "unique-title")]
+        unique_title: bool,
+        /// Add the note even when the title is taken and `unique_titles` is set in the config file
+        #[arg(long = "allow-duplicate", conflicts_with = "unique_title")]
+        allow_duplicate: bool,
     },
 
     /// List all notes
@@ -52,322 +264,2100 @@ enum Command {
         /// Shows also the body
         #[arg(short = 'l', long = "long")]
         long: bool,
+        /// Hide notes containing ANY of these tags (comma-separated)
+        #[arg(long = "not-tags", value_delimiter = ',', value_hint = ValueHint::Other)]
+        not_tags: Vec<String>,
+        /// Only notes with this custom field (repeatable, all must match)
+        #[arg(long = "meta", value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+        /// Render an aligned table (auto: only on a terminal; always: also when piped)
+        #[arg(long = "table", value_enum, value_name = "WHEN", num_args = 0..=1, default_missing_value = "auto")]
+        table: Option<TableMode>,
+        /// Only notes with unchecked checklist items, the most open first
+        #[arg(long = "todo")]
+        todo: bool,
+        /// Order of the notes (inside each group with --group-by)
+        #[arg(long = "sort", value_enum, default_value_t = SortOrder::Store)]
+        sort: SortOrder,
+        /// List the notes under a heading per tag, untagged ones last
+        #[arg(long = "group-by", value_enum, value_name = "FIELD", conflicts_with = "table")]
+        group_by: Option<GroupBy>,
+        /// With --group-by tag, list each note under its first tag only
+        #[arg(long = "first-tag-only", requires = "group_by")]
+        first_tag_only: bool,
     },
 
     /// Search notes by text and/or tags
     Search {
         /// Text to search (in title, body or tags)
         query: String,
-        /// Require the note to contain ALL these tags (comma-separated)
-        #[arg(short = 't', long = "tags", value_delimiter = ',')]
+        /// Require the note to contain ALL these tags (comma-separated, see --any-tags)
+        #[arg(short = 't', long = "tags", value_delimiter = ',', value_hint = ValueHint::Other)]
         tags: Vec<String>,
+        /// Treat the query as a regular expression, e.g.: --regex "meeting|standup"
+        #[arg(long = "regex", default_value_t = false)]
+        regex: bool,
+        /// Match the query case-sensitively in titles and bodies; tags never care about case
+        /// (default: case-insensitive, or `search_case_sensitive` in the config file)
+        #[arg(long = "case-sensitive", default_value_t = false)]
+        case_sensitive: bool,
+        /// Ignore case even when `search_case_sensitive` is set in the config file
+        #[arg(short = 'i', long = "ignore-case", conflicts_with = "case_sensitive")]
+        ignore_case: bool,
+        /// Only look in these fields (repeatable, default: all), e.g.: --in title --in tags
+        #[arg(long = "in", value_enum, value_name = "FIELD")]
+        fields: Vec<Field>,
+        /// Reject notes containing ANY of these tags (comma-separated)
+        #[arg(long = "not-tags", value_delimiter = ',', value_hint = ValueHint::Other)]
+        not_tags: Vec<String>,
+        /// Require AT LEAST ONE of the --tags instead of all of them
+        #[arg(long = "any-tags", default_value_t = false)]
+        any_tags: bool,
+        /// Require this custom field (repeatable, all must match)
+        #[arg(long = "meta", value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+        /// Order of the results
+        #[arg(long = "sort", value_enum, default_value_t = SearchSort::Relevance)]
+        sort: SearchSort,
+        /// Print only the first N results
+        #[arg(long = "limit", value_name = "N")]
+        limit: Option<usize>,
     },
 
-    /// Delete a note by id
-    Remove { id: u64 },
+    /// Show the body lines matching a regex, with line numbers and context
+    Grep {
+        /// Regular expression, matched against each line of the bodies
+        pattern: String,
+        /// Ignore case
+        #[arg(short = 'i', long = "ignore-case")]
+        ignore_case: bool,
+        /// Only match whole words
+        #[arg(short = 'w', long = "word")]
+        word: bool,
+        /// Lines of context around each match
+        #[arg(short = 'C', long = "context", value_name = "N", default_value_t = 0)]
+        context: usize,
+        /// Only look in notes having ALL these tags (comma-separated)
+        #[arg(short = 't', long = "tags", value_delimiter = ',', value_hint = ValueHint::Other)]
+        tags: Vec<String>,
+    },
+
+    /// Delete notes by id, or every note matching --tag/--query
+    Remove {
+        /// IDs, id lists or ranges (3-7,12) or titles (fuzzy-matched) of the notes to delete
+        #[arg(value_name = "NOTE", required_unless_present_any = ["tag", "query", "pick"], conflicts_with_all = ["tag", "query", "pick"])]
+        ids: Vec<String>,
+        /// Choose the note in an interactive fuzzy finder
+        #[arg(long = "pick", conflicts_with_all = ["tag", "query"])]
+        pick: bool,
+        /// Delete the notes that have ALL these tags (comma-separated)
+        #[arg(long = "tag", value_delimiter = ',', value_hint = ValueHint::Other)]
+        tag: Vec<String>,
+        /// Delete the notes whose title, body or tags contain this text
+        #[arg(long = "query")]
+        query: Option<String>,
+        /// Don't ask before deleting more than one note
+        #[arg(short = 'y', long = "yes", visible_alias = "force")]
+        yes: bool,
+        /// Also delete the notes' attachment directories
+        #[arg(long = "hard")]
+        hard: bool,
+    },
 
     /// Edit note fields by id
     Edit {
-        /// ID
-        id: u64,
+        /// ID, or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE", required_unless_present = "pick", conflicts_with = "pick")]
+        note: Option<String>,
+        /// Choose the note in an interactive fuzzy finder
+        #[arg(long = "pick")]
+        pick: bool,
         #[arg(long)]
         title: Option<String>,
+        /// New body ("-" reads it from stdin)
         #[arg(long)]
         body: Option<String>,
-        #[arg(long, value_delimiter = ',')]
+        /// Read the new body from stdin until EOF (same as --body -)
+        #[arg(long = "stdin", conflicts_with = "body")]
+        stdin: bool,
+        /// Read the new body from a UTF-8 text file (with --open-editor it seeds the editor buffer)
+        #[arg(long = "body-file", value_name = "PATH", conflicts_with_all = ["body", "stdin"])]
+        body_file: Option<PathBuf>,
+        /// Largest --body-file accepted, in bytes
+        #[arg(long = "max-body-size", value_name = "BYTES", default_value_t = input::DEFAULT_MAX_BODY_SIZE)]
+        max_body_size: u64,
+        #[arg(long, value_delimiter = ',', value_hint = ValueHint::Other)]
         tags: Option<Vec<String>>,
-        #[arg(long = "add-tags", value_delimiter = ',')]
+        #[arg(long = "add-tags", value_delimiter = ',', value_hint = ValueHint::Other)]
         add_tags: Vec<String>,
-        #[arg(long = "rm-tags", value_delimiter = ',')]
+        #[arg(long = "rm-tags", value_delimiter = ',', value_hint = ValueHint::Other)]
         rm_tags: Vec<String>,
-        /// Opens a temporary file in the editor defined in $VISUAL or $EDITOR to edit YAML/JSON/TOML
+        /// Set a custom field (repeatable), e.g.: --meta project=apollo
+        #[arg(long = "meta", value_name = "KEY=VALUE")]
+        meta: Vec<String>,
+        /// Remove a custom field (repeatable)
+        #[arg(long = "rm-meta", value_name = "KEY")]
+        rm_meta: Vec<String>,
+        /// Opens a temporary file in the editor (see --editor) to edit YAML/JSON/TOML
         #[arg(long = "open-editor", default_value_t = false)]
         open_editor: bool,
         /// Format of the temporary file opened in the editor
         #[arg(long = "editor-format", value_enum, default_value_t = EditorFmt::Yaml)]
         editor_format: EditorFmt,
+        /// Editor command, e.g. "code -w" (default: $VISUAL, $EDITOR, then `editor` in the config file)
+        #[arg(long = "editor", value_name = "CMD")]
+        editor: Option<String>,
+        /// Abort on the first invalid editor buffer instead of reopening it with the error
+        #[arg(long = "no-retry")]
+        no_retry: bool,
+        /// Apply the editor changes without showing the diff and asking first
+        #[arg(long = "no-confirm")]
+        no_confirm: bool,
     },
-}
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-struct Storage {
-    notes: Vec<Note>,
-}
+    /// Copy every note into another store, e.g. from JSON into SQLite and back
+    Convert {
+        /// Destination store
+        #[arg(long = "to", value_name = "PATH")]
+        to: PathBuf,
+        /// Destination format (default: from the --to extension)
+        #[arg(long = "to-format", value_enum)]
+        to_format: Option<Format>,
+        /// Replace a destination that already holds notes
+        #[arg(long = "force")]
+        force: bool,
+    },
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Note {
-    id: u64,
-    title: String,
-    body: String,
-    tags: Vec<String>,
-    created_at: DateTime<Utc>,
+    /// Append the notes of another store, skipping duplicates and remapping colliding ids
+    Merge {
+        /// Store to merge into this one
+        #[arg(long = "other", value_name = "PATH")]
+        other: PathBuf,
+        /// Format of the other store (default: from its extension)
+        #[arg(long = "other-format", value_enum)]
+        other_format: Option<Format>,
+        /// When titles match but bodies differ, overwrite the local note instead of keeping both
+        #[arg(long = "prefer-other")]
+        prefer_other: bool,
+        /// Also append notes whose title, body and tags are already in this store
+        #[arg(long = "keep-duplicates")]
+        keep_duplicates: bool,
+    },
+
+    /// Print one note with its body ([[links]] spelled out with their titles)
+    Show {
+        /// ID, id list or range (3-7,12), or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE", required_unless_present = "pick", conflicts_with = "pick")]
+        note: Option<String>,
+        /// Choose the note in an interactive fuzzy finder
+        #[arg(long = "pick")]
+        pick: bool,
+        /// Display the body as formatted markdown (headings, emphasis, lists, code blocks)
+        #[arg(long = "render")]
+        render: bool,
+        /// Also print the body's word, character and line counts
+        #[arg(long = "stats")]
+        stats: bool,
+    },
+
+    /// Pin a note so `list` shows it first
+    Pin {
+        /// ID, id list or range (3-7,12), or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE")]
+        note: String,
+        /// Unpin it instead
+        #[arg(long = "off")]
+        off: bool,
+    },
+
+    /// Set when a note is due, or list the notes due soon (and remind of them with --notify)
+    Due {
+        /// ID, or part of the title (fuzzy-matched), to set the due date of
+        #[arg(value_name = "NOTE", requires = "when")]
+        note: Option<String>,
+        /// Due date, e.g. 2025-10-20 or 2025-10-20T09:00:00Z (UTC unless given)
+        #[arg(long = "at", value_name = "DATE", value_parser = due::parse, group = "when", requires = "note")]
+        at: Option<DateTime<Utc>>,
+        /// Remove the due date instead
+        #[arg(long = "clear", group = "when", requires = "note")]
+        clear: bool,
+        /// List the notes due within this long, overdue ones included, e.g. 1d or 2w
+        #[arg(long = "within", value_name = "AGE", default_value = "1d", conflicts_with = "note")]
+        within: prune::Age,
+        /// Send a system notification for each one, at most once a day per note (for cron)
+        #[arg(long = "notify", conflicts_with = "note")]
+        notify: bool,
+    },
+
+    /// Put a note in your own order, shown by `list --sort manual`
+    #[command(group(ArgGroup::new("position").required(true)))]
+    Move {
+        /// ID, or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE")]
+        note: String,
+        /// Right before this note
+        #[arg(long = "before", value_name = "NOTE", group = "position")]
+        before: Option<String>,
+        /// Right after this note
+        #[arg(long = "after", value_name = "NOTE", group = "position")]
+        after: Option<String>,
+        /// Ahead of every other note
+        #[arg(long = "top", group = "position")]
+        top: bool,
+        /// After the other ranked notes (the unranked ones still follow)
+        #[arg(long = "bottom", group = "position")]
+        bottom: bool,
+    },
+
+    /// Check or uncheck a checklist item ("- [ ] ..." line) in a note's body
+    Toggle {
+        /// ID, or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE")]
+        note: String,
+        /// Item number, counting from 1 in the order they appear (nested items included)
+        #[arg(value_name = "ITEM")]
+        item: usize,
+    },
+
+    /// Totals for the whole store: notes, words, characters and the longest note
+    Stats,
+
+    /// Copy a file into the note's attachment directory
+    Attach {
+        id: u64,
+        file: PathBuf,
+    },
+
+    /// Delete one of the note's attachments
+    Detach {
+        id: u64,
+        /// File name as shown by `show`
+        name: String,
+    },
+
+    /// List the [[links]] in a note's body and what they resolve to
+    Links { id: u64 },
+
+    /// List the notes that link to this one
+    Backlinks { id: u64 },
+
+    /// List the previous versions of a note, or put one back with --restore
+    History {
+        id: u64,
+        /// Revision to restore (1 = oldest); the current version is kept as a new revision
+        #[arg(long = "restore", value_name = "N")]
+        restore: Option<usize>,
+    },
+
+    /// Revert the most recent add, edit, remove, merge or doctor --fix
+    Undo {
+        /// Show the last 10 recorded operations instead
+        #[arg(long = "list")]
+        list: bool,
+    },
+
+    /// Archive notes not updated for a while (tags them "archived"), or delete archived ones
+    Prune {
+        /// Notes not updated for this long are stale, e.g. 90d, 12w or 1y (default: auto_prune_days from the config)
+        #[arg(long = "older-than", value_name = "AGE")]
+        older_than: Option<prune::Age>,
+        /// Only list the notes that would be archived (or deleted)
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+        /// Delete the archived notes that are older than --older-than instead
+        #[arg(long = "delete", requires = "older_than")]
+        delete: bool,
+        /// Don't ask before deleting
+        #[arg(short = 'y', long = "yes", visible_alias = "force")]
+        yes: bool,
+    },
+
+    /// Rebuild the full-text index used by `search` when `search_index = true`
+    Reindex,
+
+    /// Break a note into one note per `## ` section, leaving links to them in the original
+    Split {
+        /// ID, or part of the title (fuzzy-matched)
+        #[arg(value_name = "NOTE")]
+        note: String,
+        /// Start sections at the lines beginning with this text instead of a `## ` heading
+        #[arg(long = "delimiter", value_name = "TEXT", default_value = split::DEFAULT_DELIMITER)]
+        delimiter: String,
+        /// Delete the original note instead of leaving links in it
+        #[arg(long = "remove-original")]
+        remove_original: bool,
+        /// Only print the notes that would be created
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Merge two notes into one, archiving the other and moving links to it
+    MergeNotes {
+        /// IDs or parts of the titles (fuzzy-matched) of the two notes
+        #[arg(value_name = "NOTE", num_args = 2, required = true)]
+        notes: Vec<String>,
+        /// Note to keep (default: the first one)
+        #[arg(long = "into", value_name = "NOTE")]
+        into: Option<String>,
+    },
+
+    /// Remove notes with the same title, body and tags as an older note
+    Dedupe {
+        /// Only list the duplicates
+        #[arg(long = "dry-run")]
+        dry_run: bool,
+        /// Don't ask before removing
+        #[arg(short = 'y', long = "yes", visible_alias = "force")]
+        yes: bool,
+    },
+
+    /// Check the store for duplicate ids and titles, malformed tags and other hand-editing mistakes
+    Doctor {
+        /// Repair ids and tags (the original is kept as a backup)
+        #[arg(long = "fix")]
+        fix: bool,
+    },
+
+    /// Encrypt the store in place (ChaCha20-Poly1305, key derived with Argon2)
+    Encrypt,
+
+    /// Rewrite a jsonl store without its superseded records
+    Compact,
+
+    /// Commit the store to the git repository it lives in, optionally pulling and pushing
+    Sync {
+        /// Run `git pull` after committing
+        #[arg(long = "pull")]
+        pull: bool,
+        /// Run `git push` at the end
+        #[arg(long = "push")]
+        push: bool,
+    },
+
+    /// Serve a JSON HTTP API over the store (GET/POST /notes, GET/PATCH/DELETE /notes/ID, GET /search)
+    Serve {
+        #[arg(long = "port", default_value_t = 7777)]
+        port: u16,
+        /// Address to listen on; anything but 127.0.0.1 exposes the notes to other machines
+        #[arg(long = "bind", value_name = "ADDR", default_value = "127.0.0.1")]
+        bind: String,
+    },
+
+    /// Turn the markdown files of a directory into notes, updating them when the files change
+    Ingest {
+        /// Directory to read the .md files from (not its subdirectories)
+        #[arg(long = "dir", value_name = "DIR")]
+        dir: PathBuf,
+        /// Keep running and ingest files as they appear or change
+        #[arg(long = "watch")]
+        watch: bool,
+        /// Seconds between two looks at the directory with --watch
+        #[arg(long = "interval", value_name = "SECS", default_value_t = 2.0, requires = "watch")]
+        interval: f64,
+        /// Move the ingested files into a processed/ subdirectory
+        #[arg(long = "move")]
+        move_files: bool,
+    },
+
+    /// Browse notes in a two-pane terminal interface (a add, e edit, d remove, p pin, / search, t tags, q quit)
+    Tui,
+
+    /// Write the notes out in another form
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+
+    /// Decrypt the store in place
+    Decrypt,
+
+    /// List the store backups, or put one back in place with --which
+    RestoreBackup {
+        /// Backup to restore (1 = most recent)
+        #[arg(long = "which", value_name = "N")]
+        which: Option<usize>,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long = "yes", visible_alias = "force")]
+        yes: bool,
+    },
+
+    /// Copy the store to <DIR>/<name>-YYYYMMDD-HHMMSS.<ext>, unless it is unchanged since the last snapshot
+    Snapshot {
+        /// Where the snapshots are kept (default: `snapshots` next to the store)
+        #[arg(long = "dir", value_name = "DIR", global = true, value_hint = ValueHint::DirPath)]
+        dir: Option<PathBuf>,
+        #[command(subcommand)]
+        action: Option<SnapshotAction>,
+    },
+
+    /// Print a completion script for SHELL to stdout, e.g.: rustic-notes completions zsh > _rustic-notes
+    Completions { shell: Shell },
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
-struct EditableNote {
-    title: Option<String>,
-    body: Option<String>,
-    tags: Option<Vec<String>>, // if omitted, they remain
+/// Prints a human message: to stdout in human mode, to stderr when stdout carries structured output
+macro_rules! say {
+    ($out:expr, $($arg:tt)*) => {
+        if $out == OutputFmt::Human { println!($($arg)*) } else { eprintln!($($arg)*) }
+    };
 }
 
-impl Storage {
-    fn next_id(&self) -> u64 {
-        self.notes.iter().map(|n| n.id).max().unwrap_or(0) + 1
+/// Serializes a result for --output json|yaml; commands without a porcelain form print it as
+/// JSON on one line with --porcelain
+fn emit<T: Serialize + ?Sized>(out: OutputFmt, value: &T) -> anyhow::Result<()> {
+    match out {
+        OutputFmt::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        OutputFmt::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        OutputFmt::Porcelain => println!("{}", serde_json::to_string(value)?),
+        OutputFmt::Human => {}
     }
+    Ok(())
 }
 
-fn default_store_for(format: Format) -> &'static str {
-    match format {
-        Format::Json => "notes.json",
-        Format::Toml => "notes.toml",
-        Format::Yaml => "notes.yaml",
-    }
+/// `--porcelain` rows for `notes`
+fn print_rows(notes: &[&Note]) {
+    notes.iter().for_each(|n| println!("{}", porcelain::row(n)));
 }
 
-fn load(path: &Path, format: Format) -> anyhow::Result<Storage> {
-    if !path.exists() {
-        return Ok(Storage::default());
-    }
-    let raw = fs::read_to_string(path)?;
-    if raw.trim().is_empty() {
-        return Ok(Storage::default());
-    }
-    let storage = match format {
-        Format::Json => serde_json::from_str(&raw)?,
-        Format::Toml => toml::from_str(&raw)?,
-        Format::Yaml => serde_yaml::from_str(&raw)?,
-    };
-    Ok(storage)
+/// `show --output json|yaml`: the note, plus its counts with --stats
+#[derive(Serialize)]
+struct ShownNote<'a> {
+    #[serde(flatten)]
+    note: &'a Note,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<stats::BodyStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checklist: Option<checklist::Progress>,
 }
 
-fn save(path: &Path, format: Format, storage: &Storage) -> anyhow::Result<()> {
-    if let Some(parent) = path.parent() {
-        if !parent.as_os_str().is_empty() {
-            fs::create_dir_all(parent)?;
+/// `show` for one note, in human form.
+fn show_note(style: &Style, shown: &ShownNote, storage: &Storage, store_path: &std::path::Path, render: bool) {
+    let n = shown.note;
+    print_header(style, n);
+    if let Some(p) = shown.checklist {
+        println!("Checklist: {}/{} done", p.done, p.total);
+    }
+    if !n.body.is_empty() {
+        println!();
+        let body = links::render(&n.body, storage);
+        if render {
+            println!("{}", markdown::render(&body, markdown::terminal_width(), style));
+        } else {
+            println!("{}", body);
         }
     }
-    let raw = match format {
-        Format::Json => serde_json::to_string_pretty(storage)?,
-        Format::Toml => toml::to_string_pretty(storage)?,
-        Format::Yaml => serde_yaml::to_string(storage)?,
-    };
-    fs::write(path, raw)?;
-    Ok(())
+    if !n.meta.is_empty() {
+        println!();
+        println!("Meta:");
+        for (key, value) in &n.meta {
+            println!("    {} = {}", key, value);
+        }
+    }
+    if !n.attachments.is_empty() {
+        println!();
+        println!("Attachments:");
+        for relative in &n.attachments {
+            let size = std::fs::metadata(attachments::absolute(store_path, relative))
+                .map_or_else(|_| style.alert("missing"), |m| attachments::human_size(m.len()));
+            println!("    {}  ({})", relative.display(), size);
+        }
+    }
+    if let Some(c) = &shown.stats {
+        println!();
+        println!("{} words, {} characters, {} lines", c.words, c.characters, c.lines);
+    }
 }
 
-fn normalize_tags(mut tags: Vec<String>) -> Vec<String> {
-    // Trim + dedup (case-insensitive), without empty
-    tags.iter_mut().for_each(|t| *t = t.trim().to_string());
-    tags.retain(|t| !t.is_empty());
-    let mut seen = HashSet::new();
-    tags.into_iter()
-        .filter(|t| seen.insert(t.to_lowercase()))
-        .collect()
+/// `project=apollo, source=email` for messages about --meta filters.
+fn describe_meta(meta: &[(String, String)]) -> String {
+    meta.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(", ")
 }
 
-fn add_tags(existing: &mut Vec<String>, additions: Vec<String>) {
-    let to_add = normalize_tags(additions);
-    let mut seen: HashSet<String> = existing.iter().map(|t| t.to_lowercase()).collect();
-    for t in to_add {
-        if seen.insert(t.to_lowercase()) {
-            existing.push(t);
-        }
-    }
+fn print_header(style: &Style, n: &Note) {
+    println!("{}", header_line(style, n));
 }
 
-fn remove_tags(existing: &mut Vec<String>, removals: Vec<String>) {
-    let remset: HashSet<String> = removals
-        .into_iter()
-        .map(|s| s.to_lowercase().trim().to_string())
-        .filter(|s| !s.is_empty())
+fn header_line(style: &Style, n: &Note) -> String {
+    let pin = if n.pinned { format!("  {}", style.icon(Icon::Pinned)) } else { String::new() };
+    format!(
+        "{}  {}  [{}]  {}{}",
+        style.id(&format!("#{:>3}", n.id)),
+        style.title(&n.title),
+        style.tags(&n.tags.join(",")),
+        style.timestamp(&n.created_at),
+        pin
+    )
+}
+
+const TABLE_COLUMNS: [Column; 4] = [
+    Column { header: "ID", max_width: 6 },
+    Column { header: "Title", max_width: 40 },
+    Column { header: "Tags", max_width: 30 },
+    Column { header: "Created", max_width: 26 },
+];
+
+fn print_table(style: &Style, notes: &[&Note]) {
+    let rows: Vec<Vec<String>> = notes
+        .iter()
+        .map(|n| {
+            vec![
+                n.id.to_string(),
+                n.title.clone(),
+                n.tags.join(","),
+                style.dates.format(&n.created_at),
+            ]
+        })
         .collect();
-    existing.retain(|t| !remset.contains(&t.to_lowercase()));
+    print!("{}", table::render(&TABLE_COLUMNS, &rows, style.msg));
+}
+
+/// How `--open-editor` runs for add and edit.
+struct EditorSession {
+    fmt: EditorFmt,
+    editor: EditorCommand,
+    no_retry: bool,
+    no_confirm: bool,
 }
 
-fn editable_from_note(n: &Note) -> EditableNote {
-    EditableNote {
-        title: Some(n.title.clone()),
-        body: Some(n.body.clone()),
-        tags: Some(n.tags.clone()),
+impl EditorSession {
+    /// Opens the editor on `initial`, then shows the changes against `before` and asks before
+    /// they are applied. `None` means the user declined.
+    fn run(&self, initial: &str, before: &str, style: &Style, out: OutputFmt) -> anyhow::Result<Option<EditableNote>> {
+        let retries = if self.no_retry { 0 } else { EDITOR_RETRIES };
+        let open = |buf: &str| open_in_editor(buf, self.fmt, &self.editor);
+        let (text, edited) = edit_with_retries(initial, self.fmt, retries, open)?;
+        if text == before || self.no_confirm {
+            return Ok(Some(edited));
+        }
+        let diff = TextDiff::from_lines(before, &text);
+        for change in diff.iter_all_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Insert => say!(out, "{}", style.added(&format!("+{}", line))),
+                ChangeTag::Delete => say!(out, "{}", style.removed(&format!("-{}", line))),
+                ChangeTag::Equal => say!(out, " {}", line),
+            }
+        }
+        Ok(confirm::confirm("Apply these changes?", false)?.then_some(edited))
     }
 }
 
-fn to_text(fmt: EditorFmt, e: &EditableNote) -> anyhow::Result<String> {
-    Ok(match fmt {
-        EditorFmt::Json => serde_json::to_string_pretty(e)?,
-        EditorFmt::Toml => toml::to_string_pretty(e)?,
-        EditorFmt::Yaml => serde_yaml::to_string(e)?,
-    })
+/// `--body -` and `--stdin` read the body from stdin; any other value is used as is.
+/// The note named by a NOTE argument, or picked interactively when there is none (`--pick`).
+fn note_or_pick(notes: &[Note], arg: Option<&str>, msg: Msg) -> anyhow::Result<u64> {
+    match arg {
+        Some(arg) => resolve::note_id(notes, arg),
+        None => picker::pick(notes, msg),
+    }
 }
 
-fn from_text(fmt: EditorFmt, s: &str) -> anyhow::Result<EditableNote> {
-    Ok(match fmt {
-        EditorFmt::Json => serde_json::from_str(s)?,
-        EditorFmt::Toml => toml::from_str(s)?,
-        EditorFmt::Yaml => serde_yaml::from_str(s)?,
-    })
+fn body_arg(body: Option<String>, stdin: bool) -> anyhow::Result<Option<String>> {
+    if stdin || body.as_deref() == Some("-") {
+        return Ok(Some(input::read_body(io::stdin().lock())?));
+    }
+    Ok(body)
+}
+
+const HISTORY_COLUMNS: [Column; 5] = [
+    Column { header: "Rev", max_width: 7 },
+    Column { header: "Saved", max_width: 26 },
+    Column { header: "Title", max_width: 40 },
+    Column { header: "Tags", max_width: 30 },
+    Column { header: "Body", max_width: 20 },
+];
+
+/// One row per revision plus the current version; "Body" is the word diff against the row above.
+fn print_history(style: &Style, n: &Note) {
+    let current = Revision::of(n, n.created_at);
+    let versions: Vec<&Revision> = n.history.iter().chain([&current]).collect();
+    let rows: Vec<Vec<String>> = versions
+        .iter()
+        .enumerate()
+        .map(|(i, rev)| {
+            let (label, saved) = if i == n.history.len() {
+                ("current".to_string(), String::new())
+            } else {
+                ((i + 1).to_string(), style.dates.format(&rev.at))
+            };
+            let diff = match i.checked_sub(1) {
+                Some(prev) => {
+                    let (added, removed) = history::word_diff(&versions[prev].body, &rev.body);
+                    format!("+{}/-{} words", added, removed)
+                }
+                None => String::new(),
+            };
+            vec![label, saved, rev.title.clone(), rev.tags.join(","), diff]
+        })
+        .collect();
+    print!("{}", table::render(&HISTORY_COLUMNS, &rows, style.msg));
 }
 
-fn open_in_editor(initial: &str, fmt: EditorFmt) -> anyhow::Result<String> {
-    let ext = match fmt { EditorFmt::Json => "json", EditorFmt::Toml => "toml", EditorFmt::Yaml => "yaml" };
-    let mut tmp = TempBuilder::new().suffix(&format!(".{}", ext)).tempfile()?;
-    tmp.write_all(initial.as_bytes())?;
-    tmp.flush()?;
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("Error: {:?}", e);
+        let code = if e.is::<confirm::ConfirmationRequired>() { confirm::EXIT_NOT_CONFIRMED } else { 1 };
+        std::process::exit(code);
+    }
+}
 
-    let path = tmp.path().to_path_buf();
+/// The clap definition, shared by argument parsing, `completions` and `--generate-man`.
+fn cli_command() -> clap::Command {
+    Cli::command()
+}
 
-    // Select default editor based on OS: $VISUAL > $EDITOR > default
-    let default_editor = if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() };
-    let editor_env = env::var("VISUAL").ok().or_else(|| env::var("EDITOR").ok()).unwrap_or(default_editor);
+fn run() -> anyhow::Result<()> {
+    let matches = cli_command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    if cli.generate_man {
+        clap_mangen::Man::new(cli_command()).render(&mut io::stdout())?;
+        return Ok(());
+    }
+    let command = match cli.command {
+        Some(Command::Completions { shell }) => {
+            clap_complete::generate(shell, &mut cli_command(), "rustic-notes", &mut io::stdout());
+            return Ok(());
+        }
+        Some(command) => command,
+        None => cli_command().error(ErrorKind::MissingSubcommand, "a subcommand is required").exit(),
+    };
+    // Before anything is locked or loaded, so scripts fail right away
+    let picking = matches!(command, Command::Edit { pick: true, .. } | Command::Show { pick: true, .. } | Command::Remove { pick: true, .. });
+    if picking && !picker::is_available() {
+        anyhow::bail!("--pick needs an interactive terminal");
+    }
 
-    // Allow commands with flags, e.g. "code -w"
-    let mut parts = shell_words::split(&editor_env).unwrap_or_else(|_| vec![editor_env.clone()]);
-    if parts.is_empty() { parts.push(editor_env); }
+    let out = if cli.porcelain { OutputFmt::Porcelain } else { cli.output };
+    let config = Config::load()?;
+    let local = cli.local || config.timezone == Some(Timezone::Local);
+    let msg = Msg::detect(if cli.plain { Some(true) } else { config.plain });
+    let style = Style::detect(cli.no_color, msg).with_dates(DateFormat::new(local, cli.date_format.clone())?);
 
-    let status = ProcCommand::new(&parts[0])
-        .args(&parts[1..])
-        .arg(&path)
-        .status();
+    // Precedence: CLI flag > environment variable > config file > store extension / data-directory default
+    let env_store = std::env::var_os(location::STORE_ENV).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let env_format = std::env::var(location::FORMAT_ENV).ok().filter(|v| !v.trim().is_empty());
+    // Only a --format typed by the user counts as a flag (not clap's default value)
+    let flag_format = (matches.value_source("format") == Some(ValueSource::CommandLine)).then_some(cli.format);
+    let named_store = if cli.cwd { None } else { cli.store.clone().or_else(|| env_store.clone()).or_else(|| config.store.clone()) };
+    let (format, format_source, warning) =
+        resolve_format(flag_format, env_format.as_deref(), config.format.as_deref(), named_store.as_deref())?;
+    if let Some(w) = warning {
+        eprintln!("{} {}", style.icon(Icon::Warn), w);
+    }
+    let (store_path, store_source) = resolve_store(cli.store, cli.cwd, env_store, config.store.clone(), format);
+    if cli.debug {
+        eprintln!("store: {} (from {})", store_path.display(), store_source);
+        eprintln!("format: {} (from {})", format.name(), format_source);
+    }
 
-    match status {
-        Ok(s) if s.success() => {
-            let edited = fs::read_to_string(&path)?;
-            Ok(edited)
+    // Long-running commands lock the store for each read or change instead
+    if matches!(command, Command::Serve { .. } | Command::Tui | Command::Ingest { .. }) {
+        let session = Session {
+            store: store::open_with(&store_path, format, StoreOptions {
+                backups: cli.backups,
+                force_save: cli.force_save,
+                compress_bodies: config.compress_above(),
+                ..Default::default()
+            })?,
+            store_path: store_path.clone(),
+            lock_timeout: cli.lock_timeout,
+            history_depth: cli.history_depth,
+            journal_size: cli.journal_size,
+            auto_commit: config.auto_commit,
+        };
+        if !cli.no_verify {
+            session.read(|s| {
+                warn_tampered(s, &store_path, &style);
+                anyhow::Ok(())
+            })?;
+        }
+        if let Command::Serve { port, bind } = command {
+            let server = server::bind(&format!("{}:{}", bind, port))?;
+            let addr = server.server_addr().to_ip().map_or_else(|| format!("{}:{}", bind, port), |a| a.to_string());
+            say!(out, "Serving {} on http://{} (Ctrl-C to stop)", store_path.display(), addr);
+            server::Api { session }.run(&server);
+            return Ok(());
         }
-        Ok(s) => anyhow::bail!("The editor ended with status: {:?}", s.code()),
-        Err(e) => anyhow::bail!("Couldn't run the editor: {}", e),
+        if let Command::Ingest { dir, watch, interval, move_files } = command {
+            let interval = watch.then(|| Duration::from_secs_f64(interval.max(0.1)));
+            return ingest_dir(&session, &dir, interval, move_files, out, &style);
+        }
+        return browse(&session, &config, &style);
     }
-}
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    // Hold the lock for the whole load-modify-save cycle (shared for read-only commands)
+    let kind = match command {
+        Command::Completions { .. } => unreachable!("handled before resolving the store"),
+        Command::Serve { .. } | Command::Tui | Command::Ingest { .. } => unreachable!("handled before locking the store"),
+        Command::Add { .. }
+        | Command::Remove { .. }
+        | Command::Edit { .. }
+        | Command::Pin { .. }
+        | Command::Due { note: Some(_), .. }
+        | Command::Due { notify: true, .. }
+        | Command::Move { .. }
+        | Command::Toggle { .. }
+        | Command::RestoreBackup { .. }
+        | Command::Snapshot { action: Some(SnapshotAction::Restore { .. }), .. }
+        | Command::Merge { .. }
+        | Command::MergeNotes { .. }
+        | Command::Undo { .. }
+        | Command::Attach { .. }
+        | Command::Detach { .. }
+        | Command::History { restore: Some(_), .. }
+        | Command::Encrypt
+        | Command::Compact
+        | Command::Sync { .. }
+        | Command::Decrypt => LockKind::Exclusive,
+        Command::Doctor { fix: true }
+        | Command::Prune { dry_run: false, .. }
+        | Command::Dedupe { dry_run: false, .. }
+        | Command::Split { dry_run: false, .. } => LockKind::Exclusive,
+        Command::List { .. }
+        | Command::Search { .. }
+        | Command::Grep { .. }
+        | Command::Convert { .. }
+        | Command::Doctor { .. }
+        | Command::Prune { .. }
+        | Command::Dedupe { .. }
+        | Command::Split { .. }
+        | Command::Reindex
+        | Command::History { .. }
+        | Command::Show { .. }
+        | Command::Stats
+        | Command::Export { .. }
+        | Command::Snapshot { .. }
+        | Command::Links { .. }
+        | Command::Backlinks { .. }
+        | Command::Due { .. } => LockKind::Shared,
+    };
+    let _lock = if kind == LockKind::Shared && !store_path.exists() {
+        None
+    } else {
+        Some(StoreLock::acquire(&store_path, kind, cli.lock_timeout)?)
+    };
 
-    let store_path = cli
-        .store
-        .unwrap_or_else(|| PathBuf::from(default_store_for(cli.format)));
+    // Handled before load() so a broken store can still be restored
+    if let Command::RestoreBackup { which, yes } = command {
+        return restore_backup(&store_path, format, cli.backups, which, yes, &style);
+    }
+    if let Command::Sync { pull, push } = command {
+        return sync(&store_path, pull, push, out, &style);
+    }
+    // Snapshots are byte copies of the store file, so it is never parsed
+    if let Command::Snapshot { dir, action } = command {
+        let dir = dir.unwrap_or_else(|| snapshot::default_dir(&store_path));
+        return snapshots(&store_path, &dir, action, cli.backups, out, &style);
+    }
+
+    let encryption = match command {
+        Command::Encrypt => Encryption::On,
+        Command::Decrypt => Encryption::Off,
+        _ if cli.encrypt || config.encrypt => Encryption::On,
+        _ => Encryption::Keep,
+    };
+    let was_encrypted = std::fs::read(&store_path).is_ok_and(|data| crypto::is_encrypted(&data));
+    // doctor --fix always keeps the original, even with --backups 0
+    let backups = if matches!(command, Command::Doctor { fix: true }) { cli.backups.max(1) } else { cli.backups };
+    let store = store::open_with(&store_path, format, StoreOptions {
+        backups,
+        encryption,
+        force_save: cli.force_save,
+        compress_bodies: config.compress_above(),
+    })?;
+    let mut storage = store.load_all()?;
+    // doctor reports it as an issue instead
+    if !cli.no_verify && !matches!(command, Command::Doctor { .. }) {
+        warn_tampered(&storage, &store_path, &style);
+    }
 
-    let mut storage = load(&store_path, cli.format)?;
+    // The journal holds note contents in plaintext, so encrypted stores don't get one
+    let journaling = !was_encrypted && encryption != Encryption::On;
 
-    match cli.command {
-        Command::Add { title, body, mut tags } => {
-            tags = normalize_tags(tags);
-            let note = Note {
-                id: storage.next_id(),
-                title,
-                body,
-                tags,
-                created_at: Utc::now(),
+    // auto_prune_days: at most once a day, piggybacking on a command that writes anyway
+    let now = Utc::now();
+    if let Some(days) = config.auto_prune_days.filter(|d| *d > 0)
+        && kind == LockKind::Exclusive
+        && !matches!(command, Command::Undo { .. } | Command::Prune { .. })
+        && prune::auto_due(&storage, now)
+    {
+        let (hash, snapshot) = (journal::hash(&storage), storage.notes.clone());
+        let archived = prune::archive(&mut storage, prune::cutoff(now, days), now);
+        store.save_all(&storage)?;
+        if !archived.is_empty() {
+            let command = format!("prune: archive {} notes", archived.len());
+            if journaling {
+                let entry = Entry { at: now, command: command.clone(), inverse: Inverse::Snapshot { notes: snapshot } };
+                journal::record(&store_path, &hash, &storage, entry, cli.journal_size)?;
+            }
+            if config.auto_commit {
+                git::commit_store(&store_path, &command)?;
+            }
+            say!(out, "{} Archived {} notes not updated in {} days (auto_prune_days)", style.icon(Icon::Ok), archived.len(), days);
+        }
+    }
+    let before_hash = journal::hash(&storage);
+    let commit = |summary: &str| -> anyhow::Result<()> {
+        if config.auto_commit {
+            git::commit_store(&store_path, summary)?;
+        }
+        Ok(())
+    };
+    // Called after every journaled change: records how to undo it, then auto-commits
+    let record = |command: String, inverse: Inverse, after: &Storage| -> anyhow::Result<()> {
+        if journaling {
+            let entry = Entry { at: Utc::now(), command: command.clone(), inverse };
+            journal::record(&store_path, &before_hash, after, entry, cli.journal_size)?;
+        }
+        commit(&command)
+    };
+    // search_index: add, edit and remove update the index in place, other changes leave it
+    // for the next search to rebuild
+    let reindex = |changed: &[u64], after: &Storage| -> anyhow::Result<()> {
+        if config.search_index {
+            index::update(&store_path, &before_hash, after, changed)?;
+        }
+        Ok(())
+    };
+
+    match command {
+        Command::Add {
+            title,
+            body,
+            stdin,
+            body_file,
+            max_body_size,
+            tags,
+            meta,
+            open_editor,
+            editor_format,
+            editor,
+            no_retry,
+            no_confirm,
+            unique_title,
+            allow_duplicate,
+        } => {
+            let meta = meta::pairs(&meta)?;
+            let body = match body_file {
+                Some(path) => input::read_body_file(&path, max_body_size)?,
+                None => body_arg(body, stdin)?.unwrap_or_default(),
             };
+            let mut note = Note {
+                body: body.into(),
+                tags: normalize_tags(tags),
+                meta: meta.into_iter().collect(),
+                ..Note::new(storage.next_id(), title.unwrap_or_default())
+            };
+            if open_editor {
+                let initial = to_text(editor_format, &editable_from_note(&note))?;
+                let editor = resolve_editor(editor.as_deref(), config.editor.as_deref());
+                let session = EditorSession { fmt: editor_format, editor, no_retry, no_confirm };
+                let Some(edited) = session.run(&initial, &initial, &style, out)? else {
+                    say!(out, "Aborted, nothing was added");
+                    return Ok(());
+                };
+                apply_editable(&mut note, edited);
+                if note.title.trim().is_empty() {
+                    anyhow::bail!("a note needs a title");
+                }
+            }
+            if (unique_title || config.unique_titles)
+                && !allow_duplicate
+                && let Some(existing) = storage.find_title(&note.title)
+            {
+                anyhow::bail!(
+                    "note #{} is already titled {:?} (use --allow-duplicate to add it anyway)",
+                    existing.id,
+                    existing.title
+                );
+            }
             storage.notes.push(note.clone());
-            save(&store_path, cli.format, &storage)?;
-            println!(
-                "✅ Note #{} saved to {}",
+            store.save_all(&storage)?;
+            record(format!("add #{} {:?}", note.id, note.title), Inverse::Remove { id: note.id }, &storage)?;
+            reindex(&[note.id], &storage)?;
+            say!(
+                out,
+                "{} Note #{} saved to {}",
+                style.icon(Icon::Ok),
                 note.id,
                 store_path.display()
             );
+            if out == OutputFmt::Porcelain {
+                println!("{}", note.id);
+            } else {
+                emit(out, &note)?;
+            }
         }
-        Command::List { long } => {
+        Command::List { long, not_tags, meta, table, todo, sort, group_by, first_tag_only } => {
+            let meta = meta::pairs(&meta)?;
+            let mut visible: Vec<&Note> =
+                storage.notes.iter().filter(|n| !has_any_tag(n, &not_tags) && meta::matches(n, &meta)).collect();
+            match sort {
+                SortOrder::Store => visible.sort_by_key(|n| !n.pinned),
+                SortOrder::Manual => visible.sort_by_key(|n| rank::sort_key(n)),
+            }
+            let open = |n: &Note| checklist::progress(&n.body).map_or(0, |p| p.open());
+            if todo {
+                visible.retain(|n| open(n) > 0);
+                visible.sort_by_key(|n| std::cmp::Reverse(open(n)));
+            }
             if storage.notes.is_empty() {
-                println!("(No Notes yet)");
-            } else {
-                for n in &storage.notes {
-                    println!(
-                        "#{:>3}  {}  [{}]  {}",
-                        n.id,
-                        n.title,
-                        if n.tags.is_empty() { "".to_string() } else { n.tags.join(",") },
-                        n.created_at.format("%Y-%m-%d %H:%M:%S UTC")
-                    );
-                    if long && !n.body.is_empty() {
-                        println!("    {}", n.body);
+                say!(out, "(No Notes yet)");
+            } else if visible.is_empty() && todo {
+                say!(out, "(No notes with open checklist items)");
+            } else if visible.is_empty() && !meta.is_empty() {
+                say!(out, "(No notes with {})", describe_meta(&meta));
+            } else if visible.is_empty() {
+                say!(out, "(No notes left after excluding tags {:?})", not_tags);
+            }
+            let as_table = match table {
+                Some(TableMode::Always) => true,
+                Some(TableMode::Auto) => std::io::stdout().is_terminal(),
+                None => false,
+            };
+            let print_note = |n: &Note, indent: &str| {
+                println!("{}{}", indent, header_line(&style, n));
+                if let Some(p) = checklist::progress(&n.body).filter(|_| todo) {
+                    println!("{}    {} open ({}/{} done)", indent, p.open(), p.done, p.total);
+                }
+                if long && !n.body.is_empty() {
+                    println!("{}    {}", indent, n.body);
+                }
+            };
+            let groups = group_by.map(|GroupBy::Tag| group::by_tag(&visible, first_tag_only));
+            match groups {
+                _ if out == OutputFmt::Porcelain => print_rows(&visible),
+                Some(groups) if out != OutputFmt::Human => emit(out, &groups)?,
+                None if out != OutputFmt::Human => emit(out, &visible)?,
+                Some(groups) => {
+                    for (i, g) in groups.iter().enumerate() {
+                        if i > 0 { println!(); }
+                        let tag = g.tag.as_deref().map_or_else(|| "(untagged)".to_string(), |t| style.tags(t));
+                        println!("{} ({})", tag, g.notes.len());
+                        g.notes.iter().for_each(|n| print_note(n, "    "));
                     }
                 }
+                None if as_table => {
+                    if !visible.is_empty() { print_table(&style, &visible); }
+                }
+                None => visible.iter().for_each(|n| print_note(n, "")),
             }
         }
-        Command::Search { query, tags } => {
-            let q = query.to_lowercase();
-
-            let results = storage.notes.iter().filter(|n| {
-                let text_match = n.title.to_lowercase().contains(&q)
-                    || n.body.to_lowercase().contains(&q)
-                    || n.tags.iter().any(|t| t.to_lowercase().contains(&q));
-
-                let tags_match = if tags.is_empty() {
-                    true
-                } else {
-                    let ntags: Vec<String> = n.tags.iter().map(|t| t.to_lowercase()).collect();
-                    tags.iter().all(|t| ntags.contains(&t.to_lowercase()))
-                };
+        Command::Search { query, tags, regex, case_sensitive, ignore_case, fields, not_tags, any_tags, meta, sort, limit } => {
+            let meta = meta::pairs(&meta)?;
+            let case_sensitive = case_sensitive || (config.search_case_sensitive && !ignore_case);
+            let filter = SearchFilter {
+                matcher: Matcher::new(&query, regex, case_sensitive)?,
+                mask: FieldMask::from_fields(&fields),
+                mode: if any_tags { TagMode::Any } else { TagMode::All },
+                tags,
+                not_tags,
+            };
+            // The index only answers case-insensitive substring queries, and only narrows them down
+            let candidates = if config.search_index && !regex && !case_sensitive {
+                index::fresh(&store_path, &storage)?.candidates(&query)
+            } else {
+                None
+            };
+            let mut scored: Vec<(u32, &Note)> = storage
+                .notes
+                .iter()
+                .filter(|n| candidates.as_ref().is_none_or(|ids| ids.contains(&n.id)) && meta::matches(n, &meta))
+                .filter_map(|n| Some((filter.score(n)?, n)))
+                .collect();
+            // Stable sorts: equal keys keep the store order
+            match sort {
+                SearchSort::Relevance => scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score)),
+                SearchSort::Created => scored.sort_by_key(|(_, n)| std::cmp::Reverse(n.created_at)),
+                SearchSort::Updated => scored.sort_by_key(|(_, n)| std::cmp::Reverse(n.updated_at.unwrap_or(n.created_at))),
+            }
+            let matched = scored.len();
+            let results: Vec<&Note> = scored.into_iter().map(|(_, n)| n).take(limit.unwrap_or(usize::MAX)).collect();
 
-                text_match && tags_match
-            });
-
-            let mut count = 0;
-            for n in results {
-                count += 1;
-                println!(
-                    "#{:>3}  {}  [{}]  {}",
-                    n.id,
-                    n.title,
-                    if n.tags.is_empty() { "".to_string() } else { n.tags.join(",") },
-                    n.created_at.format("%Y-%m-%d %H:%M:%S UTC")
-                );
+            if out == OutputFmt::Porcelain {
+                print_rows(&results);
+            } else if out != OutputFmt::Human {
+                emit(out, &results)?;
+            } else {
+                results.iter().for_each(|n| print_header(&style, n));
             }
-            if count == 0 {
-                if tags.is_empty() {
-                    println!("No results for \"{}\"", query);
-                } else {
-                    println!("No results for \"{}\" with tags {:?}", query, tags);
+            if results.len() < matched {
+                say!(out, "{} matches, showing {}", matched, results.len());
+            } else if matched > 0 {
+                say!(out, "{} {}", matched, if matched == 1 { "match" } else { "matches" });
+            }
+            if matched == 0 {
+                let scope = if fields.is_empty() { String::new() } else { format!(" in {}", filter.mask.describe()) };
+                let mut filters = String::new();
+                if !filter.tags.is_empty() {
+                    let which = if filter.mode == TagMode::Any { "any of the tags" } else { "tags" };
+                    filters.push_str(&format!(" with {} {:?}", which, filter.tags));
+                }
+                if !filter.not_tags.is_empty() { filters.push_str(&format!(" excluding tags {:?}", filter.not_tags)); }
+                if !meta.is_empty() { filters.push_str(&format!(" with {}", describe_meta(&meta))); }
+                say!(out, "No results for \"{}\"{}{}", query, scope, filters);
+                for hint in suggest::hints(&filter.tags, &storage.notes) {
+                    say!(out, "{}", hint);
                 }
             }
         }
-        Command::Remove { id } => {
-            if let Some(pos) = storage.notes.iter().position(|n| n.id == id) {
-                let removed = storage.notes.remove(pos);
-                save(&store_path, cli.format, &storage)?;
-                println!("🗑️ Note deleted #{}: {}", removed.id, removed.title);
+        Command::Grep { pattern, ignore_case, word, context, tags } => {
+            let re = grep::pattern(&pattern, ignore_case, word)?;
+            let candidates = storage.notes.iter().filter(|n| TagMode::All.matches(n, &tags));
+            let found = grep::search(&re, candidates, context);
+            if out != OutputFmt::Human {
+                emit(out, &found)?;
+            } else {
+                for (i, hits) in found.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{}  {}", style.id(&format!("#{}", hits.id)), style.title(hits.title));
+                    let mut previous = None;
+                    for line in &hits.lines {
+                        // Like grep: ":" after hits, "-" after context lines and "--" between context groups
+                        if context > 0 && previous.is_some_and(|p| line.number > p + 1) {
+                            println!("{}", style.id("--"));
+                        }
+                        let sep = if line.matches.is_empty() { '-' } else { ':' };
+                        let text = style.highlight(&line.text, &line.matches);
+                        println!("{}", format!("{}{} {}", style.id(&line.number.to_string()), sep, text).trim_end());
+                        previous = Some(line.number);
+                    }
+                }
+            }
+            if found.is_empty() {
+                say!(out, "No lines match \"{}\"", pattern);
+                for hint in suggest::hints(&tags, &storage.notes) {
+                    say!(out, "{}", hint);
+                }
+                std::process::exit(1);
+            }
+        }
+        Command::Remove { ids, pick, tag, query, yes, hard } => {
+            let by_filter = ids.is_empty() && !pick;
+            let targets: Vec<u64> = if by_filter {
+                let matcher = Matcher::new(query.as_deref().unwrap_or(""), false, false)?;
+                let candidates: Vec<&Note> = storage
+                    .notes
+                    .iter()
+                    .filter(|n| {
+                        let text_match = matcher.is_match(&n.title)
+                            || matcher.is_match(&n.body)
+                            || n.tags.iter().any(|t| matcher.is_match(t));
+                        text_match && TagMode::All.matches(n, &tag)
+                    })
+                    .collect();
+                // With --porcelain only the removed ids are printed
+                if out == OutputFmt::Human {
+                    candidates.iter().for_each(|n| print_header(&style, n));
+                } else if out != OutputFmt::Porcelain {
+                    emit(out, &candidates)?;
+                }
+                candidates.iter().map(|n| n.id).collect()
+            } else if pick {
+                vec![picker::pick(&storage.notes, style.msg)?]
             } else {
-                println!("⚠️ Didn't find the note with id {}", id);
+                let mut targets = Vec::new();
+                for arg in &ids {
+                    // Overlapping arguments such as `3-5 4` remove each note once
+                    for id in resolve::note_ids(&storage.notes, arg)? {
+                        if !targets.contains(&id) {
+                            targets.push(id);
+                        }
+                    }
+                }
+                targets
+            };
+            let affected = targets.iter().filter(|id| storage.notes.iter().any(|n| n.id == **id)).count();
+            if affected > 1 && !confirm::confirm(&format!("This will affect {} notes. Continue?", affected), yes)? {
+                say!(out, "Aborted");
+                std::process::exit(1);
+            }
+
+            let snapshot = storage.notes.clone();
+            let mut removed = Vec::new();
+            for id in targets {
+                if let Some(pos) = storage.notes.iter().position(|n| n.id == id) {
+                    let note = storage.notes.remove(pos);
+                    say!(out, "{} Note deleted #{}: {}", style.icon(Icon::Deleted), note.id, note.title);
+                    if out == OutputFmt::Porcelain {
+                        println!("{}", note.id);
+                    }
+                    removed.push((pos, note));
+                } else {
+                    say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                }
+            }
+            match removed.as_slice() {
+                [] => {
+                    if by_filter {
+                        say!(out, "{} No notes match the filter", style.icon(Icon::Warn));
+                    }
+                    std::process::exit(1);
+                }
+                [(pos, note)] => {
+                    store.save_all(&storage)?;
+                    let command = format!("remove #{} {:?}", note.id, note.title);
+                    record(command, Inverse::Restore { note: note.clone(), position: *pos }, &storage)?;
+                    reindex(&[note.id], &storage)?;
+                }
+                many => {
+                    store.save_all(&storage)?;
+                    let ids: Vec<String> = many.iter().map(|(_, n)| format!("#{}", n.id)).collect();
+                    record(format!("remove {}", ids.join(" ")), Inverse::Snapshot { notes: snapshot }, &storage)?;
+                    reindex(&many.iter().map(|(_, n)| n.id).collect::<Vec<_>>(), &storage)?;
+                }
+            }
+            // Only once the removal is saved, so a failed save leaves the files in place
+            if hard {
+                for (_, note) in &removed {
+                    attachments::remove_all(&store_path, note)?;
+                }
             }
         }
-        Command::Edit { id, title, body, tags, add_tags: plus, rm_tags: minus, open_editor, editor_format } => {
+        Command::Edit {
+            note,
+            pick: _,
+            title,
+            body,
+            stdin,
+            body_file,
+            max_body_size,
+            tags,
+            add_tags: plus,
+            rm_tags: minus,
+            meta: set_meta,
+            rm_meta,
+            open_editor,
+            editor_format,
+            editor,
+            no_retry,
+            no_confirm,
+        } => {
+            let set_meta = meta::pairs(&set_meta)?;
+            let rm_meta = rm_meta.iter().map(|k| meta::key(k)).collect::<anyhow::Result<Vec<_>>>()?;
+            let id = note_or_pick(&storage.notes, note.as_deref(), style.msg)?;
+            let mut body = body_arg(body, stdin)?;
+            let mut file_body = body_file.map(|p| input::read_body_file(&p, max_body_size)).transpose()?;
+            if !open_editor {
+                body = body.or(file_body.take());
+            }
             // To avoid the active mutable borrow when saving, two phases:
             // 1) Mutate and prepare data to print. 2) Save and then print.
-            let mut out: Option<(u64, String, String)> = None;
+            let mut edited_out: Option<(Note, String)> = None;
 
             if let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) {
+                let original = n.clone();
                 // 1) Edit in editor if requested
                 if open_editor {
+                    // --body-file only seeds the buffer; what comes back from the editor wins
+                    if let Some(b) = file_body.take() { n.body = b.into(); }
+                    let before = to_text(editor_format, &editable_from_note(&original))?;
                     let initial = to_text(editor_format, &editable_from_note(n))?;
-                    let edited_text = open_in_editor(&initial, editor_format)?;
-                    let edited = from_text(editor_format, &edited_text)?;
-
-                    if let Some(t) = edited.title { n.title = t; }
-                    if let Some(b) = edited.body { n.body = b; }
-                    if let Some(ts) = edited.tags { n.tags = normalize_tags(ts); }
+                    let editor = resolve_editor(editor.as_deref(), config.editor.as_deref());
+                    let session = EditorSession { fmt: editor_format, editor, no_retry, no_confirm };
+                    let Some(edited) = session.run(&initial, &before, &style, out)? else {
+                        say!(out, "Aborted, note #{} left unchanged", id);
+                        return Ok(());
+                    };
+                    *n = original.clone();
+                    apply_editable(n, edited);
                 }
 
                 // 2) Flags from command line (applied after editor)
                 if let Some(t) = title { n.title = t; }
-                if let Some(b) = body { n.body = b; }
+                if let Some(b) = body { n.body = b.into(); }
                 if let Some(ts) = tags { n.tags = normalize_tags(ts); }
                 if !plus.is_empty() { add_tags(&mut n.tags, plus); }
                 if !minus.is_empty() { remove_tags(&mut n.tags, minus); }
+                for k in &rm_meta { n.meta.remove(k); }
+                n.meta.extend(set_meta);
 
-                let id_out = n.id;
-                let title_out = n.title.clone();
+                if !history::content_changed(&original, n) {
+                    say!(out, "{} No changes to note #{}", style.icon(Icon::Ok), id);
+                    return Ok(());
+                }
+                history::push(n, Revision::of(&original, Utc::now()), cli.history_depth);
                 let tags_out = if n.tags.is_empty() { String::new() } else { n.tags.join(",") };
-                out = Some((id_out, title_out, tags_out));
+                edited_out = Some((original, tags_out));
+            } else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+            }
+
+            if let Some((original, tags_out)) = edited_out {
+                store.save_all(&storage)?;
+                let edited = storage.notes.iter().find(|n| n.id == id).expect("edited note is in the store");
+                let title_out = edited.title.clone();
+                if *edited != original {
+                    record(format!("edit #{} {:?}", id, original.title), Inverse::Replace { note: original }, &storage)?;
+                    reindex(&[id], &storage)?;
+                }
+                say!(out, "{} Note #{} updated: {}  [{}]", style.icon(Icon::Edited), id, title_out, tags_out);
+                if out == OutputFmt::Porcelain {
+                    println!("{}", id);
+                }
+            }
+        }
+        Command::Convert { to, to_format, force } => {
+            let (to_format, _, _) = resolve_format(to_format, None, None, Some(&to))?;
+            let target = load(&to, to_format)?;
+            if !target.notes.is_empty() && !force {
+                anyhow::bail!("{} already contains {} notes (use --force to replace them)", to.display(), target.notes.len());
+            }
+            save(&to, to_format, &storage, cli.backups)?;
+            // Attachment paths are relative to the store, so the files travel with it
+            attachments::copy_all(&store_path, &to, &storage.notes)?;
+            say!(
+                out,
+                "{} Converted {} notes from {} ({}) to {} ({})",
+                style.icon(Icon::Ok),
+                storage.notes.len(),
+                store_path.display(),
+                format.name(),
+                to.display(),
+                to_format.name()
+            );
+        }
+        Command::Merge { other, other_format, prefer_other, keep_duplicates } => {
+            let (other_format, _, _) = resolve_format(other_format, None, None, Some(&other))?;
+            if !other.exists() {
+                anyhow::bail!("{} does not exist", other.display());
+            }
+            let incoming = load(&other, other_format)?;
+            let snapshot = storage.notes.clone();
+            let report = merge::merge(&mut storage, incoming, prefer_other, keep_duplicates);
+            store.save_all(&storage)?;
+            if storage.notes != snapshot {
+                record(format!("merge {}", other.display()), Inverse::Snapshot { notes: snapshot }, &storage)?;
+            }
+            let replaced = if prefer_other { format!(", replaced {}", report.replaced) } else { String::new() };
+            say!(
+                out,
+                "{} merged {}, skipped {} duplicates, remapped ids {}{}",
+                style.icon(Icon::Ok),
+                report.merged,
+                report.skipped.len(),
+                report.remapped,
+                replaced
+            );
+            for (id, title) in &report.skipped {
+                say!(out, "  skipped #{} {:?} (already in this store)", id, title);
+            }
+        }
+        Command::Reindex => {
+            let built = index::Index::build(&storage);
+            built.save(&store_path)?;
+            say!(
+                out,
+                "{} Indexed {} words from {} notes into {}",
+                style.icon(Icon::Ok),
+                built.len(),
+                storage.notes.len(),
+                index::index_path(&store_path).display()
+            );
+            if !config.search_index {
+                say!(out, "{} search only uses it with search_index = true in the config file", style.icon(Icon::Warn));
+            }
+        }
+        Command::Split { note, delimiter, remove_original, dry_run } => {
+            let id = resolve::note_id(&storage.notes, &note)?;
+            let Some(pos) = storage.notes.iter().position(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let original = storage.notes[pos].clone();
+            let mut plan = split::plan(&original.body, &delimiter);
+            if plan.sections.is_empty() {
+                anyhow::bail!("note #{} has no sections starting with {:?} to split at", id, delimiter);
+            }
+            // Without the original, what comes before the first section becomes a note of its own
+            if remove_original && !plan.preamble.is_empty() {
+                let preamble = std::mem::take(&mut plan.preamble);
+                plan.sections.insert(0, split::Section { title: original.title.clone(), body: preamble });
+            }
+            if dry_run {
+                if out != OutputFmt::Human {
+                    emit(out, &plan.sections)?;
+                } else {
+                    for s in &plan.sections {
+                        println!("  {:?} ({} words)", s.title, stats::BodyStats::of(&s.body).words);
+                    }
+                }
+                let fate = if remove_original { "removed" } else { "left with links to them" };
+                say!(out, "{} notes would be split out of #{}, which would be {}", plan.sections.len(), id, fate);
+                return Ok(());
+            }
+            let snapshot = storage.notes.clone();
+            let first = storage.next_id();
+            let ids: Vec<u64> = (first..).take(plan.sections.len()).collect();
+            for (id, section) in ids.iter().zip(plan.sections) {
+                storage.notes.push(Note {
+                    id: *id,
+                    title: section.title,
+                    body: section.body.into(),
+                    tags: original.tags.clone(),
+                    created_at: now,
+                    ..Default::default()
+                });
+            }
+            if remove_original {
+                storage.notes.remove(pos);
+            } else {
+                let n = &mut storage.notes[pos];
+                n.body = split::index_body(&plan.preamble, &ids).into();
+                history::push(n, Revision::of(&original, now), cli.history_depth);
+            }
+            store.save_all(&storage)?;
+            record(format!("split #{} {:?}", id, original.title), Inverse::Snapshot { notes: snapshot }, &storage)?;
+            reindex(&[&[id][..], &ids].concat(), &storage)?;
+            let new: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+            let fate = if remove_original { "removed it" } else { "left links to them in it" };
+            say!(out, "{} Split #{} into {} and {}", style.icon(Icon::Ok), id, new.join(", "), fate);
+            if out == OutputFmt::Porcelain {
+                ids.iter().for_each(|id| println!("{}", id));
+            } else {
+                emit(out, &storage.notes.iter().filter(|n| ids.contains(&n.id)).collect::<Vec<_>>())?;
+            }
+        }
+        Command::MergeNotes { notes, into } => {
+            let (first, second) = (resolve::note_id(&storage.notes, &notes[0])?, resolve::note_id(&storage.notes, &notes[1])?);
+            let into = into.map(|n| resolve::note_id(&storage.notes, &n)).transpose()?.unwrap_or(first);
+            let from = match into {
+                _ if first == second => first,
+                id if id == first => second,
+                id if id == second => first,
+                id => anyhow::bail!("--into must be one of the merged notes, not #{}", id),
+            };
+            let snapshot = storage.notes.clone();
+            let relinked = merge::absorb(&mut storage, into, from, now, cli.history_depth)?;
+            store.save_all(&storage)?;
+            record(format!("merge-notes #{} into #{}", from, into), Inverse::Snapshot { notes: snapshot }, &storage)?;
+            reindex(&[&[into, from][..], &relinked].concat(), &storage)?;
+            let kept = storage.notes.iter().find(|n| n.id == into).expect("merged note is in the store");
+            let words = stats::BodyStats::of(&kept.body).words;
+            say!(out, "{} Merged #{} into #{} ({} words), #{} is archived", style.icon(Icon::Ok), from, into, words, from);
+            if !relinked.is_empty() {
+                let ids: Vec<String> = relinked.iter().map(|id| format!("#{}", id)).collect();
+                say!(out, "  links to #{} now point at #{} in {}", from, into, ids.join(", "));
+            }
+            if out == OutputFmt::Porcelain {
+                println!("{}", into);
+            } else {
+                emit(out, kept)?;
+            }
+        }
+        Command::Dedupe { dry_run, yes } => {
+            let groups = dedupe::find(&storage.notes);
+            let title = |id: u64| storage.notes.iter().find(|n| n.id == id).map_or("", |n| n.title.as_str());
+            if out != OutputFmt::Human {
+                emit(out, &groups)?;
+            } else {
+                for g in &groups {
+                    let ids: Vec<String> = g.removed.iter().map(|id| format!("#{}", id)).collect();
+                    println!("{} #{} {:?} is repeated by {}", style.icon(Icon::Warn), g.kept, title(g.kept), ids.join(", "));
+                }
+            }
+            let ids: Vec<u64> = groups.iter().flat_map(|g| g.removed.iter().copied()).collect();
+            if ids.is_empty() {
+                say!(out, "{} No duplicates found in {} notes", style.icon(Icon::Ok), storage.notes.len());
+                return Ok(());
+            }
+            if dry_run {
+                say!(out, "{} duplicate notes would be removed", ids.len());
+                return Ok(());
+            }
+            if !confirm::confirm(&format!("Remove {} duplicate notes, keeping the oldest copy of each?", ids.len()), yes)? {
+                say!(out, "Aborted");
+                std::process::exit(1);
+            }
+            let snapshot = storage.notes.clone();
+            storage.notes.retain(|n| !ids.contains(&n.id));
+            store.save_all(&storage)?;
+            record(format!("dedupe: remove {} notes", ids.len()), Inverse::Snapshot { notes: snapshot }, &storage)?;
+            say!(out, "{} Removed {} duplicate notes", style.icon(Icon::Deleted), ids.len());
+        }
+        Command::Prune { older_than, dry_run, delete, yes } => {
+            let days = match older_than {
+                Some(age) => age.days,
+                None => config
+                    .auto_prune_days
+                    .ok_or_else(|| anyhow::anyhow!("prune needs --older-than or auto_prune_days in the config file"))?,
+            };
+            let cutoff = prune::cutoff(now, days);
+            let ids = if delete { prune::deletable(&storage.notes, cutoff) } else { prune::stale(&storage.notes, cutoff) };
+            let targets: Vec<&Note> = storage.notes.iter().filter(|n| ids.contains(&n.id)).collect();
+            if out != OutputFmt::Human {
+                emit(out, &targets)?;
+            } else {
+                targets.iter().for_each(|n| print_header(&style, n));
+            }
+            let verb = if delete { "deleted" } else { "archived" };
+            if dry_run {
+                say!(out, "{} notes not updated in {} days would be {}", ids.len(), days, verb);
+                return Ok(());
+            }
+            let snapshot = storage.notes.clone();
+            if delete {
+                if ids.is_empty() {
+                    say!(out, "No archived notes older than {} days", days);
+                    return Ok(());
+                }
+                if !confirm::confirm(&format!("Delete {} archived notes?", ids.len()), yes)? {
+                    say!(out, "Aborted");
+                    std::process::exit(1);
+                }
+                storage.notes.retain(|n| !ids.contains(&n.id));
+            } else {
+                prune::archive(&mut storage, cutoff, now);
+            }
+            store.save_all(&storage)?;
+            if !ids.is_empty() {
+                let command = format!("prune: {} {} notes", if delete { "delete" } else { "archive" }, ids.len());
+                record(command, Inverse::Snapshot { notes: snapshot }, &storage)?;
+            }
+            let icon = if delete { Icon::Deleted } else { Icon::Ok };
+            say!(out, "{} {} {} notes not updated in {} days", style.icon(icon), if delete { "Deleted" } else { "Archived" }, ids.len(), days);
+        }
+        Command::Doctor { fix } => {
+            let issues = doctor::check(&storage, Utc::now());
+            if out != OutputFmt::Human {
+                emit(out, &issues)?;
+            } else {
+                issues.iter().for_each(|i| println!("{} {}", style.icon(Icon::Warn), i));
+            }
+            if issues.is_empty() {
+                say!(out, "{} No issues found in {} notes", style.icon(Icon::Ok), storage.notes.len());
+            } else if fix {
+                let snapshot = storage.notes.clone();
+                let changed = doctor::fix(&mut storage);
+                let rehash = issues.contains(&doctor::Issue::ChecksumMismatch);
+                if changed > 0 || rehash {
+                    store.save_all(&storage)?;
+                }
+                if rehash {
+                    say!(out, "{} Recomputed the store checksum", style.icon(Icon::Ok));
+                }
+                if changed > 0 {
+                    record("doctor --fix".to_string(), Inverse::Snapshot { notes: snapshot }, &storage)?;
+                    say!(
+                        out,
+                        "{} Fixed {} notes (original kept in {})",
+                        style.icon(Icon::Ok),
+                        changed,
+                        backup::backup_path(&store_path, 1).display()
+                    );
+                }
+                let manual = issues.iter().filter(|i| !i.fixable()).count();
+                if manual > 0 {
+                    say!(out, "{} {} issues need a manual fix", style.icon(Icon::Warn), manual);
+                }
+            } else {
+                say!(out, "{} issues found (run `doctor --fix` to repair ids, tags and the checksum)", issues.len());
+                std::process::exit(1);
+            }
+        }
+        Command::Pin { note, off } => {
+            let ids = resolve::note_ids(&storage.notes, &note)?;
+            let snapshot = storage.notes.clone();
+            let (verb, command) = if off { ("Unpinned", "unpin") } else { ("Pinned", "pin") };
+            let mut found = 0;
+            let mut changed = Vec::new();
+            for id in ids {
+                let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                    say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                    continue;
+                };
+                found += 1;
+                if n.pinned == off {
+                    changed.push(n.clone());
+                }
+                n.pinned = !off;
+                say!(out, "{} {} #{}: {}", style.icon(Icon::Pinned), verb, id, n.title);
+            }
+            if found == 0 {
+                std::process::exit(1);
+            }
+            match changed.as_slice() {
+                [] => {}
+                [original] => {
+                    store.save_all(&storage)?;
+                    let command = format!("{} #{} {:?}", command, original.id, original.title);
+                    record(command, Inverse::Replace { note: original.clone() }, &storage)?;
+                }
+                many => {
+                    store.save_all(&storage)?;
+                    let ids: Vec<String> = many.iter().map(|n| format!("#{}", n.id)).collect();
+                    record(format!("{} {}", command, ids.join(" ")), Inverse::Snapshot { notes: snapshot }, &storage)?;
+                }
+            }
+        }
+        Command::Due { note: Some(note), at, .. } => {
+            let id = resolve::note_id(&storage.notes, &note)?;
+            let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let original = n.clone();
+            // A new due date gets its own reminder
+            (n.due, n.notified_at) = (at, None);
+            match at {
+                Some(at) => say!(out, "{} #{} {} is due {}", style.icon(Icon::Ok), id, n.title, style.timestamp(&at)),
+                None => say!(out, "{} #{} {} has no due date", style.icon(Icon::Ok), id, n.title),
+            }
+            if *n != original {
+                store.save_all(&storage)?;
+                record(format!("due #{} {:?}", id, original.title), Inverse::Replace { note: original }, &storage)?;
+            }
+        }
+        Command::Due { note: None, within, notify, .. } => {
+            let within = TimeDelta::days(within.days as i64);
+            let ids = if notify {
+                let snapshot = storage.notes.clone();
+                let ids = due::notify(&mut storage, now, within, &mut due::Desktop { dates: style.dates.clone() })?;
+                if !ids.is_empty() {
+                    store.save_all(&storage)?;
+                    record(format!("due --notify: {} notes", ids.len()), Inverse::Snapshot { notes: snapshot }, &storage)?;
+                }
+                ids
+            } else {
+                due::upcoming(&storage.notes, now, within).iter().map(|n| n.id).collect()
+            };
+            let listed: Vec<&Note> = ids.iter().filter_map(|id| storage.notes.iter().find(|n| n.id == *id)).collect();
+            if out != OutputFmt::Human {
+                emit(out, &listed)?;
+            } else {
+                for n in &listed {
+                    let due = style.timestamp(&n.due.unwrap_or_default());
+                    let overdue = if due::overdue(n, now) { format!("  {}", style.alert("overdue")) } else { String::new() };
+                    println!("{}  due {}{}", header_line(&style, n), due, overdue);
+                }
+            }
+            let verb = if notify { "Notified of" } else { "Found" };
+            say!(out, "{} {} notes due within {} days", verb, ids.len(), within.num_days());
+        }
+        Command::Move { note, before, after, top, bottom: _ } => {
+            let id = resolve::note_id(&storage.notes, &note)?;
+            let Some(original) = storage.notes.iter().find(|n| n.id == id).cloned() else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let to = match (before, after) {
+                (Some(anchor), _) => rank::Position::Before(resolve::note_id(&storage.notes, &anchor)?),
+                (_, Some(anchor)) => rank::Position::After(resolve::note_id(&storage.notes, &anchor)?),
+                _ if top => rank::Position::Top,
+                _ => rank::Position::Bottom,
+            };
+            let place = match to {
+                rank::Position::Top => "to the top".to_string(),
+                rank::Position::Bottom => "to the bottom".to_string(),
+                rank::Position::Before(anchor) => format!("before #{}", anchor),
+                rank::Position::After(anchor) => format!("after #{}", anchor),
+            };
+            let snapshot = storage.notes.clone();
+            let changed = rank::place(&mut storage.notes, id, to)?;
+            let command = format!("move #{} {}", id, place);
+            match changed.as_slice() {
+                [] => say!(out, "{} #{} is already there", style.icon(Icon::Ok), id),
+                [_] => {
+                    store.save_all(&storage)?;
+                    record(command, Inverse::Replace { note: original }, &storage)?;
+                    say!(out, "{} Moved #{} {}", style.icon(Icon::Edited), id, place);
+                }
+                many => {
+                    store.save_all(&storage)?;
+                    record(command, Inverse::Snapshot { notes: snapshot }, &storage)?;
+                    say!(out, "{} Moved #{} {} (renumbered {} notes)", style.icon(Icon::Edited), id, place, many.len());
+                }
+            }
+        }
+        Command::Toggle { note, item } => {
+            let id = resolve::note_id(&storage.notes, &note)?;
+            let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let original = n.clone();
+            let (body, toggled) = checklist::toggle(&n.body, item)?;
+            n.body = body.into();
+            history::push(n, Revision::of(&original, Utc::now()), cli.history_depth);
+            let p = checklist::progress(&n.body).expect("the note has the toggled item");
+            store.save_all(&storage)?;
+            let command = format!("toggle #{} item {}", id, item);
+            record(command, Inverse::Replace { note: original }, &storage)?;
+            let mark = if toggled.done { "x" } else { " " };
+            say!(out, "{} #{} [{}] {}  ({}/{} done)", style.icon(Icon::Edited), id, mark, toggled.text, p.done, p.total);
+        }
+        Command::Show { note, pick: _, render, stats: with_stats } => {
+            let ids = match note {
+                Some(arg) => resolve::note_ids(&storage.notes, &arg)?,
+                None => vec![picker::pick(&storage.notes, style.msg)?],
+            };
+            let mut found = Vec::new();
+            for id in &ids {
+                match storage.notes.iter().find(|n| n.id == *id) {
+                    Some(n) => found.push(n),
+                    None => say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id),
+                }
+            }
+            if found.is_empty() {
+                std::process::exit(1);
+            }
+            let shown: Vec<ShownNote> = found
+                .iter()
+                .map(|n| ShownNote {
+                    note: n,
+                    stats: with_stats.then(|| stats::BodyStats::of(&n.body)),
+                    checklist: checklist::progress(&n.body),
+                })
+                .collect();
+            if out != OutputFmt::Human {
+                if ids.len() == 1 { emit(out, &shown[0])? } else { emit(out, &shown)? }
+            } else {
+                for (i, shown) in shown.iter().enumerate() {
+                    if ids.len() > 1 {
+                        if i > 0 { println!(); }
+                        println!("--- #{}", shown.note.id);
+                    }
+                    show_note(&style, shown, &storage, &store_path, render);
+                }
+            }
+        }
+        Command::Stats => {
+            let summary = stats::summarize(&storage);
+            if out != OutputFmt::Human {
+                emit(out, &summary)?;
+            } else {
+                let label = |name| style.msg.label(name, 13);
+                println!("{}{}", label("Notes"), summary.notes);
+                println!("{}{}", label("Words"), summary.words);
+                println!("{}{}", label("Characters"), summary.characters);
+                if let Some(l) = &summary.longest {
+                    println!(
+                        "{}{}  {}  ({} words)",
+                        label("Longest"),
+                        style.id(&format!("#{}", l.id)),
+                        style.title(&l.title),
+                        l.words
+                    );
+                }
+            }
+        }
+        Command::Attach { id, file } => {
+            let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let relative = attachments::attach(&store_path, n, &file)?;
+            store.save_all(&storage)?;
+            commit(&format!("attach {} to #{}", relative.display(), id))?;
+            say!(out, "{} Attached {} to note #{}", style.icon(Icon::Ok), relative.display(), id);
+        }
+        Command::Detach { id, name } => {
+            let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let relative = attachments::detach(&store_path, n, &name)?;
+            store.save_all(&storage)?;
+            commit(&format!("detach {} from #{}", relative.display(), id))?;
+            say!(out, "{} Deleted {} from note #{}", style.icon(Icon::Deleted), relative.display(), id);
+        }
+        Command::Links { id } => {
+            let Some(n) = storage.notes.iter().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let found = links::links(n, &storage);
+            if out != OutputFmt::Human {
+                emit(out, &found)?;
+            } else if found.is_empty() {
+                println!("(No links in #{})", id);
+            } else {
+                for link in &found {
+                    match &link.target {
+                        Target::Resolved { id } => {
+                            let title = storage.notes.iter().find(|n| n.id == *id).map_or("", |n| n.title.as_str());
+                            println!("[[{}]]  ->  {}  {}", link.text, style.id(&format!("#{}", id)), style.title(title));
+                        }
+                        Target::Ambiguous { ids } => {
+                            let ids: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+                            println!("[[{}]]  ->  {} ambiguous: {}", link.text, style.icon(Icon::Warn), ids.join(", "));
+                        }
+                        Target::Dangling => println!("[[{}]]  ->  {}", link.text, style.alert("dangling")),
+                    }
+                }
+            }
+        }
+        Command::Backlinks { id } => {
+            if !storage.notes.iter().any(|n| n.id == id) {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            }
+            let found = links::backlinks(id, &storage);
+            if out != OutputFmt::Human {
+                let notes: Vec<&Note> = found.iter().map(|(n, _)| *n).collect();
+                emit(out, &notes)?;
+            } else if found.is_empty() {
+                println!("(No notes link to #{})", id);
+            } else {
+                for (n, hits) in &found {
+                    print_header(&style, n);
+                    for link in hits.iter().filter(|l| matches!(l.target, Target::Ambiguous { .. })) {
+                        println!("    {} [[{}]] is ambiguous; it may mean another note", style.icon(Icon::Warn), link.text);
+                    }
+                }
+            }
+        }
+        Command::History { id, restore } => {
+            let Some(n) = storage.notes.iter_mut().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                return Ok(());
+            };
+            if let Some(which) = restore {
+                let original = n.clone();
+                history::restore(n, which, cli.history_depth, Utc::now())?;
+                let title = n.title.clone();
+                store.save_all(&storage)?;
+                let command = format!("history #{} --restore {}", id, which);
+                record(command, Inverse::Replace { note: original }, &storage)?;
+                say!(out, "{} Note #{} restored to revision {}: {}", style.icon(Icon::Edited), id, which, title);
+            } else if out != OutputFmt::Human {
+                emit(out, &n.history)?;
+            } else if n.history.is_empty() {
+                println!("(No earlier versions of #{})", id);
+            } else {
+                print_history(&style, n);
+            }
+        }
+        Command::Undo { list } => {
+            let mut journal = Journal::load(&store_path)?;
+            if !journal.entries.is_empty() && !journal.matches(&storage) {
+                Journal::default().save(&store_path)?;
+                anyhow::bail!("the store was changed outside rustic-notes since the last recorded operation; the undo journal was discarded");
+            }
+            if list {
+                let recent: Vec<&Entry> = journal.entries.iter().rev().take(10).collect();
+                if out != OutputFmt::Human {
+                    emit(out, &recent)?;
+                } else if recent.is_empty() {
+                    println!("(Nothing to undo)");
+                } else {
+                    for e in recent {
+                        println!("{}  {}", style.timestamp(&e.at), e.command);
+                    }
+                }
+            } else if let Some(entry) = journal.entries.pop() {
+                journal::apply(&mut storage, entry.inverse);
+                store.save_all(&storage)?;
+                journal.hash = journal::hash(&storage);
+                journal.save(&store_path)?;
+                commit(&format!("undo {}", entry.command))?;
+                say!(out, "{} Undid {}", style.icon(Icon::Ok), entry.command);
             } else {
-                println!("⚠️ Didn't find the note with id {}", id);
+                say!(out, "(Nothing to undo)");
             }
+        }
+        Command::Encrypt => {
+            if was_encrypted {
+                say!(out, "{} {} is already encrypted", style.icon(Icon::Warn), store_path.display());
+            } else {
+                store.save_all(&storage)?;
+                // The journal would keep a plaintext copy of the notes next to the encrypted store
+                Journal::default().save(&store_path)?;
+                commit("encrypt")?;
+                say!(out, "{} Encrypted {}", style.icon(Icon::Ok), store_path.display());
+            }
+        }
+        Command::Decrypt => {
+            if !was_encrypted {
+                say!(out, "{} {} is not encrypted", style.icon(Icon::Warn), store_path.display());
+            } else {
+                store.save_all(&storage)?;
+                commit("decrypt")?;
+                say!(out, "{} Decrypted {}", style.icon(Icon::Ok), store_path.display());
+            }
+        }
+        Command::Export { target: ExportTarget::Html { dir, tag } } => {
+            let selected: Vec<&Note> = storage.notes.iter().filter(|n| TagMode::All.matches(n, &tag)).collect();
+            let report = html::export(&storage, &selected, &dir)?;
+            say!(
+                out,
+                "{} Exported {} notes and {} tag pages to {}",
+                style.icon(Icon::Ok),
+                report.notes,
+                report.tags,
+                dir.join("index.html").display()
+            );
+        }
+        Command::Export { target: ExportTarget::Note { note, out: path, format, force } } => {
+            let id = resolve::note_id(&storage.notes, &note)?;
+            let Some(n) = storage.notes.iter().find(|n| n.id == id) else {
+                say!(out, "{} Didn't find the note with id {}", style.icon(Icon::Warn), id);
+                std::process::exit(1);
+            };
+            let format = format.or_else(|| NoteFormat::from_extension(&path)).unwrap_or(NoteFormat::Markdown);
+            export::write(&path, &export::render(n, format)?, force)?;
+            if path != std::path::Path::new("-") {
+                say!(out, "{} Exported #{} to {}", style.icon(Icon::Ok), id, path.display());
+            }
+        }
+        Command::Compact => match store.compact()? {
+            Some(dropped) => {
+                commit("compact")?;
+                say!(
+                    out,
+                    "{} Compacted {}: {} superseded records dropped, {} notes kept",
+                    style.icon(Icon::Ok),
+                    store_path.display(),
+                    dropped,
+                    storage.notes.len()
+                );
+            }
+            None => say!(out, "Nothing to compact: {} stores don't keep superseded records", format.name()),
+        },
+        Command::RestoreBackup { .. } => unreachable!("handled before loading the store"),
+        Command::Snapshot { .. } => unreachable!("handled before loading the store"),
+        Command::Completions { .. } => unreachable!("handled before resolving the store"),
+        Command::Sync { .. } => unreachable!("handled before loading the store"),
+        Command::Serve { .. } | Command::Tui | Command::Ingest { .. } => unreachable!("handled before locking the store"),
+    }
 
-            if let Some((id_out, title_out, tags_out)) = out {
-                save(&store_path, cli.format, &storage)?;
-                println!("✏️ Note #{} updated: {}  [{}]", id_out, title_out, tags_out);
+    Ok(())
+}
+
+/// `ingest`: one pass over `dir`, or with an `interval` (--watch) one pass after another until
+/// interrupted, telling only about the passes that found something.
+fn ingest_dir(
+    session: &Session,
+    dir: &std::path::Path,
+    interval: Option<Duration>,
+    move_files: bool,
+    out: OutputFmt,
+    style: &Style,
+) -> anyhow::Result<()> {
+    if interval.is_some() {
+        say!(out, "Watching {} for markdown files (Ctrl-C to stop)", dir.display());
+    }
+    loop {
+        let report = session.change(|storage| {
+            let before = storage.notes.clone();
+            let report = ingest::ingest(dir, storage, session.history_depth, Utc::now())?;
+            let changed = storage.notes != before;
+            let change = changed.then(|| (format!("ingest {}", dir.display()), Inverse::Snapshot { notes: before }));
+            anyhow::Ok((report, change))
+        })?;
+        // Only once the notes are saved, so a failed save leaves the files to retry
+        if move_files {
+            ingest::move_processed(&report.files)?;
+        }
+        let quiet = interval.is_some() && report.is_empty();
+        if out != OutputFmt::Human && !quiet {
+            emit(out, &report)?;
+        } else if !quiet {
+            for (what, notes) in [("added", &report.added), ("updated", &report.updated)] {
+                for n in notes {
+                    say!(out, "  {} #{} {:?} from {}", what, n.id, n.title, n.file.display());
+                }
+            }
+            for f in &report.failed {
+                say!(out, "{} Skipped {}: {}", style.icon(Icon::Warn), f.file.display(), f.error);
             }
+            say!(
+                out,
+                "{} Ingested {} new and {} updated notes from {}",
+                style.icon(Icon::Ok),
+                report.added.len(),
+                report.updated.len(),
+                dir.display()
+            );
         }
+        let Some(interval) = interval else { return Ok(()) };
+        std::thread::sleep(interval);
     }
+}
 
+/// `tui`: every change the interface asks for is made through `session`, locking, journaling
+/// and auto-committing like the matching CLI command.
+fn browse(session: &Session, config: &Config, style: &Style) -> anyhow::Result<()> {
+    if !io::stdout().is_terminal() {
+        anyhow::bail!("tui needs an interactive terminal");
+    }
+    let load = || session.read(|s| anyhow::Ok(s.notes.clone()));
+    let editor = EditorSession {
+        fmt: EditorFmt::Yaml,
+        editor: resolve_editor(None, config.editor.as_deref()),
+        no_retry: false,
+        no_confirm: true,
+    };
+    let edit = |initial: &str| editor.run(initial, initial, style, OutputFmt::Human);
+    tui::run(load()?, style.msg, |action| {
+        let (message, select) = session.change(|storage| match action {
+            Action::Add => {
+                let mut note = Note::new(storage.next_id(), "");
+                let Some(edited) = edit(&to_text(EditorFmt::Yaml, &editable_from_note(&note))?)? else {
+                    return Ok((("Aborted, nothing was added".to_string(), None), None));
+                };
+                apply_editable(&mut note, edited);
+                if note.title.trim().is_empty() {
+                    anyhow::bail!("a note needs a title");
+                }
+                storage.notes.push(note.clone());
+                let message = format!("{} Note #{} added", style.icon(Icon::Ok), note.id);
+                let command = format!("add #{} {:?}", note.id, note.title);
+                Ok(((message, Some(note.id)), Some((command, Inverse::Remove { id: note.id }))))
+            }
+            Action::Edit(id) => {
+                let n = storage.notes.iter_mut().find(|n| n.id == id).ok_or_else(|| anyhow::anyhow!("note #{} is gone", id))?;
+                let original = n.clone();
+                let Some(edited) = edit(&to_text(EditorFmt::Yaml, &editable_from_note(n))?)? else {
+                    return Ok(((format!("Aborted, note #{} left unchanged", id), None), None));
+                };
+                apply_editable(n, edited);
+                if !history::content_changed(&original, n) {
+                    return Ok(((format!("{} No changes to note #{}", style.icon(Icon::Ok), id), None), None));
+                }
+                history::push(n, Revision::of(&original, Utc::now()), session.history_depth);
+                let message = format!("{} Note #{} updated: {}", style.icon(Icon::Edited), id, n.title);
+                let command = format!("edit #{} {:?}", id, original.title);
+                Ok(((message, Some(id)), Some((command, Inverse::Replace { note: original }))))
+            }
+            Action::Remove(id) => {
+                let position = storage.notes.iter().position(|n| n.id == id).ok_or_else(|| anyhow::anyhow!("note #{} is gone", id))?;
+                let note = storage.notes.remove(position);
+                let message = format!("{} Removed #{}: {} (`undo` brings it back)", style.icon(Icon::Deleted), id, note.title);
+                let command = format!("remove #{} {:?}", id, note.title);
+                Ok(((message, None), Some((command, Inverse::Restore { note, position }))))
+            }
+            Action::TogglePin(id) => {
+                let n = storage.notes.iter_mut().find(|n| n.id == id).ok_or_else(|| anyhow::anyhow!("note #{} is gone", id))?;
+                let original = n.clone();
+                n.pinned = !n.pinned;
+                let (verb, command) = if n.pinned { ("Pinned", "pin") } else { ("Unpinned", "unpin") };
+                let message = format!("{} {} #{}: {}", style.icon(Icon::Pinned), verb, id, n.title);
+                let command = format!("{} #{} {:?}", command, id, original.title);
+                Ok(((message, Some(id)), Some((command, Inverse::Replace { note: original }))))
+            }
+            Action::Quit => unreachable!("the interface quits by itself"),
+        })?;
+        Ok(Outcome { notes: load()?, message, select })
+    })
+}
+
+fn sync(store_path: &std::path::Path, pull: bool, push: bool, out: OutputFmt, style: &Style) -> anyhow::Result<()> {
+    if !git::is_repo(store_path) {
+        anyhow::bail!("{} is not inside a git repository", store_path.display());
+    }
+    if git::commit_store(store_path, "sync")? {
+        say!(out, "{} Committed {}", style.icon(Icon::Ok), store_path.display());
+    } else {
+        say!(out, "Nothing to commit");
+    }
+    if pull {
+        git::pull(store_path)?;
+        say!(out, "{} Pulled", style.icon(Icon::Ok));
+    }
+    if push {
+        git::push(store_path)?;
+        say!(out, "{} Pushed", style.icon(Icon::Ok));
+    }
+    Ok(())
+}
+
+fn snapshots(
+    store_path: &std::path::Path,
+    dir: &std::path::Path,
+    action: Option<SnapshotAction>,
+    keep_backups: usize,
+    out: OutputFmt,
+    style: &Style,
+) -> anyhow::Result<()> {
+    match action {
+        None => match snapshot::take(store_path, dir, Utc::now())? {
+            snapshot::Taken::New(s) => {
+                say!(out, "{} Snapshot {} saved in {}", style.icon(Icon::Ok), s.name, dir.display());
+                emit(out, &s)?;
+            }
+            snapshot::Taken::Unchanged(s) => {
+                say!(out, "Unchanged since snapshot {}, nothing saved", s.name);
+                emit(out, &s)?;
+            }
+        },
+        Some(SnapshotAction::List) => {
+            let snapshots = snapshot::list(dir)?;
+            if out != OutputFmt::Human {
+                emit(out, &snapshots)?;
+            } else if snapshots.is_empty() {
+                println!("(No snapshots in {})", dir.display());
+            } else {
+                for s in &snapshots {
+                    println!("{}  {}  {} bytes", s.name, style.timestamp(&s.taken_at), s.size);
+                }
+            }
+        }
+        Some(SnapshotAction::Restore { name, yes }) => {
+            if !confirm::confirm(&format!("Restore snapshot {} over {}?", name, store_path.display()), yes)? {
+                say!(out, "Aborted");
+                return Ok(());
+            }
+            snapshot::restore(store_path, dir, &name, keep_backups)?;
+            say!(out, "{} Restored snapshot {} into {}", style.icon(Icon::Ok), name, store_path.display());
+        }
+        Some(SnapshotAction::Prune { keep }) => {
+            let removed = snapshot::prune(dir, keep)?;
+            for s in &removed {
+                say!(out, "{} Deleted snapshot {}", style.icon(Icon::Deleted), s.name);
+            }
+            say!(out, "{} snapshots deleted, {} kept", removed.len(), snapshot::list(dir)?.len());
+        }
+    }
+    Ok(())
+}
+
+/// Warns on stderr when the notes don't match the checksum of the last save (`--no-verify`
+/// turns it off).
+fn warn_tampered(storage: &Storage, store_path: &std::path::Path, style: &Style) {
+    if storage.checksum_mismatch() {
+        eprintln!(
+            "{} WARNING: {} doesn't match its checksum: it was edited outside rustic-notes or is corrupted. Check it, then run `doctor --fix` to accept it (--no-verify skips this check).",
+            style.icon(Icon::Warn),
+            store_path.display()
+        );
+    }
+}
+
+fn restore_backup(
+    store_path: &std::path::Path,
+    format: Format,
+    keep: usize,
+    which: Option<usize>,
+    yes: bool,
+    style: &Style,
+) -> anyhow::Result<()> {
+    let backups = backup::list(store_path, format)?;
+    if backups.is_empty() {
+        println!("(No backups of {})", store_path.display());
+        return Ok(());
+    }
+    for b in &backups {
+        let notes = match b.notes {
+            Some(n) => format!("{} notes", n),
+            None if b.encrypted => "encrypted".to_string(),
+            None => "unreadable".to_string(),
+        };
+        println!(
+            "{}  {}  {}",
+            style.id(&format!("{:>2}", b.which)),
+            style.timestamp(&b.modified),
+            notes
+        );
+    }
+    let Some(which) = which else { return Ok(()) };
+
+    if !confirm::confirm(&format!("Restore backup {} over {}?", which, store_path.display()), yes)? {
+        println!("Aborted");
+        return Ok(());
+    }
+    backup::restore(store_path, format, which, keep)?;
+    println!("{} Restored backup {} into {}", style.icon(Icon::Ok), which, store_path.display());
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,288 @@
+use crate::msg::{Glyph, Msg};
+use crate::style::Style;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use unicode_width::UnicodeWidthStr;
+
+/// Used when the terminal width can't be detected (e.g. output is piped).
+pub const FALLBACK_WIDTH: usize = 80;
+
+/// Width of the terminal stdout is attached to, or [`FALLBACK_WIDTH`].
+pub fn terminal_width() -> usize {
+    terminal_size::terminal_size().map_or(FALLBACK_WIDTH, |(w, _)| w.0 as usize)
+}
+
+/// Renders a markdown body for the terminal, wrapped to `width` columns.
+/// Without colors the body is returned as is, since the markup is already readable as plain text.
+pub fn render(body: &str, width: usize, style: &Style) -> String {
+    if !style.color {
+        return body.to_string();
+    }
+    let mut r = Renderer { width: width.max(20), msg: style.msg, ..Default::default() };
+    for event in Parser::new_ext(body, Options::ENABLE_STRIKETHROUGH) {
+        r.event(event);
+    }
+    r.finish()
+}
+
+#[derive(Default)]
+struct Renderer {
+    width: usize,
+    msg: Msg,
+    out: String,
+    line: String,
+    /// Display width of `line`
+    col: usize,
+    /// Indentation of wrapped lines (list nesting)
+    margin: usize,
+    space_pending: bool,
+    bold: usize,
+    italic: usize,
+    strike: usize,
+    heading: Option<HeadingLevel>,
+    /// Next number of each open list (`None` for bullets)
+    lists: Vec<Option<u64>>,
+    /// Prefix widths of the open list items
+    items: Vec<usize>,
+    links: Vec<String>,
+    code_block: bool,
+}
+
+impl Renderer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.code_block => self.code_lines(&text),
+            Event::Text(text) => self.text(&text, &self.codes()),
+            Event::Code(code) => {
+                let codes = self.codes();
+                let codes = if codes.is_empty() { "2".to_string() } else { format!("{};2", codes) };
+                self.word(&code, &codes);
+            }
+            Event::SoftBreak => self.space_pending = true,
+            Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.block();
+                let rule = self.msg.glyph(Glyph::Rule).repeat(self.width.saturating_sub(self.margin).min(40));
+                self.line.push_str(&paint("2", &rule));
+                self.newline();
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.text(&html, ""),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            // Paragraphs in (loose) lists stay on the item's line
+            Tag::Paragraph if self.items.is_empty() => self.block(),
+            Tag::Heading { level, .. } => {
+                self.block();
+                self.heading = Some(level);
+            }
+            Tag::CodeBlock(kind) => {
+                self.block();
+                if let CodeBlockKind::Fenced(lang) = kind
+                    && !lang.is_empty()
+                {
+                    self.line.push_str(&paint("2", &format!("    [{}]", lang)));
+                    self.newline();
+                }
+                self.code_block = true;
+            }
+            Tag::List(start) => {
+                if self.items.is_empty() {
+                    self.block();
+                } else {
+                    self.newline_if_needed();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.newline_if_needed();
+                let bullet = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => format!("{} ", self.msg.glyph(Glyph::Bullet)),
+                };
+                let w = bullet.width();
+                self.line.push_str(&paint("36", &bullet));
+                self.col += w;
+                self.margin += w;
+                self.items.push(w);
+                self.space_pending = false;
+            }
+            Tag::Emphasis => self.italic += 1,
+            Tag::Strong => self.bold += 1,
+            Tag::Strikethrough => self.strike += 1,
+            Tag::Link { dest_url, .. } => self.links.push(dest_url.to_string()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Heading(_) => {
+                self.heading = None;
+                self.newline_if_needed();
+            }
+            TagEnd::CodeBlock => self.code_block = false,
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            TagEnd::Item => {
+                self.newline_if_needed();
+                self.margin -= self.items.pop().unwrap_or(0);
+                self.line = " ".repeat(self.margin);
+                self.col = self.margin;
+            }
+            TagEnd::Emphasis => self.italic -= 1,
+            TagEnd::Strong => self.bold -= 1,
+            TagEnd::Strikethrough => self.strike -= 1,
+            TagEnd::Link => {
+                if let Some(url) = self.links.pop() {
+                    self.space_pending = true;
+                    self.word(&format!("({})", url), "2;4");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// ANSI codes for the inline styles currently open.
+    fn codes(&self) -> String {
+        let mut codes = Vec::new();
+        match self.heading {
+            Some(HeadingLevel::H1) => codes.extend(["1", "4", "35"]),
+            Some(HeadingLevel::H2) => codes.extend(["1", "36"]),
+            Some(_) => codes.extend(["1", "33"]),
+            None => {}
+        }
+        if self.bold > 0 {
+            codes.push("1");
+        }
+        if self.italic > 0 {
+            codes.push("3");
+        }
+        if self.strike > 0 {
+            codes.push("9");
+        }
+        codes.join(";")
+    }
+
+    fn text(&mut self, text: &str, codes: &str) {
+        if text.starts_with(char::is_whitespace) {
+            self.space_pending = true;
+        }
+        for word in text.split_whitespace() {
+            self.word(word, codes);
+            self.space_pending = true;
+        }
+        if !text.ends_with(char::is_whitespace) {
+            self.space_pending = false;
+        }
+    }
+
+    /// Appends a word, moving to a new line first when it wouldn't fit.
+    fn word(&mut self, word: &str, codes: &str) {
+        let w = word.width();
+        let at_start = self.col <= self.margin;
+        let space = usize::from(self.space_pending && !at_start);
+        if !at_start && self.col + space + w > self.width {
+            self.newline();
+        } else if space == 1 {
+            self.line.push(' ');
+            self.col += 1;
+        }
+        self.line.push_str(&paint(codes, word));
+        self.col += w;
+        self.space_pending = false;
+    }
+
+    /// Code blocks keep their lines as written, indented and dimmed.
+    fn code_lines(&mut self, text: &str) {
+        for line in text.lines() {
+            self.line.push_str(&paint("2", &format!("    {}", line)));
+            self.newline();
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push_str(self.line.trim_end());
+        self.out.push('\n');
+        self.line = " ".repeat(self.margin);
+        self.col = self.margin;
+        self.space_pending = false;
+    }
+
+    fn newline_if_needed(&mut self) {
+        if self.col > self.margin {
+            self.newline();
+        }
+    }
+
+    /// Ends the current line and leaves one blank line before the next block.
+    fn block(&mut self) {
+        self.newline_if_needed();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.newline_if_needed();
+        self.out.trim_end().to_string()
+    }
+}
+
+fn paint(codes: &str, s: &str) -> String {
+    if codes.is_empty() { s.to_string() } else { format!("\x1b[{}m{}\x1b[0m", codes, s) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &str = "# Release plan\n\nShip the **new** parser, see [the docs](https://example.com/docs).\n\n## Steps\n\n- write *tests*\n- update the changelog\n  1. add entry\n  2. bump version\n\n```sh\ncargo test\ncargo publish\n```\n";
+
+    fn strip_ansi(s: &str) -> String {
+        regex::Regex::new("\x1b\\[[0-9;]*m").unwrap().replace_all(s, "").into_owned()
+    }
+
+    #[test]
+    fn renders_headings_lists_and_code_blocks() {
+        let rendered = render(BODY, 80, &Style { color: true, ..Default::default() });
+        let expected = "Release plan\n\
+                        \n\
+                        Ship the new parser, see the docs (https://example.com/docs).\n\
+                        \n\
+                        Steps\n\
+                        \n\
+                        • write tests\n\
+                        • update the changelog\n  \
+                          1. add entry\n  \
+                          2. bump version\n\
+                        \n    \
+                            [sh]\n    \
+                            cargo test\n    \
+                            cargo publish";
+        assert_eq!(strip_ansi(&rendered), expected);
+        assert!(rendered.contains("\x1b[1;4;35mRelease\x1b[0m"));
+        assert!(rendered.contains("\x1b[1mnew\x1b[0m"));
+        assert!(rendered.contains("\x1b[3mtests\x1b[0m"));
+        assert!(rendered.contains("\x1b[2m    cargo test\x1b[0m"));
+    }
+
+    #[test]
+    fn wraps_to_the_width_and_keeps_list_indentation() {
+        let rendered = render("- one two three four five six", 20, &Style { color: true, ..Default::default() });
+        assert_eq!(strip_ansi(&rendered), "• one two three four\n  five six");
+    }
+
+    #[test]
+    fn without_colors_the_body_is_left_alone() {
+        assert_eq!(render(BODY, 80, &Style::default()), BODY);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::dedupe::content_hash;
+use crate::history::{self, Revision};
+use crate::{add_tags, links, normalize_tags, prune, Note, Storage};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+
+/// Outcome of [`merge`], printed as "merged N, skipped N duplicates, remapped ids N".
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    pub merged: usize,
+    /// Id (in the other store) and title of the notes skipped as duplicates
+    pub skipped: Vec<(u64, String)>,
+    pub remapped: usize,
+    /// Local notes overwritten by the other copy (only with `prefer_other`)
+    pub replaced: usize,
+}
+
+fn title_key(n: &Note) -> String {
+    n.title.trim().to_lowercase()
+}
+
+/// Appends `other`'s notes to `target`: exact duplicates (same [`content_hash`]) are skipped
+/// unless `keep_duplicates`, and colliding ids get a fresh one from `next_id()`. When a title
+/// exists on both sides with a different body, both copies are kept unless `prefer_other`, in
+/// which case the other copy overwrites the local one.
+pub fn merge(target: &mut Storage, other: Storage, prefer_other: bool, keep_duplicates: bool) -> MergeReport {
+    let mut report = MergeReport::default();
+    let mut seen: HashSet<_> = target.notes.iter().map(content_hash).collect();
+
+    for mut note in other.notes {
+        if !seen.insert(content_hash(&note)) && !keep_duplicates {
+            report.skipped.push((note.id, note.title));
+            continue;
+        }
+        if prefer_other
+            && let Some(local) = target.notes.iter_mut().find(|n| title_key(n) == title_key(&note))
+        {
+            local.title = note.title;
+            local.body = note.body;
+            local.tags = normalize_tags(note.tags);
+            report.replaced += 1;
+            continue;
+        }
+        if target.notes.iter().any(|n| n.id == note.id) {
+            note.id = target.next_id();
+            report.remapped += 1;
+        }
+        note.tags = normalize_tags(note.tags);
+        target.notes.push(note);
+        report.merged += 1;
+    }
+    report
+}
+
+/// Meta field naming the note an archived note was merged into (see [`absorb`])
+pub const MERGED_INTO: &str = "merged-into";
+
+/// `merge-notes`: appends note `from` to note `into` after a `---` divider and its title as a
+/// `## ` heading, adds its tags and keeps the earlier creation date; the previous version of
+/// `into` goes to its history (at most `depth` revisions). Links to `from` in any note now
+/// point at `into`, and `from` itself is archived. Returns the ids of the notes whose links
+/// were rewritten.
+pub fn absorb(storage: &mut Storage, into: u64, from: u64, now: DateTime<Utc>, depth: usize) -> anyhow::Result<Vec<u64>> {
+    if into == from {
+        anyhow::bail!("can't merge note #{} into itself", into);
+    }
+    let find = |id: u64| storage.notes.iter().position(|n| n.id == id).ok_or_else(|| anyhow::anyhow!("no note with id {}", id));
+    let (target, source) = (find(into)?, find(from)?);
+    let original = storage.notes[target].clone();
+    let absorbed = storage.notes[source].clone();
+
+    // Links are resolved before anything changes, so title links still find `from`
+    let retargeted: Vec<(usize, String)> = storage
+        .notes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, n)| links::retarget(&n.body, from, into, storage).map(|body| (i, body)))
+        .collect();
+    let rewritten = retargeted.iter().map(|(i, _)| storage.notes[*i].id).collect();
+    for (i, body) in retargeted {
+        storage.notes[i].body = body.into();
+    }
+
+    // Rewritten too when `from` linked to itself
+    let section = format!("---\n\n## {}\n\n{}", absorbed.title, storage.notes[source].body);
+    let n = &mut storage.notes[target];
+    n.body = if n.body.trim().is_empty() { section } else { format!("{}\n\n{}", n.body.trim_end(), section) }.into();
+    // Archiving `from` must not archive the merged note
+    add_tags(&mut n.tags, absorbed.tags.iter().filter(|t| *t != prune::ARCHIVED).cloned().collect());
+    n.created_at = n.created_at.min(absorbed.created_at);
+    history::push(n, Revision::of(&original, now), depth);
+
+    let gone = &mut storage.notes[source];
+    add_tags(&mut gone.tags, vec![prune::ARCHIVED.to_string()]);
+    gone.meta.insert(MERGED_INTO.to_string(), into.to_string());
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn note(id: u64, title: &str, body: &str, tags: &[&str]) -> Note {
+        Note {
+            body: body.into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Note::new(id, title)
+        }
+    }
+
+    #[test]
+    fn remaps_colliding_ids() {
+        let mut local = Storage { notes: vec![note(1, "a", "", &[]), note(2, "b", "", &[])], ..Default::default() };
+        let other = Storage { notes: vec![note(2, "c", "", &[]), note(7, "d", "", &[])], ..Default::default() };
+        let report = merge(&mut local, other, false, false);
+        assert_eq!(report, MergeReport { merged: 2, skipped: vec![], remapped: 1, replaced: 0 });
+        let ids: Vec<u64> = local.notes.iter().map(|n| n.id).collect();
+        assert_eq!(ids, [1, 2, 3, 7]);
+        assert_eq!(local.notes[2].title, "c");
+    }
+
+    #[test]
+    fn skips_duplicates_after_normalization() {
+        let mut local = Storage { notes: vec![note(1, "Bread", "buy it", &["Home", "errands"])], ..Default::default() };
+        let other = Storage {
+            notes: vec![
+                note(1, " Bread ", "buy it\n", &["errands", "home", "HOME"]),
+                note(2, "Bread", "buy it", &["errands"]),
+            ],
+            ..Default::default()
+        };
+        let report = merge(&mut local, other.clone(), false, false);
+        assert_eq!(report.skipped, [(1, " Bread ".to_string())]);
+        // Different tags: not a duplicate, kept with a fresh id
+        assert_eq!(report.merged, 1);
+        assert_eq!(local.notes.len(), 2);
+
+        let report = merge(&mut local, other, false, true);
+        assert_eq!((report.merged, report.skipped.len()), (2, 0));
+        assert_eq!(local.notes.len(), 4);
+    }
+
+    #[test]
+    fn same_title_different_body_keeps_both_or_prefers_other() {
+        let other = || Storage { notes: vec![note(5, "plan", "new plan", &[])], ..Default::default() };
+
+        let mut keep_both = Storage { notes: vec![note(1, "Plan", "old plan", &[])], ..Default::default() };
+        merge(&mut keep_both, other(), false, false);
+        assert_eq!(keep_both.notes.len(), 2);
+
+        let mut prefer = Storage { notes: vec![note(1, "Plan", "old plan", &[])], ..Default::default() };
+        let report = merge(&mut prefer, other(), true, false);
+        assert_eq!(report.replaced, 1);
+        assert_eq!(prefer.notes.len(), 1);
+        assert_eq!((prefer.notes[0].id, prefer.notes[0].body.as_str()), (1, "new plan"));
+    }
+
+    #[test]
+    fn absorb_appends_unions_tags_and_moves_links() {
+        let mut s = Storage {
+            notes: vec![
+                note(1, "Budget", "Q3 numbers", &["work"]),
+                note(2, "Budget notes", "more numbers, see [[1]]", &["Work", "finance", "archived"]),
+                note(3, "Index", "[[2]], [[budget notes]] and [[1]]", &[]),
+            ],
+            ..Default::default()
+        };
+        s.notes[1].created_at = s.notes[0].created_at - chrono::TimeDelta::days(1);
+        let now = Utc::now();
+        assert_eq!(absorb(&mut s, 1, 2, now, 5).unwrap(), [3]);
+
+        let kept = &s.notes[0];
+        assert_eq!(kept.body, "Q3 numbers\n\n---\n\n## Budget notes\n\nmore numbers, see [[1]]");
+        assert_eq!(kept.tags, ["work", "finance"]);
+        assert_eq!(kept.created_at, s.notes[1].created_at);
+        assert_eq!((kept.history.len(), kept.updated_at), (1, Some(now)));
+        assert_eq!(s.notes[2].body, "[[1]], [[1]] and [[1]]");
+        assert!(prune::is_archived(&s.notes[1]));
+        assert_eq!(s.notes[1].meta[MERGED_INTO], "1");
+
+        assert!(absorb(&mut s, 3, 3, now, 5).unwrap_err().to_string().contains("into itself"));
+        assert!(absorb(&mut s, 3, 9, now, 5).is_err());
+    }
+}
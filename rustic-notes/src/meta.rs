@@ -0,0 +1,55 @@
+//! Custom `key=value` metadata on notes, e.g. `--meta project=apollo --meta source=email`.
+//! Keys are stored lowercase and may only use `a-z`, `0-9`, `_` and `-`; values are free text.
+
+use crate::Note;
+use std::collections::BTreeMap;
+
+/// `raw` lowercased, or an error when it isn't a valid key.
+pub fn key(raw: &str) -> anyhow::Result<String> {
+    let key = raw.trim().to_lowercase();
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-') {
+        anyhow::bail!("invalid meta key {:?}: use only letters, digits, '_' and '-'", raw);
+    }
+    Ok(key)
+}
+
+/// Splits `key=value` (the value may contain `=`).
+pub fn pair(raw: &str) -> anyhow::Result<(String, String)> {
+    let Some((k, v)) = raw.split_once('=') else {
+        anyhow::bail!("expected key=value for --meta, got {:?}", raw);
+    };
+    Ok((key(k)?, v.to_string()))
+}
+
+pub fn pairs(raw: &[String]) -> anyhow::Result<Vec<(String, String)>> {
+    raw.iter().map(|p| pair(p)).collect()
+}
+
+/// The map with its keys checked and lowercased, e.g. after an editor session.
+pub fn normalize(map: BTreeMap<String, String>) -> anyhow::Result<BTreeMap<String, String>> {
+    map.into_iter().map(|(k, v)| Ok((key(&k)?, v))).collect()
+}
+
+/// Whether the note has every `key=value` of `wanted` (values compared exactly).
+pub fn matches(n: &Note, wanted: &[(String, String)]) -> bool {
+    wanted.iter().all(|(k, v)| n.meta.get(k) == Some(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_lowercased_and_checked() {
+        assert_eq!(pair("Project=apollo").unwrap(), ("project".to_string(), "apollo".to_string()));
+        assert_eq!(pair("url=https://x.y/?a=b").unwrap().1, "https://x.y/?a=b");
+        assert_eq!(pair("due_by-2=").unwrap(), ("due_by-2".to_string(), String::new()));
+        for bad in ["project", "=apollo", "my key=x", "café=1", "a.b=c"] {
+            assert!(pair(bad).is_err(), "{:?}", bad);
+        }
+        let err = key("a b").unwrap_err().to_string();
+        assert!(err.contains("invalid meta key \"a b\""), "{}", err);
+        let map = BTreeMap::from([("Source".to_string(), "email".to_string())]);
+        assert_eq!(normalize(map).unwrap(), BTreeMap::from([("source".to_string(), "email".to_string())]));
+    }
+}
@@ -0,0 +1,81 @@
+//! Every decoration of human output goes through [`Msg`]: message prefixes, the glyphs of
+//! tables and rendered markdown, and padded labels. Plain output (`--plain`, `plain = true`
+//! in the config file, or stdout not being a terminal) is pure ASCII without alignment
+//! padding, so it shows on any terminal and is easy to grep in logs.
+
+use std::io::IsTerminal;
+
+/// Message prefixes; each has an emoji and a bracketed ASCII form
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Icon {
+    Ok,
+    Deleted,
+    Warn,
+    Edited,
+    Pinned,
+}
+
+/// Non-ASCII characters used to lay out output
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Glyph {
+    /// End of a truncated table cell
+    Ellipsis,
+    /// Markdown list item
+    Bullet,
+    /// Markdown horizontal rule, repeated
+    Rule,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Msg {
+    pub plain: bool,
+}
+
+impl Msg {
+    /// `plain` is the --plain flag or the config setting; when neither says, output is plain
+    /// unless stdout is a terminal.
+    pub fn detect(plain: Option<bool>) -> Self {
+        Msg { plain: plain.unwrap_or_else(|| !std::io::stdout().is_terminal()) }
+    }
+
+    pub fn icon(self, icon: Icon) -> &'static str {
+        let (emoji, ascii) = match icon {
+            Icon::Ok => ("✅", "[ok]"),
+            Icon::Deleted => ("🗑️", "[deleted]"),
+            Icon::Warn => ("⚠️", "[warn]"),
+            Icon::Edited => ("✏️", "[edited]"),
+            Icon::Pinned => ("📌", "[pinned]"),
+        };
+        if self.plain { ascii } else { emoji }
+    }
+
+    pub fn glyph(self, glyph: Glyph) -> &'static str {
+        let (fancy, ascii) = match glyph {
+            Glyph::Ellipsis => ("…", "..."),
+            Glyph::Bullet => ("•", "*"),
+            Glyph::Rule => ("─", "-"),
+        };
+        if self.plain { ascii } else { fancy }
+    }
+
+    /// `name:` padded to `width` columns so the values after it line up; no padding when plain.
+    pub fn label(self, name: &str, width: usize) -> String {
+        let label = format!("{}:", name);
+        if self.plain { format!("{} ", label) } else { format!("{:<width$}", label, width = width) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_is_ascii_and_unpadded() {
+        let (fancy, plain) = (Msg { plain: false }, Msg { plain: true });
+        assert_eq!((fancy.icon(Icon::Deleted), plain.icon(Icon::Deleted)), ("🗑️", "[deleted]"));
+        assert_eq!((fancy.glyph(Glyph::Ellipsis), plain.glyph(Glyph::Ellipsis)), ("…", "..."));
+        assert_eq!((fancy.label("Notes", 13), plain.label("Notes", 13)), ("Notes:       ".to_string(), "Notes: ".to_string()));
+        assert_eq!(Msg::detect(Some(true)), plain);
+        assert_eq!(Msg::detect(Some(false)), fancy);
+    }
+}
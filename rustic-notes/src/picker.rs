@@ -0,0 +1,247 @@
+//! `--pick`: an inline fuzzy finder for when the id isn't at hand. Typing filters titles and
+//! tags with the same scorer as title arguments, the arrows move, Enter picks, Esc gives up.
+
+use crate::msg::Msg;
+use crate::{resolve, table, Note};
+use fuzzy_matcher::FuzzyMatcher;
+use ratatui::crossterm::cursor::{MoveToColumn, MoveUp};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::style::{Attribute, SetAttribute};
+use ratatui::crossterm::terminal::{self, Clear, ClearType};
+use ratatui::crossterm::{execute, queue};
+use std::io::{self, IsTerminal, Write};
+use unicode_width::UnicodeWidthStr;
+
+/// Matches listed below the prompt at once.
+const ROWS: usize = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Picked {
+    Note(u64),
+    Aborted,
+}
+
+pub struct Picker<'a> {
+    notes: &'a [Note],
+    pub query: String,
+    /// Positions in `notes` of the notes matching the query, best first
+    pub matches: Vec<usize>,
+    /// Position in `matches`
+    pub selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    pub fn new(notes: &'a [Note]) -> Self {
+        let mut picker = Picker { notes, query: String::new(), matches: vec![], selected: 0 };
+        picker.refilter();
+        picker
+    }
+
+    /// Without a query every note is listed in store order, pinned ones first.
+    fn refilter(&mut self) {
+        let matcher = resolve::matcher();
+        let mut scored: Vec<(i64, usize)> = self
+            .notes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, n)| {
+                let text = format!("{} {}", n.title, n.tags.join(" "));
+                let score = if self.query.is_empty() { 0 } else { matcher.fuzzy_match(&text, &self.query)? };
+                Some((score, i))
+            })
+            .collect();
+        scored.sort_by_key(|&(score, i)| (-score, !self.notes[i].pinned, i));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+    }
+
+    pub fn selected(&self) -> Option<&Note> {
+        self.matches.get(self.selected).map(|&i| &self.notes[i])
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Picked> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Picked::Aborted),
+            KeyCode::Char('c') if ctrl => return Some(Picked::Aborted),
+            KeyCode::Enter => return self.selected().map(|n| Picked::Note(n.id)),
+            KeyCode::Down | KeyCode::Tab => self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1)),
+            KeyCode::Char('n') if ctrl => self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1)),
+            KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('p') if ctrl => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Backspace if self.query.pop().is_some() => self.refilter(),
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// The matches shown under the prompt: at most `rows`, scrolled so the selected one is
+    /// visible, each with whether it is the selected one.
+    pub fn lines(&self, rows: usize) -> Vec<(String, bool)> {
+        let first = self.selected.saturating_sub(rows.saturating_sub(1));
+        self.matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(pos, &i)| {
+                let n = &self.notes[i];
+                let tags = if n.tags.is_empty() { String::new() } else { format!("  [{}]", n.tags.join(",")) };
+                (format!("#{}  {}{}", n.id, n.title, tags), pos == self.selected)
+            })
+            .collect()
+    }
+}
+
+/// Redraws the prompt and the matches below it, leaving the cursor at the end of the query.
+fn draw(out: &mut impl Write, picker: &Picker, msg: Msg) -> io::Result<()> {
+    // Some pseudo-terminals report a width of 0
+    let width = terminal::size().ok().filter(|&(w, _)| w > 0).map_or(80, |(w, _)| w as usize) - 1;
+    let prompt = format!("> {}", picker.query);
+    queue!(out, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+    write!(out, "{}", table::truncate(&prompt, width, msg))?;
+    let lines = picker.lines(ROWS);
+    for (line, selected) in &lines {
+        // Raw mode: a bare \n wouldn't return to the first column
+        write!(out, "\r\n")?;
+        let line = table::truncate(&format!("  {}", line), width, msg);
+        if *selected {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+            write!(out, "{}", line)?;
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        } else {
+            write!(out, "{}", line)?;
+        }
+    }
+    if !lines.is_empty() {
+        queue!(out, MoveUp(lines.len() as u16))?;
+    }
+    queue!(out, MoveToColumn(prompt.width().min(width) as u16))?;
+    out.flush()
+}
+
+/// Lets the user pick one of `notes` on the terminal (drawn on stderr, so stdout can still be
+/// redirected). Fails right away without a terminal, and when the user gives up.
+pub fn pick(notes: &[Note], msg: Msg) -> anyhow::Result<u64> {
+    if !is_available() {
+        anyhow::bail!("--pick needs an interactive terminal");
+    }
+    if notes.is_empty() {
+        anyhow::bail!("there are no notes to pick from");
+    }
+    let mut picker = Picker::new(notes);
+    let mut err = io::stderr();
+    terminal::enable_raw_mode()?;
+    let picked = (|| -> anyhow::Result<Picked> {
+        loop {
+            draw(&mut err, &picker, msg)?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && let Some(picked) = picker.on_key(key)
+            {
+                return Ok(picked);
+            }
+        }
+    })();
+    let _ = execute!(err, MoveToColumn(0), Clear(ClearType::FromCursorDown));
+    terminal::disable_raw_mode()?;
+    match picked? {
+        Picked::Note(id) => Ok(id),
+        Picked::Aborted => anyhow::bail!("no note picked"),
+    }
+}
+
+/// Whether there is a terminal to pick on.
+pub fn is_available() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: u64, title: &str, tags: &[&str]) -> Note {
+        Note { tags: tags.iter().map(|t| t.to_string()).collect(), ..Note::new(id, title) }
+    }
+
+    fn notes() -> Vec<Note> {
+        let mut notes = vec![
+            note(1, "Weekly sync", &["work"]),
+            note(2, "Groceries", &["home"]),
+            note(3, "Budget review", &["work", "money"]),
+            note(4, "Weekly meeting", &[]),
+        ];
+        notes[2].pinned = true;
+        notes
+    }
+
+    fn type_keys(picker: &mut Picker, keys: &str) -> Option<Picked> {
+        keys.chars().find_map(|c| picker.on_key(KeyEvent::from(KeyCode::Char(c))))
+    }
+
+    fn key(picker: &mut Picker, code: KeyCode) -> Option<Picked> {
+        picker.on_key(KeyEvent::from(code))
+    }
+
+    fn listed(picker: &Picker) -> Vec<u64> {
+        picker.matches.iter().map(|&i| picker.notes[i].id).collect()
+    }
+
+    #[test]
+    fn typing_filters_titles_and_tags_live() {
+        let notes = notes();
+        let mut picker = Picker::new(&notes);
+        assert_eq!(listed(&picker), [3, 1, 2, 4]);
+        assert_eq!(type_keys(&mut picker, "week"), None);
+        assert_eq!(listed(&picker), [1, 4]);
+        type_keys(&mut picker, "ly m");
+        assert_eq!(listed(&picker), [4]);
+        key(&mut picker, KeyCode::Backspace);
+        key(&mut picker, KeyCode::Backspace);
+        assert_eq!(picker.query, "weekly");
+        assert_eq!(listed(&picker), [1, 4]);
+
+        // Tags count too
+        let mut picker = Picker::new(&notes);
+        type_keys(&mut picker, "money");
+        assert_eq!(listed(&picker), [3]);
+        type_keys(&mut picker, "zzz");
+        assert!(listed(&picker).is_empty());
+        assert_eq!(key(&mut picker, KeyCode::Enter), None);
+    }
+
+    #[test]
+    fn arrows_move_enter_picks_and_esc_aborts() {
+        let notes = notes();
+        let mut picker = Picker::new(&notes);
+        key(&mut picker, KeyCode::Up);
+        key(&mut picker, KeyCode::Down);
+        key(&mut picker, KeyCode::Down);
+        assert_eq!(picker.selected().unwrap().id, 2);
+        for _ in 0..10 {
+            key(&mut picker, KeyCode::Down);
+        }
+        assert_eq!(key(&mut picker, KeyCode::Enter), Some(Picked::Note(4)));
+
+        // A new query starts again from the best match
+        type_keys(&mut picker, "gro");
+        assert_eq!(key(&mut picker, KeyCode::Enter), Some(Picked::Note(2)));
+        assert_eq!(key(&mut picker, KeyCode::Esc), Some(Picked::Aborted));
+        assert_eq!(picker.on_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Picked::Aborted));
+    }
+
+    #[test]
+    fn lines_scroll_to_keep_the_selection_visible() {
+        let notes = notes();
+        let mut picker = Picker::new(&notes);
+        let shown = |p: &Picker| p.lines(2).into_iter().map(|(l, s)| format!("{}{}", if s { ">" } else { "" }, l)).collect::<Vec<_>>();
+        assert_eq!(shown(&picker), [">#3  Budget review  [work,money]", "#1  Weekly sync  [work]"]);
+        key(&mut picker, KeyCode::Down);
+        key(&mut picker, KeyCode::Down);
+        assert_eq!(shown(&picker), ["#1  Weekly sync  [work]", ">#2  Groceries  [home]"]);
+    }
+}
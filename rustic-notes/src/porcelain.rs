@@ -0,0 +1,54 @@
+//! `--porcelain`: output for scripts, stable across versions. Notes are printed one per line as
+//! `id<TAB>title<TAB>tags<TAB>created_at` (tags comma-separated, `created_at` in RFC 3339 UTC),
+//! with backslashes, tabs and line breaks inside a field escaped as `\\`, `\t`, `\n` and `\r`.
+
+use crate::Note;
+use chrono::SecondsFormat;
+
+/// `field` with the characters that would break a row escaped.
+pub fn escape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    for c in field.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The row of `n`, without the trailing newline.
+pub fn row(n: &Note) -> String {
+    let tags: Vec<String> = n.tags.iter().map(|t| escape(t)).collect();
+    format!(
+        "{}\t{}\t{}\t{}",
+        n.id,
+        escape(&n.title),
+        tags.join(","),
+        n.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn rows_escape_separators() {
+        let n = Note {
+            id: 4,
+            title: "a\tb\\c\nd".to_string(),
+            body: "not\tprinted".into(),
+            tags: vec!["work".to_string(), "x y".to_string()],
+            created_at: Utc.with_ymd_and_hms(2025, 9, 14, 12, 34, 56).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(row(&n), "4\ta\\tb\\\\c\\nd\twork,x y\t2025-09-14T12:34:56Z");
+        assert_eq!(escape("plain"), "plain");
+        assert_eq!(escape("\r\n"), "\\r\\n");
+    }
+}
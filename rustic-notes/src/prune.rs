@@ -0,0 +1,140 @@
+//! `prune` and `auto_prune_days`: notes that haven't been updated for a while are archived by
+//! tagging them `archived` (so `--not-tags archived` hides them); only archived notes are ever
+//! deleted, and only by `prune --delete`.
+
+use crate::{add_tags, Note, Storage};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+/// The tag marking archived notes.
+pub const ARCHIVED: &str = "archived";
+
+/// An age given on the command line: `90d`, `12w` or `1y` (365 days); a bare number is days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Age {
+    pub days: u64,
+}
+
+impl FromStr for Age {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+            Some(i) => s.split_at(i),
+            None => (s, "d"),
+        };
+        let per_unit = match unit {
+            "d" => 1,
+            "w" => 7,
+            "y" => 365,
+            _ => return Err(format!("invalid age \"{}\" (expected e.g. 90d, 12w or 1y)", s)),
+        };
+        let n: u64 = number.parse().map_err(|_| format!("invalid age \"{}\" (expected e.g. 90d, 12w or 1y)", s))?;
+        Ok(Age { days: n * per_unit })
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d", self.days)
+    }
+}
+
+/// Notes last updated before this are stale.
+pub fn cutoff(now: DateTime<Utc>, days: u64) -> DateTime<Utc> {
+    now - TimeDelta::days(days as i64)
+}
+
+pub fn is_archived(n: &Note) -> bool {
+    n.tags.iter().any(|t| t == ARCHIVED)
+}
+
+/// Ids of the notes to archive: not updated since `cutoff`, not pinned, not archived yet.
+pub fn stale(notes: &[Note], cutoff: DateTime<Utc>) -> Vec<u64> {
+    notes.iter().filter(|n| !n.pinned && !is_archived(n) && n.last_updated() < cutoff).map(|n| n.id).collect()
+}
+
+/// Ids of the archived notes `prune --delete` removes: not updated since `cutoff`, not pinned.
+pub fn deletable(notes: &[Note], cutoff: DateTime<Utc>) -> Vec<u64> {
+    notes.iter().filter(|n| !n.pinned && is_archived(n) && n.last_updated() < cutoff).map(|n| n.id).collect()
+}
+
+/// Archives the stale notes and records the run in `last_prune`, returning the archived ids.
+/// Archiving doesn't count as an update, so the notes keep their `updated_at`.
+pub fn archive(storage: &mut Storage, cutoff: DateTime<Utc>, now: DateTime<Utc>) -> Vec<u64> {
+    let ids = stale(&storage.notes, cutoff);
+    for n in storage.notes.iter_mut().filter(|n| ids.contains(&n.id)) {
+        add_tags(&mut n.tags, vec![ARCHIVED.to_string()]);
+    }
+    storage.last_prune = Some(now);
+    ids
+}
+
+/// Whether the automatic pass is due: it runs at most once a day.
+pub fn auto_due(storage: &Storage, now: DateTime<Utc>) -> bool {
+    storage.last_prune.is_none_or(|at| now - at >= TimeDelta::days(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 6, 1, 12, 0, 0).unwrap()
+    }
+
+    fn note(id: u64, updated_days_ago: i64) -> Note {
+        Note {
+            id,
+            title: format!("note {}", id),
+            created_at: now() - TimeDelta::days(1000),
+            updated_at: Some(now() - TimeDelta::days(updated_days_ago)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ages_parse() {
+        assert_eq!("90d".parse(), Ok(Age { days: 90 }));
+        assert_eq!("2w".parse(), Ok(Age { days: 14 }));
+        assert_eq!("1y".parse(), Ok(Age { days: 365 }));
+        assert_eq!("30".parse(), Ok(Age { days: 30 }));
+        assert!("1m".parse::<Age>().is_err());
+        assert!("d".parse::<Age>().is_err());
+    }
+
+    #[test]
+    fn only_unpinned_notes_older_than_the_cutoff_are_archived() {
+        let mut pinned = note(3, 400);
+        pinned.pinned = true;
+        // Never edited: created_at counts
+        let mut never_edited = note(4, 0);
+        never_edited.updated_at = None;
+        let mut storage = Storage { notes: vec![note(1, 29), note(2, 31), pinned, never_edited], ..Default::default() };
+
+        let ids = archive(&mut storage, cutoff(now(), 30), now());
+        assert_eq!(ids, [2, 4]);
+        let archived: Vec<bool> = storage.notes.iter().map(is_archived).collect();
+        assert_eq!(archived, [false, true, false, true]);
+        assert_eq!(storage.notes[1].updated_at, Some(now() - TimeDelta::days(31)));
+        assert_eq!(storage.last_prune, Some(now()));
+
+        // Already archived notes aren't archived again, but can be deleted once old enough
+        assert!(stale(&storage.notes, cutoff(now(), 30)).is_empty());
+        assert_eq!(deletable(&storage.notes, cutoff(now(), 365)), [4]);
+        assert_eq!(deletable(&storage.notes, cutoff(now(), 30)), [2, 4]);
+    }
+
+    #[test]
+    fn the_automatic_pass_runs_once_a_day() {
+        let mut storage = Storage::default();
+        assert!(auto_due(&storage, now()));
+        storage.last_prune = Some(now() - TimeDelta::hours(23));
+        assert!(!auto_due(&storage, now()));
+        storage.last_prune = Some(now() - TimeDelta::hours(24));
+        assert!(auto_due(&storage, now()));
+    }
+}
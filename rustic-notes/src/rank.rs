@@ -0,0 +1,150 @@
+//! `move` and `list --sort manual`: notes with a `rank` come first, lowest rank first, and the
+//! rest follow by id. Ranks are handed out 10 apart, so a move usually only ranks the moved
+//! note; when two neighbours leave no room in between, the ranked notes are renumbered.
+
+use crate::Note;
+use std::collections::HashMap;
+
+/// Distance between neighbouring ranks after a renumbering.
+pub const GAP: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Top,
+    /// Right after the last ranked note, ahead of the unranked ones
+    Bottom,
+    Before(u64),
+    After(u64),
+}
+
+/// Sorting by this gives the manual order.
+pub fn sort_key(n: &Note) -> (bool, Option<u32>, u64) {
+    (n.rank.is_none(), n.rank, n.id)
+}
+
+/// Ids in manual order.
+pub fn manual_order(notes: &[Note]) -> Vec<u64> {
+    let mut sorted: Vec<&Note> = notes.iter().collect();
+    sorted.sort_by_key(|n| sort_key(n));
+    sorted.into_iter().map(|n| n.id).collect()
+}
+
+/// Moves note `id` to `to`, returning the notes whose rank changed (none when it already
+/// was there). Notes before the moved one end up ranked, the ones after it keep their rank.
+pub fn place(notes: &mut [Note], id: u64, to: Position) -> anyhow::Result<Vec<u64>> {
+    let ranks: HashMap<u64, Option<u32>> = notes.iter().map(|n| (n.id, n.rank)).collect();
+    let Some(&current) = ranks.get(&id) else {
+        anyhow::bail!("note #{} doesn't exist", id);
+    };
+    let mut order = manual_order(notes);
+    order.retain(|&i| i != id);
+    let ranked = order.iter().filter(|i| ranks[i].is_some()).count();
+    let index = match to {
+        Position::Top => 0,
+        Position::Bottom => ranked,
+        Position::Before(anchor) | Position::After(anchor) => {
+            if anchor == id {
+                anyhow::bail!("can't move note #{} next to itself", id);
+            }
+            let Some(at) = order.iter().position(|&i| i == anchor) else {
+                anyhow::bail!("note #{} doesn't exist", anchor);
+            };
+            if matches!(to, Position::After(_)) { at + 1 } else { at }
+        }
+    };
+    order.insert(index, id);
+
+    // The rank has to fall strictly between the neighbours'; an unranked note before it
+    // means the notes in front have to be ranked too
+    let lower = index.checked_sub(1).map(|i| ranks[&order[i]]);
+    let upper = order.get(index + 1).and_then(|i| ranks[i]);
+    let fits = |r: u32| lower.is_none_or(|lo| lo.is_some_and(|lo| r > lo)) && upper.is_none_or(|hi| r < hi);
+    if current.is_some_and(fits) {
+        return Ok(vec![]);
+    }
+    let free = match (lower, upper) {
+        (None, None) => Some(GAP),
+        (None, Some(hi)) => (hi > 0).then_some(hi / 2),
+        (Some(Some(lo)), None) => lo.checked_add(GAP),
+        (Some(Some(lo)), Some(hi)) => (hi - lo >= 2).then_some(lo + (hi - lo) / 2),
+        (Some(None), _) => None,
+    };
+    let new_ranks: HashMap<u64, u32> = match free {
+        Some(rank) => HashMap::from([(id, rank)]),
+        None => {
+            // Renumber everything up to the moved note or the last ranked one, whichever is later
+            let end = order.iter().rposition(|i| ranks[i].is_some()).map_or(index, |last| last.max(index));
+            order[..=end].iter().enumerate().map(|(pos, &i)| (i, (pos as u32 + 1) * GAP)).collect()
+        }
+    };
+    let mut changed = Vec::new();
+    for n in notes.iter_mut() {
+        if let Some(&rank) = new_ranks.get(&n.id)
+            && n.rank != Some(rank)
+        {
+            n.rank = Some(rank);
+            changed.push(n.id);
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(count: u64) -> Vec<Note> {
+        (1..=count)
+            .map(|id| Note::new(id, format!("note {}", id)))
+            .collect()
+    }
+
+    fn ranks(notes: &[Note]) -> Vec<Option<u32>> {
+        notes.iter().map(|n| n.rank).collect()
+    }
+
+    #[test]
+    fn moves_rank_only_the_moved_note_while_there_is_room() {
+        let mut notes = notes(5);
+        assert_eq!(place(&mut notes, 3, Position::Top).unwrap(), [3]);
+        assert_eq!(manual_order(&notes), [3, 1, 2, 4, 5]);
+        assert_eq!(place(&mut notes, 5, Position::Top).unwrap(), [5]);
+        assert_eq!(place(&mut notes, 4, Position::After(5)).unwrap(), [4]);
+        assert_eq!(place(&mut notes, 1, Position::Bottom).unwrap(), [1]);
+        assert_eq!(manual_order(&notes), [5, 4, 3, 1, 2]);
+        assert_eq!(ranks(&notes), [Some(20), None, Some(10), Some(7), Some(5)]);
+
+        // Already in place: nothing to write
+        assert!(place(&mut notes, 5, Position::Top).unwrap().is_empty());
+        assert!(place(&mut notes, 3, Position::Before(1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn inserting_between_adjacent_ranks_renumbers() {
+        let mut notes = notes(4);
+        for (n, rank) in notes.iter_mut().zip([Some(10), Some(11), None, Some(30)]) {
+            n.rank = rank;
+        }
+        assert_eq!(manual_order(&notes), [1, 2, 4, 3]);
+        let mut changed = place(&mut notes, 4, Position::After(1)).unwrap();
+        changed.sort();
+        assert_eq!(changed, [2, 4]);
+        assert_eq!(manual_order(&notes), [1, 4, 2, 3]);
+        assert_eq!(ranks(&notes), [Some(10), Some(30), None, Some(20)]);
+
+        // Before an unranked note: the notes in front of it get ranked too
+        let mut notes = self::notes(4);
+        notes[0].rank = Some(10);
+        place(&mut notes, 1, Position::Before(4)).unwrap();
+        assert_eq!(manual_order(&notes), [2, 3, 1, 4]);
+        assert_eq!(ranks(&notes), [Some(30), Some(10), Some(20), None]);
+    }
+
+    #[test]
+    fn anchors_must_exist_and_differ() {
+        let mut notes = notes(2);
+        assert!(place(&mut notes, 1, Position::After(9)).is_err());
+        assert!(place(&mut notes, 9, Position::Top).is_err());
+        assert_eq!(place(&mut notes, 1, Position::Before(1)).unwrap_err().to_string(), "can't move note #1 next to itself");
+    }
+}
@@ -0,0 +1,234 @@
+//! Turns a NOTE argument into an id: numbers are ids, anything else is fuzzy-matched
+//! against the titles (`show kickoff`, `remove budgt`). Where several notes are accepted,
+//! id lists and ranges such as `3-7,12` work as well.
+
+use crate::Note;
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Minimum score per query character; a matched character scores about 16-20 with the
+/// skim algorithm, less when the match is scattered across the title.
+const MIN_SCORE_PER_CHAR: i64 = 12;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Candidate {
+    pub id: u64,
+    pub title: String,
+    pub score: i64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resolution {
+    Id(u64),
+    /// Best first
+    Ambiguous(Vec<Candidate>),
+    NotFound,
+}
+
+/// The fuzzy scorer for titles, shared with `--pick`.
+pub(crate) fn matcher() -> SkimMatcherV2 {
+    SkimMatcherV2::default().ignore_case()
+}
+
+/// Candidates scoring within 80% of the best one are "close"; one close candidate wins.
+fn is_close(score: i64, best: i64) -> bool {
+    score * 5 >= best * 4
+}
+
+pub fn resolve(notes: &[Note], arg: &str) -> Resolution {
+    let query = arg.trim();
+    if let Ok(id) = query.parse::<u64>() {
+        return Resolution::Id(id);
+    }
+    let exact: Vec<&Note> = notes.iter().filter(|n| n.title.to_lowercase() == query.to_lowercase()).collect();
+    match exact.as_slice() {
+        [n] => return Resolution::Id(n.id),
+        [_, _, ..] => {
+            let all = exact.iter().map(|n| Candidate { id: n.id, title: n.title.clone(), score: 0 }).collect();
+            return Resolution::Ambiguous(all);
+        }
+        [] => {}
+    }
+
+    let matcher = matcher();
+    let threshold = MIN_SCORE_PER_CHAR * query.chars().count() as i64;
+    let mut scored: Vec<Candidate> = notes
+        .iter()
+        .filter_map(|n| {
+            let score = matcher.fuzzy_match(&n.title, query).filter(|s| *s >= threshold)?;
+            Some(Candidate { id: n.id, title: n.title.clone(), score })
+        })
+        .collect();
+    scored.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id)));
+    let Some(best) = scored.first().map(|c| c.score) else {
+        return Resolution::NotFound;
+    };
+    scored.retain(|c| is_close(c.score, best));
+    if scored.len() == 1 { Resolution::Id(scored[0].id) } else { Resolution::Ambiguous(scored) }
+}
+
+/// Whether `arg` is an id list or range (`3-7,12`) rather than a single id or a title.
+pub fn is_id_spec(arg: &str) -> bool {
+    let arg = arg.trim();
+    arg.contains([',', '-'])
+        && arg.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '-' || c == ' ')
+        && arg.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Expands an id spec such as `3-7,12,15-16` into sorted ids without duplicates. Ranges include
+/// both ends; a trailing comma is fine, empty items, reversed ranges and anything else aren't.
+pub fn parse_id_spec(spec: &str) -> anyhow::Result<Vec<u64>> {
+    let trimmed = spec.trim();
+    let items = trimmed.strip_suffix(',').unwrap_or(trimmed);
+    let mut ids = BTreeSet::new();
+    for item in items.split(',').map(str::trim) {
+        let number = |s: &str| {
+            s.trim().parse::<u64>().map_err(|_| anyhow::anyhow!("invalid id list \"{}\": \"{}\" is not an id or a range", spec, item))
+        };
+        match item.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (number(from)?, number(to)?);
+                if from > to {
+                    anyhow::bail!("invalid id list \"{}\": the range {}-{} is reversed", spec, from, to);
+                }
+                ids.extend(from..=to);
+            }
+            None => {
+                ids.insert(number(item)?);
+            }
+        }
+    }
+    Ok(ids.into_iter().collect())
+}
+
+/// The ids an argument names: an id spec is expanded, anything else goes through [`note_id`].
+pub fn note_ids(notes: &[Note], arg: &str) -> anyhow::Result<Vec<u64>> {
+    if is_id_spec(arg) { parse_id_spec(arg) } else { note_id(notes, arg).map(|id| vec![id]) }
+}
+
+/// [`resolve`], asking which note was meant when several match and stdin/stdout are a terminal.
+/// Otherwise an ambiguous or unmatched argument is an error listing the candidates.
+pub fn note_id(notes: &[Note], arg: &str) -> anyhow::Result<u64> {
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    note_id_with(notes, arg, interactive, &mut io::stdin().lock(), &mut io::stdout())
+}
+
+/// [`note_id`] with the terminal check, input and output supplied by the caller.
+pub fn note_id_with(
+    notes: &[Note],
+    arg: &str,
+    interactive: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> anyhow::Result<u64> {
+    let candidates = match resolve(notes, arg) {
+        Resolution::Id(id) => return Ok(id),
+        Resolution::NotFound => anyhow::bail!("no note id or title matches \"{}\"", arg),
+        Resolution::Ambiguous(candidates) => candidates,
+    };
+    let list: Vec<String> =
+        candidates.iter().enumerate().map(|(i, c)| format!("  {}) #{}  {}", i + 1, c.id, c.title)).collect();
+    if !interactive {
+        anyhow::bail!("\"{}\" matches several notes, use the id instead:\n{}", arg, list.join("\n"));
+    }
+    writeln!(output, "\"{}\" matches several notes:\n{}", arg, list.join("\n"))?;
+    write!(output, "Which one? [1-{}, empty to cancel] ", candidates.len())?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    match answer.trim().parse::<usize>() {
+        Ok(n) if (1..=candidates.len()).contains(&n) => Ok(candidates[n - 1].id),
+        _ => anyhow::bail!("no note picked"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(titles: &[&str]) -> Vec<Note> {
+        titles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Note::new(i as u64 + 1, *t))
+            .collect()
+    }
+
+    fn ids(r: Resolution) -> Vec<u64> {
+        match r {
+            Resolution::Id(id) => vec![id],
+            Resolution::Ambiguous(c) => c.iter().map(|c| c.id).collect(),
+            Resolution::NotFound => vec![],
+        }
+    }
+
+    const TITLES: [&str; 6] =
+        ["Project kickoff", "Weekly sync", "Budget review", "Weekly meeting", "Kick scooter repair", "Groceries"];
+
+    #[test]
+    fn numbers_are_ids_even_when_missing() {
+        assert_eq!(resolve(&notes(&TITLES), "42"), Resolution::Id(42));
+    }
+
+    #[test]
+    fn one_clear_winner_is_used() {
+        let n = notes(&TITLES);
+        assert_eq!(resolve(&n, "kickoff"), Resolution::Id(1));
+        assert_eq!(resolve(&n, "weekly sync"), Resolution::Id(2));
+        assert_eq!(resolve(&n, "budgt"), Resolution::Id(3));
+        assert_eq!(resolve(&n, "GROCERIES"), Resolution::Id(6));
+    }
+
+    #[test]
+    fn close_scores_are_ambiguous() {
+        let n = notes(&TITLES);
+        assert_eq!(ids(resolve(&n, "weekly")), [2, 4]);
+        // "Kick scooter repair" scores a bit higher, but "Project kickoff" is close
+        assert_eq!(ids(resolve(&n, "kick")), [5, 1]);
+        // Identical titles can only be told apart by id
+        assert_eq!(ids(resolve(&notes(&["Todo", "todo"]), "todo")), [1, 2]);
+    }
+
+    #[test]
+    fn weak_matches_are_below_the_threshold() {
+        let n = notes(&TITLES);
+        assert_eq!(resolve(&n, "xyz"), Resolution::NotFound);
+        // k, y and g appear in order in "Weekly meeting", but scattered mid-word
+        assert_eq!(resolve(&n, "kyg"), Resolution::NotFound);
+    }
+
+    #[test]
+    fn id_specs_expand_sorted_and_deduplicated() {
+        assert_eq!(parse_id_spec("3-7,12,15-16").unwrap(), [3, 4, 5, 6, 7, 12, 15, 16]);
+        assert_eq!(parse_id_spec("5-8,2,6-9,2").unwrap(), [2, 5, 6, 7, 8, 9]);
+        assert_eq!(parse_id_spec("4").unwrap(), [4]);
+        assert_eq!(parse_id_spec("4-4").unwrap(), [4]);
+        assert_eq!(parse_id_spec(" 1, 3 - 4 ,").unwrap(), [1, 3, 4]);
+        assert_eq!(parse_id_spec("7-3").unwrap_err().to_string(), "invalid id list \"7-3\": the range 7-3 is reversed");
+        for bad in ["", ",", "1,,2", "1-", "-3", "1-2-3", "a-b", "1,x"] {
+            assert!(parse_id_spec(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn only_digit_lists_and_ranges_are_specs() {
+        assert!(is_id_spec("3-7") && is_id_spec("12,") && is_id_spec("1, 2"));
+        assert!(!is_id_spec("12") && !is_id_spec("to-do") && !is_id_spec("2024 budget") && !is_id_spec("-"));
+        assert_eq!(note_ids(&notes(&TITLES), "2-3").unwrap(), [2, 3]);
+        assert_eq!(note_ids(&notes(&TITLES), "groceries").unwrap(), [6]);
+    }
+
+    #[test]
+    fn ambiguity_is_an_error_or_a_numbered_question() {
+        let n = notes(&TITLES);
+        let err = note_id_with(&n, "weekly", false, &mut "".as_bytes(), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.to_string(), "\"weekly\" matches several notes, use the id instead:\n  1) #2  Weekly sync\n  2) #4  Weekly meeting");
+
+        let mut out = Vec::new();
+        assert_eq!(note_id_with(&n, "weekly", true, &mut "2\n".as_bytes(), &mut out).unwrap(), 4);
+        assert!(String::from_utf8(out).unwrap().ends_with("Which one? [1-2, empty to cancel] "));
+        assert!(note_id_with(&n, "weekly", true, &mut "\n".as_bytes(), &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! `serve`: a small JSON HTTP API over the store. Every request goes through a
+//! [`Session`], so it locks, loads and saves like a CLI command and both can be used side by side.
+
+use crate::history::{self, Revision};
+use crate::journal::Inverse;
+use crate::session::Session;
+use crate::store::StoreChanged;
+use crate::{add_tags, normalize_tags, remove_tags, FieldMask, Matcher, Note, SearchFilter, TagMode};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Read;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest request body accepted, in bytes.
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// Body of `POST /notes`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewNote {
+    title: String,
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Body of `PATCH /notes/:id`: the same changes `edit` takes as flags.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NoteChanges {
+    title: Option<String>,
+    body: Option<String>,
+    tags: Option<Vec<String>>,
+    #[serde(default)]
+    add_tags: Vec<String>,
+    #[serde(default)]
+    rm_tags: Vec<String>,
+}
+
+/// A failed request: the status code and the message sent back as `{"error": ...}`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into() }
+    }
+
+    fn not_found(id: u64) -> Self {
+        ApiError::new(404, format!("no note with id {}", id))
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        // Another writer got in between load and save: the client can simply retry
+        let status = if e.is::<StoreChanged>() { 409 } else { 500 };
+        ApiError::new(status, format!("{:#}", e))
+    }
+}
+
+type Reply = Result<(u16, Value), ApiError>;
+
+pub struct Api {
+    pub session: Session,
+}
+
+/// Decodes `%XX` escapes and `+` in a query string component.
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() && bytes[i + 1..i + 3].iter().all(u8::is_ascii_hexdigit) => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).expect("hex digits are ASCII");
+                out.push(u8::from_str_radix(hex, 16).expect("checked hex digits"));
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .find(|(k, _)| decode(k) == name)
+        .map(|(_, v)| decode(v))
+}
+
+fn parse_id(segment: &str) -> Result<u64, ApiError> {
+    segment.parse().map_err(|_| ApiError::new(400, format!("invalid note id \"{}\"", decode(segment))))
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, ApiError> {
+    serde_json::from_str(body).map_err(|e| ApiError::new(400, format!("invalid request body: {}", e)))
+}
+
+fn note_json(n: &Note) -> Value {
+    serde_json::to_value(n).expect("notes always serialize")
+}
+
+impl Api {
+    /// Answers one request; `url` is the path with its query string.
+    pub fn handle(&self, method: &Method, url: &str, body: &str) -> Reply {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            (Method::Get, ["notes"]) => self.session.read(|s| Ok((200, json!(s.notes)))),
+            (Method::Post, ["notes"]) => self.add(parse_body(body)?),
+            (Method::Get, ["notes", id]) => {
+                let id = parse_id(id)?;
+                self.session.read(|s| s.notes.iter().find(|n| n.id == id).map(|n| (200, note_json(n))).ok_or(ApiError::not_found(id)))
+            }
+            (Method::Patch, ["notes", id]) => self.edit(parse_id(id)?, parse_body(body)?),
+            (Method::Delete, ["notes", id]) => self.remove(parse_id(id)?),
+            (Method::Get, ["search"]) => {
+                let tags = query_param(query, "tags").map(|t| t.split(',').map(str::to_string).collect()).unwrap_or_default();
+                let filter = SearchFilter {
+                    matcher: Matcher::new(&query_param(query, "q").unwrap_or_default(), false, false)?,
+                    mask: FieldMask::from_fields(&[]),
+                    mode: TagMode::All,
+                    tags: normalize_tags(tags),
+                    not_tags: vec![],
+                };
+                self.session.read(|s| Ok((200, json!(s.notes.iter().filter(|n| filter.matches(n)).collect::<Vec<_>>()))))
+            }
+            (_, ["notes"] | ["notes", _] | ["search"]) => Err(ApiError::new(405, format!("{} is not allowed on {}", method, path))),
+            _ => Err(ApiError::new(404, format!("no such endpoint: {}", path))),
+        }
+    }
+
+    fn add(&self, new: NewNote) -> Reply {
+        if new.title.trim().is_empty() {
+            return Err(ApiError::new(400, "a note needs a title"));
+        }
+        self.session.change(|storage| {
+            let note = Note {
+                body: new.body.into(),
+                tags: normalize_tags(new.tags),
+                ..Note::new(storage.next_id(), new.title)
+            };
+            storage.notes.push(note.clone());
+            let command = format!("add #{} {:?}", note.id, note.title);
+            Ok(((201, note_json(&note)), Some((command, Inverse::Remove { id: note.id }))))
+        })
+    }
+
+    fn edit(&self, id: u64, changes: NoteChanges) -> Reply {
+        self.session.change(|storage| {
+            let n = storage.notes.iter_mut().find(|n| n.id == id).ok_or(ApiError::not_found(id))?;
+            let original = n.clone();
+            if let Some(t) = changes.title {
+                n.title = t;
+            }
+            if let Some(b) = changes.body {
+                n.body = b.into();
+            }
+            if let Some(ts) = changes.tags {
+                n.tags = normalize_tags(ts);
+            }
+            add_tags(&mut n.tags, changes.add_tags);
+            remove_tags(&mut n.tags, changes.rm_tags);
+            if n.title.trim().is_empty() {
+                return Err(ApiError::new(400, "a note needs a title"));
+            }
+            if !history::content_changed(&original, n) {
+                return Ok(((200, note_json(n)), None));
+            }
+            history::push(n, Revision::of(&original, Utc::now()), self.session.history_depth);
+            let command = format!("edit #{} {:?}", id, original.title);
+            Ok(((200, note_json(n)), Some((command, Inverse::Replace { note: original }))))
+        })
+    }
+
+    fn remove(&self, id: u64) -> Reply {
+        self.session.change(|storage| {
+            let position = storage.notes.iter().position(|n| n.id == id).ok_or(ApiError::not_found(id))?;
+            let note = storage.notes.remove(position);
+            let command = format!("remove #{} {:?}", note.id, note.title);
+            Ok(((200, note_json(&note)), Some((command, Inverse::Restore { note, position }))))
+        })
+    }
+
+    fn respond(&self, mut request: Request) -> std::io::Result<()> {
+        let mut body = String::new();
+        let read = request.as_reader().take(MAX_BODY + 1).read_to_string(&mut body);
+        let reply = match read {
+            Err(_) => Err(ApiError::new(400, "request body is not UTF-8")),
+            Ok(n) if n as u64 > MAX_BODY => Err(ApiError::new(413, "request body is too large")),
+            Ok(_) => self.handle(request.method(), request.url(), &body),
+        };
+        let (status, value) = reply.unwrap_or_else(|e| (e.status, json!({ "error": e.message })));
+        let content_type = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+        request.respond(Response::from_string(value.to_string()).with_status_code(status).with_header(content_type))
+    }
+
+    /// Answers requests one at a time until the server is shut down.
+    pub fn run(&self, server: &Server) {
+        for request in server.incoming_requests() {
+            if let Err(e) = self.respond(request) {
+                eprintln!("couldn't send a response: {}", e);
+            }
+        }
+    }
+}
+
+/// Starts listening on `addr` (e.g. `127.0.0.1:7777`; port 0 picks a free one).
+pub fn bind(addr: &str) -> anyhow::Result<Server> {
+    Server::http(addr).map_err(|e| anyhow::anyhow!("couldn't listen on {}: {}", addr, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_strings_are_decoded() {
+        assert_eq!(decode("weekly+sync%21"), "weekly sync!");
+        assert_eq!(decode("%E4%BC%9A%E8%AD%B0"), "会議");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(query_param("q=a%20b&tags=work,home", "tags").as_deref(), Some("work,home"));
+        assert_eq!(query_param("q&tags=x", "q").as_deref(), Some(""));
+        assert_eq!(query_param("q=a", "tags"), None);
+    }
+
+    #[test]
+    fn store_changes_are_conflicts_and_bad_ids_are_client_errors() {
+        let changed = anyhow::Error::from(StoreChanged { loaded: 1, on_disk: 2 });
+        assert_eq!(ApiError::from(changed).status, 409);
+        assert_eq!(parse_id("12").unwrap(), 12);
+        assert_eq!(parse_id("abc").unwrap_err(), ApiError::new(400, "invalid note id \"abc\""));
+    }
+}
@@ -0,0 +1,66 @@
+//! The store as seen by long-running commands (`serve`, `tui`): instead of holding the lock
+//! for their whole run, they lock and reload for every read or change, like a one-shot
+//! command would, so other rustic-notes processes can use the store in between.
+
+use crate::journal::{self, Entry, Inverse};
+use crate::lock::{LockKind, StoreLock};
+use crate::store::Store;
+use crate::{crypto, git, Storage};
+use chrono::Utc;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// What a change did, for `undo --list` and the auto-commit message (e.g. `edit #3 "Title"`),
+/// and how to revert it.
+pub type Change = (String, Inverse);
+
+pub struct Session {
+    pub store_path: PathBuf,
+    pub store: Box<dyn Store>,
+    pub lock_timeout: Duration,
+    /// Revisions kept per note by edits (--history-depth)
+    pub history_depth: usize,
+    /// Operations kept for `undo` (--journal-size)
+    pub journal_size: usize,
+    pub auto_commit: bool,
+}
+
+impl Session {
+    fn lock(&self, kind: LockKind) -> anyhow::Result<Option<StoreLock>> {
+        if kind == LockKind::Shared && !self.store_path.exists() {
+            return Ok(None);
+        }
+        StoreLock::acquire(&self.store_path, kind, self.lock_timeout).map(Some)
+    }
+
+    /// Loads the notes under a shared lock.
+    pub fn read<T, E: From<anyhow::Error>>(&self, f: impl FnOnce(&Storage) -> Result<T, E>) -> Result<T, E> {
+        let _lock = self.lock(LockKind::Shared)?;
+        f(&self.store.load_all()?)
+    }
+
+    /// Loads under the exclusive lock and lets `f` change the notes. When it reports a
+    /// [`Change`] the store is saved, the change journaled for `undo` and auto-committed.
+    pub fn change<T, E: From<anyhow::Error>>(
+        &self,
+        f: impl FnOnce(&mut Storage) -> Result<(T, Option<Change>), E>,
+    ) -> Result<T, E> {
+        let _lock = self.lock(LockKind::Exclusive)?;
+        let mut storage = self.store.load_all()?;
+        let before_hash = journal::hash(&storage);
+        let (value, change) = f(&mut storage)?;
+        if let Some((command, inverse)) = change {
+            self.store.save_all(&storage)?;
+            // The journal holds note contents in plaintext, so encrypted stores don't get one
+            let encrypted = std::fs::read(&self.store_path).is_ok_and(|data| crypto::is_encrypted(&data));
+            if !encrypted {
+                let entry = Entry { at: Utc::now(), command: command.clone(), inverse };
+                journal::record(&self.store_path, &before_hash, &storage, entry, self.journal_size)?;
+            }
+            if self.auto_commit {
+                git::commit_store(&self.store_path, &command)?;
+            }
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,179 @@
+//! `snapshot`: timestamped copies of the store file kept in a directory, independent of the
+//! backups rotated on every save. A copy is only taken when the store's bytes changed since
+//! the newest snapshot; `index.json` in the directory records each copy's sha256.
+
+use crate::backup;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the index file inside the snapshot directory.
+pub const INDEX: &str = "index.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// File name inside the snapshot directory
+    pub name: String,
+    pub taken_at: DateTime<Utc>,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Taken {
+    New(Snapshot),
+    /// The store is identical to this, the newest snapshot
+    Unchanged(Snapshot),
+}
+
+/// `snapshots/` next to the store.
+pub fn default_dir(store: &Path) -> PathBuf {
+    store.parent().unwrap_or(Path::new("")).join("snapshots")
+}
+
+pub fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The snapshots in `dir`, oldest first; a missing directory has none.
+pub fn list(dir: &Path) -> anyhow::Result<Vec<Snapshot>> {
+    let path = dir.join(INDEX);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    serde_json::from_str(&fs::read_to_string(&path)?).map_err(|e| anyhow::anyhow!("invalid snapshot index {}: {}", path.display(), e))
+}
+
+fn save_index(dir: &Path, snapshots: &[Snapshot]) -> anyhow::Result<()> {
+    fs::write(dir.join(INDEX), serde_json::to_string_pretty(snapshots)?)?;
+    Ok(())
+}
+
+/// `<stem>-YYYYMMDD-HHMMSS.<ext>`, with a counter when that name is taken.
+fn file_name(store: &Path, dir: &Path, at: DateTime<Utc>) -> String {
+    let stem = store.file_stem().map_or("notes".into(), |s| s.to_string_lossy());
+    let ext = store.extension().map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+    let base = format!("{}-{}", stem, at.format("%Y%m%d-%H%M%S"));
+    let mut name = format!("{}{}", base, ext);
+    let mut n = 1;
+    while dir.join(&name).exists() {
+        n += 1;
+        name = format!("{}-{}{}", base, n, ext);
+    }
+    name
+}
+
+/// Copies the store into `dir` unless the newest snapshot has the same content.
+pub fn take(store: &Path, dir: &Path, now: DateTime<Utc>) -> anyhow::Result<Taken> {
+    if !store.exists() {
+        anyhow::bail!("there is no store at {} yet", store.display());
+    }
+    let data = fs::read(store)?;
+    let hash = sha256(&data);
+    let mut snapshots = list(dir)?;
+    if let Some(last) = snapshots.last().filter(|s| s.sha256 == hash) {
+        return Ok(Taken::Unchanged(last.clone()));
+    }
+    fs::create_dir_all(dir)?;
+    let name = file_name(store, dir, now);
+    fs::write(dir.join(&name), &data)?;
+    let snapshot = Snapshot { name, taken_at: now, sha256: hash, size: data.len() as u64 };
+    snapshots.push(snapshot.clone());
+    save_index(dir, &snapshots)?;
+    Ok(Taken::New(snapshot))
+}
+
+/// Copies snapshot `name` back over the store, after checking it still has the recorded hash.
+/// The replaced store is rotated into the backups.
+pub fn restore(store: &Path, dir: &Path, name: &str, keep: usize) -> anyhow::Result<Snapshot> {
+    let Some(snapshot) = list(dir)?.into_iter().find(|s| s.name == name) else {
+        anyhow::bail!("there is no snapshot {} in {}", name, dir.display());
+    };
+    let data = fs::read(dir.join(name))?;
+    if sha256(&data) != snapshot.sha256 {
+        anyhow::bail!("snapshot {} was changed after it was taken (its sha256 doesn't match the index)", name);
+    }
+    backup::rotate(store, keep)?;
+    fs::write(store, data)?;
+    Ok(snapshot)
+}
+
+/// Deletes all but the newest `keep` snapshots, returning the deleted ones.
+pub fn prune(dir: &Path, keep: usize) -> anyhow::Result<Vec<Snapshot>> {
+    let mut snapshots = list(dir)?;
+    let excess = snapshots.len().saturating_sub(keep);
+    let removed: Vec<Snapshot> = snapshots.drain(..excess).collect();
+    for s in &removed {
+        match fs::remove_file(dir.join(&s.name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    if !removed.is_empty() {
+        save_index(dir, &snapshots)?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn at(second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 5, 4, 10, 30, second).unwrap()
+    }
+
+    fn name(taken: Taken) -> String {
+        match taken {
+            Taken::New(s) => s.name,
+            Taken::Unchanged(s) => format!("unchanged {}", s.name),
+        }
+    }
+
+    #[test]
+    fn only_changed_content_is_copied() {
+        let tmp = TempDir::new().unwrap();
+        let (store, dir) = (tmp.path().join("notes.json"), tmp.path().join("snaps"));
+        assert!(take(&store, &dir, at(0)).is_err());
+
+        fs::write(&store, "one").unwrap();
+        assert_eq!(name(take(&store, &dir, at(0)).unwrap()), "notes-20260504-103000.json");
+        assert_eq!(name(take(&store, &dir, at(1)).unwrap()), "unchanged notes-20260504-103000.json");
+        fs::write(&store, "two").unwrap();
+        assert_eq!(name(take(&store, &dir, at(0)).unwrap()), "notes-20260504-103000-2.json");
+        // Going back to older content is a change too
+        fs::write(&store, "one").unwrap();
+        assert_eq!(name(take(&store, &dir, at(2)).unwrap()), "notes-20260504-103002.json");
+
+        let snapshots = list(&dir).unwrap();
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots[1].sha256, sha256(b"two"));
+        assert_eq!(fs::read_to_string(dir.join(&snapshots[1].name)).unwrap(), "two");
+    }
+
+    #[test]
+    fn restore_checks_the_hash_and_prune_keeps_the_newest() {
+        let tmp = TempDir::new().unwrap();
+        let (store, dir) = (tmp.path().join("notes.json"), tmp.path().join("snaps"));
+        for (i, content) in ["a", "b", "c"].iter().enumerate() {
+            fs::write(&store, content).unwrap();
+            take(&store, &dir, at(i as u32)).unwrap();
+        }
+        restore(&store, &dir, "notes-20260504-103000.json", 1).unwrap();
+        assert_eq!(fs::read_to_string(&store).unwrap(), "a");
+        assert_eq!(fs::read_to_string(backup::backup_path(&store, 1)).unwrap(), "c");
+        assert!(restore(&store, &dir, "missing.json", 1).is_err());
+        fs::write(dir.join("notes-20260504-103001.json"), "tampered").unwrap();
+        assert!(restore(&store, &dir, "notes-20260504-103001.json", 1).is_err());
+
+        let removed: Vec<String> = prune(&dir, 1).unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(removed, ["notes-20260504-103000.json", "notes-20260504-103001.json"]);
+        assert_eq!(list(&dir).unwrap().len(), 1);
+        assert!(!dir.join("notes-20260504-103000.json").exists());
+        assert!(prune(&dir, 5).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+//! `split`: a note holding several topics broken into one note per section. Sections start
+//! at `## ` headings, or at lines starting with a `--delimiter`; lines inside fenced code
+//! blocks never start one.
+
+use serde::Serialize;
+
+/// Heading that starts a section unless another delimiter is given
+pub const DEFAULT_DELIMITER: &str = "## ";
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Section {
+    pub title: String,
+    pub body: String,
+}
+
+/// How a body divides: what comes before the first section stays with the original note.
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    pub preamble: String,
+    pub sections: Vec<Section>,
+}
+
+/// Divides `body` at the lines starting with `delimiter`. A section is titled by the rest of
+/// its delimiter line, or by its first line when that is empty; sections without any text
+/// are left out.
+pub fn plan(body: &str, delimiter: &str) -> Plan {
+    let mut preamble = Vec::new();
+    let mut sections: Vec<(String, Vec<&str>)> = Vec::new();
+    let mut fenced = false;
+    for line in body.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+        match line.strip_prefix(delimiter) {
+            Some(heading) if !fenced => sections.push((heading.trim().to_string(), vec![])),
+            _ => match sections.last_mut() {
+                Some((_, lines)) => lines.push(line),
+                None => preamble.push(line),
+            },
+        }
+    }
+    let sections = sections
+        .into_iter()
+        .filter_map(|(heading, lines)| {
+            let mut body = lines.join("\n").trim().to_string();
+            if body.is_empty() {
+                return None;
+            }
+            let title = if heading.is_empty() {
+                let (first, rest) = body.split_once('\n').unwrap_or((&body, ""));
+                let (first, rest) = (first.trim().to_string(), rest.trim().to_string());
+                body = rest;
+                first
+            } else {
+                heading
+            };
+            Some(Section { title, body })
+        })
+        .collect();
+    Plan { preamble: preamble.join("\n").trim().to_string(), sections }
+}
+
+/// Body left in the original note: its preamble, then a `[[id]]` link to each new note.
+pub fn index_body(preamble: &str, ids: &[u64]) -> String {
+    let links: Vec<String> = ids.iter().map(|id| format!("- [[{}]]", id)).collect();
+    if preamble.is_empty() { links.join("\n") } else { format!("{}\n\n{}", preamble, links.join("\n")) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(title: &str, body: &str) -> Section {
+        Section { title: title.to_string(), body: body.to_string() }
+    }
+
+    #[test]
+    fn splits_at_headings_keeping_the_preamble() {
+        let body = "Dump from Monday\n\n## Budget\nQ3 numbers\n\n## Empty\n\n## Hiring\n- two devs\n```\n## not a heading\n```\n## Travel\nBook flights\n";
+        let plan = plan(body, DEFAULT_DELIMITER);
+        assert_eq!(plan.preamble, "Dump from Monday");
+        assert_eq!(
+            plan.sections,
+            [
+                section("Budget", "Q3 numbers"),
+                section("Hiring", "- two devs\n```\n## not a heading\n```"),
+                section("Travel", "Book flights"),
+            ]
+        );
+        assert_eq!(index_body(&plan.preamble, &[4, 5]), "Dump from Monday\n\n- [[4]]\n- [[5]]");
+        assert_eq!(index_body("", &[4]), "- [[4]]");
+    }
+
+    #[test]
+    fn custom_delimiters_take_the_title_from_the_first_line() {
+        let plan = plan("---\nFirst idea\nmore\n--- Second\nbody\n---\n", "---");
+        assert_eq!(plan.preamble, "");
+        assert_eq!(plan.sections, [section("First idea", "more"), section("Second", "body")]);
+        assert!(super::plan("no headings at all", DEFAULT_DELIMITER).sections.is_empty());
+    }
+}
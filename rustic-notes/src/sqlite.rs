@@ -0,0 +1,372 @@
+use crate::store::{ensure_parent, Store, Versions};
+use crate::{backup, Note, Storage};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS notes (
+    id         INTEGER PRIMARY KEY,
+    title      TEXT NOT NULL,
+    body       TEXT NOT NULL,
+    tags       TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    history    TEXT NOT NULL DEFAULT '[]',
+    attachments TEXT NOT NULL DEFAULT '[]',
+    pinned     INTEGER NOT NULL DEFAULT 0,
+    rank       INTEGER,
+    meta       TEXT NOT NULL DEFAULT '{}',
+    due        TEXT NOT NULL DEFAULT '',
+    notified_at TEXT NOT NULL DEFAULT '',
+    position   INTEGER
+);
+CREATE TABLE IF NOT EXISTS meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);";
+
+/// Columns added after the first release, created on open for older databases.
+const ADDED_COLUMNS: [(&str, &str); 8] = [
+    ("history", "TEXT NOT NULL DEFAULT '[]'"),
+    ("attachments", "TEXT NOT NULL DEFAULT '[]'"),
+    ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+    ("rank", "INTEGER"),
+    ("meta", "TEXT NOT NULL DEFAULT '{}'"),
+    ("due", "TEXT NOT NULL DEFAULT ''"),
+    ("notified_at", "TEXT NOT NULL DEFAULT ''"),
+    // Notes used to be listed by id, which `position = id` keeps
+    ("position", "INTEGER"),
+];
+
+/// The notes as of the last load or save, each with its `position`.
+struct Rows {
+    version: u64,
+    last_prune: Option<DateTime<Utc>>,
+    notes: Vec<(i64, Note)>,
+}
+
+/// SQLite database with one row per note (tags stored as a JSON array), kept in store order by
+/// a `position` column. Saving only writes the rows that were added, changed or removed.
+pub struct SqliteStore {
+    pub path: PathBuf,
+    /// Rotated copies kept when a save removes notes (adding or changing notes doesn't rotate)
+    pub backups: usize,
+    /// Rows as of the last load or save, so a save doesn't have to read them all again
+    last: RefCell<Option<Rows>>,
+    versions: Versions,
+}
+
+impl SqliteStore {
+    pub fn new(path: PathBuf, backups: usize) -> Self {
+        SqliteStore { path, backups, last: RefCell::new(None), versions: Versions::default() }
+    }
+
+    /// Saves even when another process saved the database since it was loaded.
+    pub fn force_save(mut self, force: bool) -> Self {
+        self.versions.force = force;
+        self
+    }
+
+    fn connect(&self) -> anyhow::Result<Connection> {
+        ensure_parent(&self.path)?;
+        let conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+        for (column, definition) in ADDED_COLUMNS {
+            let exists = conn.prepare("SELECT 1 FROM pragma_table_info('notes') WHERE name = ?1")?.exists([column])?;
+            if !exists {
+                conn.execute_batch(&format!("ALTER TABLE notes ADD COLUMN {} {}", column, definition))?;
+                if column == "position" {
+                    conn.execute_batch("UPDATE notes SET position = id")?;
+                }
+            }
+        }
+        Ok(conn)
+    }
+}
+
+fn meta(conn: &Connection, key: &str) -> anyhow::Result<Option<String>> {
+    Ok(conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |r| r.get(0)).optional()?)
+}
+
+/// Timestamps stored as RFC 3339 text, empty for none.
+fn parse_time(s: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc)))
+}
+
+fn format_time(t: Option<DateTime<Utc>>) -> String {
+    t.map(|t| t.to_rfc3339()).unwrap_or_default()
+}
+
+fn read_version(conn: &Connection) -> anyhow::Result<u64> {
+    Ok(meta(conn, "version")?.map(|v| v.parse()).transpose()?.unwrap_or(0))
+}
+
+fn read_rows(conn: &Connection) -> anyhow::Result<Rows> {
+    let version = read_version(conn)?;
+    let last_prune = parse_time(&meta(conn, "last_prune")?.unwrap_or_default())?;
+    Ok(Rows { version, last_prune, notes: read_notes(conn)? })
+}
+
+fn read_notes(conn: &Connection) -> anyhow::Result<Vec<(i64, Note)>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, title, body, tags, created_at, updated_at, history, attachments, pinned, rank, meta, due, notified_at, position
+         FROM notes ORDER BY position, id",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        Ok((
+            r.get::<_, i64>(0)?,
+            r.get(1)?,
+            r.get::<_, String>(2)?,
+            r.get::<_, String>(3)?,
+            r.get::<_, String>(4)?,
+            r.get::<_, String>(5)?,
+            r.get::<_, String>(6)?,
+            r.get::<_, String>(7)?,
+            r.get::<_, bool>(8)?,
+            r.get::<_, Option<u32>>(9)?,
+            r.get::<_, String>(10)?,
+            r.get::<_, String>(11)?,
+            r.get::<_, String>(12)?,
+            r.get::<_, Option<i64>>(13)?,
+        ))
+    })?;
+    let mut notes = Vec::new();
+    for row in rows {
+        let (id, title, body, tags, created_at, updated_at, history, attachments, pinned, rank, meta, due, notified_at, position) = row?;
+        let note = Note {
+            id: id as u64,
+            title,
+            body: body.into(),
+            tags: serde_json::from_str(&tags)?,
+            created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+            updated_at: parse_time(&updated_at)?,
+            history: serde_json::from_str(&history)?,
+            attachments: serde_json::from_str(&attachments)?,
+            pinned,
+            rank,
+            meta: serde_json::from_str(&meta)?,
+            due: parse_time(&due)?,
+            notified_at: parse_time(&notified_at)?,
+        };
+        notes.push((position.unwrap_or(id), note));
+    }
+    Ok(notes)
+}
+
+/// Positions for `after`: the ones the notes already have, with new notes after the highest
+/// one, as long as that keeps them in order; otherwise (e.g. `undo` putting a removed note back
+/// in the middle) 1, 2, 3, ...
+fn positions(before: &HashMap<u64, (i64, &Note)>, after: &[Note]) -> Vec<i64> {
+    let mut next = before.values().map(|(p, _)| *p).max().unwrap_or(0);
+    let kept: Vec<i64> = after
+        .iter()
+        .map(|n| match before.get(&n.id) {
+            Some((p, _)) => *p,
+            None => {
+                next += 1;
+                next
+            }
+        })
+        .collect();
+    if kept.windows(2).all(|w| w[0] < w[1]) {
+        kept
+    } else {
+        (1..=after.len() as i64).collect()
+    }
+}
+
+impl Store for SqliteStore {
+    fn load_all(&self) -> anyhow::Result<Storage> {
+        if !self.path.exists() {
+            self.versions.seen(0);
+            return Ok(Storage::default());
+        }
+        let conn = self.connect()?;
+        let rows = read_rows(&conn)?;
+        let saved_at = parse_time(&meta(&conn, "saved_at")?.unwrap_or_default())?;
+        self.versions.seen(rows.version);
+        let storage = Storage {
+            version: rows.version,
+            saved_at,
+            last_prune: rows.last_prune,
+            checksum: None,
+            notes: rows.notes.iter().map(|(_, n)| n.clone()).collect(),
+        };
+        *self.last.borrow_mut() = Some(rows);
+        Ok(storage)
+    }
+
+    fn save_all(&self, storage: &Storage) -> anyhow::Result<()> {
+        let existed = self.path.exists();
+        let mut conn = self.connect()?;
+        // IMMEDIATE takes the write lock up front, so the version can't move after it's checked
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let on_disk = read_version(&tx)?;
+        // The cached rows are only what's on disk if nobody saved since
+        let cached = self.last.borrow_mut().take().filter(|rows| rows.version == on_disk);
+        let before = match cached {
+            Some(rows) => rows,
+            None => read_rows(&tx)?,
+        };
+        if before.notes.iter().map(|(_, n)| n).eq(&storage.notes) && before.last_prune == storage.last_prune {
+            *self.last.borrow_mut() = Some(before);
+            return Ok(());
+        }
+        let version = self.versions.next(on_disk, storage)?;
+        let old: HashMap<u64, (i64, &Note)> = before.notes.iter().map(|(p, n)| (n.id, (*p, n))).collect();
+        let keep: HashSet<u64> = storage.notes.iter().map(|n| n.id).collect();
+        let removed: Vec<u64> = old.keys().copied().filter(|id| !keep.contains(id)).collect();
+        if existed && !removed.is_empty() {
+            backup::rotate(&self.path, self.backups)?;
+        }
+        let positions = positions(&old, &storage.notes);
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO notes (id, title, body, tags, created_at, updated_at, history, attachments, pinned, rank, meta, due, notified_at, position)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+                 ON CONFLICT(id) DO UPDATE SET
+                    title = excluded.title, body = excluded.body, tags = excluded.tags,
+                    created_at = excluded.created_at, updated_at = excluded.updated_at,
+                    history = excluded.history, attachments = excluded.attachments, pinned = excluded.pinned,
+                    rank = excluded.rank, meta = excluded.meta, due = excluded.due, notified_at = excluded.notified_at,
+                    position = excluded.position",
+            )?;
+            for (n, &position) in storage.notes.iter().zip(&positions) {
+                if old.get(&n.id).is_some_and(|(p, o)| *p == position && *o == n) {
+                    continue;
+                }
+                upsert.execute(params![
+                    n.id as i64,
+                    n.title,
+                    n.body.as_str(),
+                    serde_json::to_string(&n.tags)?,
+                    n.created_at.to_rfc3339(),
+                    format_time(n.updated_at),
+                    serde_json::to_string(&n.history)?,
+                    serde_json::to_string(&n.attachments)?,
+                    n.pinned,
+                    n.rank,
+                    serde_json::to_string(&n.meta)?,
+                    format_time(n.due),
+                    format_time(n.notified_at),
+                    position,
+                ])?;
+            }
+            let mut delete = tx.prepare("DELETE FROM notes WHERE id = ?1")?;
+            for id in removed {
+                delete.execute([id as i64])?;
+            }
+            let mut set_meta = tx.prepare("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)")?;
+            set_meta.execute(["version", &version.to_string()])?;
+            set_meta.execute(["saved_at", &Utc::now().to_rfc3339()])?;
+            set_meta.execute(["last_prune", &format_time(storage.last_prune)])?;
+        }
+        tx.commit()?;
+        self.versions.seen(version);
+        let notes = positions.into_iter().zip(storage.notes.iter().cloned()).collect();
+        *self.last.borrow_mut() = Some(Rows { version, last_prune: storage.last_prune, notes });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn note(id: u64, title: &str) -> Note {
+        Note {
+            id,
+            title: title.to_string(),
+            body: format!("body of {}", title).into(),
+            tags: vec!["a".to_string(), "b".to_string()],
+            created_at: DateTime::parse_from_rfc3339("2025-09-14T12:34:56Z").unwrap().with_timezone(&Utc),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_and_deletes_missing_rows() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::new(dir.path().join("notes.db"), 0);
+        let mut storage = Storage { notes: vec![note(1, "one"), note(2, "two"), note(3, "three")], ..Default::default() };
+        storage.notes[2].rank = Some(20);
+        storage.notes[2].meta.insert("project".to_string(), "apollo".to_string());
+        storage.notes[2].due = Some(storage.notes[2].created_at);
+        store.save_all(&storage).unwrap();
+        assert_eq!(store.load_all().unwrap().notes, storage.notes);
+
+        storage.notes.remove(1);
+        storage.notes[0].title = "uno".to_string();
+        store.save_all(&storage).unwrap();
+        let loaded = store.load_all().unwrap();
+        assert_eq!(loaded.notes, storage.notes);
+        assert_eq!(loaded.version, 2);
+    }
+
+    #[test]
+    fn saves_only_write_the_rows_that_changed() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::new(dir.path().join("notes.db"), 1);
+        let storage = Storage { notes: vec![note(1, "one"), note(2, "two")], ..Default::default() };
+        store.save_all(&storage).unwrap();
+        let mut storage = store.load_all().unwrap();
+
+        // Changed behind the store's back without a new version: a save that rewrote every row
+        // would put it back
+        let conn = Connection::open(&store.path).unwrap();
+        conn.execute("UPDATE notes SET title = 'untouched' WHERE id = 1", []).unwrap();
+        storage.notes[1].title = "deux".to_string();
+        store.save_all(&storage).unwrap();
+        let titles: Vec<String> = store.load_all().unwrap().notes.into_iter().map(|n| n.title).collect();
+        assert_eq!(titles, ["untouched", "deux"]);
+        assert!(!crate::backup::backup_path(&store.path, 1).exists(), "only removals rotate backups");
+
+        storage.notes.remove(0);
+        store.save_all(&storage).unwrap();
+        assert!(crate::backup::backup_path(&store.path, 1).exists());
+    }
+
+    #[test]
+    fn notes_keep_their_order() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::new(dir.path().join("notes.db"), 0);
+        let mut storage = Storage { notes: vec![note(3, "three"), note(1, "one"), note(2, "two")], ..Default::default() };
+        store.save_all(&storage).unwrap();
+        let ids = |store: &SqliteStore| store.load_all().unwrap().notes.iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(&store), [3, 1, 2]);
+
+        // Added at the end, then one put back in the middle (as `undo` does)
+        storage.notes.push(note(4, "four"));
+        store.save_all(&storage).unwrap();
+        let one = storage.notes.remove(1);
+        store.save_all(&storage).unwrap();
+        storage.notes.insert(1, one);
+        store.save_all(&storage).unwrap();
+        assert_eq!(ids(&store), [3, 1, 2, 4]);
+        assert_eq!(ids(&SqliteStore::new(store.path.clone(), 0)), [3, 1, 2, 4]);
+    }
+
+    #[test]
+    fn adds_new_columns_to_old_databases() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE notes (id INTEGER PRIMARY KEY, title TEXT NOT NULL, body TEXT NOT NULL,
+                tags TEXT NOT NULL, created_at TEXT NOT NULL, updated_at TEXT NOT NULL);
+             INSERT INTO notes VALUES (1, 'one', 'body of one', '[\"a\",\"b\"]', '2025-09-14T12:34:56+00:00', '');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let store = SqliteStore::new(path, 0);
+        assert_eq!(store.load_all().unwrap().notes, vec![note(1, "one")]);
+    }
+}
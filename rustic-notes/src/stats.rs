@@ -0,0 +1,112 @@
+use crate::{Note, Storage};
+use serde::Serialize;
+
+/// Counts words: whitespace-separated runs, except that each CJK character is a word of its own
+/// (Chinese and Japanese don't put spaces between words).
+pub fn words(text: &str) -> usize {
+    let mut count = 0;
+    for token in text.split_whitespace() {
+        let mut in_word = false;
+        for c in token.chars() {
+            if is_cjk(c) {
+                count += 1;
+                in_word = false;
+            } else if is_cjk_punctuation(c) {
+                in_word = false;
+            } else if !in_word {
+                count += 1;
+                in_word = true;
+            }
+        }
+    }
+    count
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'         // Hiragana, Katakana
+        | '\u{3400}'..='\u{4DBF}'       // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}'       // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}'       // Compatibility Ideographs
+        | '\u{20000}'..='\u{2FA1F}')    // Extensions B-F, Compatibility Supplement
+}
+
+/// 、。「」 and fullwidth punctuation separate words just like spaces
+fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}') || (matches!(c, '\u{FF00}'..='\u{FFEF}') && !c.is_alphanumeric())
+}
+
+/// Size of one note's body.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct BodyStats {
+    pub words: usize,
+    /// Unicode scalar values, not bytes
+    pub characters: usize,
+    pub lines: usize,
+}
+
+impl BodyStats {
+    pub fn of(body: &str) -> Self {
+        BodyStats { words: words(body), characters: body.chars().count(), lines: body.lines().count() }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Longest {
+    pub id: u64,
+    pub title: String,
+    pub words: usize,
+}
+
+/// Totals over the whole store.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub notes: usize,
+    pub words: usize,
+    pub characters: usize,
+    /// Note with the most words in its body (the first one on ties)
+    pub longest: Option<Longest>,
+}
+
+pub fn summarize(storage: &Storage) -> Summary {
+    let mut summary = Summary { notes: storage.notes.len(), words: 0, characters: 0, longest: None };
+    let mut longest: Option<(&Note, usize)> = None;
+    for n in &storage.notes {
+        let s = BodyStats::of(&n.body);
+        summary.words += s.words;
+        summary.characters += s.characters;
+        if longest.is_none_or(|(_, w)| s.words > w) {
+            longest = Some((n, s.words));
+        }
+    }
+    summary.longest = longest.map(|(n, words)| Longest { id: n.id, title: n.title.clone(), words });
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_words_separated_by_any_whitespace() {
+        assert_eq!(words(""), 0);
+        assert_eq!(words("  \n\t "), 0);
+        assert_eq!(words("  buy milk,\n\teggs  "), 3);
+    }
+
+    #[test]
+    fn emoji_are_part_of_words_and_characters_are_not_bytes() {
+        assert_eq!(words("ship it 🚀 today"), 4);
+        assert_eq!(words("done✅"), 1);
+        assert_eq!(BodyStats::of("café 🚀\nok"), BodyStats { words: 3, characters: 9, lines: 2 });
+    }
+
+    #[test]
+    fn cjk_text_counts_characters() {
+        assert_eq!(words("今日は晴れ"), 5);
+        assert_eq!(words("我喜欢Rust。你呢？"), 6);
+        assert_eq!(words("会议 notes 明天"), 5);
+        // Hangul is written with spaces
+        assert_eq!(words("안녕하세요 세계"), 2);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::jsonl::JsonlStore;
+use crate::sqlite::SqliteStore;
+use crate::{backup, body, crypto, journal, parse_storage, serialize_storage, Format, Storage};
+use chrono::Utc;
+use std::cell::{Cell, OnceCell};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A place where notes are persisted; every subcommand goes through this.
+pub trait Store {
+    fn load_all(&self) -> anyhow::Result<Storage>;
+    fn save_all(&self, storage: &Storage) -> anyhow::Result<()>;
+
+    /// Drops records that no longer contribute to the notes, returning how many;
+    /// `None` for backends that never keep any.
+    fn compact(&self) -> anyhow::Result<Option<usize>> {
+        Ok(None)
+    }
+}
+
+/// Whether a file store is written encrypted.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Encryption {
+    /// Stay as the file currently is (new files are plaintext)
+    #[default]
+    Keep,
+    On,
+    Off,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StoreOptions {
+    /// Rotated `<store>.bak.N` copies kept on save
+    pub backups: usize,
+    pub encryption: Encryption,
+    /// Save even when another process saved the store since it was loaded
+    pub force_save: bool,
+    /// Bodies longer than this many bytes are written compressed (JSON/TOML/YAML files only)
+    pub compress_bodies: Option<usize>,
+}
+
+/// Picks the backend for `format`.
+pub fn open(path: &Path, format: Format, backups: usize) -> Box<dyn Store> {
+    match format {
+        Format::Sqlite => Box::new(SqliteStore::new(path.to_path_buf(), backups)),
+        Format::Jsonl => Box::new(JsonlStore::new(path.to_path_buf(), backups)),
+        _ => Box::new(FileStore::new(path, format, StoreOptions { backups, ..Default::default() })),
+    }
+}
+
+/// Like [`open`], with every store option.
+pub fn open_with(path: &Path, format: Format, opts: StoreOptions) -> anyhow::Result<Box<dyn Store>> {
+    if matches!(format, Format::Sqlite | Format::Jsonl) && opts.encryption != Encryption::Keep {
+        anyhow::bail!("encryption is only supported for file stores (json, toml, yaml)");
+    }
+    Ok(match format {
+        Format::Sqlite => Box::new(SqliteStore::new(path.to_path_buf(), opts.backups).force_save(opts.force_save)),
+        Format::Jsonl => Box::new(JsonlStore::new(path.to_path_buf(), opts.backups).force_save(opts.force_save)),
+        _ => Box::new(FileStore::new(path, format, opts)),
+    })
+}
+
+/// Returned by a save when the store on disk is no longer the version this process loaded.
+#[derive(Debug)]
+pub struct StoreChanged {
+    pub loaded: u64,
+    pub on_disk: u64,
+}
+
+impl fmt::Display for StoreChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "store changed by another process, re-run your command (loaded version {}, now {}; --force-save overwrites it)",
+            self.loaded, self.on_disk
+        )
+    }
+}
+
+impl std::error::Error for StoreChanged {}
+
+/// Optimistic concurrency: remembers the version of the last load or save and compares it
+/// with the version on disk right before writing.
+#[derive(Debug, Default)]
+pub(crate) struct Versions {
+    seen: Cell<Option<u64>>,
+    pub(crate) force: bool,
+}
+
+impl Versions {
+    pub(crate) fn seen(&self, version: u64) {
+        self.seen.set(Some(version));
+    }
+
+    /// The version to write over `on_disk`, or [`StoreChanged`] when the store moved since it
+    /// was last seen. A store that was never loaded (e.g. a `convert` target) isn't checked.
+    pub(crate) fn next(&self, on_disk: u64, storage: &Storage) -> anyhow::Result<u64> {
+        if let Some(loaded) = self.seen.get()
+            && loaded != on_disk
+            && !self.force
+        {
+            return Err(StoreChanged { loaded, on_disk }.into());
+        }
+        Ok(on_disk.max(storage.version) + 1)
+    }
+}
+
+/// `storage`'s notes stamped with the version being written and their checksum.
+pub(crate) fn stamped(storage: &Storage, version: u64) -> Storage {
+    Storage {
+        version,
+        saved_at: Some(Utc::now()),
+        last_prune: storage.last_prune,
+        checksum: Some(journal::hash(storage)),
+        notes: storage.notes.clone(),
+    }
+}
+
+pub(crate) fn ensure_parent(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(())
+}
+
+/// A single JSON/TOML/YAML file (optionally encrypted) rewritten on every save.
+pub struct FileStore {
+    pub path: PathBuf,
+    pub format: Format,
+    pub opts: StoreOptions,
+    passphrase: OnceCell<String>,
+    versions: Versions,
+}
+
+impl FileStore {
+    pub fn new(path: &Path, format: Format, opts: StoreOptions) -> Self {
+        let versions = Versions { force: opts.force_save, ..Default::default() };
+        FileStore { path: path.to_path_buf(), format, opts, passphrase: OnceCell::new(), versions }
+    }
+
+    /// Uses `passphrase` instead of asking for it (RUSTIC_NOTES_PASSPHRASE / terminal prompt).
+    pub fn with_passphrase(self, passphrase: &str) -> Self {
+        let _ = self.passphrase.set(passphrase.to_string());
+        self
+    }
+
+    fn passphrase(&self, new: bool) -> anyhow::Result<&str> {
+        if let Some(p) = self.passphrase.get() {
+            return Ok(p);
+        }
+        let p = if new {
+            crypto::ask_new_passphrase()?
+        } else {
+            crypto::ask_passphrase(&format!("Passphrase for {}: ", self.path.display()))?
+        };
+        Ok(self.passphrase.get_or_init(|| p))
+    }
+
+    fn plaintext(&self, data: &[u8]) -> anyhow::Result<String> {
+        let bytes = if crypto::is_encrypted(data) {
+            crypto::decrypt(data, self.passphrase(false)?)
+                .map_err(|e| anyhow::anyhow!("{}: {}", self.path.display(), e))?
+        } else {
+            data.to_vec()
+        };
+        Ok(String::from_utf8(bytes)?)
+    }
+}
+
+impl Store for FileStore {
+    fn load_all(&self) -> anyhow::Result<Storage> {
+        if !self.path.exists() {
+            self.versions.seen(0);
+            return Ok(Storage::default());
+        }
+        let raw = self.plaintext(&fs::read(&self.path)?)?;
+        let storage = parse_storage(&raw, self.format)?;
+        self.versions.seen(storage.version);
+        Ok(storage)
+    }
+
+    fn save_all(&self, storage: &Storage) -> anyhow::Result<()> {
+        ensure_parent(&self.path)?;
+        let existing = if self.path.exists() { Some(fs::read(&self.path)?) } else { None };
+        let was_encrypted = existing.as_deref().is_some_and(crypto::is_encrypted);
+        let encrypt = match self.opts.encryption {
+            Encryption::Keep => was_encrypted,
+            Encryption::On => true,
+            Encryption::Off => false,
+        };
+        let current = match &existing {
+            Some(old) => parse_storage(&self.plaintext(old)?, self.format)?,
+            None => Storage::default(),
+        };
+        // Nothing to back up (or write) when neither the content nor the encryption changed;
+        // `doctor --fix` clears the checksum to have it recomputed
+        if existing.is_some() && was_encrypted == encrypt && current.same_content(storage) && current.checksum == storage.checksum {
+            return Ok(());
+        }
+        let version = self.versions.next(current.version, storage)?;
+        // Hashed before the bodies are compressed
+        let stamped = stamped(storage, version);
+        let raw = body::compressing(self.opts.compress_bodies, || serialize_storage(&stamped, self.format))?;
+        if existing.is_some() {
+            backup::rotate(&self.path, self.opts.backups)?;
+        }
+        let bytes = if encrypt {
+            crypto::encrypt(raw.as_bytes(), self.passphrase(!was_encrypted)?)?
+        } else {
+            raw.into_bytes()
+        };
+        // Written next to the store and renamed over it, so a crash mid-write can't leave it torn
+        let dir = self.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(".".as_ref());
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        tmp.write_all(&bytes)?;
+        tmp.as_file().sync_all()?;
+        tmp.persist(&self.path)?;
+        self.versions.seen(version);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Note;
+    use chrono::DateTime;
+    use tempfile::TempDir;
+
+    fn storage() -> Storage {
+        Storage {
+            notes: vec![Note {
+                id: 1,
+                title: "Private".to_string(),
+                body: "very secret".into(),
+                created_at: DateTime::parse_from_rfc3339("2025-09-14T12:34:56Z").unwrap().into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn encrypted_store_round_trips_and_hides_content() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.json");
+        let opts = StoreOptions { backups: 0, encryption: Encryption::On, ..Default::default() };
+        let store = FileStore::new(&path, Format::Json, opts).with_passphrase("fixed passphrase");
+        store.save_all(&storage()).unwrap();
+
+        let first = fs::read(&path).unwrap();
+        assert!(crypto::is_encrypted(&first));
+        assert!(!String::from_utf8_lossy(&first).contains("very secret"));
+
+        let reader = FileStore::new(&path, Format::Json, StoreOptions::default()).with_passphrase("fixed passphrase");
+        assert_eq!(reader.load_all().unwrap().notes, storage().notes);
+
+        // Same notes, new title: the file is rewritten with fresh salt/nonce
+        let mut changed = storage();
+        changed.notes[0].title = "Still private".to_string();
+        reader.save_all(&changed).unwrap();
+        let second = fs::read(&path).unwrap();
+        assert!(crypto::is_encrypted(&second));
+        assert_ne!(first[..40], second[..40]);
+    }
+
+    #[test]
+    fn saving_replaces_the_store_without_leaving_temporary_files() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.json");
+        let store = FileStore::new(&path, Format::Json, StoreOptions { backups: 0, ..Default::default() });
+        store.save_all(&storage()).unwrap();
+        let mut changed = storage();
+        changed.notes[0].title = "Renamed".to_string();
+        store.save_all(&changed).unwrap();
+
+        assert_eq!(store.load_all().unwrap().notes, changed.notes);
+        let files: Vec<_> = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(files, ["notes.json"]);
+    }
+
+    #[test]
+    fn wrong_passphrase_fails_cleanly() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.yaml");
+        let opts = StoreOptions { backups: 0, encryption: Encryption::On, ..Default::default() };
+        FileStore::new(&path, Format::Yaml, opts).with_passphrase("right").save_all(&storage()).unwrap();
+
+        let err = FileStore::new(&path, Format::Yaml, StoreOptions::default())
+            .with_passphrase("wrong")
+            .load_all()
+            .unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase or corrupted store"));
+    }
+
+    #[test]
+    fn long_bodies_are_compressed_on_disk_and_read_back_unchanged() {
+        let long: String = (0..4000).map(|i| format!("{} minutes of the meeting\n", i)).collect();
+        assert!(long.len() > 100 * 1024);
+        for format in [Format::Json, Format::Toml, Format::Yaml] {
+            let dir = TempDir::new().unwrap();
+            let (plain, packed) = (dir.path().join("plain"), dir.path().join("packed"));
+            let mut notes = storage();
+            notes.notes.push(Note { id: 2, body: long.clone().into(), ..notes.notes[0].clone() });
+            FileStore::new(&plain, format, StoreOptions::default()).save_all(&notes).unwrap();
+            let opts = StoreOptions { compress_bodies: Some(body::DEFAULT_THRESHOLD), ..Default::default() };
+            FileStore::new(&packed, format, opts).save_all(&notes).unwrap();
+
+            let on_disk = fs::read_to_string(&packed).unwrap();
+            assert!(on_disk.len() * 10 < fs::read(&plain).unwrap().len(), "{:?}", format);
+            // The short body stays readable
+            assert!(on_disk.contains("very secret") && !on_disk.contains("minutes of the meeting"), "{:?}", format);
+            let loaded = FileStore::new(&packed, format, StoreOptions::default()).load_all().unwrap();
+            assert_eq!(*loaded.notes[1].body, long, "{:?}", format);
+            assert_eq!(loaded.notes, notes.notes);
+        }
+    }
+
+    #[test]
+    fn checksum_survives_conversion_and_catches_edits() {
+        let dir = TempDir::new().unwrap();
+        let mut loaded = storage();
+        for format in [Format::Json, Format::Toml, Format::Yaml] {
+            let path = dir.path().join("notes").with_extension(format.name());
+            FileStore::new(&path, format, StoreOptions::default()).save_all(&loaded).unwrap();
+            loaded = FileStore::new(&path, format, StoreOptions::default()).load_all().unwrap();
+            assert!(loaded.checksum.is_some() && !loaded.checksum_mismatch(), "{:?}", format);
+        }
+
+        let path = dir.path().join("notes.yaml");
+        let edited = fs::read_to_string(&path).unwrap().replace("very secret", "very secreT");
+        fs::write(&path, edited).unwrap();
+        let store = FileStore::new(&path, Format::Yaml, StoreOptions::default());
+        let mut tampered = store.load_all().unwrap();
+        assert!(tampered.checksum_mismatch());
+        // Saving the same notes leaves the file alone, clearing the checksum rewrites it
+        store.save_all(&tampered).unwrap();
+        assert!(store.load_all().unwrap().checksum_mismatch());
+        tampered.checksum = None;
+        store.save_all(&tampered).unwrap();
+        assert!(!store.load_all().unwrap().checksum_mismatch());
+    }
+
+    #[test]
+    fn last_prune_is_saved_even_when_the_notes_are_unchanged() {
+        for format in [Format::Json, Format::Toml, Format::Yaml, Format::Sqlite, Format::Jsonl] {
+            let dir = TempDir::new().unwrap();
+            let path = dir.path().join("notes").with_extension(format.name());
+            let store = open(&path, format, 0);
+            store.save_all(&storage()).unwrap();
+            let mut loaded = store.load_all().unwrap();
+            assert_eq!(loaded.last_prune, None, "{:?}", format);
+            let at: DateTime<Utc> = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().into();
+            loaded.last_prune = Some(at);
+            store.save_all(&loaded).unwrap();
+            let reloaded = open(&path, format, 0).load_all().unwrap();
+            assert_eq!((reloaded.last_prune, reloaded.version), (Some(at), 2), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn save_refuses_to_overwrite_a_newer_version() {
+        for format in [Format::Json, Format::Toml, Format::Yaml, Format::Sqlite, Format::Jsonl] {
+            let dir = TempDir::new().unwrap();
+            let path = dir.path().join("notes").with_extension(format.name());
+            let open = |force_save| open_with(&path, format, StoreOptions { force_save, ..Default::default() }).unwrap();
+            open(false).save_all(&storage()).unwrap();
+
+            // This process loads, another one saves in between
+            let slow = open(false);
+            let mut mine = slow.load_all().unwrap();
+            assert_eq!(mine.version, 1, "{:?}", format);
+            let other = open(false);
+            let mut theirs = other.load_all().unwrap();
+            theirs.notes[0].title = "theirs".to_string();
+            other.save_all(&theirs).unwrap();
+
+            mine.notes[0].title = "mine".to_string();
+            let err = slow.save_all(&mine).unwrap_err();
+            let changed = err.downcast_ref::<StoreChanged>().unwrap();
+            assert_eq!((changed.loaded, changed.on_disk), (1, 2), "{:?}", format);
+            assert_eq!(open(false).load_all().unwrap().notes[0].title, "theirs");
+
+            // Forced saves overwrite; saving again after our own save isn't a conflict either
+            let forced = open(true);
+            forced.load_all().unwrap();
+            other.save_all(&mine).unwrap();
+            forced.save_all(&storage()).unwrap();
+            forced.save_all(&mine).unwrap();
+            let last = open(false).load_all().unwrap();
+            assert_eq!((last.notes[0].title.as_str(), last.version), ("mine", 5), "{:?}", format);
+            assert!(last.saved_at.is_some());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::msg::{Icon, Msg};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use std::env;
+use std::io::IsTerminal;
+
+/// How timestamps are displayed; they are always stored in UTC.
+#[derive(Clone, Debug, Default)]
+pub struct DateFormat {
+    /// Convert to the local time zone (--local, or `timezone = "local"` in the config file)
+    pub local: bool,
+    /// strftime pattern from --date-format
+    pub pattern: Option<String>,
+}
+
+impl DateFormat {
+    /// Rejects patterns chrono can't format (e.g. `%Q`), so they fail at startup rather than mid-output.
+    pub fn new(local: bool, pattern: Option<String>) -> anyhow::Result<Self> {
+        if let Some(p) = &pattern
+            && StrftimeItems::new(p).any(|item| matches!(item, Item::Error))
+        {
+            anyhow::bail!("invalid --date-format `{}`: not a valid strftime pattern", p);
+        }
+        Ok(DateFormat { local, pattern })
+    }
+
+    /// Default: `2025-09-14 12:34:56 UTC`, or `2025-09-14 14:34:56 +02:00` with `local`.
+    pub fn format(&self, t: &DateTime<Utc>) -> String {
+        let default = if self.local { "%Y-%m-%d %H:%M:%S %:z" } else { "%Y-%m-%d %H:%M:%S UTC" };
+        let pattern = self.pattern.as_deref().unwrap_or(default);
+        if self.local { t.with_timezone(&Local).format(pattern).to_string() } else { t.format(pattern).to_string() }
+    }
+}
+
+/// Decides how human output is decorated (ANSI colors, emoji or ASCII through [`Msg`], timestamps).
+#[derive(Clone, Debug, Default)]
+pub struct Style {
+    pub color: bool,
+    pub msg: Msg,
+    pub dates: DateFormat,
+}
+
+impl Style {
+    /// Colors are used only if --no-color is absent, NO_COLOR is unset/empty and stdout is a terminal.
+    pub fn detect(no_color: bool, msg: Msg) -> Self {
+        let env_off = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        let color = !no_color && !env_off && std::io::stdout().is_terminal();
+        Style { color, msg, dates: DateFormat::default() }
+    }
+
+    pub fn with_dates(self, dates: DateFormat) -> Self {
+        Style { dates, ..self }
+    }
+
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color { format!("\x1b[{}m{}\x1b[0m", code, s) } else { s.to_string() }
+    }
+
+    pub fn id(&self, s: &str) -> String { self.paint("2", s) }
+    pub fn title(&self, s: &str) -> String { self.paint("1", s) }
+    pub fn tags(&self, s: &str) -> String { self.paint("36", s) }
+    pub fn date(&self, s: &str) -> String { self.paint("2", s) }
+    /// Paints the byte `ranges` of `text` (sorted, non-overlapping) as matches.
+    pub fn highlight(&self, text: &str, ranges: &[(usize, usize)]) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for &(start, end) in ranges {
+            out.push_str(&text[last..start]);
+            out.push_str(&self.paint("1;31", &text[start..end]));
+            last = end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+    pub fn timestamp(&self, t: &DateTime<Utc>) -> String { self.date(&self.dates.format(t)) }
+    pub fn alert(&self, s: &str) -> String { self.paint("31", s) }
+    pub fn added(&self, s: &str) -> String { self.paint("32", s) }
+    pub fn removed(&self, s: &str) -> String { self.paint("31", s) }
+
+    pub fn icon(&self, icon: Icon) -> String {
+        let text = self.msg.icon(icon);
+        if icon == Icon::Warn { self.alert(text) } else { text.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_style_leaves_text_untouched() {
+        let s = Style { color: false, msg: Msg { plain: false }, ..Default::default() };
+        assert_eq!(s.title("Hello"), "Hello");
+        assert_eq!(s.icon(Icon::Ok), "✅");
+    }
+
+    #[test]
+    fn color_wraps_in_ansi() {
+        let s = Style { color: true, msg: Msg { plain: true }, ..Default::default() };
+        assert_eq!(s.tags("work"), "\x1b[36mwork\x1b[0m");
+        assert_eq!(s.icon(Icon::Warn), "\x1b[31m[warn]\x1b[0m");
+    }
+
+    #[test]
+    fn highlight_paints_only_the_ranges() {
+        let s = Style { color: true, ..Default::default() };
+        assert_eq!(s.highlight("a todo b", &[(2, 6)]), "a \x1b[1;31mtodo\x1b[0m b");
+        assert_eq!(Style::default().highlight("a todo b", &[(2, 6)]), "a todo b");
+    }
+
+    #[test]
+    fn dates_default_to_utc_and_take_a_custom_pattern() {
+        let t = DateTime::parse_from_rfc3339("2025-09-14T12:34:56Z").unwrap().into();
+        assert_eq!(DateFormat::default().format(&t), "2025-09-14 12:34:56 UTC");
+        let custom = DateFormat::new(false, Some("%d/%m/%Y %H:%M".to_string())).unwrap();
+        assert_eq!(custom.format(&t), "14/09/2025 12:34");
+        let err = DateFormat::new(false, Some("%Y-%Q".to_string())).unwrap_err();
+        assert!(err.to_string().contains("invalid --date-format `%Y-%Q`"));
+    }
+}
@@ -0,0 +1,92 @@
+//! "did you mean" hints for tags that no note has, e.g. `search -t wrok` when `work` exists.
+
+use crate::Note;
+use std::collections::HashSet;
+
+/// Largest edit distance still offered as a suggestion.
+pub const MAX_DISTANCE: usize = 2;
+
+/// Levenshtein distance between `a` and `b`, counted in characters.
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Every tag used by `notes`, spelled as in the first note that has it; tags differing only in
+/// case are one tag.
+pub fn known_tags(notes: &[Note]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    notes.iter().flat_map(|n| &n.tags).filter(|t| seen.insert(t.to_lowercase())).cloned().collect()
+}
+
+/// The `known` tags closest to `tag` (ignoring case) within [`MAX_DISTANCE`], alphabetically
+/// when several are as close. Nothing when `tag` is itself known.
+pub fn did_you_mean<'a>(tag: &str, known: &'a [String]) -> Vec<&'a str> {
+    let tag = tag.to_lowercase();
+    let mut scored: Vec<(usize, &str)> = known.iter().map(|k| (distance(&tag, &k.to_lowercase()), k.as_str())).collect();
+    let Some(best) = scored.iter().map(|(d, _)| *d).min().filter(|d| (1..=MAX_DISTANCE).contains(d)) else {
+        // Nothing close enough, or the tag exists
+        return vec![];
+    };
+    scored.retain(|(d, _)| *d == best);
+    let mut closest: Vec<&str> = scored.into_iter().map(|(_, k)| k).collect();
+    closest.sort_by_key(|k| k.to_lowercase());
+    closest
+}
+
+/// `did you mean: a or b?` for the `requested` tags no note has, one line per tag.
+pub fn hints(requested: &[String], notes: &[Note]) -> Vec<String> {
+    let known = known_tags(notes);
+    requested
+        .iter()
+        .map(|tag| did_you_mean(tag, &known))
+        .filter(|closest| !closest.is_empty())
+        .map(|closest| format!("did you mean: {}?", closest.join(" or ")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn distance_counts_edits() {
+        assert_eq!(distance("work", "work"), 0);
+        assert_eq!(distance("wrok", "work"), 2);
+        assert_eq!(distance("wor", "work"), 1);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn suggests_the_closest_tags_in_any_case() {
+        let known = tags(&["Work", "personal", "word", "team"]);
+        assert_eq!(did_you_mean("wrok", &known), ["Work"]);
+        assert_eq!(did_you_mean("PERSONL", &known), ["personal"]);
+        // Ties are all offered, alphabetically
+        assert_eq!(did_you_mean("wor", &known), ["word", "Work"]);
+    }
+
+    #[test]
+    fn no_candidate_means_no_suggestion() {
+        let known = tags(&["work", "team"]);
+        assert!(did_you_mean("groceries", &known).is_empty());
+        assert!(did_you_mean("work", &known).is_empty());
+        assert!(did_you_mean("WORK", &known).is_empty());
+        assert!(did_you_mean("wrok", &[]).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::msg::{Glyph, Msg};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A column of a rendered table: header text and the maximum display width of its cells.
+pub struct Column {
+    pub header: &'static str,
+    pub max_width: usize,
+}
+
+/// Cuts `s` to at most `max` terminal columns, ending with an ellipsis ("…", or "..." when
+/// plain) when something was dropped.
+pub fn truncate(s: &str, max: usize, msg: Msg) -> String {
+    if s.width() <= max {
+        return s.to_string();
+    }
+    let ellipsis = msg.glyph(Glyph::Ellipsis);
+    if max < ellipsis.width() {
+        return ".".repeat(max);
+    }
+    let mut out = String::new();
+    let mut width = 0;
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        // Keep room for the ellipsis
+        if width + w > max - ellipsis.width() {
+            break;
+        }
+        out.push(c);
+        width += w;
+    }
+    out.push_str(ellipsis);
+    out
+}
+
+fn pad(s: &str, width: usize) -> String {
+    let fill = width.saturating_sub(s.width());
+    format!("{}{}", s, " ".repeat(fill))
+}
+
+/// Renders rows as a column-aligned table (widths measured in terminal columns, so CJK stays aligned).
+pub fn render(columns: &[Column], rows: &[Vec<String>], msg: Msg) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|r| r.iter().zip(columns).map(|(c, col)| truncate(c, col.max_width, msg)).collect())
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, col)| cells.iter().map(|r| r[i].width()).chain([col.header.width()]).max().unwrap_or(0))
+        .collect();
+
+    let line = |row: Vec<String>| -> String {
+        let padded: Vec<String> = row.iter().zip(&widths).map(|(c, w)| pad(c, *w)).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut out = String::new();
+    out.push_str(&line(columns.iter().map(|c| c.header.to_string()).collect()));
+    out.push('\n');
+    out.push_str(&line(widths.iter().map(|w| "-".repeat(*w)).collect()));
+    out.push('\n');
+    for row in cells {
+        out.push_str(&line(row));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: [Column; 2] = [
+        Column { header: "ID", max_width: 4 },
+        Column { header: "Title", max_width: 8 },
+    ];
+
+    const FANCY: Msg = Msg { plain: false };
+    const PLAIN: Msg = Msg { plain: true };
+
+    #[test]
+    fn truncate_adds_ellipsis() {
+        assert_eq!(truncate("Hello world", 6, FANCY), "Hello…");
+        assert_eq!(truncate("short", 6, FANCY), "short");
+        assert_eq!(truncate("Hello world", 6, PLAIN), "Hel...");
+        assert_eq!(truncate("Hello world", 2, PLAIN), "..");
+    }
+
+    #[test]
+    fn truncate_counts_wide_chars() {
+        // Each CJK char takes two columns
+        assert_eq!(truncate("日本語のノート", 5, FANCY), "日本…");
+    }
+
+    #[test]
+    fn render_aligns_columns() {
+        let rows = vec![
+            vec!["1".to_string(), "Groceries".to_string()],
+            vec!["12".to_string(), "日本語".to_string()],
+        ];
+        let expected = "\
+ID  Title
+--  --------
+1   Groceri…
+12  日本語
+";
+        assert_eq!(render(&COLUMNS, &rows, FANCY), expected);
+    }
+}
@@ -0,0 +1,387 @@
+//! `tui`: a two-pane terminal browser. [`App`] holds everything the screen shows and turns key
+//! presses into [`Action`]s; applying those to the store is up to the caller, and the terminal
+//! handling below is only drawing and reading keys.
+
+use crate::msg::{Icon, Msg};
+use crate::{normalize_tags, FieldMask, Matcher, Note, SearchFilter, TagMode};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Text};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+const HELP: &str = "a add  e edit  d remove  p pin  / search  t tags  Esc clear  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Browse,
+    /// Typing edits the search query
+    Search,
+    /// Typing edits the tag filter
+    Tags,
+    /// Waiting for `y` before removing the note
+    ConfirmRemove(u64),
+}
+
+/// What a key press asks the caller to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Add,
+    Edit(u64),
+    Remove(u64),
+    TogglePin(u64),
+    Quit,
+}
+
+impl Action {
+    /// Whether the action opens the editor, so the terminal has to be handed over.
+    fn needs_terminal(self) -> bool {
+        matches!(self, Action::Add | Action::Edit(_))
+    }
+}
+
+pub struct App {
+    pub notes: Vec<Note>,
+    pub query: String,
+    /// Tags every listed note must have, separated by spaces or commas
+    pub tags: String,
+    pub mode: Mode,
+    /// Position in [`App::visible`]
+    pub selected: usize,
+    /// Result of the last action or a prompt; replaces the help line until the next key
+    pub status: String,
+    pub msg: Msg,
+}
+
+impl App {
+    pub fn new(notes: Vec<Note>) -> Self {
+        App {
+            notes,
+            query: String::new(),
+            tags: String::new(),
+            mode: Mode::Browse,
+            selected: 0,
+            status: String::new(),
+            msg: Msg::default(),
+        }
+    }
+
+    /// The notes matching the query and tag filter, pinned ones first.
+    pub fn visible(&self) -> Vec<&Note> {
+        let filter = SearchFilter {
+            matcher: Matcher::new(&self.query, false, false).expect("substring matchers always build"),
+            mask: FieldMask::from_fields(&[]),
+            mode: TagMode::All,
+            tags: normalize_tags(self.tags.split([' ', ',']).map(str::to_string).collect()),
+            not_tags: vec![],
+        };
+        let mut visible: Vec<&Note> = self.notes.iter().filter(|n| filter.matches(n)).collect();
+        visible.sort_by_key(|n| !n.pinned);
+        visible
+    }
+
+    pub fn selected(&self) -> Option<&Note> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// Replaces the notes after a change, selecting `select` if it is still listed and
+    /// otherwise staying at the same position.
+    pub fn set_notes(&mut self, notes: Vec<Note>, select: Option<u64>) {
+        self.notes = notes;
+        let visible: Vec<u64> = self.visible().iter().map(|n| n.id).collect();
+        let position = select.and_then(|id| visible.iter().position(|&v| v == id));
+        self.selected = position.unwrap_or(self.selected).min(visible.len().saturating_sub(1));
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        match self.mode {
+            Mode::Browse => self.browse_key(key),
+            Mode::Search | Mode::Tags => {
+                self.filter_key(key);
+                None
+            }
+            Mode::ConfirmRemove(id) => {
+                self.mode = Mode::Browse;
+                if matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                    return Some(Action::Remove(id));
+                }
+                self.status = format!("Kept note #{}", id);
+                None
+            }
+        }
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.status.clear();
+        let selected = self.selected().map(|n| (n.id, n.title.clone()));
+        match (key.code, selected) {
+            (KeyCode::Char('q'), _) => return Some(Action::Quit),
+            (KeyCode::Char('a'), _) => return Some(Action::Add),
+            (KeyCode::Char('e') | KeyCode::Enter, Some((id, _))) => return Some(Action::Edit(id)),
+            (KeyCode::Char('p'), Some((id, _))) => return Some(Action::TogglePin(id)),
+            (KeyCode::Char('d') | KeyCode::Delete, Some((id, title))) => {
+                self.mode = Mode::ConfirmRemove(id);
+                self.status = format!("Remove #{} {:?}? (y/N)", id, title);
+            }
+            (KeyCode::Char('j') | KeyCode::Down, _) => self.move_by(1),
+            (KeyCode::Char('k') | KeyCode::Up, _) => self.move_by(-1),
+            (KeyCode::PageDown, _) => self.move_by(10),
+            (KeyCode::PageUp, _) => self.move_by(-10),
+            (KeyCode::Char('g') | KeyCode::Home, _) => self.selected = 0,
+            (KeyCode::Char('G') | KeyCode::End, _) => self.move_by(isize::MAX),
+            (KeyCode::Char('/'), _) => self.mode = Mode::Search,
+            (KeyCode::Char('t'), _) => self.mode = Mode::Tags,
+            (KeyCode::Esc, _) => {
+                self.query.clear();
+                self.tags.clear();
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn filter_key(&mut self, key: KeyEvent) {
+        let text = if self.mode == Mode::Search { &mut self.query } else { &mut self.tags };
+        match key.code {
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Esc => {
+                text.clear();
+                self.mode = Mode::Browse;
+            }
+            KeyCode::Enter => self.mode = Mode::Browse,
+            _ => return,
+        }
+        self.selected = 0;
+    }
+}
+
+fn preview(n: &Note) -> Text<'_> {
+    let mut lines = vec![Line::styled(n.created_at.format("%Y-%m-%d %H:%M UTC").to_string(), Modifier::DIM)];
+    if !n.tags.is_empty() {
+        lines.push(Line::from(format!("tags: {}", n.tags.join(", "))));
+    }
+    lines.push(Line::default());
+    lines.extend(n.body.lines().map(Line::from));
+    Text::from(lines)
+}
+
+/// Draws the list on the left, the selected note on the right and the status line below.
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] = Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main);
+
+    let visible = app.visible();
+    let items: Vec<ListItem> = visible
+        .iter()
+        .map(|n| {
+            let pin = if n.pinned { format!("{} ", app.msg.icon(Icon::Pinned)) } else { String::new() };
+            ListItem::new(format!("{}#{} {}", pin, n.id, n.title))
+        })
+        .collect();
+    let mut title = format!(" Notes {}/{} ", visible.len(), app.notes.len());
+    if !app.query.is_empty() {
+        title.push_str(&format!("/{} ", app.query));
+    }
+    if !app.tags.trim().is_empty() {
+        title.push_str(&format!("tags: {} ", app.tags.trim()));
+    }
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected((!visible.is_empty()).then_some(app.selected));
+    frame.render_stateful_widget(list, left, &mut state);
+
+    let (heading, body) = match app.selected() {
+        Some(n) => (format!(" #{} {} ", n.id, n.title), preview(n)),
+        None => (String::new(), Text::from("(No notes)")),
+    };
+    let preview = Paragraph::new(body).wrap(Wrap { trim: false }).block(Block::default().borders(Borders::ALL).title(heading));
+    frame.render_widget(preview, right);
+
+    let line = match app.mode {
+        Mode::Search => format!("/{}", app.query),
+        Mode::Tags => format!("tags: {}", app.tags),
+        _ if !app.status.is_empty() => app.status.clone(),
+        _ => HELP.to_string(),
+    };
+    frame.render_widget(Paragraph::new(line), status);
+    if matches!(app.mode, Mode::Search | Mode::Tags) {
+        let typed = if app.mode == Mode::Search { app.query.chars().count() + 1 } else { app.tags.chars().count() + 6 };
+        frame.set_cursor_position((status.x + typed as u16, status.y));
+    }
+}
+
+/// What applying an [`Action`] left behind.
+pub struct Outcome {
+    /// The notes as they are now in the store
+    pub notes: Vec<Note>,
+    /// Shown in the status line
+    pub message: String,
+    /// Note to select, e.g. the one just added
+    pub select: Option<u64>,
+}
+
+/// Runs the interface until the user quits. `apply` performs every action other than quitting;
+/// while it opens the editor the terminal is back in its normal mode.
+pub fn run(notes: Vec<Note>, msg: Msg, apply: impl FnMut(Action) -> anyhow::Result<Outcome>) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, App { msg, ..App::new(notes) }, apply);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    mut app: App,
+    mut apply: impl FnMut(Action) -> anyhow::Result<Outcome>,
+) -> anyhow::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, &app))?;
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let Some(action) = app.on_key(key) else { continue };
+        if action == Action::Quit {
+            return Ok(());
+        }
+        if action.needs_terminal() {
+            ratatui::restore();
+        }
+        let outcome = apply(action);
+        if action.needs_terminal() {
+            enable_raw_mode()?;
+            execute!(std::io::stdout(), EnterAlternateScreen)?;
+            terminal.clear()?;
+        }
+        match outcome {
+            Ok(o) => {
+                app.set_notes(o.notes, o.select);
+                app.status = o.message;
+            }
+            Err(e) => app.status = format!("Error: {:#}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: u64, title: &str, tags: &[&str], pinned: bool) -> Note {
+        Note {
+            body: format!("body of {}", title).into(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            pinned,
+            ..Note::new(id, title)
+        }
+    }
+
+    fn app() -> App {
+        App::new(vec![
+            note(1, "Groceries", &["home"], false),
+            note(2, "Standup", &["work"], false),
+            note(3, "Taxes", &["home", "money"], true),
+        ])
+    }
+
+    fn press(app: &mut App, keys: &str) -> Vec<Action> {
+        keys.chars().filter_map(|c| app.on_key(KeyEvent::from(KeyCode::Char(c)))).collect()
+    }
+
+    fn key(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.on_key(KeyEvent::from(code))
+    }
+
+    fn ids(app: &App) -> Vec<u64> {
+        app.visible().iter().map(|n| n.id).collect()
+    }
+
+    #[test]
+    fn pinned_first_and_moving_stays_in_bounds() {
+        let mut app = app();
+        assert_eq!(ids(&app), [3, 1, 2]);
+        assert_eq!(press(&mut app, "kjjjj"), []);
+        assert_eq!(app.selected().unwrap().id, 2);
+        press(&mut app, "g");
+        assert_eq!(app.selected().unwrap().id, 3);
+        press(&mut app, "G");
+        assert_eq!(press(&mut app, "ep"), [Action::Edit(2), Action::TogglePin(2)]);
+    }
+
+    #[test]
+    fn search_and_tag_filters_narrow_the_list() {
+        let mut app = app();
+        press(&mut app, "j/TA");
+        assert_eq!(app.mode, Mode::Search);
+        // Typed letters go to the query, not to the key bindings
+        assert_eq!(ids(&app), [3, 2]);
+        assert_eq!(app.selected, 0);
+        key(&mut app, KeyCode::Backspace);
+        press(&mut app, "ax");
+        assert_eq!(ids(&app), [3]);
+        key(&mut app, KeyCode::Enter);
+        assert_eq!((app.mode, app.query.as_str()), (Mode::Browse, "Tax"));
+
+        key(&mut app, KeyCode::Esc);
+        press(&mut app, "thome");
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(ids(&app), [3, 1]);
+        press(&mut app, "t,money");
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(ids(&app), [3]);
+
+        // Esc while typing clears just that filter, Esc while browsing clears both
+        press(&mut app, "/zzz");
+        key(&mut app, KeyCode::Esc);
+        assert_eq!((app.query.as_str(), ids(&app)), ("", vec![3]));
+        key(&mut app, KeyCode::Esc);
+        assert_eq!(ids(&app), [3, 1, 2]);
+    }
+
+    #[test]
+    fn remove_asks_first() {
+        let mut app = app();
+        assert_eq!(press(&mut app, "jd"), []);
+        assert_eq!(app.mode, Mode::ConfirmRemove(1));
+        assert_eq!(app.status, "Remove #1 \"Groceries\"? (y/N)");
+        assert_eq!(press(&mut app, "n"), []);
+        assert_eq!((app.mode, app.status.as_str()), (Mode::Browse, "Kept note #1"));
+        assert_eq!(press(&mut app, "dy"), [Action::Remove(1)]);
+
+        // Nothing to act on in an empty list, but adding and quitting still work
+        let mut empty = App::new(vec![]);
+        assert_eq!(press(&mut empty, "jdepaq"), [Action::Add, Action::Quit]);
+        assert_eq!(empty.on_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+    }
+
+    #[test]
+    fn set_notes_follows_the_selected_note() {
+        let mut app = app();
+        press(&mut app, "G");
+        let mut notes = app.notes.clone();
+        notes.push(note(4, "Added", &[], false));
+        app.set_notes(notes.clone(), Some(4));
+        assert_eq!(app.selected().unwrap().id, 4);
+
+        // The selected note is gone: stay at the same position, within bounds
+        notes.retain(|n| n.id != 4);
+        app.set_notes(notes, None);
+        assert_eq!(app.selected().unwrap().id, 2);
+    }
+}
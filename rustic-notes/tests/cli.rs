@@ -0,0 +1,2680 @@
+use predicates::prelude::*;
+use rustic_notes::Note;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn notes(store: &Path) -> Result<assert_cmd::Command, Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("rustic-notes")?;
+    cmd.env("NO_COLOR", "1").arg("--store").arg(store);
+    Ok(cmd)
+}
+
+/// Creates a temp store with a few notes to search through.
+fn fixture() -> Result<(TempDir, PathBuf), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?.args(["add", "Weekly meeting", "-b", "Agenda for Monday", "-t", "work"]).assert().success();
+    notes(&store)?.args(["add", "Daily standup", "-b", "Blockers and progress", "-t", "work,team"]).assert().success();
+    notes(&store)?.args(["add", "Groceries", "-b", "Milk, bread and a meeting snack", "-t", "personal"]).assert().success();
+    Ok((dir, store))
+}
+
+#[test]
+fn search_substring_is_case_insensitive() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "MEETING"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stdout(predicate::str::contains("Groceries"));
+    Ok(())
+}
+
+#[test]
+fn misspelled_tags_get_a_suggestion() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "meeting", "-t", "wrok"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No results"))
+        .stdout(predicate::str::contains("did you mean: work?"));
+    notes(&store)?
+        .args(["grep", "Milk", "-t", "persnal,tema"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("did you mean: personal?\ndid you mean: team?"));
+    // Unrelated or existing tags don't get one
+    let output = notes(&store)?.args(["search", "nothing", "-t", "groceries,work"]).output()?;
+    assert!(!String::from_utf8(output.stdout)?.contains("did you mean"));
+    Ok(())
+}
+
+#[test]
+fn search_regex_alternation() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "--regex", "weekly|standup"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stdout(predicate::str::contains("Daily standup"))
+        .stdout(predicate::str::contains("Groceries").not());
+    Ok(())
+}
+
+#[test]
+fn search_regex_anchors() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // "meeting" ends the title of #1 but sits in the middle of #3's body
+    notes(&store)?
+        .args(["search", "--regex", "meeting$"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stdout(predicate::str::contains("Groceries").not());
+    Ok(())
+}
+
+#[test]
+fn search_regex_case_sensitive() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "--regex", "--case-sensitive", "^daily"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No results"));
+    notes(&store)?
+        .args(["search", "--regex", "^daily"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daily standup"));
+    Ok(())
+}
+
+#[test]
+fn search_regex_respects_tag_filter() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "--regex", "meeting|standup", "-t", "team"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daily standup"))
+        .stdout(predicate::str::contains("Weekly meeting").not());
+    Ok(())
+}
+
+#[test]
+fn search_invalid_regex_errors() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "--regex", "(unclosed"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid regex \"(unclosed\""));
+    Ok(())
+}
+
+#[test]
+fn search_in_title_skips_body_matches() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // "meeting" is in #1's title but only in #3's body
+    notes(&store)?
+        .args(["search", "meeting", "--in", "title"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stdout(predicate::str::contains("Groceries").not());
+    Ok(())
+}
+
+#[test]
+fn search_in_tags_with_regex() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "--regex", "^team$", "--in", "tags", "--in", "title"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daily standup"))
+        .stdout(predicate::str::contains("Weekly meeting").not());
+    Ok(())
+}
+
+#[test]
+fn search_in_no_results_mentions_fields() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "bread", "--in", "title", "--in", "tags", "-t", "personal"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No results for \"bread\" in title,tags with tags"));
+    Ok(())
+}
+
+#[test]
+fn search_not_tags_excludes_matching_note() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // #2 matches the query and the positive tag but carries the excluded "team" tag
+    notes(&store)?
+        .args(["search", "--regex", "meeting|standup", "-t", "work", "--not-tags", "TEAM"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stdout(predicate::str::contains("Daily standup").not());
+    Ok(())
+}
+
+#[test]
+fn search_not_tags_no_results_mentions_both_filters() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "standup", "-t", "work", "--not-tags", "team"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "No results for \"standup\" with tags [\"work\"] excluding tags [\"team\"]",
+        ));
+    Ok(())
+}
+
+#[test]
+fn list_not_tags_hides_notes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["list", "--not-tags", "work"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Groceries"))
+        .stdout(predicate::str::contains("Weekly meeting").not())
+        .stdout(predicate::str::contains("Daily standup").not());
+    Ok(())
+}
+
+#[test]
+fn search_tags_all_vs_any() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // Default AND: no note carries both "team" and "personal"
+    notes(&store)?
+        .args(["search", "", "-t", "team,personal"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No results for \"\" with tags"));
+    // OR: #2 (team) and #3 (personal) qualify
+    notes(&store)?
+        .args(["search", "", "-t", "team,personal", "--any-tags"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Daily standup"))
+        .stdout(predicate::str::contains("Groceries"))
+        .stdout(predicate::str::contains("Weekly meeting").not());
+    Ok(())
+}
+
+#[test]
+fn search_any_tags_exclusion_wins() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["search", "", "-t", "team,personal", "--any-tags", "--not-tags", "work"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Groceries"))
+        .stdout(predicate::str::contains("Daily standup").not());
+    Ok(())
+}
+
+#[test]
+fn list_output_json_parses_as_notes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let assert = notes(&store)?.args(["--output", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed[1].id, 2);
+    assert_eq!(parsed[1].title, "Daily standup");
+    assert_eq!(parsed[1].body, "Blockers and progress");
+    assert_eq!(parsed[1].tags, vec!["work", "team"]);
+    Ok(())
+}
+
+#[test]
+fn search_output_json_keeps_chatter_on_stderr() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let assert = notes(&store)?
+        .args(["--output", "json", "search", "nothing-matches-this"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("No results"));
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert!(parsed.is_empty());
+    Ok(())
+}
+
+#[test]
+fn add_output_yaml_echoes_note() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let assert = notes(&store)?
+        .args(["-o", "yaml", "add", "Echo me", "-t", "a,b"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("saved to"));
+    let note: Note = serde_yaml::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(note.id, 1);
+    assert_eq!(note.title, "Echo me");
+    assert_eq!(note.tags, vec!["a", "b"]);
+    Ok(())
+}
+
+#[test]
+fn list_table_snapshot() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    std::fs::write(
+        &store,
+        r#"{"notes": [
+            {"id": 1, "title": "Buy bread", "body": "", "tags": ["personal", "errands"], "created_at": "2025-09-14T12:34:56Z"},
+            {"id": 12, "title": "会議のメモ", "body": "", "tags": [], "created_at": "2025-09-15T08:00:00Z"},
+            {"id": 103, "title": "A rather long title that will not fit in the title column", "body": "", "tags": ["work"], "created_at": "2025-09-16T09:30:00Z"}
+        ]}"#,
+    )?;
+    let expected = "\
+ID   Title                                     Tags              Created
+---  ----------------------------------------  ----------------  -----------------------
+1    Buy bread                                 personal,errands  2025-09-14 12:34:56 UTC
+12   会議のメモ                                                  2025-09-15 08:00:00 UTC
+103  A rather long title that will not fit...  work              2025-09-16 09:30:00 UTC
+";
+    notes(&store)?.args(["list", "--table=always"]).assert().success().stdout(expected);
+    Ok(())
+}
+
+#[test]
+fn list_table_auto_falls_back_when_piped() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["list", "--table"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#  1  Weekly meeting"))
+        .stdout(predicate::str::contains("Created").not());
+    Ok(())
+}
+
+#[test]
+fn ascii_replaces_emoji_prefixes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["--plain", "add", "Plain"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("[ok] Note #4 saved"));
+    notes(&store)?
+        .args(["--plain", "edit", "4", "--title", "Plainer"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("[edited] Note #4 updated: Plainer"));
+    notes(&store)?
+        .args(["--plain", "remove", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("[deleted] Note deleted #4"));
+    notes(&store)?
+        .args(["--plain", "remove", "4"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::starts_with("[warn] Didn't find the note with id 4"));
+    Ok(())
+}
+
+#[test]
+fn no_color_env_keeps_output_free_of_ansi() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[").not());
+    Ok(())
+}
+
+#[test]
+fn locked_store_fails_after_timeout() -> Result<(), Box<dyn Error>> {
+    use rustic_notes::lock::{LockKind, StoreLock};
+    let (_dir, store) = fixture()?;
+    let _held = StoreLock::acquire(&store, LockKind::Exclusive, std::time::Duration::ZERO)?;
+    notes(&store)?
+        .args(["--lock-timeout", "0.2", "add", "Blocked"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("store is locked by another process"));
+    Ok(())
+}
+
+#[test]
+fn lock_timeout_must_be_a_sane_number_of_seconds() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    for bad in ["inf", "NaN", "-1", "1e300", "soon"] {
+        notes(&store)?
+            .arg(format!("--lock-timeout={}", bad))
+            .args(["add", "Never added"])
+            .assert()
+            .failure()
+            .code(2)
+            .stderr(predicate::str::contains("--lock-timeout").and(predicate::str::contains("panicked").not()));
+    }
+    notes(&store)?.args(["--lock-timeout", "0", "add", "Added"]).assert().success();
+    Ok(())
+}
+
+fn stored_titles(path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let storage: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(storage["notes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n["title"].as_str().unwrap().to_string())
+        .collect())
+}
+
+fn bak(store: &Path, which: usize) -> PathBuf {
+    PathBuf::from(format!("{}.bak.{}", store.display(), which))
+}
+
+#[test]
+fn backups_rotate_newest_first() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    for title in ["one", "two", "three", "four"] {
+        notes(&store)?.args(["--backups", "2", "add", title]).assert().success();
+    }
+    assert_eq!(stored_titles(&store)?, ["one", "two", "three", "four"]);
+    assert_eq!(stored_titles(&bak(&store, 1))?, ["one", "two", "three"]);
+    assert_eq!(stored_titles(&bak(&store, 2))?, ["one", "two"]);
+    assert!(!bak(&store, 3).exists());
+    Ok(())
+}
+
+#[test]
+fn backups_skip_unchanged_content_and_can_be_disabled() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?.args(["add", "one"]).assert().success();
+    notes(&store)?.args(["add", "two"]).assert().success();
+    // Re-setting the same title writes identical content: no new backup
+    notes(&store)?.args(["edit", "2", "--title", "two"]).assert().success();
+    assert_eq!(stored_titles(&bak(&store, 1))?, ["one"]);
+    assert!(!bak(&store, 2).exists());
+
+    notes(&store)?.args(["--backups", "0", "add", "three"]).assert().success();
+    assert_eq!(stored_titles(&bak(&store, 1))?, ["one"]);
+    Ok(())
+}
+
+#[test]
+fn restore_backup_lists_and_swaps_back() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    for title in ["one", "two", "three"] {
+        notes(&store)?.args(["add", title]).assert().success();
+    }
+    notes(&store)?
+        .args(["restore-backup"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(" 1  "))
+        .stdout(predicate::str::contains("2 notes"))
+        .stdout(predicate::str::contains("1 notes"));
+
+    // Without confirmation nothing changes
+    // No terminal to ask on: refused with the dedicated exit code
+    notes(&store)?
+        .args(["restore-backup", "--which", "2"])
+        .write_stdin("y\n")
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("needs confirmation"));
+    assert_eq!(stored_titles(&store)?, ["one", "two", "three"]);
+
+    notes(&store)?.args(["restore-backup", "--which", "2", "--yes"]).assert().success();
+    assert_eq!(stored_titles(&store)?, ["one"]);
+    // The replaced store is itself kept as the newest backup
+    assert_eq!(stored_titles(&bak(&store, 1))?, ["one", "two", "three"]);
+    Ok(())
+}
+
+#[test]
+fn format_is_inferred_from_store_extension() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    for (file, marker) in [("notes.yaml", "- id: 1"), ("notes.yml", "- id: 1"), ("notes.toml", "[[notes]]"), ("notes.json", "\"id\": 1")] {
+        let store = dir.path().join(file);
+        notes(&store)?.args(["add", "Inferred"]).assert().success();
+        let raw = std::fs::read_to_string(&store)?;
+        assert!(raw.contains(marker), "{} was not written in its format:\n{}", file, raw);
+        notes(&store)?.args(["list"]).assert().success().stdout(predicate::str::contains("Inferred"));
+    }
+    Ok(())
+}
+
+#[test]
+fn explicit_format_conflicting_with_extension_warns() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.yaml");
+    notes(&store)?
+        .args(["--format", "json", "add", "Explicit"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("format json (from --format) doesn't match the extension"));
+    // The explicit flag won: the .yaml file holds JSON
+    assert!(std::fs::read_to_string(&store)?.trim_start().starts_with('{'));
+    Ok(())
+}
+
+fn notes_without_store() -> Result<assert_cmd::Command, Box<dyn Error>> {
+    let mut cmd = assert_cmd::Command::cargo_bin("rustic-notes")?;
+    cmd.env("NO_COLOR", "1");
+    Ok(cmd)
+}
+
+#[test]
+fn default_store_lives_in_xdg_data_home() -> Result<(), Box<dyn Error>> {
+    let data = TempDir::new()?;
+    let cwd = TempDir::new()?;
+    let expected = data.path().join("rustic-notes").join("notes.json");
+    notes_without_store()?
+        .env("XDG_DATA_HOME", data.path())
+        .current_dir(cwd.path())
+        .args(["add", "Far away"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(expected.display().to_string()));
+    assert!(expected.exists());
+    assert!(!cwd.path().join("notes.json").exists());
+    Ok(())
+}
+
+#[test]
+fn cwd_flag_and_store_dir_keep_old_behavior() -> Result<(), Box<dyn Error>> {
+    let data = TempDir::new()?;
+    let cwd = TempDir::new()?;
+    notes_without_store()?
+        .env("XDG_DATA_HOME", data.path())
+        .current_dir(cwd.path())
+        .args(["--cwd", "add", "Right here"])
+        .assert()
+        .success();
+    notes_without_store()?
+        .env("XDG_DATA_HOME", data.path())
+        .current_dir(cwd.path())
+        .args(["--store", ".", "--format", "yaml", "add", "Also here"])
+        .assert()
+        .success();
+    assert!(cwd.path().join("notes.json").exists());
+    assert!(cwd.path().join("notes.yaml").exists());
+    assert!(!data.path().join("rustic-notes").exists());
+    Ok(())
+}
+
+#[test]
+fn store_env_var_is_used_and_flag_overrides_it() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let env_store = dir.path().join("env.yaml");
+    let flag_store = dir.path().join("flag.json");
+    notes_without_store()?
+        .env("RUSTIC_NOTES_STORE", &env_store)
+        .args(["-v", "add", "From env"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(from environment variable)"))
+        .stderr(predicate::str::contains("format: yaml (from store file extension)"));
+    assert!(std::fs::read_to_string(&env_store)?.contains("title: From env"));
+
+    notes_without_store()?
+        .env("RUSTIC_NOTES_STORE", &env_store)
+        .args(["--store"])
+        .arg(&flag_store)
+        .args(["--debug", "add", "From flag"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(from command-line flag)"));
+    assert!(std::fs::read_to_string(&flag_store)?.contains("From flag"));
+    assert!(!std::fs::read_to_string(&env_store)?.contains("From flag"));
+    Ok(())
+}
+
+#[test]
+fn config_store_and_format_come_between_env_and_default() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let config = dir.path().join("config.toml");
+    let config_store = dir.path().join("from-config");
+    std::fs::write(&config, format!("store = {:?}\nformat = \"yaml\"\n", config_store))?;
+    notes_without_store()?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .args(["-v", "add", "From config"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(from config file)"))
+        .stderr(predicate::str::contains("format: yaml (from config file)"));
+    assert!(std::fs::read_to_string(&config_store)?.contains("title: From config"));
+
+    let env_store = dir.path().join("env.json");
+    notes_without_store()?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .env("RUSTIC_NOTES_STORE", &env_store)
+        .env("RUSTIC_NOTES_FORMAT", "json")
+        .args(["-v", "add", "From env"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("(from environment variable)"))
+        .stderr(predicate::str::contains("format: json (from environment variable)"));
+    assert!(std::fs::read_to_string(&env_store)?.contains("From env"));
+    assert!(!std::fs::read_to_string(&config_store)?.contains("From env"));
+
+    // encrypt = true works like --encrypt
+    std::fs::write(&config, "encrypt = true\n")?;
+    notes(&env_store)?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .env("RUSTIC_NOTES_PASSPHRASE", "fixed passphrase")
+        .args(["add", "Sealed"])
+        .assert()
+        .success();
+    assert!(std::fs::read(&env_store)?.starts_with(b"RNOTES"));
+
+    std::fs::write(&config, "format = \"xml\"\n")?;
+    notes_without_store()?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .current_dir(dir.path())
+        .args(["--cwd", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("config file format: unknown format \"xml\""));
+    Ok(())
+}
+
+#[test]
+fn format_env_var_is_used_and_flag_overrides_it() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    notes_without_store()?
+        .env("RUSTIC_NOTES_FORMAT", "toml")
+        .current_dir(dir.path())
+        .args(["--cwd", "-v", "add", "Env format"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("format: toml (from environment variable)"));
+    assert!(dir.path().join("notes.toml").exists());
+
+    notes_without_store()?
+        .env("RUSTIC_NOTES_FORMAT", "toml")
+        .current_dir(dir.path())
+        .args(["--cwd", "--format", "yaml", "add", "Flag format"])
+        .assert()
+        .success();
+    assert!(dir.path().join("notes.yaml").exists());
+
+    notes_without_store()?
+        .env("RUSTIC_NOTES_FORMAT", "xml")
+        .current_dir(dir.path())
+        .args(["--cwd", "list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("RUSTIC_NOTES_FORMAT: unknown format \"xml\""));
+    Ok(())
+}
+
+#[test]
+fn sqlite_store_runs_every_command() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.db");
+    notes(&store)?.args(["add", "Weekly meeting", "-b", "Agenda", "-t", "work"]).assert().success();
+    notes(&store)?.args(["add", "Groceries", "-b", "Milk", "-t", "personal"]).assert().success();
+    notes(&store)?.args(["add", "Scratch"]).assert().success();
+    assert!(std::fs::read(&store)?.starts_with(b"SQLite format 3"));
+
+    notes(&store)?
+        .args(["list", "-l"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#  1  Weekly meeting  [work]"))
+        .stdout(predicate::str::contains("    Milk"));
+    notes(&store)?
+        .args(["search", "--regex", "meet|groc", "-t", "personal"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Groceries"))
+        .stdout(predicate::str::contains("Weekly").not());
+    notes(&store)?
+        .args(["edit", "2", "--title", "Shopping", "--add-tags", "errands"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Note #2 updated: Shopping  [personal,errands]"));
+    notes(&store)?.args(["remove", "3"]).assert().success();
+
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed.iter().map(|n| n.id).collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(parsed[1].tags, vec!["personal", "errands"]);
+
+    // Removing the note left a backup behind, and it can be restored
+    notes(&store)?.args(["restore-backup", "--which", "1", "--yes"]).assert().success();
+    notes(&store)?.args(["list"]).assert().success().stdout(predicate::str::contains("Scratch"));
+    Ok(())
+}
+
+#[test]
+fn convert_file_store_to_sqlite_and_back() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let db = dir.path().join("migrated.db");
+    let yaml = dir.path().join("back.yaml");
+    notes(&store)?
+        .args(["convert", "--to"])
+        .arg(&db)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Converted 3 notes"));
+    notes(&db)?.args(["convert", "--to"]).arg(&yaml).assert().success();
+
+    let original: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    let round_trip: serde_json::Value = serde_yaml::from_str(&std::fs::read_to_string(&yaml)?)?;
+    assert_eq!(original["notes"], round_trip["notes"]);
+
+    // Refuses to clobber a non-empty destination
+    notes(&store)?
+        .args(["convert", "--to"])
+        .arg(&db)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already contains 3 notes"));
+    Ok(())
+}
+
+#[test]
+fn encrypt_and_decrypt_store_in_place() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .env("RUSTIC_NOTES_PASSPHRASE", "fixed passphrase")
+        .args(["encrypt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Encrypted"));
+    let sealed = std::fs::read(&store)?;
+    assert!(sealed.starts_with(b"RNOTES"));
+    assert!(!String::from_utf8_lossy(&sealed).contains("Groceries"));
+
+    // Mutations keep the store encrypted
+    notes(&store)?
+        .env("RUSTIC_NOTES_PASSPHRASE", "fixed passphrase")
+        .args(["add", "Diary"])
+        .assert()
+        .success();
+    assert!(std::fs::read(&store)?.starts_with(b"RNOTES"));
+    notes(&store)?
+        .env("RUSTIC_NOTES_PASSPHRASE", "fixed passphrase")
+        .args(["search", "diary"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("#  4  Diary"));
+
+    notes(&store)?
+        .env("RUSTIC_NOTES_PASSPHRASE", "not it")
+        .args(["list"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("wrong passphrase or corrupted store"));
+
+    notes(&store)?
+        .env("RUSTIC_NOTES_PASSPHRASE", "fixed passphrase")
+        .args(["decrypt"])
+        .assert()
+        .success();
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries", "Diary"]);
+    Ok(())
+}
+
+#[test]
+fn merge_other_store_remaps_ids_and_skips_duplicates() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let other = dir.path().join("laptop.yaml");
+    notes(&other)?.args(["add", "Groceries", "-b", "Milk, bread and a meeting snack", "-t", "Personal"]).assert().success();
+    notes(&other)?.args(["add", "Trip ideas", "-t", "travel"]).assert().success();
+    notes(&other)?.args(["add", "Books"]).assert().success();
+
+    notes(&store)?
+        .args(["merge", "--other"])
+        .arg(&other)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("merged 2, skipped 1 duplicates, remapped ids 2"))
+        .stdout(predicate::str::contains("skipped #1 \"Groceries\" (already in this store)"));
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    let summary: Vec<(u64, &str)> = parsed.iter().map(|n| (n.id, n.title.as_str())).collect();
+    assert_eq!(
+        summary,
+        [(1, "Weekly meeting"), (2, "Daily standup"), (3, "Groceries"), (4, "Trip ideas"), (5, "Books")]
+    );
+
+    notes(&store)?
+        .args(["merge", "--keep-duplicates", "--other"])
+        .arg(&other)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("merged 3, skipped 0 duplicates, remapped ids 3"));
+    Ok(())
+}
+
+#[test]
+fn dedupe_removes_exact_copies_and_keeps_the_oldest() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["add", " Groceries", "-b", "Milk, bread and a meeting snack\n", "-t", "PERSONAL"]).assert().success();
+    // Near duplicate: same text, different tags
+    notes(&store)?.args(["add", "Groceries", "-b", "Milk, bread and a meeting snack", "-t", "home"]).assert().success();
+    notes(&store)?.args(["add", "Weekly meeting", "-b", "Agenda for Monday", "-t", "work"]).assert().success();
+
+    notes(&store)?
+        .args(["--plain", "dedupe", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[warn] #1 \"Weekly meeting\" is repeated by #6"))
+        .stdout(predicate::str::contains("[warn] #3 \"Groceries\" is repeated by #4"))
+        .stdout(predicate::str::contains("2 duplicate notes would be removed"));
+    notes(&store)?.arg("dedupe").assert().code(3);
+    notes(&store)?.args(["dedupe", "--yes"]).assert().success().stdout(predicate::str::contains("Removed 2 duplicate notes"));
+
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    let ids: Vec<u64> = parsed.iter().map(|n| n.id).collect();
+    assert_eq!(ids, [1, 2, 3, 5]);
+    notes(&store)?.arg("dedupe").assert().success().stdout(predicate::str::contains("No duplicates found in 4 notes"));
+    notes(&store)?.arg("undo").assert().success();
+    let output = notes(&store)?.args(["-o", "json", "dedupe", "--dry-run"]).output()?;
+    let groups: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(groups, serde_json::json!([{"kept": 1, "removed": [6]}, {"kept": 3, "removed": [4]}]));
+    Ok(())
+}
+
+/// The same broken store (duplicate id 2, id 0, "Work"/"work" tags, a note without tags) in each text format.
+const CORRUPTED: [(&str, &str); 3] = [
+    (
+        "broken.json",
+        r#"{"notes": [
+  {"id": 1, "title": "a", "body": "", "tags": ["Work", "work"], "created_at": "2025-01-01T00:00:00Z"},
+  {"id": 2, "title": "b", "body": "", "tags": [], "created_at": "2025-01-01T00:00:00Z"},
+  {"id": 2, "title": "c", "created_at": "2025-01-01T00:00:00Z"},
+  {"id": 0, "title": "d", "body": "x", "tags": [], "created_at": "2025-01-01T00:00:00Z"}
+]}"#,
+    ),
+    (
+        "broken.toml",
+        r#"[[notes]]
+id = 1
+title = "a"
+body = ""
+tags = ["Work", "work"]
+created_at = "2025-01-01T00:00:00Z"
+
+[[notes]]
+id = 2
+title = "b"
+body = ""
+tags = []
+created_at = "2025-01-01T00:00:00Z"
+
+[[notes]]
+id = 2
+title = "c"
+created_at = "2025-01-01T00:00:00Z"
+
+[[notes]]
+id = 0
+title = "d"
+body = "x"
+tags = []
+created_at = "2025-01-01T00:00:00Z"
+"#,
+    ),
+    (
+        "broken.yaml",
+        r#"notes:
+- {id: 1, title: a, body: "", tags: [Work, work], created_at: 2025-01-01T00:00:00Z}
+- {id: 2, title: b, body: "", tags: [], created_at: 2025-01-01T00:00:00Z}
+- {id: 2, title: c, created_at: 2025-01-01T00:00:00Z}
+- {id: 0, title: d, body: x, tags: [], created_at: 2025-01-01T00:00:00Z}
+"#,
+    ),
+];
+
+#[test]
+fn doctor_reports_issues_in_every_format() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    for (name, content) in CORRUPTED {
+        let store = dir.path().join(name);
+        std::fs::write(&store, content)?;
+        notes(&store)?
+            .args(["--plain", "doctor"])
+            .assert()
+            .code(1)
+            .stdout(predicate::str::contains(r#"id 2 is used by 2 notes: ["b", "c"]"#))
+            .stdout(predicate::str::contains(r#"note "d" has id 0"#))
+            .stdout(predicate::str::contains(r#"note #1 has malformed tags ["Work", "work"]"#))
+            .stdout(predicate::str::contains("3 issues found"));
+    }
+    Ok(())
+}
+
+#[test]
+fn doctor_fix_repairs_and_keeps_a_backup() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    for (name, content) in CORRUPTED {
+        let store = dir.path().join(name);
+        std::fs::write(&store, content)?;
+        notes(&store)?
+            .args(["--backups", "0", "doctor", "--fix"])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Fixed 3 notes"));
+        assert_eq!(std::fs::read_to_string(bak(&store, 1))?, content);
+        notes(&store)?.arg("doctor").assert().success().stdout(predicate::str::contains("No issues found in 4 notes"));
+
+        let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+        let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+        let summary: Vec<(u64, &str)> = parsed.iter().map(|n| (n.id, n.title.as_str())).collect();
+        assert_eq!(summary, [(1, "a"), (2, "b"), (3, "c"), (4, "d")]);
+        assert_eq!(parsed[0].tags, ["Work"]);
+    }
+    Ok(())
+}
+
+#[test]
+fn edits_outside_rustic_notes_are_detected_until_doctor_fix() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.arg("list").assert().success().stderr("");
+    // One byte of a body changed by hand
+    let content = std::fs::read_to_string(&store)?;
+    std::fs::write(&store, content.replacen("Milk, bread", "Milk, bead", 1))?;
+
+    notes(&store)?
+        .args(["--plain", "list"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("[warn] WARNING:"))
+        .stderr(predicate::str::contains("doesn't match its checksum"));
+    notes(&store)?.args(["--no-verify", "list"]).assert().success().stderr("");
+    notes(&store)?
+        .arg("doctor")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("the store's checksum doesn't match its notes"));
+
+    notes(&store)?
+        .args(["doctor", "--fix"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Recomputed the store checksum"))
+        .stdout(predicate::str::contains("Fixed").not());
+    notes(&store)?.args(["show", "3"]).assert().success().stderr("").stdout(predicate::str::contains("Milk, bead"));
+    notes(&store)?.arg("doctor").assert().success();
+
+    // Converting to another format keeps the checksum valid
+    let yaml = store.with_extension("yaml");
+    notes(&store)?.arg("convert").arg("--to").arg(&yaml).assert().success();
+    notes(&yaml)?.arg("list").assert().success().stderr("");
+    Ok(())
+}
+
+#[test]
+fn undo_reverts_edit_add_and_remove() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let stored_notes = || -> Result<serde_json::Value, Box<dyn Error>> {
+        let storage: serde_json::Value = serde_json::from_slice(&std::fs::read(&store)?)?;
+        Ok(storage["notes"].clone())
+    };
+    let original = stored_notes()?;
+
+    notes(&store)?.args(["edit", "2", "--title", "Standup", "--add-tags", "daily"]).assert().success();
+    notes(&store)?.args(["add", "Call mom"]).assert().success();
+    notes(&store)?.args(["remove", "1"]).assert().success();
+    notes(&store)?
+        .args(["undo", "--list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r#"(?s)remove #1 "Weekly meeting".*add #4 "Call mom".*edit #2 "Daily standup""#)?);
+
+    notes(&store)?.arg("undo").assert().success().stdout(predicate::str::contains(r#"Undid remove #1 "Weekly meeting""#));
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Standup", "Groceries", "Call mom"]);
+    notes(&store)?.arg("undo").assert().success().stdout(predicate::str::contains(r#"Undid add #4 "Call mom""#));
+    notes(&store)?.arg("undo").assert().success().stdout(predicate::str::contains(r#"Undid edit #2 "Daily standup""#));
+    assert_eq!(stored_notes()?, original);
+    // The fixture's own adds are in the journal too
+    notes(&store)?.arg("undo").assert().success().stdout(predicate::str::contains(r#"Undid add #3 "Groceries""#));
+    Ok(())
+}
+
+#[test]
+fn undo_refuses_after_external_changes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["remove", "3"]).assert().success();
+    let edited = std::fs::read_to_string(&store)?.replace("Weekly meeting", "Weekly sync");
+    std::fs::write(&store, edited)?;
+
+    notes(&store)?
+        .arg("undo")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("changed outside rustic-notes"));
+    assert!(!store.with_extension("json.journal").exists());
+    assert_eq!(stored_titles(&store)?, ["Weekly sync", "Daily standup"]);
+    Ok(())
+}
+
+#[test]
+fn history_lists_revisions_and_restores_the_first() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // Stores written before history existed have no such field and keep not having one
+    assert!(!std::fs::read_to_string(&store)?.contains("history"));
+
+    notes(&store)?.args(["edit", "3", "--body", "Milk and bread"]).assert().success();
+    notes(&store)?.args(["edit", "3", "--body", "Milk, bread, eggs and coffee"]).assert().success();
+    notes(&store)?.args(["edit", "3", "--title", "Shopping"]).assert().success();
+
+    notes(&store)?
+        .args(["history", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^1 .*Groceries\s+personal$")?)
+        .stdout(predicate::str::is_match(r"(?m)^2 .*Groceries\s+personal\s+\+1/-4 words$")?)
+        .stdout(predicate::str::is_match(r"(?m)^3 .*Groceries\s+personal\s+\+4/-2 words$")?)
+        .stdout(predicate::str::is_match(r"(?m)^current\s+Shopping\s+personal\s+\+0/-0 words$")?);
+
+    notes(&store)?
+        .args(["history", "3", "--restore", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Note #3 restored to revision 1: Groceries"));
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!((parsed[2].title.as_str(), parsed[2].body.as_str()), ("Groceries", "Milk, bread and a meeting snack"));
+    // The version that was replaced is now the newest revision
+    assert_eq!(parsed[2].history.len(), 4);
+    assert_eq!(parsed[2].history[3].title, "Shopping");
+    Ok(())
+}
+
+#[test]
+fn history_depth_caps_revisions() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    for i in 0..4 {
+        notes(&store)?.args(["--history-depth", "2", "edit", "1", "--body"]).arg(format!("v{}", i)).assert().success();
+    }
+    let assert = notes(&store)?.args(["-o", "json", "history", "1"]).assert().success();
+    let revisions: serde_json::Value = serde_json::from_slice(&assert.get_output().stdout)?;
+    let bodies: Vec<&str> = revisions.as_array().unwrap().iter().map(|r| r["body"].as_str().unwrap()).collect();
+    assert_eq!(bodies, ["v1", "v2"]);
+    Ok(())
+}
+
+#[test]
+fn add_and_edit_read_the_body_from_stdin() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let log = "commit 1a2b3c\nAuthor: someone\n\n    Release 1.2\n";
+    notes(&store)?.args(["add", "release notes", "--body", "-"]).write_stdin(log).assert().success();
+    notes(&store)?.args(["add", "second", "--stdin"]).write_stdin("line 1\nline 2\n\n").assert().success();
+
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed[0].body, "commit 1a2b3c\nAuthor: someone\n\n    Release 1.2");
+    assert_eq!(parsed[1].body, "line 1\nline 2\n");
+
+    notes(&store)?.args(["edit", "1", "--body", "-"]).write_stdin("rewritten\nbody\n").assert().success();
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed[0].body, "rewritten\nbody");
+    Ok(())
+}
+
+#[test]
+fn stdin_body_rejects_binary_and_conflicts_with_body() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?
+        .args(["add", "blob", "--body", "-"])
+        .write_stdin(&b"\x89PNG\r\n\x1a\n\x00\x00"[..])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("binary"));
+    notes(&store)?
+        .args(["add", "both", "-b", "text", "--stdin"])
+        .write_stdin("ignored")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    assert!(!store.exists());
+    Ok(())
+}
+
+#[test]
+fn body_file_strips_bom_and_respects_size_guard() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let minutes = dir.path().join("minutes.txt");
+    std::fs::write(&minutes, "\u{feff}Attendees: Ana, Luis\nDecisions: ship on Friday\n")?;
+
+    notes(&store)?.args(["add", "Minutes"]).arg("--body-file").arg(&minutes).assert().success();
+    notes(&store)?
+        .args(["add", "Too big", "--max-body-size", "16", "--body-file"])
+        .arg(&minutes)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("more than the 16 byte limit"));
+    notes(&store)?
+        .args(["add", "Missing", "--body-file"])
+        .arg(dir.path().join("nope.txt"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("can't read body file"));
+
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].body, "Attendees: Ana, Luis\nDecisions: ship on Friday");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn body_file_seeds_the_editor_buffer() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let minutes = dir.path().join("minutes.txt");
+    std::fs::write(&minutes, "From the file\n")?;
+    // `true` leaves the buffer untouched, so the note gets exactly what the editor was given
+    notes(&store)?
+        .env("VISUAL", "true")
+        .args(["edit", "1", "--open-editor", "--no-confirm", "--body-file"])
+        .arg(&minutes)
+        .assert()
+        .success();
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed[0].body, "From the file");
+    Ok(())
+}
+
+#[test]
+fn remove_several_ids_reports_missing_ones() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["remove", "3", "9", "1", "--force"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Note deleted #3: Groceries"))
+        .stdout(predicate::str::contains("Didn't find the note with id 9"))
+        .stdout(predicate::str::contains("Note deleted #1: Weekly meeting"));
+    assert_eq!(stored_titles(&store)?, ["Daily standup"]);
+
+    notes(&store)?.args(["remove", "7", "8"]).assert().code(1);
+    assert_eq!(stored_titles(&store)?, ["Daily standup"]);
+
+    // One operation, so one undo brings both back
+    notes(&store)?.arg("undo").assert().success();
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+    Ok(())
+}
+
+#[test]
+fn remove_by_filter_needs_yes_for_several_notes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    // Not a terminal and no --yes: nothing is deleted
+    notes(&store)?
+        .args(["remove", "--tag", "work"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("Weekly meeting"))
+        .stderr(predicate::str::contains("This will affect 2 notes"));
+    assert_eq!(stored_titles(&store)?.len(), 3);
+
+    notes(&store)?.args(["remove", "--tag", "work", "--yes"]).assert().success();
+    assert_eq!(stored_titles(&store)?, ["Groceries"]);
+
+    // A single match is deleted without asking
+    notes(&store)?.args(["remove", "--query", "SNACK"]).assert().success();
+    assert!(stored_titles(&store)?.is_empty());
+    notes(&store)?
+        .args(["remove", "--query", "anything"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("No notes match the filter"));
+    Ok(())
+}
+
+#[test]
+fn removing_several_ids_without_a_terminal_needs_yes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["remove", "1", "2"])
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("pass --yes (or --force)"));
+    assert_eq!(stored_titles(&store)?.len(), 3);
+    // A missing id doesn't count towards the confirmation threshold
+    notes(&store)?.args(["remove", "1", "42"]).assert().success();
+    Ok(())
+}
+
+/// An editor script that replaces the whole buffer with `content`.
+#[cfg(unix)]
+fn editor_writing(dir: &Path, content: &str) -> Result<PathBuf, Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let script = dir.join("fake-editor.sh");
+    std::fs::write(&script, format!("#!/bin/sh\ncat > \"$1\" <<'EOF'\n{}EOF\n", content))?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+#[cfg(unix)]
+#[test]
+fn editor_without_changes_skips_the_save() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let before = std::fs::read(&store)?;
+    let modified = std::fs::metadata(&store)?.modified()?;
+    notes(&store)?
+        .env("VISUAL", "true")
+        .args(["edit", "1", "--open-editor"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No changes to note #1"));
+    assert_eq!(std::fs::read(&store)?, before);
+    assert_eq!(std::fs::metadata(&store)?.modified()?, modified);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn editor_changes_are_shown_and_need_confirmation() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let editor = editor_writing(dir.path(), "title: Weekly sync\nbody: Agenda for Monday\ntags:\n- work\n")?;
+    notes(&store)?
+        .env("VISUAL", &editor)
+        .args(["edit", "1", "--open-editor"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("-title: Weekly meeting\n+title: Weekly sync\n body: Agenda for Monday"));
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+
+    notes(&store)?.env("VISUAL", &editor).args(["edit", "1", "--open-editor", "--no-confirm"]).assert().success();
+    assert_eq!(stored_titles(&store)?, ["Weekly sync", "Daily standup", "Groceries"]);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn add_through_the_editor() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let editor = editor_writing(dir.path(), "title: Written in the editor\nbody: ''\ntags: [ideas]\n")?;
+    notes(&store)?
+        .env("VISUAL", &editor)
+        .args(["add", "--open-editor"])
+        .assert()
+        .code(3)
+        .stdout(predicate::str::contains("+title: Written in the editor"));
+    notes(&store)?
+        .env("VISUAL", &editor)
+        .args(["add", "--open-editor", "--no-confirm"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Note #4 saved"));
+    assert_eq!(stored_titles(&store)?[3], "Written in the editor");
+    Ok(())
+}
+
+/// An editor script that appends a line to the buffer (a new tag, since `tags` is the last YAML key).
+#[cfg(unix)]
+fn appending_editor(dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let script = dir.join("append-tag.sh");
+    std::fs::write(&script, "#!/bin/sh\necho '- appended' >> \"$1\"\n")?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+    Ok(script)
+}
+
+#[cfg(unix)]
+#[test]
+fn editor_flag_beats_visual_and_config_is_the_fallback() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let script = appending_editor(dir.path())?;
+    notes(&store)?
+        .env("VISUAL", "/nonexistent/editor")
+        .args(["edit", "1", "--open-editor", "--no-confirm", "--editor"])
+        .arg(format!("sh {}", script.display()))
+        .assert()
+        .success();
+
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, format!("editor = \"{}\"\n", script.display()))?;
+    notes(&store)?
+        .env_remove("VISUAL")
+        .env_remove("EDITOR")
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .args(["edit", "2", "--open-editor", "--no-confirm"])
+        .assert()
+        .success();
+
+    let assert = notes(&store)?.args(["-o", "json", "list"]).assert().success();
+    let parsed: Vec<Note> = serde_json::from_str(&String::from_utf8(assert.get_output().stdout.clone())?)?;
+    assert_eq!(parsed[0].tags, ["work", "appended"]);
+    assert_eq!(parsed[1].tags, ["work", "team", "appended"]);
+
+    // A misspelled key is an error rather than a silently ignored setting
+    std::fs::write(&config, format!("editr = \"{}\"\n", script.display()))?;
+    notes(&store)?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .arg("list")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid config file").and(predicate::str::contains("unknown field `editr`")));
+    Ok(())
+}
+
+#[test]
+fn missing_editor_names_the_command_and_its_source() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .env("EDITOR", "no-such-editor --wait")
+        .env_remove("VISUAL")
+        .args(["edit", "1", "--open-editor"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Couldn't run the editor `no-such-editor --wait` (from $EDITOR)"));
+    Ok(())
+}
+
+#[test]
+fn links_backlinks_and_show() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["add", "Standup notes", "-b", "From [[daily standup]], see [[1]] and [[Retro]]"]).assert().success();
+    notes(&store)?.args(["add", "Daily Standup", "-b", "old copy"]).assert().success();
+    notes(&store)?.args(["add", "Index", "-b", "[[Groceries]] [[99]]"]).assert().success();
+
+    notes(&store)?
+        .args(["--plain", "links", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[[daily standup]]  ->  [warn] ambiguous: #2, #5"))
+        .stdout(predicate::str::contains("[[1]]  ->  #1  Weekly meeting"))
+        .stdout(predicate::str::contains("[[Retro]]  ->  dangling"));
+
+    // The ambiguous title link counts for both candidates, with a warning
+    notes(&store)?
+        .args(["--plain", "backlinks", "5"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Standup notes"))
+        .stdout(predicate::str::contains("[warn] [[daily standup]] is ambiguous"));
+    notes(&store)?.args(["backlinks", "3"]).assert().success().stdout(predicate::str::contains("Index"));
+
+    notes(&store)?
+        .args(["show", "6"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[[3: Groceries]] [[99]]"));
+    notes(&store)?
+        .arg("doctor")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("note #4 links to [[Retro]], which doesn't exist"))
+        .stdout(predicate::str::contains("note #6 links to [[99]], which doesn't exist"));
+    Ok(())
+}
+
+#[test]
+fn attachments_are_copied_listed_and_removed_with_hard_delete() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let minutes = dir.path().join("minutes.txt");
+    std::fs::write(&minutes, "x".repeat(1536))?;
+
+    notes(&store)?.args(["attach", "1"]).arg(&minutes).assert().success();
+    notes(&store)?
+        .args(["attach", "1"])
+        .arg(&minutes)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("minutes-1.txt"));
+    let copy = dir.path().join("attachments").join("1").join("minutes.txt");
+    assert_eq!(std::fs::read(&copy)?, std::fs::read(&minutes)?);
+
+    // Stored relative to the store's directory
+    let raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    let recorded = raw["notes"][0]["attachments"][0].as_str().unwrap().replace('\\', "/");
+    assert_eq!(recorded, "attachments/1/minutes.txt");
+
+    notes(&store)?
+        .args(["show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("minutes.txt  (1.5 KiB)"));
+
+    // The files travel with a converted store
+    let elsewhere = TempDir::new()?;
+    let converted = elsewhere.path().join("notes.yaml");
+    notes(&store)?.args(["convert", "--to"]).arg(&converted).assert().success();
+    assert!(elsewhere.path().join("attachments").join("1").join("minutes-1.txt").exists());
+
+    notes(&store)?.args(["detach", "1", "minutes-1.txt"]).assert().success();
+    assert!(!dir.path().join("attachments").join("1").join("minutes-1.txt").exists());
+
+    notes(&store)?.args(["remove", "1"]).assert().success();
+    assert!(copy.exists(), "a plain remove keeps the files");
+    notes(&converted)?.args(["remove", "1", "--hard"]).assert().success();
+    assert!(!elsewhere.path().join("attachments").join("1").exists());
+    Ok(())
+}
+
+#[test]
+fn a_new_note_reusing_a_removed_id_does_not_get_its_attachments() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, "a")?;
+    let attachments = dir.path().join("attachments");
+
+    notes(&store)?.args(["attach", "3"]).arg(&file).assert().success();
+    notes(&store)?.args(["remove", "3"]).assert().success();
+    notes(&store)?.args(["add", "Reuses #3"]).assert().success().stdout(predicate::str::contains("Note #3 saved"));
+    notes(&store)?
+        .args(["attach", "3"])
+        .arg(&file)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.txt").and(predicate::str::contains("a-1.txt").not()));
+    assert!(attachments.join("3-1").join("a.txt").exists());
+
+    // Deleting the new note's files leaves the removed note's alone
+    notes(&store)?.args(["remove", "3", "--hard"]).assert().success();
+    assert!(!attachments.join("3-1").exists());
+    assert!(attachments.join("3").join("a.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn hard_remove_keeps_the_files_when_the_save_fails() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let file = dir.path().join("a.txt");
+    std::fs::write(&file, "a")?;
+    notes(&store)?.args(["attach", "1"]).arg(&file).assert().success();
+
+    // Rotating the backups fails when the oldest one can't be removed
+    let oldest = dir.path().join("notes.json.bak.1");
+    std::fs::remove_file(&oldest)?;
+    std::fs::create_dir(&oldest)?;
+    notes(&store)?.args(["--backups", "1", "remove", "1", "--hard"]).assert().failure();
+    assert!(dir.path().join("attachments").join("1").join("a.txt").exists());
+    notes(&store)?.args(["show", "1"]).assert().success().stdout(predicate::str::contains("a.txt"));
+    Ok(())
+}
+
+#[test]
+fn show_and_stats_count_emoji_and_cjk_words() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?.args(["add", "Launch", "-b", "ship it 🚀\ntoday"]).assert().success();
+    notes(&store)?.args(["add", "会议", "-b", "明天开会，带电脑"]).assert().success();
+    notes(&store)?
+        .args(["show", "1", "--stats"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4 words, 15 characters, 2 lines"));
+
+    let shown: serde_json::Value =
+        serde_json::from_slice(&notes(&store)?.args(["--output", "json", "show", "2", "--stats"]).output()?.stdout)?;
+    assert_eq!(shown["title"], "会议");
+    assert_eq!(shown["stats"], serde_json::json!({"words": 7, "characters": 8, "lines": 1}));
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&notes(&store)?.args(["--output", "json", "stats"]).output()?.stdout)?;
+    assert_eq!(summary["notes"], 2);
+    assert_eq!(summary["words"], 11);
+    assert_eq!(summary["longest"], serde_json::json!({"id": 2, "title": "会议", "words": 7}));
+    notes(&store)?
+        .arg("stats")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Words: 11"))
+        .stdout(predicate::str::contains("#2  会议  (7 words)"));
+    Ok(())
+}
+
+/// Splits `--porcelain` rows into their fields, undoing the escapes.
+fn porcelain_rows(stdout: &[u8]) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let unescape = |field: &str| {
+        let mut out = String::new();
+        let mut chars = field.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                out.push(c);
+                continue;
+            }
+            out.push(match chars.next() {
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                _ => '\\',
+            });
+        }
+        out
+    };
+    Ok(String::from_utf8(stdout.to_vec())?.lines().map(|line| line.split('\t').map(unescape).collect()).collect())
+}
+
+#[test]
+fn porcelain_prints_ids_and_escaped_rows() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let add = |args: &[&str]| -> Result<String, Box<dyn Error>> {
+        let output = notes(&store)?.arg("--porcelain").arg("add").args(args).output()?;
+        assert!(output.status.success());
+        // The confirmation goes to stderr
+        assert!(String::from_utf8(output.stderr)?.contains("saved to"));
+        Ok(String::from_utf8(output.stdout)?)
+    };
+    assert_eq!(add(&["Tabs\there", "-t", "work,ideas"])?, "1\n");
+    assert_eq!(add(&["Back\\slash\nand newline"])?, "2\n");
+    assert_eq!(add(&["Third"])?, "3\n");
+
+    let output = notes(&store)?.args(["--porcelain", "list"]).output()?;
+    let rows = porcelain_rows(&output.stdout)?;
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0][..3], ["1", "Tabs\there", "work,ideas"]);
+    assert_eq!(rows[1][..3], ["2", "Back\\slash\nand newline", ""]);
+    assert!(rows.iter().all(|r| r.len() == 4 && r[3].ends_with('Z')));
+    assert!(String::from_utf8(output.stdout)?.starts_with("1\tTabs\\there\twork,ideas\t"));
+
+    let output = notes(&store)?.args(["--porcelain", "search", "here"]).output()?;
+    assert_eq!(porcelain_rows(&output.stdout)?.iter().map(|r| r[0].as_str()).collect::<Vec<_>>(), ["1"]);
+
+    notes(&store)?.args(["--porcelain", "edit", "3", "--title", "Third note"]).assert().success().stdout("3\n");
+    notes(&store)?.args(["--porcelain", "remove", "2-3", "--yes"]).assert().success().stdout("2\n3\n");
+    notes(&store)?.args(["--porcelain", "-o", "json", "list"]).assert().failure();
+    Ok(())
+}
+
+#[test]
+fn plain_output_is_pure_ascii() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let runs: [&[&str]; 9] = [
+        &["add", "A title long enough to be cut short in the table view", "-b", "- one\n- two", "-t", "work"],
+        &["add", "Second", "-t", "wrk"],
+        &["pin", "1"],
+        &["edit", "2", "--title", "Second note"],
+        &["list", "--table=always"],
+        &["search", "nothing", "-t", "wor"],
+        &["stats"],
+        &["doctor"],
+        &["remove", "2"],
+    ];
+    for args in runs {
+        let output = notes(&store)?.arg("--plain").args(args).output()?;
+        let all = [output.stdout, output.stderr].concat();
+        assert!(all.is_ascii(), "{:?}: {}", args, String::from_utf8_lossy(&all));
+    }
+
+    // Without a terminal plain is the default, unless the config file turns it off
+    notes(&store)?.args(["pin", "1"]).assert().success().stdout(predicate::str::contains("[pinned]"));
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "plain = false\n")?;
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["pin", "1"]).assert().success().stdout(predicate::str::contains("📌"));
+    Ok(())
+}
+
+fn dated_store(dir: &TempDir) -> Result<PathBuf, Box<dyn Error>> {
+    let store = dir.path().join("notes.json");
+    std::fs::write(
+        &store,
+        r#"{"notes": [{"id": 1, "title": "Late call", "body": "", "tags": [], "created_at": "2025-09-14T22:30:00Z"}]}"#,
+    )?;
+    Ok(store)
+}
+
+#[test]
+fn local_flag_and_config_convert_displayed_timestamps() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dated_store(&dir)?;
+    notes(&store)?.arg("list").assert().success().stdout(predicate::str::contains("2025-09-14 22:30:00 UTC"));
+    notes(&store)?
+        .env("TZ", "Asia/Tokyo")
+        .args(["--local", "show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2025-09-15 07:30:00 +09:00"));
+
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "timezone = \"local\"\n")?;
+    notes(&store)?
+        .env("TZ", "America/New_York")
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .args(["search", "late"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2025-09-14 18:30:00 -04:00"));
+
+    // Storage stays in UTC
+    notes(&store)?.env("TZ", "Asia/Tokyo").args(["--local", "edit", "1", "--title", "Late call!"]).assert().success();
+    assert!(std::fs::read_to_string(&store)?.contains("2025-09-14T22:30:00Z"));
+    Ok(())
+}
+
+#[test]
+fn date_format_applies_everywhere_and_is_validated() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dated_store(&dir)?;
+    notes(&store)?
+        .args(["--date-format", "%d/%m/%Y %H:%M", "list", "--table=always"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("14/09/2025 22:30"));
+    notes(&store)?
+        .env("TZ", "Asia/Tokyo")
+        .args(["--local", "--date-format", "%a %d %b %H:%M", "show", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Mon 15 Sep 07:30"));
+    notes(&store)?
+        .args(["--date-format", "%Y-%Q", "list"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("invalid --date-format `%Y-%Q`"));
+    Ok(())
+}
+
+#[test]
+fn completions_and_man_page_go_to_stdout() -> Result<(), Box<dyn Error>> {
+    notes_without_store()?
+        .args(["completions", "zsh"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--open-editor"))
+        .stdout(predicate::str::contains("yaml"));
+    notes_without_store()?
+        .arg("--generate-man")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".TH rustic-notes 1"))
+        .stdout(predicate::str::contains("rustic\\-notes\\-search(1)"));
+    notes_without_store()?.assert().code(2).stderr(predicate::str::contains("a subcommand is required"));
+    Ok(())
+}
+
+#[test]
+fn grep_prints_matching_lines_with_context() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let body = "intro\nTODO: call Ana\nnotes\nmore notes\ntodo: send report\nend\n\n\nlast todo";
+    notes(&store)?.args(["add", "Plan", "-b", body, "-t", "work"]).assert().success();
+    notes(&store)?.args(["add", "Home", "-b", "todo: water plants", "-t", "home"]).assert().success();
+    notes(&store)?.args(["add", "Backlog", "-b", "todos piled up", "-t", "work"]).assert().success();
+
+    let expected = "\
+#1  Plan
+1- intro
+2: TODO: call Ana
+3- notes
+4- more notes
+5: todo: send report
+6- end
+--
+8-
+9: last todo
+
+#2  Home
+1: todo: water plants
+
+#3  Backlog
+1: todos piled up
+";
+    notes(&store)?.args(["grep", "-i", "todo", "-C", "1"]).assert().success().stdout(expected);
+
+    // -w skips "todos", --tags skips "Home", and without -i "TODO" doesn't match
+    notes(&store)?
+        .args(["grep", "-w", "todo", "--tags", "work"])
+        .assert()
+        .success()
+        .stdout("#1  Plan\n5: todo: send report\n9: last todo\n");
+
+    let found: serde_json::Value =
+        serde_json::from_slice(&notes(&store)?.args(["--output", "json", "grep", "TODO"]).output()?.stdout)?;
+    assert_eq!(found[0]["lines"][0], serde_json::json!({"number": 2, "text": "TODO: call Ana", "matches": [[0, 4]]}));
+
+    notes(&store)?.args(["grep", "nothing"]).assert().code(1).stdout(predicate::str::contains("No lines match"));
+    Ok(())
+}
+
+#[test]
+fn notes_can_be_named_by_fuzzy_title() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["show", "grocries"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Milk, bread and a meeting snack"));
+    notes(&store)?.args(["edit", "weekly meet", "--add-tags", "monday"]).assert().success();
+    assert!(notes(&store)?.args(["show", "1"]).output()?.stdout.windows(6).any(|w| w == b"monday"));
+
+    // "ly" fits "Weekly meeting" and "Daily standup" equally well: nothing is removed
+    notes(&store)?
+        .args(["remove", "ly"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("\"ly\" matches several notes, use the id instead"))
+        .stderr(predicate::str::contains("#2  Daily standup"));
+    notes(&store)?
+        .args(["remove", "xyzzy"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("no note id or title matches \"xyzzy\""));
+    assert_eq!(stored_titles(&store)?.len(), 3);
+
+    notes(&store)?.args(["remove", "standup"]).assert().success().stdout(predicate::str::contains("Note deleted #2"));
+    Ok(())
+}
+
+#[test]
+fn jsonl_store_appends_and_compacts() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.jsonl");
+    let lines = || std::fs::read_to_string(&store).map(|s| s.lines().count());
+    notes(&store)?.args(["add", "Weekly meeting", "-b", "Agenda", "-t", "work"]).assert().success();
+    notes(&store)?.args(["add", "Groceries", "-b", "Milk", "-t", "personal"]).assert().success();
+    notes(&store)?.args(["add", "Scratch"]).assert().success();
+    // Each save appends its records and then the store version
+    assert_eq!(lines()?, 6);
+
+    notes(&store)?.args(["edit", "2", "--title", "Shopping", "--add-tags", "errands"]).assert().success();
+    notes(&store)?.args(["remove", "3"]).assert().success();
+    assert_eq!(lines()?, 10);
+    assert!(std::fs::read_to_string(&store)?.contains("{\"id\":3,\"deleted\":true}\n{\"version\":5,"));
+
+    let listed = |store: &Path| -> Result<Vec<Note>, Box<dyn Error>> {
+        Ok(serde_json::from_slice(&notes(store)?.args(["-o", "json", "list"]).output()?.stdout)?)
+    };
+    let before = listed(&store)?;
+    assert_eq!(before.iter().map(|n| n.title.as_str()).collect::<Vec<_>>(), ["Weekly meeting", "Shopping"]);
+
+    notes(&store)?
+        .arg("compact")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("7 superseded records dropped, 2 notes kept"));
+    assert_eq!(lines()?, 3);
+    assert_eq!(listed(&store)?, before);
+
+    // undo still works on top of the log
+    notes(&store)?.arg("undo").assert().success();
+    assert_eq!(listed(&store)?.len(), 3);
+
+    let json = dir.path().join("notes.json");
+    notes(&json)?.args(["add", "x"]).assert().success();
+    notes(&json)?.arg("compact").assert().success().stdout(predicate::str::contains("Nothing to compact"));
+    Ok(())
+}
+
+#[test]
+fn export_html_escapes_titles_and_writes_tag_pages() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    notes(&store)?
+        .args(["add", "<script>alert(1)</script>", "-b", "# Heading\n\n**bold** <b>raw</b>", "-t", "Work,Web Dev"])
+        .assert()
+        .success();
+    let site = dir.path().join("site");
+    notes(&store)?
+        .args(["export", "html", "--dir"])
+        .arg(&site)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported 4 notes and 4 tag pages"));
+
+    let index = std::fs::read_to_string(site.join("index.html"))?;
+    assert!(index.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!index.contains("<script>"));
+    assert!(index.contains("<a href=\"notes/1.html\">Weekly meeting</a>"));
+    assert!(index.contains("href=\"tags/web-dev.html\">Web Dev</a>"));
+
+    let page = std::fs::read_to_string(site.join("notes/4.html"))?;
+    assert!(page.contains("<title>&lt;script&gt;alert(1)&lt;/script&gt;</title>"));
+    assert!(page.contains("<h1>Heading</h1>"));
+    assert!(page.contains("<strong>bold</strong> &lt;b&gt;raw&lt;/b&gt;"));
+    assert!(page.contains("href=\"../tags/work.html\">Work</a>"));
+
+    // "work" and "Work" share a page listing both notes
+    let work = std::fs::read_to_string(site.join("tags/work.html"))?;
+    assert!(work.contains("Weekly meeting") && work.contains("Daily standup") && work.contains("alert(1)"));
+    assert!(site.join("tags/team.html").exists() && site.join("tags/personal.html").exists());
+
+    let subset = dir.path().join("subset");
+    notes(&store)?.args(["export", "html", "--tag", "team", "--dir"]).arg(&subset).assert().success();
+    assert!(subset.join("notes/2.html").exists());
+    assert!(!subset.join("notes/1.html").exists());
+    assert!(!subset.join("tags/personal.html").exists());
+    Ok(())
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = std::process::Command::new("git")
+        .current_dir(dir)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()?;
+    assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn notes_in_repo(store: &Path, config: &Path) -> Result<assert_cmd::Command, Box<dyn Error>> {
+    let mut cmd = notes(store)?;
+    cmd.env("RUSTIC_NOTES_CONFIG", config)
+        .env("GIT_CONFIG_GLOBAL", "/dev/null")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com");
+    Ok(cmd)
+}
+
+#[test]
+fn auto_commit_commits_only_the_store() -> Result<(), Box<dyn Error>> {
+    let repo = TempDir::new()?;
+    git(repo.path(), &["init", "-q"])?;
+    std::fs::write(repo.path().join("other.txt"), "unrelated")?;
+    git(repo.path(), &["add", "other.txt"])?;
+    let config = repo.path().join("config.toml");
+    std::fs::write(&config, "auto_commit = true\n")?;
+    let store = repo.path().join("notes.json");
+
+    notes_in_repo(&store, &config)?.args(["add", "First"]).assert().success();
+    notes_in_repo(&store, &config)?.args(["edit", "1", "--title", "First, edited"]).assert().success();
+    notes_in_repo(&store, &config)?.args(["remove", "1"]).assert().success();
+    let log = git(repo.path(), &["log", "--format=%s"])?;
+    assert_eq!(log, "notes: remove #1 \"First, edited\"\nnotes: edit #1 \"First\"\nnotes: add #1 \"First\"\n");
+    // The unrelated staged file stayed staged and out of the commits
+    assert_eq!(git(repo.path(), &["diff", "--cached", "--name-only"])?, "other.txt\n");
+    assert_eq!(git(repo.path(), &["show", "--name-only", "--format=", "HEAD"])?, "notes.json\n");
+
+    // Without the config key nothing is committed; `sync` does it
+    notes(&store)?.args(["add", "Second"]).assert().success();
+    assert_eq!(git(repo.path(), &["log", "--format=%s"])?.lines().count(), 3);
+    notes_in_repo(&store, &config)?.arg("sync").assert().success().stdout(predicate::str::contains("Committed"));
+    assert!(git(repo.path(), &["log", "-1", "--format=%s"])?.starts_with("notes: sync"));
+    notes_in_repo(&store, &config)?.arg("sync").assert().success().stdout(predicate::str::contains("Nothing to commit"));
+    Ok(())
+}
+
+#[test]
+fn sync_push_and_pull_report_git_failures() -> Result<(), Box<dyn Error>> {
+    let remote = TempDir::new()?;
+    git(remote.path(), &["init", "-q", "--bare"])?;
+    let repo = TempDir::new()?;
+    git(repo.path(), &["init", "-q"])?;
+    let config = repo.path().join("config.toml");
+    std::fs::write(&config, "")?;
+    let store = repo.path().join("notes.json");
+    notes_in_repo(&store, &config)?.args(["add", "First"]).assert().success();
+
+    // No remote yet: git's own error comes through and the command fails
+    notes_in_repo(&store, &config)?
+        .args(["sync", "--push"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("`git push --quiet` failed"));
+
+    git(repo.path(), &["remote", "add", "origin", remote.path().to_str().unwrap()])?;
+    git(repo.path(), &["push", "-q", "-u", "origin", "HEAD"])?;
+    notes_in_repo(&store, &config)?.args(["add", "Second"]).assert().success();
+    notes_in_repo(&store, &config)?.args(["sync", "--pull", "--push"]).assert().success();
+    let pushed = git(remote.path(), &["log", "--format=%s"])?;
+    assert_eq!(pushed.lines().count(), 2);
+
+    let plain = TempDir::new()?;
+    notes(&plain.path().join("notes.json"))?
+        .arg("sync")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("is not inside a git repository"));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn save_refuses_when_the_store_changed_while_editing() -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let (dir, store) = fixture()?;
+    let original = std::fs::read(&store)?;
+    let newer = dir.path().join("newer.json");
+    std::fs::copy(&store, &newer)?;
+    notes(&newer)?.args(["add", "Saved meanwhile"]).assert().success();
+    // The "editor" swaps in the newer store, as another process saving during the edit would
+    let script = dir.path().join("racing-editor.sh");
+    let body = format!("#!/bin/sh\ncp '{}' '{}'\necho '- appended' >> \"$1\"\n", newer.display(), store.display());
+    std::fs::write(&script, body)?;
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+    notes(&store)?
+        .env("VISUAL", &script)
+        .args(["edit", "1", "--open-editor", "--no-confirm"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("store changed by another process, re-run your command"));
+    assert_eq!(stored_titles(&store)?.len(), 4);
+
+    std::fs::write(&store, original)?;
+    notes(&store)?
+        .env("VISUAL", &script)
+        .args(["--force-save", "edit", "1", "--open-editor", "--no-confirm"])
+        .assert()
+        .success();
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+    let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&store)?)?;
+    assert_eq!(saved["version"], 5);
+    assert_eq!(saved["notes"][0]["tags"], serde_json::json!(["work", "appended"]));
+    Ok(())
+}
+
+/// A `serve` process on an ephemeral port, killed when dropped.
+struct Served {
+    child: std::process::Child,
+    base: String,
+}
+
+impl Served {
+    fn start(store: &Path) -> Result<Served, Box<dyn Error>> {
+        use std::io::BufRead;
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("rustic-notes"))
+            .env("NO_COLOR", "1")
+            .arg("--store")
+            .arg(store)
+            .args(["serve", "--port", "0"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()?;
+        let mut banner = String::new();
+        std::io::BufReader::new(child.stdout.take().unwrap()).read_line(&mut banner)?;
+        let addr = banner.split("http://").nth(1).and_then(|rest| rest.split_whitespace().next());
+        let base = format!("http://{}", addr.ok_or_else(|| format!("no address in {:?}", banner))?);
+        Ok(Served { child, base })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base, path)
+    }
+}
+
+impl Drop for Served {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Status code of a request, whether or not it succeeded.
+fn status(result: Result<ureq::Response, ureq::Error>) -> u16 {
+    match result {
+        Ok(r) => r.status(),
+        Err(ureq::Error::Status(code, _)) => code,
+        Err(e) => panic!("request failed: {}", e),
+    }
+}
+
+#[test]
+fn serve_exposes_crud_and_search_over_http() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let server = Served::start(&store)?;
+    assert!(server.base.starts_with("http://127.0.0.1:"));
+
+    let listed: Vec<Note> = ureq::get(&server.url("/notes")).call()?.into_json()?;
+    assert_eq!(listed.len(), 3);
+    let one: Note = ureq::get(&server.url("/notes/2")).call()?.into_json()?;
+    assert_eq!(one.title, "Daily standup");
+
+    let created = ureq::post(&server.url("/notes"))
+        .send_json(serde_json::json!({"title": "From HTTP", "body": "posted", "tags": [" Api ", "api", "work"]}))?;
+    assert_eq!(created.status(), 201);
+    let created: Note = created.into_json()?;
+    assert_eq!((created.id, created.tags.clone()), (4, vec!["Api".to_string(), "work".to_string()]));
+
+    let patched: Note = ureq::request("PATCH", &server.url("/notes/4"))
+        .send_json(serde_json::json!({"title": "Renamed", "rm_tags": ["work"]}))?
+        .into_json()?;
+    assert_eq!((patched.title.as_str(), patched.tags.clone()), ("Renamed", vec!["Api".to_string()]));
+    // The CLI sees the same store, and the change can be undone like any edit
+    notes(&store)?.args(["show", "4"]).assert().success().stdout(predicate::str::contains("Renamed"));
+    notes(&store)?.args(["undo", "--list"]).assert().success().stdout(predicate::str::contains("edit #4 \"From HTTP\""));
+
+    let found: Vec<Note> = ureq::get(&server.url("/search?q=MEETING&tags=work")).call()?.into_json()?;
+    assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), [1]);
+    let found: Vec<Note> = ureq::get(&server.url("/search?q=posted")).call()?.into_json()?;
+    assert_eq!(found.iter().map(|n| n.id).collect::<Vec<_>>(), [4]);
+
+    assert_eq!(status(ureq::delete(&server.url("/notes/4")).call()), 200);
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+
+    match ureq::get(&server.url("/notes/4")).call() {
+        Err(ureq::Error::Status(404, response)) => assert_eq!(response.into_string()?, r#"{"error":"no note with id 4"}"#),
+        other => panic!("expected a 404, got {:?}", other.map(|r| r.status())),
+    }
+    assert_eq!(status(ureq::get(&server.url("/notes/four")).call()), 400);
+    assert_eq!(status(ureq::post(&server.url("/notes")).send_json(serde_json::json!({"title": " "}))), 400);
+    assert_eq!(status(ureq::post(&server.url("/notes")).send_json(serde_json::json!({"name": "x"}))), 400);
+    assert_eq!(status(ureq::put(&server.url("/notes/1")).call()), 405);
+    assert_eq!(status(ureq::get(&server.url("/nowhere")).call()), 404);
+    Ok(())
+}
+
+#[test]
+fn pinned_notes_are_listed_first_and_unpin_is_undoable() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["--plain", "pin", "3"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[pinned] Pinned #3: Groceries"));
+    let listed = String::from_utf8(notes(&store)?.args(["--plain", "list"]).output()?.stdout)?;
+    let first = listed.lines().next().unwrap_or_default();
+    assert!(first.contains("Groceries") && first.contains("[pinned]"), "{}", listed);
+    // The file order is untouched
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+
+    notes(&store)?.args(["pin", "3", "--off"]).assert().success();
+    notes(&store)?.arg("undo").assert().success().stdout(predicate::str::contains(r#"Undid unpin #3 "Groceries""#));
+    assert!(std::fs::read_to_string(&store)?.contains("\"pinned\": true"));
+    Ok(())
+}
+
+#[test]
+fn due_notes_are_listed_and_reminded_of_once() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?
+        .args(["--plain", "due", "Groceries", "--at", "2020-01-01"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[ok] #3 Groceries is due 2020-01-01 00:00:00 UTC"));
+    notes(&store)?.args(["due", "1", "--at", "2999-01-01T09:00:00Z"]).assert().success();
+    notes(&store)?.args(["due", "2", "--at", "tomorrow"]).assert().failure().stderr(predicate::str::contains("invalid due date"));
+    notes(&store)?
+        .arg("due")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Groceries").and(predicate::str::contains("Weekly meeting").not()))
+        .stdout(predicate::str::contains("due 2020-01-01 00:00:00 UTC  overdue"))
+        .stdout(predicate::str::contains("Found 1 notes due within 1 days"));
+    notes(&store)?
+        .args(["--date-format", "%d/%m/%Y", "due", "--within", "60000w"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("due 01/01/2020  overdue"))
+        .stdout(predicate::str::is_match(r"Weekly meeting.*due 01/01/2999\n")?);
+
+    // Without notify-send on the PATH, reminders are printed to stderr, clear of the JSON on
+    // stdout; the second run from cron is quiet
+    let output = notes(&store)?.env("PATH", "").args(["--output", "json", "due", "--notify"]).output()?;
+    assert!(output.status.success());
+    let notified: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(notified[0]["title"], "Groceries");
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Reminder: #3 Groceries (due 2020-01-01 00:00:00 UTC)"), "{}", stderr);
+    assert!(stderr.contains("Notified of 1 notes"), "{}", stderr);
+    notes(&store)?
+        .env("PATH", "")
+        .args(["due", "--notify"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Reminder").not())
+        .stdout(predicate::str::contains("Notified of 0 notes"));
+    notes(&store)?.args(["due", "3", "--clear"]).assert().success();
+    notes(&store)?.arg("due").assert().success().stdout(predicate::str::contains("Found 0 notes"));
+    Ok(())
+}
+
+#[test]
+fn tui_needs_a_terminal() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.arg("tui").assert().failure().stderr(predicate::str::contains("tui needs an interactive terminal"));
+    Ok(())
+}
+
+#[test]
+fn pick_needs_a_terminal_and_excludes_a_note_argument() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    for command in ["show", "edit", "remove"] {
+        notes(&store)?
+            .args([command, "--pick"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("--pick needs an interactive terminal"));
+    }
+    notes(&store)?.args(["show", "1", "--pick"]).assert().failure().stderr(predicate::str::contains("cannot be used with"));
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+    Ok(())
+}
+
+/// A store whose notes were last updated `days` ago each (a pinned one among them).
+fn aged_store(dir: &Path, days: &[i64]) -> Result<PathBuf, Box<dyn Error>> {
+    let now = chrono::Utc::now();
+    let notes: Vec<serde_json::Value> = days
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            serde_json::json!({
+                "id": i + 1,
+                "title": format!("{} days old", d),
+                "created_at": now - chrono::TimeDelta::days(1000),
+                "updated_at": now - chrono::TimeDelta::days(*d),
+                "pinned": i == 0,
+            })
+        })
+        .collect();
+    let store = dir.join("notes.json");
+    std::fs::write(&store, serde_json::to_string_pretty(&serde_json::json!({ "notes": notes }))?)?;
+    Ok(store)
+}
+
+fn stored_tags(path: &Path) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    let storage: rustic_notes::Storage = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(storage.notes.into_iter().map(|n| n.tags).collect())
+}
+
+#[test]
+fn prune_archives_stale_notes_and_deletes_only_archived_ones() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = aged_store(dir.path(), &[400, 29, 31, 400])?;
+    let untouched = std::fs::read(&store)?;
+
+    notes(&store)?
+        .args(["prune", "--older-than", "30d", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("31 days old").and(predicate::str::contains("29 days old").not()))
+        .stdout(predicate::str::contains("2 notes not updated in 30 days would be archived"));
+    assert_eq!(std::fs::read(&store)?, untouched);
+
+    // The pinned note is as old as the last one, but stays
+    notes(&store)?.args(["prune", "--older-than", "30d"]).assert().success().stdout(predicate::str::contains("Archived 2 notes"));
+    let archived = vec!["archived".to_string()];
+    assert_eq!(stored_tags(&store)?, [vec![], vec![], archived.clone(), archived.clone()]);
+
+    notes(&store)?.args(["prune", "--delete"]).assert().failure().stderr(predicate::str::contains("--older-than"));
+    notes(&store)?.args(["prune", "--delete", "--older-than", "1y"]).assert().code(3);
+    notes(&store)?
+        .args(["prune", "--delete", "--older-than", "1y", "--yes"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Deleted 1 notes"));
+    assert_eq!(stored_titles(&store)?, ["400 days old", "29 days old", "31 days old"]);
+    notes(&store)?.arg("undo").assert().success();
+    assert_eq!(stored_titles(&store)?.len(), 4);
+    Ok(())
+}
+
+#[test]
+fn auto_prune_runs_at_most_once_a_day() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = aged_store(dir.path(), &[400, 100])?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "auto_prune_days = 90\n")?;
+
+    // Reading doesn't prune
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).arg("list").assert().success();
+    assert_eq!(stored_tags(&store)?, [Vec::<String>::new(), vec![]]);
+
+    notes(&store)?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .args(["add", "Fresh"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Archived 1 notes not updated in 90 days (auto_prune_days)"));
+    assert_eq!(stored_tags(&store)?, [vec![], vec!["archived".to_string()], vec![]]);
+    let storage: rustic_notes::Storage = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    assert!(storage.last_prune.is_some());
+
+    // Another stale note shows up, but the pass already ran today
+    let stale = std::fs::read_to_string(&store)?.replace("\"title\": \"Fresh\"", "\"title\": \"Fresh\",\n      \"updated_at\": \"2020-01-01T00:00:00Z\"");
+    std::fs::write(&store, stale)?;
+    notes(&store)?
+        .env("RUSTIC_NOTES_CONFIG", &config)
+        .args(["add", "Another"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("auto_prune_days").not());
+    assert_eq!(stored_tags(&store)?[2], Vec::<String>::new());
+    Ok(())
+}
+
+#[test]
+fn checklists_show_progress_toggle_in_place_and_list_todo() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    let body = "Packing:\n- [ ] passport\n- [X] tickets\n  - [ ] charger\n- [ ] snacks";
+    notes(&store)?.args(["add", "Trip"]).arg(format!("--body={}", body)).assert().success();
+    notes(&store)?.args(["add", "Errands", "--body=- [ ] bank\n- [ ] post office\n- [x] pharmacy"]).assert().success();
+    notes(&store)?.args(["show", "4"]).assert().success().stdout(predicate::str::contains("Checklist: 1/4 done"));
+
+    // Nested item, then the last one
+    notes(&store)?.args(["toggle", "trip", "3"]).assert().success().stdout(predicate::str::contains("#4 [x] charger  (2/4 done)"));
+    notes(&store)?.args(["toggle", "4", "4"]).assert().success().stdout(predicate::str::contains("#4 [x] snacks  (3/4 done)"));
+    let stored: Vec<Note> = serde_json::from_value(serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&store)?)?["notes"].clone())?;
+    assert_eq!(stored[3].body, "Packing:\n- [ ] passport\n- [X] tickets\n  - [x] charger\n- [x] snacks");
+    notes(&store)?.args(["toggle", "4", "5"]).assert().failure().stderr(predicate::str::contains("no checklist item 5"));
+
+    notes(&store)?.args(["-o", "json", "show", "4"]).assert().success().stdout(predicate::str::contains(r#""checklist": {"#));
+
+    // Errands has two open items, Trip only the passport left
+    let listed = String::from_utf8(notes(&store)?.args(["list", "--todo"]).output()?.stdout)?;
+    assert!(listed.contains("Trip") && !listed.contains("Groceries"), "{}", listed);
+    assert!(listed.contains("2 open (1/3 done)") && listed.contains("1 open (3/4 done)"), "{}", listed);
+    assert!(listed.find("Errands").unwrap() < listed.find("Trip").unwrap(), "{}", listed);
+    Ok(())
+}
+
+#[test]
+fn id_ranges_show_pin_and_remove_several_notes() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["add", "Fourth"]).assert().success();
+    notes(&store)?
+        .args(["show", "2-3,"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?s)^--- #2\n.*Daily standup.*\n\n--- #3\n.*Groceries")?);
+    notes(&store)?.args(["show", "3-1"]).assert().failure().stderr(predicate::str::contains("the range 3-1 is reversed"));
+    notes(&store)?.args(["--plain", "pin", "1,3"]).assert().success().stdout(predicate::str::contains("Pinned #3: Groceries"));
+    notes(&store)?.args(["undo", "--list"]).assert().success().stdout(predicate::str::contains("pin #1 #3"));
+
+    // More than one note: asks first, unless --yes
+    notes(&store)?.args(["remove", "2-4"]).assert().code(3);
+    notes(&store)?.args(["remove", "2-4", "3", "--yes"]).assert().success().stdout(predicate::str::contains("Didn't find").not());
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting"]);
+    Ok(())
+}
+
+#[test]
+fn unique_titles_reject_duplicates_unless_allowed() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    notes(&store)?
+        .args(["add", "  weekly   MEETING ", "--unique-title"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("note #1 is already titled \"Weekly meeting\""));
+    notes(&store)?.args(["add", "Weekly meetings", "--unique-title"]).assert().success();
+
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "unique_titles = true\n")?;
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["add", "groceries"]).assert().failure();
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["add", "groceries", "--allow-duplicate"]).assert().success();
+    assert_eq!(stored_titles(&store)?.len(), 5);
+
+    notes(&store)?
+        .arg("doctor")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("notes #3, #5 share the title \"Groceries\""));
+    Ok(())
+}
+
+#[test]
+fn case_sensitive_search_tells_acronyms_apart() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?.args(["add", "Acronyms", "-b", "RUST: Reliable Unified Storage Tool"]).assert().success();
+    notes(&store)?.args(["add", "Learning", "-b", "reading the rust book"]).assert().success();
+    notes(&store)?.args(["add", "Reading list", "-t", "Rust"]).assert().success();
+    let found = |args: &[&str], config: Option<&Path>| -> Result<Vec<u64>, Box<dyn Error>> {
+        let mut cmd = notes(&store)?;
+        if let Some(config) = config {
+            cmd.env("RUSTIC_NOTES_CONFIG", config);
+        }
+        let output = cmd.args(["--output", "json", "search"]).args(args).output()?;
+        let results: Vec<Note> = serde_json::from_slice(&output.stdout)?;
+        // Which notes match, whatever their relevance
+        let mut ids: Vec<u64> = results.iter().map(|n| n.id).collect();
+        ids.sort();
+        Ok(ids)
+    };
+
+    assert_eq!(found(&["rust"], None)?, [1, 2, 3]);
+    // Tags match in any case
+    assert_eq!(found(&["RUST", "--case-sensitive"], None)?, [1, 3]);
+    assert_eq!(found(&["rust", "--case-sensitive"], None)?, [2, 3]);
+    assert_eq!(found(&["--regex", "^RUST", "--case-sensitive"], None)?, [1, 3]);
+
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "search_case_sensitive = true\n")?;
+    assert_eq!(found(&["RUST"], Some(&config))?, [1, 3]);
+    assert_eq!(found(&["RUST", "-i"], Some(&config))?, [1, 2, 3]);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn moves_arrange_list_sort_manual_and_survive_edits() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    notes(&store)?.args(["add", "Fourth"]).assert().success();
+    let manual = || -> Result<Vec<String>, Box<dyn Error>> {
+        let output = notes(&store)?.args(["--output", "json", "list", "--sort", "manual"]).output()?;
+        let listed: Vec<Note> = serde_json::from_slice(&output.stdout)?;
+        Ok(listed.into_iter().map(|n| n.title).collect())
+    };
+
+    notes(&store)?.args(["move", "3", "--top"]).assert().success().stdout(predicate::str::contains("Moved #3 to the top"));
+    notes(&store)?.args(["move", "4", "--before", "groceries"]).assert().success();
+    notes(&store)?.args(["move", "1", "--bottom"]).assert().success();
+    assert_eq!(manual()?, ["Fourth", "Groceries", "Weekly meeting", "Daily standup"]);
+    // Ranks 5 and 10 leave room for two notes in between, then #1 and #2 are adjacent
+    notes(&store)?.args(["move", "2", "--after", "4"]).assert().success();
+    notes(&store)?.args(["move", "1", "--after", "4"]).assert().success().stdout(predicate::str::contains("renumbered").not());
+    assert_eq!(manual()?, ["Fourth", "Weekly meeting", "Daily standup", "Groceries"]);
+    notes(&store)?
+        .args(["move", "3", "--after", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Moved #3 after #1 (renumbered 4 notes)"));
+    assert_eq!(manual()?, ["Fourth", "Weekly meeting", "Groceries", "Daily standup"]);
+    notes(&store)?.args(["move", "4", "--top"]).assert().success().stdout(predicate::str::contains("already there"));
+    notes(&store)?.args(["move", "4"]).assert().failure();
+
+    // Editing a note keeps its place
+    let editor = editor_writing(dir.path(), "title: Renamed\nbody: ''\ntags: []\n")?;
+    notes(&store)?.env("VISUAL", &editor).args(["edit", "1", "--open-editor", "--no-confirm"]).assert().success();
+    notes(&store)?.args(["edit", "2", "--title", "Standup"]).assert().success();
+    assert_eq!(manual()?, ["Fourth", "Renamed", "Groceries", "Standup"]);
+    notes(&store)?.arg("undo").assert().success();
+    notes(&store)?.arg("undo").assert().success();
+    notes(&store)?.arg("undo").assert().success();
+    assert_eq!(manual()?, ["Fourth", "Weekly meeting", "Daily standup", "Groceries"]);
+    Ok(())
+}
+
+#[test]
+fn list_group_by_tag_prints_a_section_per_tag() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let note = |id: u64, title: &str, tags: &[&str], rank: Option<u32>| {
+        serde_json::json!({ "id": id, "title": title, "tags": tags, "created_at": "2025-03-01T09:00:00Z", "rank": rank })
+    };
+    let fixed = serde_json::json!({ "notes": [
+        note(1, "Roadmap", &["work", "ideas"], Some(20)),
+        note(2, "Shopping", &[], None),
+        note(3, "Standup", &["work"], Some(10)),
+        note(4, "Blog post", &["ideas"], None),
+    ]});
+    std::fs::write(&store, serde_json::to_string_pretty(&fixed)?)?;
+
+    notes(&store)?.args(["list", "--group-by", "tag", "--sort", "manual"]).assert().success().stdout(
+        "ideas (2)\n\
+         \x20   #  1  Roadmap  [work,ideas]  2025-03-01 09:00:00 UTC\n\
+         \x20   #  4  Blog post  [ideas]  2025-03-01 09:00:00 UTC\n\
+         \n\
+         work (2)\n\
+         \x20   #  3  Standup  [work]  2025-03-01 09:00:00 UTC\n\
+         \x20   #  1  Roadmap  [work,ideas]  2025-03-01 09:00:00 UTC\n\
+         \n\
+         (untagged) (1)\n\
+         \x20   #  2  Shopping  []  2025-03-01 09:00:00 UTC\n",
+    );
+    notes(&store)?
+        .args(["list", "--group-by", "tag", "--first-tag-only"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ideas (1)\n    #  4").and(predicate::str::contains("work (2)\n    #  1  Roadmap")));
+
+    let output = notes(&store)?.args(["--output", "json", "list", "--group-by", "tag"]).output()?;
+    let groups: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(groups[2]["tag"], serde_json::Value::Null);
+    assert_eq!(groups[2]["notes"][0]["title"], "Shopping");
+    notes(&store)?.args(["list", "--first-tag-only"]).assert().failure();
+    Ok(())
+}
+
+#[test]
+fn export_note_writes_each_format_and_guards_existing_files() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let md = dir.path().join("out/nested/meeting.md");
+    notes(&store)?
+        .args(["export", "note", "weekly"])
+        .arg("--out")
+        .arg(&md)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Exported #1 to"));
+    let text = std::fs::read_to_string(&md)?;
+    assert!(text.starts_with("---\nid: 1\ntitle: Weekly meeting\ntags:\n- work\ncreated_at: "));
+    assert!(text.ends_with("---\n\nAgenda for Monday\n"));
+
+    notes(&store)?.args(["export", "note", "1"]).arg("--out").arg(&md).assert().failure().stderr(predicate::str::contains("already exists (use --force"));
+    notes(&store)?.args(["export", "note", "2", "--force"]).arg("--out").arg(&md).assert().success();
+    assert!(std::fs::read_to_string(&md)?.contains("title: Daily standup"));
+
+    // The extension picks the format, --format overrides it
+    for (file, format) in [("n.json", None), ("n.yml", None), ("n.toml", None), ("n.txt", Some("json"))] {
+        let path = dir.path().join(file);
+        let mut cmd = notes(&store)?;
+        cmd.args(["export", "note", "3"]).arg("--out").arg(&path);
+        if let Some(format) = format {
+            cmd.args(["--format", format]);
+        }
+        cmd.assert().success();
+        let text = std::fs::read_to_string(&path)?;
+        let note: Note = match file {
+            "n.yml" => serde_yaml::from_str(&text)?,
+            "n.toml" => toml::from_str(&text)?,
+            _ => serde_json::from_str(&text)?,
+        };
+        assert_eq!((note.id, note.title.as_str()), (3, "Groceries"));
+    }
+
+    notes(&store)?
+        .args(["export", "note", "3", "--out", "-", "--format", "yaml"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("id: 3\ntitle: Groceries\n"));
+    Ok(())
+}
+
+#[test]
+fn snapshots_skip_unchanged_stores_and_restore_exact_copies() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let snaps = dir.path().join("snapshots");
+    notes(&store)?.arg("snapshot").assert().success().stdout(predicate::str::contains("Snapshot notes-"));
+    let original = std::fs::read(&store)?;
+    notes(&store)?.arg("snapshot").assert().success().stdout(predicate::str::contains("Unchanged since snapshot notes-"));
+
+    notes(&store)?.args(["remove", "1"]).assert().success();
+    notes(&store)?.arg("snapshot").assert().success().stdout(predicate::str::contains("Snapshot notes-"));
+    let output = notes(&store)?.args(["--output", "json", "snapshot", "list"]).output()?;
+    let listed: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(listed.len(), 2);
+    let first = listed[0]["name"].as_str().unwrap_or_default().to_string();
+    assert_eq!(std::fs::read(snaps.join(&first))?, original);
+
+    notes(&store)?.args(["snapshot", "restore", &first]).assert().code(3);
+    notes(&store)?.args(["snapshot", "restore", &first, "--yes"]).assert().success();
+    assert_eq!(std::fs::read(&store)?, original);
+    assert_eq!(stored_titles(&store)?, ["Weekly meeting", "Daily standup", "Groceries"]);
+
+    // Restored content equals the first snapshot, not the newest, so it is taken again
+    let other = dir.path().join("elsewhere");
+    notes(&store)?.args(["snapshot", "prune", "--keep", "1"]).assert().success().stdout(predicate::str::contains("1 snapshots deleted, 1 kept"));
+    notes(&store)?.arg("snapshot").assert().success().stdout(predicate::str::contains("Snapshot notes-"));
+    notes(&store)?.args(["snapshot", "--dir"]).arg(&other).assert().success();
+    notes(&store)?.args(["snapshot", "list", "--dir"]).arg(&other).assert().success().stdout(predicate::str::contains("notes-").count(1));
+    Ok(())
+}
+
+#[test]
+fn compressed_bodies_are_transparent_and_never_exported_compressed() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "compress_bodies = true\n")?;
+    let long: String = (0..4000).map(|i| format!("{} minutes of the meeting\n", i)).collect();
+    let file = dir.path().join("minutes.txt");
+    std::fs::write(&file, &long)?;
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["add", "Minutes", "--body-file"]).arg(&file).assert().success();
+
+    // The short bodies of the fixture stay plain next to the compressed one
+    let raw = std::fs::read_to_string(&store)?;
+    assert!(raw.len() < long.len() / 10);
+    assert!(raw.contains("\"compressed\": true") && raw.contains("Agenda for Monday"));
+    let output = notes(&store)?.args(["--output", "json", "show", "4"]).output()?;
+    let shown: Note = serde_json::from_slice(&output.stdout)?;
+    // --body-file drops the final newline
+    assert!(shown.body.as_str() == long.trim_end());
+    notes(&store)?.args(["search", "3999 minutes"]).assert().success().stdout(predicate::str::contains("Minutes"));
+
+    let converted = dir.path().join("copy.yaml");
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["convert", "--to"]).arg(&converted).assert().success();
+    let exported = dir.path().join("minutes.json");
+    notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(["export", "note", "4", "--out"]).arg(&exported).assert().success();
+    for path in [converted, exported] {
+        let text = std::fs::read_to_string(&path)?;
+        assert!(text.contains("3999 minutes of the meeting") && !text.contains("compressed"), "{}", path.display());
+    }
+    Ok(())
+}
+
+#[test]
+fn search_index_gives_the_same_results_and_follows_changes() -> Result<(), Box<dyn Error>> {
+    let (dir, store) = fixture()?;
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "search_index = true\n")?;
+    let index = dir.path().join("notes.json.idx");
+    let ids = |query: &str, indexed: bool| -> Result<Vec<String>, Box<dyn Error>> {
+        let mut cmd = notes(&store)?;
+        if indexed {
+            cmd.env("RUSTIC_NOTES_CONFIG", &config);
+        }
+        let output = cmd.args(["--porcelain", "search", query]).output()?;
+        Ok(porcelain_rows(&output.stdout)?.into_iter().map(|r| r[0].clone()).collect())
+    };
+    let same = |query: &str| -> Result<Vec<String>, Box<dyn Error>> {
+        let indexed = ids(query, true)?;
+        assert_eq!(indexed, ids(query, false)?, "{:?}", query);
+        Ok(indexed)
+    };
+
+    // Built by the first search, then kept up to date by add, edit and remove
+    assert!(!index.exists());
+    assert_eq!(same("MEETING")?, ["1", "3"]);
+    assert!(index.exists());
+    let indexed = |args: &[&str]| -> Result<(), Box<dyn Error>> {
+        notes(&store)?.env("RUSTIC_NOTES_CONFIG", &config).args(args).assert().success();
+        Ok(())
+    };
+    indexed(&["add", "Retro", "-b", "What went well at the meeting", "-t", "team"])?;
+    indexed(&["edit", "1", "--title", "Weekly sync"])?;
+    indexed(&["remove", "3", "--yes"])?;
+    let before = std::fs::read(&index)?;
+    for query in ["meeting", "sync", "snack", "ing at", "team", "nothing"] {
+        same(query)?;
+    }
+    assert_eq!(std::fs::read(&index)?, before, "searching an up-to-date index doesn't rewrite it");
+    assert_eq!(same("meeting")?, ["4"]);
+
+    // Changes made without the setting leave a stale index, rebuilt on the next search
+    notes(&store)?.args(["add", "Another meeting"]).assert().success();
+    assert_eq!(same("meeting")?, ["5", "4"]);
+
+    std::fs::remove_file(&index)?;
+    notes(&store)?
+        .arg("reindex")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("from 4 notes into").and(predicate::str::contains("search_index = true")));
+    assert!(index.exists());
+    Ok(())
+}
+
+#[test]
+fn meta_fields_are_set_filtered_and_validated() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["add", "Launch plan", "--meta", "Project=apollo", "--meta", "source=email"]).assert().success();
+    notes(&store)?.args(["edit", "1", "--meta", "project=apollo"]).assert().success();
+    notes(&store)?.args(["edit", "2", "--meta", "project=gemini"]).assert().success();
+
+    let ids = |args: &[&str]| -> Result<Vec<String>, Box<dyn Error>> {
+        let output = notes(&store)?.arg("--porcelain").args(args).output()?;
+        assert!(output.status.success());
+        Ok(porcelain_rows(&output.stdout)?.into_iter().map(|r| r[0].clone()).collect())
+    };
+    assert_eq!(ids(&["list", "--meta", "project=apollo"])?, ["1", "4"]);
+    assert_eq!(ids(&["list", "--meta", "project=apollo", "--meta", "SOURCE=email"])?, ["4"]);
+    assert_eq!(ids(&["search", "meeting", "--meta", "project=apollo"])?, ["1"]);
+    assert!(ids(&["search", "meeting", "--meta", "project=Apollo"])?.is_empty());
+    notes(&store)?
+        .args(["list", "--meta", "project=mercury"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("(No notes with project=mercury)"));
+
+    notes(&store)?
+        .args(["show", "4"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Meta:\n    project = apollo\n    source = email\n"));
+    notes(&store)?.args(["edit", "4", "--rm-meta", "Source"]).assert().success();
+    let stored: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    assert_eq!(stored["notes"][3]["meta"], serde_json::json!({"project": "apollo"}));
+    // Notes without fields don't get an empty map
+    assert!(stored["notes"][2].get("meta").is_none());
+
+    for bad in [&["add", "x", "--meta", "no value"][..], &["edit", "1", "--meta", "a b=c"], &["edit", "1", "--rm-meta", "a.b"]] {
+        notes(&store)?.args(bad).assert().failure().stderr(predicate::str::contains("meta"));
+    }
+    notes(&store)?
+        .args(["list", "--meta", "café=1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid meta key \"café\""));
+    Ok(())
+}
+
+#[test]
+fn search_ranks_by_relevance_and_limit_caps_the_results() -> Result<(), Box<dyn Error>> {
+    let (_dir, store) = fixture()?;
+    notes(&store)?.args(["add", "Notes", "-b", "nothing to see", "-t", "meeting-notes"]).assert().success();
+    notes(&store)?.args(["add", "Meeting room booking"]).assert().success();
+    let ids = |args: &[&str]| -> Result<Vec<String>, Box<dyn Error>> {
+        let output = notes(&store)?.args(["--porcelain", "search", "meeting"]).args(args).output()?;
+        assert!(output.status.success());
+        Ok(porcelain_rows(&output.stdout)?.into_iter().map(|r| r[0].clone()).collect())
+    };
+
+    // Titles first (earlier match first), then tags, then bodies
+    assert_eq!(ids(&[])?, ["5", "1", "4", "3"]);
+    assert_eq!(ids(&["--limit", "2"])?, ["5", "1"]);
+    assert_eq!(ids(&["--sort", "created"])?, ["5", "4", "3", "1"]);
+    notes(&store)?.args(["edit", "3", "--title", "Groceries for the meeting"]).assert().success();
+    assert_eq!(ids(&["--sort", "updated", "--limit", "1"])?, ["3"]);
+
+    notes(&store)?
+        .args(["search", "meeting", "--limit", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("4 matches, showing 2").and(predicate::str::contains("Groceries").not()));
+    notes(&store)?.args(["search", "meeting", "--limit", "9"]).assert().success().stdout(predicate::str::contains("4 matches\n"));
+    notes(&store)?.args(["search", "booking"]).assert().success().stdout(predicate::str::contains("1 match\n"));
+    notes(&store)?
+        .args(["search", "nope", "--limit", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No results for \"nope\"").and(predicate::str::contains("match").not()));
+    Ok(())
+}
+
+#[test]
+fn ingest_imports_markdown_once_and_updates_changed_files() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let inbox = dir.path().join("inbox");
+    std::fs::create_dir(&inbox)?;
+    std::fs::write(inbox.join("groceries.md"), "# Groceries\n\nmilk\n")?;
+    std::fs::write(inbox.join("idea.md"), "---\ntitle: Side project\ntags: [ideas]\n---\n\nA notes app\n")?;
+    std::fs::write(inbox.join("readme.txt"), "not markdown")?;
+    let ingest = || -> Result<assert_cmd::assert::Assert, Box<dyn Error>> {
+        Ok(notes(&store)?.args(["ingest", "--dir"]).arg(&inbox).assert().success())
+    };
+
+    ingest()?.stdout(predicate::str::contains("Ingested 2 new and 0 updated notes"));
+    let first = std::fs::read(&store)?;
+    // Unchanged files are skipped and the store isn't saved again
+    ingest()?.stdout(predicate::str::contains("Ingested 0 new and 0 updated notes"));
+    assert_eq!(std::fs::read(&store)?, first);
+    let stored: serde_json::Value = serde_json::from_slice(&first)?;
+    assert_eq!(stored["notes"][1]["title"], "Side project");
+    assert_eq!(stored["notes"][1]["tags"], serde_json::json!(["ideas"]));
+    assert!(stored["notes"][1]["meta"]["source"].as_str().unwrap().ends_with("idea.md"));
+
+    // An edited file updates its note, keeping the old version in the history
+    let groceries = inbox.join("groceries.md");
+    std::fs::write(&groceries, "# Groceries\n\nmilk\nbread\n")?;
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+    std::fs::File::options().write(true).open(&groceries)?.set_modified(later)?;
+    ingest()?.stdout(predicate::str::contains("updated #1 \"Groceries\"").and(predicate::str::contains("0 new and 1 updated")));
+    let stored: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    assert_eq!(stored["notes"].as_array().unwrap().len(), 2);
+    assert_eq!(stored["notes"][0]["body"], "milk\nbread");
+    assert_eq!(stored["notes"][0]["history"][0]["body"], "milk");
+
+    // Broken files are reported and left for later; --move puts the others aside
+    std::fs::write(inbox.join("broken.md"), "---\ntitle: [oops\n---\n")?;
+    notes(&store)?
+        .args(["ingest", "--move", "--dir"])
+        .arg(&inbox)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Skipped").and(predicate::str::contains("broken.md")));
+    assert!(inbox.join("processed/groceries.md").exists() && inbox.join("processed/idea.md").exists());
+    assert!(inbox.join("broken.md").exists() && !groceries.exists());
+    notes(&store)?.args(["ingest", "--dir", "missing"]).assert().failure().stderr(predicate::str::contains("can't read missing"));
+    Ok(())
+}
+
+#[test]
+fn split_breaks_sections_into_linked_notes() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    let body = "Monday dump\n\n## Budget\nQ3 numbers\n\n## Nothing here\n\n## Hiring\ntwo devs\n\n## Travel\nBook flights\n";
+    notes(&store)?.args(["add", "Monday", "-t", "work", "-b", body]).assert().success();
+    notes(&store)?
+        .args(["split", "1", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"Budget\" (2 words)").and(predicate::str::contains("3 notes would be split out of #1")));
+    assert_eq!(stored_titles(&store)?, ["Monday"]);
+
+    notes(&store)?.args(["split", "Monday"]).assert().success().stdout(predicate::str::contains("Split #1 into #2, #3, #4"));
+    assert_eq!(stored_titles(&store)?, ["Monday", "Budget", "Hiring", "Travel"]);
+    let stored: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    assert_eq!(stored["notes"][0]["body"], "Monday dump\n\n- [[2]]\n- [[3]]\n- [[4]]");
+    assert_eq!(stored["notes"][0]["history"][0]["body"], body);
+    assert_eq!(stored["notes"][3]["body"], "Book flights");
+    assert!(stored["notes"].as_array().unwrap()[1..].iter().all(|n| n["tags"] == serde_json::json!(["work"])));
+    notes(&store)?.args(["show", "1"]).assert().success().stdout(predicate::str::contains("Hiring"));
+
+    // One undo puts the original back as it was
+    notes(&store)?.args(["undo"]).assert().success();
+    assert_eq!(stored_titles(&store)?, ["Monday"]);
+    notes(&store)?
+        .args(["--porcelain", "split", "1", "--remove-original"])
+        .assert()
+        .success()
+        .stdout("2\n3\n4\n5\n");
+    assert_eq!(stored_titles(&store)?, ["Monday", "Budget", "Hiring", "Travel"]);
+    notes(&store)?.args(["split", "4"]).assert().failure().stderr(predicate::str::contains("no sections"));
+    Ok(())
+}
+
+#[test]
+fn merge_notes_keeps_one_and_archives_the_other() -> Result<(), Box<dyn Error>> {
+    let dir = TempDir::new()?;
+    let store = dir.path().join("notes.json");
+    notes(&store)?.args(["add", "Trip", "-b", "Book flights", "-t", "travel"]).assert().success();
+    notes(&store)?.args(["add", "Hotels", "-b", "Two nights downtown", "-t", "travel,budget"]).assert().success();
+    notes(&store)?.args(["add", "Plans", "-b", "See [[2]] and [[hotels]]"]).assert().success();
+
+    notes(&store)?.args(["merge-notes", "3", "3"]).assert().failure().stderr(predicate::str::contains("into itself"));
+    notes(&store)?.args(["merge-notes", "1", "2", "--into", "3"]).assert().failure().stderr(predicate::str::contains("--into"));
+    notes(&store)?
+        .args(["merge-notes", "2", "1", "--into", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Merged #2 into #1 (8 words), #2 is archived").and(predicate::str::contains("in #3")));
+
+    let stored: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&store)?)?;
+    let kept = &stored["notes"][0];
+    assert_eq!(kept["body"], "Book flights\n\n---\n\n## Hotels\n\nTwo nights downtown");
+    assert_eq!(kept["tags"], serde_json::json!(["travel", "budget"]));
+    assert_eq!(stored["notes"][2]["body"], "See [[1]] and [[1]]");
+    // The absorbed note stays until `prune --delete`, archived and pointing at the survivor
+    let absorbed = &stored["notes"][1];
+    assert_eq!(absorbed["tags"], serde_json::json!(["travel", "budget", "archived"]));
+    assert_eq!(absorbed["meta"]["merged-into"], "1");
+    notes(&store)?.args(["list", "--not-tags", "archived"]).assert().success().stdout(predicate::str::contains("Hotels").not());
+
+    notes(&store)?.args(["--porcelain", "undo"]).assert().success();
+    assert_eq!(stored_titles(&store)?, ["Trip", "Hotels", "Plans"]);
+    notes(&store)?.args(["--porcelain", "merge-notes", "Trip", "Hotels"]).assert().success().stdout("1\n");
+    Ok(())
+}